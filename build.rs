@@ -0,0 +1,62 @@
+//! Embeds build metadata for `version --verbose`. Release pipelines without
+//! a git checkout, such as a Docker build context, can pass the commit in
+//! `JEFF_GIT_HASH`, and reproducible builds the date in `SOURCE_DATE_EPOCH`.
+
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-env-changed=JEFF_GIT_HASH");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let hash = env::var("JEFF_GIT_HASH")
+        .ok()
+        .filter(|hash| !hash.is_empty())
+        .or_else(git_hash)
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=JEFF_GIT_HASH={hash}");
+
+    let epoch = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()));
+    println!("cargo:rustc-env=JEFF_BUILD_DATE={}", utc_date(epoch));
+
+    println!("cargo:rustc-env=JEFF_TARGET={}", env::var("TARGET").unwrap_or_default());
+    println!("cargo:rustc-env=JEFF_PROFILE={}", env::var("PROFILE").unwrap_or_default());
+
+    // Cargo sets `CARGO_FEATURE_<NAME>` for each enabled feature.
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=JEFF_FEATURES={}", features.join(","));
+}
+
+/// The short commit hash, with `-dirty` if the tree has changes.
+fn git_hash() -> Option<String> {
+    let git = |args: &[&str]| Command::new("git").args(args).output().ok().filter(|out| out.status.success());
+    let hash = String::from_utf8(git(&["rev-parse", "--short", "HEAD"])?.stdout).ok()?;
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|out| !out.stdout.is_empty());
+    Some(format!("{}{}", hash.trim(), if dirty { "-dirty" } else { "" }))
+}
+
+/// `YYYY-MM-DD` for seconds since the Unix epoch, without a date library.
+fn utc_date(epoch: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = (epoch / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
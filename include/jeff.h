@@ -0,0 +1,52 @@
+/*
+ * C interface to the jeff library (src/ffi.rs), built with the `ffi` feature:
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * Results are NUL-terminated JSON strings owned by the caller; free them with
+ * jeff_string_free. Functions returning a pointer return NULL on failure, and
+ * jeff_last_error then describes the failure on the calling thread.
+ */
+
+#ifndef JEFF_H
+#define JEFF_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Jeff Jeff;
+
+/* Loads and validates a config file, as the command line does. */
+Jeff *jeff_open(const char *path);
+
+/* Like jeff_open, from the config's JSON text; "include" isn't supported. */
+Jeff *jeff_open_json(const char *json);
+
+void jeff_close(Jeff *jeff);
+
+/* Runs the configured filters over a slots/asLocations response:
+ * [{"keep": bool, "reason": string|null, "location": {...}}] */
+char *jeff_filter(const Jeff *jeff, const char *response);
+
+/* Scans every configured date once, blocking until done:
+ * {"dates_scanned": n, "dates_failed": n, "slots": [{"slot", "date",
+ *  "provider", "location_id", "name", "city", "state", "location"}]} */
+char *jeff_scan(const Jeff *jeff);
+
+/* The soonest open interview times at one location:
+ * [{"start": "2025-01-14T08:15:00", "end": ..., "duration": minutes}] */
+char *jeff_location_slots(const Jeff *jeff, size_t location_id, size_t limit);
+
+/* The last error on this thread, valid until the next call on it; NULL if none. */
+const char *jeff_last_error(void);
+
+void jeff_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* JEFF_H */
@@ -0,0 +1,126 @@
+//! Flags locations whose availability this cycle is far above their usual,
+//! such as a center that normally has nothing suddenly showing 30 open days.
+//! That is often a data glitch or a mass cancellation, and worth a second
+//! look before dropping everything to book.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{clock, format::CenterNotes, history::History, AppError, FetchedLocation};
+
+#[derive(Debug, Deserialize)]
+pub struct AnomalyConfig {
+    /// How many standard deviations above its usual a location's open days
+    /// must be to be flagged; lower flags more.
+    #[serde(default = "default_sensitivity")]
+    pub sensitivity: f64,
+    /// Fewer open days than this are never flagged, however unusual.
+    #[serde(default = "default_min_open_days")]
+    pub min_open_days: usize,
+    /// Days of history the usual is taken from.
+    #[serde(default = "default_window_days")]
+    pub window_days: u32,
+    /// Cycles the window must hold before anything is flagged.
+    #[serde(default = "default_min_cycles")]
+    pub min_cycles: usize,
+}
+
+fn default_sensitivity() -> f64 {
+    4.0
+}
+
+fn default_min_open_days() -> usize {
+    10
+}
+
+fn default_window_days() -> u32 {
+    30
+}
+
+fn default_min_cycles() -> usize {
+    20
+}
+
+impl AnomalyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sensitivity <= 0.0 {
+            return Err(format!("sensitivity must be above 0, got {}", self.sensitivity));
+        }
+        if self.window_days == 0 {
+            return Err("window_days must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Mean and standard deviation of a location's open days per cycle, over
+/// `cycles` cycles of which those in `counts` found any.
+fn baseline(counts: &[usize], cycles: usize) -> (f64, f64) {
+    let n = cycles.max(counts.len()) as f64;
+    let mean = counts.iter().sum::<usize>() as f64 / n;
+    let variance = counts.iter().map(|&c| (c as f64).powi(2)).sum::<f64>() / n - mean.powi(2);
+    (mean, variance.max(0.0).sqrt())
+}
+
+/// Whether `days` open days stand out against `mean` and `sd`. A spread under
+/// one day counts as one, so a location that's always empty isn't flagged for
+/// a day or two.
+fn unusual(days: usize, (mean, sd): (f64, f64), config: &AnomalyConfig) -> bool {
+    days >= config.min_open_days && (days as f64 - mean) / sd.max(1.0) >= config.sensitivity
+}
+
+/// A warning for each location in `found` with unusually many open days,
+/// keyed by `Location::key`.
+pub fn notes(config: &AnomalyConfig, found: &[FetchedLocation], history: &History) -> Result<CenterNotes, AppError> {
+    let since = clock::now() - chrono::Duration::days(i64::from(config.window_days));
+    let (cycles, counts) = history.open_days_per_cycle(since)?;
+    if cycles < config.min_cycles {
+        return Ok(CenterNotes::new());
+    }
+    let mut open: HashMap<String, BTreeSet<_>> = HashMap::new();
+    for item in found {
+        open.entry(item.loc.key()).or_default().insert(item.date);
+    }
+    let mut notes = CenterNotes::new();
+    for (key, dates) in open {
+        let usual = baseline(counts.get(&key).map_or(&[][..], Vec::as_slice), cycles);
+        if unusual(dates.len(), usual, config) {
+            info!("Unusual availability at {key}: {} open days against {:.1} usually", dates.len(), usual.0);
+            notes.insert(
+                key,
+                vec![format!(
+                    "⚠️ Unusual: {} open days here, against {:.1} in a typical cycle over the last {} days; \
+                     it may be a data glitch or a mass cancellation",
+                    dates.len(),
+                    usual.0,
+                    config.window_days
+                )],
+            );
+        }
+    }
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_a_spike_at_a_quiet_location() {
+        let config = AnomalyConfig {
+            sensitivity: default_sensitivity(),
+            min_open_days: default_min_open_days(),
+            window_days: default_window_days(),
+            min_cycles: default_min_cycles(),
+        };
+        // One day open in 10 of 100 cycles.
+        let quiet = baseline(&[1; 10], 100);
+        assert!(unusual(30, quiet, &config));
+        assert!(!unusual(3, quiet, &config));
+        // Anywhere from 10 to 30 days open.
+        let busy = baseline(&[10, 30, 15, 25, 20, 12, 28, 18, 22, 20], 10);
+        assert!(!unusual(30, busy, &config));
+    }
+}
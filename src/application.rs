@@ -0,0 +1,201 @@
+//! Watches the status of Trusted Traveler applications, such as
+//! "conditionally approved" or "interview waived", through the same signed-in
+//! browser session the TTP dashboard uses, and tells the notification
+//! channels when one changes. Each account, say one per member of a family,
+//! has its own session, schedule and channels.
+
+use std::{path::PathBuf, sync::Mutex};
+
+use chrono::NaiveDate;
+
+use tokio::time::{Duration, Instant};
+
+use reqwest::{header::HeaderMap, Client, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::{
+    notify::Dispatcher,
+    session::BrowserSession,
+    state::StateStore,
+    ttp_client::{Location, RequestDecorator},
+    AppError,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ApplicationStatusConfig {
+    /// Whose application this is, used in messages; required when there's
+    /// more than one account.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Session file, in the `session_path` format, with the cookies or
+    /// `Authorization` header of a signed-in dashboard.
+    pub session_path: PathBuf,
+    /// The dashboard request that returns the application as JSON.
+    pub url: String,
+    /// JSON pointer to the status in the response, e.g. `/0/status`; the
+    /// whole response if empty.
+    #[serde(default)]
+    pub pointer: String,
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: u64,
+    /// Channels to tell, each a kind such as `slack` or a target such as a
+    /// channel ID or address; every channel if empty. These channels also get
+    /// this applicant's own availability alerts instead of the general ones.
+    #[serde(default)]
+    pub notify: Vec<String>,
+    /// States among `search_states` this applicant can get to; all of them
+    /// if empty.
+    #[serde(default, deserialize_with = "crate::de_states")]
+    pub states: Vec<String>,
+    /// This applicant's interview, if booked; only earlier slots are theirs.
+    #[serde(default)]
+    pub booked_date: Option<NaiveDate>,
+}
+
+fn default_interval_minutes() -> u64 {
+    60
+}
+
+impl ApplicationStatusConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.url.starts_with("https://") && !self.url.starts_with("http://") {
+            return Err(format!("url {:?} is not an http(s) URL", self.url));
+        }
+        if !self.pointer.is_empty() && !self.pointer.starts_with('/') {
+            return Err(format!("pointer {:?} must start with /", self.pointer));
+        }
+        if self.interval_minutes == 0 {
+            return Err("interval_minutes must be at least 1".to_string());
+        }
+        if self.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            return Err("name is empty".to_string());
+        }
+        if self.notify.is_empty() && (!self.states.is_empty() || self.booked_date.is_some()) {
+            return Err("states and booked_date need notify, to say where this applicant's alerts go".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a slot at `loc` on `date` suits this applicant.
+    pub fn wants(&self, loc: &Location, date: NaiveDate) -> bool {
+        (self.states.is_empty() || self.states.contains(&loc.state)) && self.booked_date.is_none_or(|booked| date < booked)
+    }
+}
+
+pub struct StatusWatcher<'a> {
+    config: &'a ApplicationStatusConfig,
+    client: Client,
+    session: BrowserSession,
+    /// Whether the channels have been told the session needs renewing, so
+    /// they're told once rather than every check.
+    expired: bool,
+    /// When the next check is due.
+    next: Instant,
+}
+
+impl<'a> StatusWatcher<'a> {
+    /// Fails if the session can't be read or `notify` names a channel that
+    /// isn't configured.
+    pub fn new(config: &'a ApplicationStatusConfig, client: Client, dispatcher: &Dispatcher) -> Result<Self, AppError> {
+        if let Some(route) = config.notify.iter().find(|route| !dispatcher.routes_to(route)) {
+            return Err(AppError::Config(format!(
+                "application_status {}: notify names {route:?}, which isn't a configured channel",
+                config.name.as_deref().unwrap_or("")
+            )));
+        }
+        Ok(StatusWatcher {
+            config,
+            client,
+            session: BrowserSession::load(&config.session_path)?,
+            expired: false,
+            next: Instant::now(),
+        })
+    }
+
+    /// [`StatusWatcher::check`] if `interval_minutes` has passed since the
+    /// last check.
+    pub async fn check_if_due(&mut self, dispatcher: &Dispatcher, state: &Mutex<StateStore>) {
+        if Instant::now() < self.next {
+            return;
+        }
+        self.next = Instant::now() + Duration::from_secs(self.config.interval_minutes * 60);
+        self.check(dispatcher, state).await;
+    }
+
+    /// Reads the status, and tells the account's channels if it differs from
+    /// the last one seen or the session has expired. Failures are only logged.
+    pub async fn check(&mut self, dispatcher: &Dispatcher, state: &Mutex<StateStore>) {
+        let (whose, subject) = match &self.config.name {
+            Some(name) => (format!("{name}'s"), format!("Application status: {name}")),
+            None => ("your".to_string(), "Application status".to_string()),
+        };
+        let status = match self.fetch().await {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                let text = format!(
+                    "The session for checking {whose} application status has expired. \
+                     Sign in to the TTP dashboard again and paste fresh cookies into {}.",
+                    self.config.session_path.display()
+                );
+                warn!("{text}");
+                if !std::mem::replace(&mut self.expired, true) {
+                    dispatcher.notice_to(&self.config.notify, &subject, &text).await;
+                }
+                return;
+            }
+            Err(e) => {
+                warn!("Error checking {whose} application status: {e}");
+                return;
+            }
+        };
+        self.expired = false;
+
+        let account = self.config.name.as_deref().unwrap_or("");
+        let last = state.lock().unwrap().application_status(account).map(str::to_string);
+        if last.as_deref() == Some(status.as_str()) {
+            debug!("Application status unchanged for {whose}: {status}");
+            return;
+        }
+        let text = match &last {
+            Some(last) => format!("{} application status changed from {last} to {status}.", capitalize(&whose)),
+            None => format!("Watching {whose} application status, currently {status}."),
+        };
+        info!("{text}");
+        dispatcher.notice_to(&self.config.notify, &subject, &text).await;
+        let mut store = state.lock().unwrap();
+        store.set_application_status(account, status);
+        if let Err(e) = store.save() {
+            warn!("Error saving state: {e}");
+        }
+    }
+
+    /// The current status, or `None` if the session is no longer signed in.
+    async fn fetch(&self) -> Result<Option<String>, AppError> {
+        let mut headers = HeaderMap::new();
+        self.session.decorate(&mut headers);
+        let resp = self.client.get(&self.config.url).headers(headers).send().await?;
+        self.session.observe(&resp);
+        if matches!(resp.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+            return Ok(None);
+        }
+        let resp = resp.error_for_status()?;
+        // A signed-out dashboard answers with the sign-in page.
+        let Ok(body) = resp.json::<Value>().await else {
+            return Ok(None);
+        };
+        let found = body.pointer(&self.config.pointer).ok_or_else(|| {
+            AppError::General(format!("application_status: nothing at {} in the response", self.config.pointer))
+        })?;
+        Ok(Some(match found {
+            Value::String(status) => status.clone(),
+            other => other.to_string(),
+        }))
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
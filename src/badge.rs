@@ -0,0 +1,201 @@
+//! Live status for embedding in community sites: a shields.io endpoint badge
+//! and a JSON summary of the earliest slot per watched state, written to disk
+//! for static hosting and/or served over HTTP.
+
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::{CycleReport, FetchedLocation, JeffConfig};
+
+#[derive(Debug, Deserialize)]
+pub struct BadgeConfig {
+    /// Directory receiving `badge.json` and `summary.json` after every cycle.
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    /// Address to serve `/badge.json` and `/summary.json` on, e.g. `127.0.0.1:8080`.
+    #[serde(default)]
+    pub listen: Option<SocketAddr>,
+    /// Left-hand text of the badge.
+    #[serde(default = "default_label")]
+    pub label: String,
+}
+
+fn default_label() -> String {
+    "Global Entry".to_string()
+}
+
+impl BadgeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.output_dir.is_none() && self.listen.is_none() {
+            return Err("set output_dir and/or listen".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    updated_at: DateTime<Utc>,
+    /// False when some dates failed to load, so slots may be missing.
+    complete: bool,
+    slots: usize,
+    earliest: Option<Earliest>,
+    states: BTreeMap<String, StateSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct StateSummary {
+    slots: usize,
+    earliest: Option<Earliest>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Earliest {
+    date: NaiveDate,
+    /// Only known to be on or before `date`.
+    undated: bool,
+    location_id: usize,
+    location: String,
+    city: String,
+    state: String,
+}
+
+/// The latest rendered `badge.json` and `summary.json`, for the server.
+static LATEST: RwLock<Option<(String, String)>> = RwLock::new(None);
+
+/// Renders the status for one finished cycle, then writes and/or stores it
+/// for serving as configured. Errors are logged.
+pub fn publish(config: &JeffConfig, found: &[FetchedLocation], report: &CycleReport) {
+    let Some(badge_config) = &config.badge else {
+        return;
+    };
+    let summary = summarize(config, found, report);
+    let badge = badge(badge_config, &summary, config.fetch_interval_minutes * 60);
+    let (badge, summary) = match (serde_json::to_string_pretty(&badge), serde_json::to_string_pretty(&summary)) {
+        (Ok(badge), Ok(summary)) => (badge, summary),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Error rendering status JSON: {e}");
+            return;
+        }
+    };
+
+    if let Some(dir) = &badge_config.output_dir {
+        let written = std::fs::create_dir_all(dir)
+            .and_then(|()| write_atomic(&dir.join("badge.json"), &badge))
+            .and_then(|()| write_atomic(&dir.join("summary.json"), &summary));
+        if let Err(e) = written {
+            error!("Error writing status to {}: {e}", dir.display());
+        }
+    }
+    *LATEST.write().unwrap() = Some((badge, summary));
+}
+
+/// Swaps the file in whole so a web server never serves half of it.
+fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+fn summarize(config: &JeffConfig, found: &[FetchedLocation], report: &CycleReport) -> Summary {
+    let earliest = |item: &FetchedLocation| Earliest {
+        date: item.date,
+        undated: item.undated,
+        location_id: item.loc.id,
+        location: config.display.name(&item.loc).to_string(),
+        city: item.loc.city.clone(),
+        state: item.loc.state.clone(),
+    };
+    let mut states: BTreeMap<String, StateSummary> = config
+        .search_states
+        .iter()
+        .map(|state| (state.clone(), StateSummary { slots: 0, earliest: None }))
+        .collect();
+    let mut sorted: Vec<&FetchedLocation> = found.iter().collect();
+    sorted.sort_by(|a, b| (a.date, &a.loc.name).cmp(&(b.date, &b.loc.name)));
+    for item in &sorted {
+        let entry = states.entry(item.loc.state.clone()).or_insert(StateSummary {
+            slots: 0,
+            earliest: None,
+        });
+        entry.slots += 1;
+        entry.earliest.get_or_insert_with(|| earliest(item));
+    }
+    Summary {
+        updated_at: report.finished_at,
+        complete: report.dates_failed == 0,
+        slots: found.len(),
+        earliest: sorted.first().map(|item| earliest(item)),
+        states,
+    }
+}
+
+/// A shields.io endpoint badge, e.g. "Global Entry | Mar 14 (CA)".
+fn badge(config: &BadgeConfig, summary: &Summary, cache_seconds: u64) -> serde_json::Value {
+    let (message, color) = match &summary.earliest {
+        Some(e) => {
+            let by = if e.undated { "by " } else { "" };
+            (format!("{by}{} ({})", e.date.format("%b %-d"), e.state), "brightgreen")
+        }
+        None if summary.complete => ("none open".to_string(), "lightgrey"),
+        None => ("unknown".to_string(), "orange"),
+    };
+    json!({
+        "schemaVersion": 1,
+        "label": config.label,
+        "message": message,
+        "color": color,
+        // shields.io won't cache for less than 300 seconds anyway.
+        "cacheSeconds": cache_seconds.max(300),
+    })
+}
+
+/// Serves the latest status until the process exits.
+pub async fn serve(addr: SocketAddr) {
+    let make = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+    let server = match Server::try_bind(&addr) {
+        Ok(builder) => builder.serve(make),
+        Err(e) => {
+            error!("Error serving status on {addr}: {e}");
+            return;
+        }
+    };
+    info!("Serving status on http://{addr}/badge.json and /summary.json");
+    if let Err(e) = server.await {
+        error!("Status server stopped: {e}");
+    }
+}
+
+async fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let latest = LATEST.read().unwrap().clone();
+    let (status, body) = match (req.method(), req.uri().path(), latest) {
+        (&Method::GET, "/badge.json", Some((badge, _))) => (StatusCode::OK, badge),
+        (&Method::GET, "/summary.json", Some((_, summary))) => (StatusCode::OK, summary),
+        (&Method::GET, "/badge.json" | "/summary.json", None) => {
+            (StatusCode::SERVICE_UNAVAILABLE, r#"{"error":"no cycle has finished yet"}"#.to_string())
+        }
+        _ => (StatusCode::NOT_FOUND, r#"{"error":"not found"}"#.to_string()),
+    };
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        // Meant to be fetched by other sites' pages.
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Cache-Control", "no-cache")
+        .body(Body::from(body))
+        .unwrap_or_default())
+}
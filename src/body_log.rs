@@ -0,0 +1,223 @@
+//! Debug logging of API response bodies, truncated and sampled so journald
+//! isn't flooded, plus an optional directory that captures every body in full
+//! and a compressed archive kept for as long as `retention` says.
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+#[cfg(any(feature = "archive", feature = "sqlite"))]
+use std::{fs, io};
+#[cfg(feature = "archive")]
+use std::{fs::OpenOptions, io::Write};
+#[cfg(feature = "sqlite")]
+use std::path::Path;
+
+#[cfg(feature = "sqlite")]
+use chrono::NaiveDate;
+#[cfg(feature = "archive")]
+use flate2::{write::GzEncoder, Compression};
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::clock;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BodyLogConfig {
+    /// Log full bodies at debug level, not just their size; `--log-bodies`
+    /// turns this on too. They may contain personal data.
+    #[serde(default)]
+    pub log_bodies: bool,
+    /// Longest body logged before it's cut short.
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+    /// Log one response body in every `sample_every`.
+    #[serde(default = "default_sample_every")]
+    pub sample_every: u64,
+    /// Directory receiving every response body in full, one file each,
+    /// whether or not `--log-bodies` was given.
+    #[serde(default)]
+    pub capture_dir: Option<PathBuf>,
+    /// Compressed copy of every response, pruned with `retention`.
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchiveConfig {
+    pub dir: PathBuf,
+    #[cfg_attr(not(feature = "archive"), allow(dead_code))]
+    #[serde(default)]
+    pub format: ArchiveFormat,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// A directory per UTC day, with one `.gz` file per response.
+    #[default]
+    Gzip,
+    /// One `.log.zst` file per UTC day, each response appended as its own
+    /// frame after a header line, so `zstdcat` reads it whole.
+    Zstd,
+}
+
+impl Default for BodyLogConfig {
+    fn default() -> Self {
+        BodyLogConfig {
+            log_bodies: false,
+            max_bytes: default_max_bytes(),
+            sample_every: default_sample_every(),
+            capture_dir: None,
+            archive: None,
+        }
+    }
+}
+
+fn default_max_bytes() -> usize {
+    2048
+}
+
+fn default_sample_every() -> u64 {
+    1
+}
+
+/// Where a client's response bodies go, per its [`BodyLogConfig`].
+#[derive(Debug, Default)]
+pub struct BodyLog {
+    config: BodyLogConfig,
+    seen: AtomicU64,
+}
+
+impl BodyLog {
+    pub fn new(config: BodyLogConfig) -> Self {
+        BodyLog {
+            config,
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    /// Logs and/or captures one response body from `url`.
+    pub fn record(&self, url: &Url, status: StatusCode, body: &str) {
+        let config = &self.config;
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(dir) = &config.capture_dir {
+            let path = dir.join(capture_name(n, url));
+            if let Err(e) = std::fs::create_dir_all(dir).and_then(|()| std::fs::write(&path, body)) {
+                warn!("Error capturing response body to {}: {e}", path.display());
+            }
+        }
+
+        #[cfg(feature = "archive")]
+        if let Some(archive) = &config.archive {
+            if let Err(e) = archive.write(n, url, status, body) {
+                warn!("Error archiving response body to {}: {e}", archive.dir.display());
+            }
+        }
+
+        if !config.log_bodies {
+            debug!("Response body from {url} ({status}): {} bytes (pass --log-bodies to log it)", body.len());
+            return;
+        }
+        if !n.is_multiple_of(config.sample_every.max(1)) {
+            return;
+        }
+        let shown = truncate(body, config.max_bytes);
+        if shown.len() < body.len() {
+            debug!(
+                "Response body from {url} ({status}):\n{shown}\n... ({} more bytes)",
+                body.len() - shown.len()
+            );
+        } else {
+            debug!("Response body from {url} ({status}):\n{body}");
+        }
+    }
+}
+
+/// The longest prefix of `s` no longer than `max` bytes that ends on a char boundary.
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// e.g. `20250314T081500.123-42-schedulerapi_slots_asLocations_timestamp_2025-03-14.txt`
+fn capture_name(n: u64, url: &Url) -> String {
+    let mut what = url.path().trim_start_matches('/').to_string();
+    for (key, value) in url.query_pairs() {
+        if key == "timestamp" || key == "date" || key == "locationId" {
+            what.push_str(&format!("_{key}_{value}"));
+        }
+    }
+    let what: String = what
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .take(100)
+        .collect();
+    format!("{}-{n}-{what}.txt", clock::now().format("%Y%m%dT%H%M%S%.3f"))
+}
+
+impl ArchiveConfig {
+    #[cfg(feature = "archive")]
+    fn write(&self, n: u64, url: &Url, status: StatusCode, body: &str) -> io::Result<()> {
+        let day = clock::now().format("%Y-%m-%d").to_string();
+        match self.format {
+            ArchiveFormat::Gzip => {
+                let dir = self.dir.join(&day);
+                fs::create_dir_all(&dir)?;
+                let file = fs::File::create(dir.join(format!("{}.gz", capture_name(n, url))))?;
+                let mut gz = GzEncoder::new(file, Compression::default());
+                gz.write_all(body.as_bytes())?;
+                gz.finish()?;
+            }
+            ArchiveFormat::Zstd => {
+                fs::create_dir_all(&self.dir)?;
+                let entry = format!("=== {} {status} {url} ({} bytes)\n{body}\n", clock::now().to_rfc3339(), body.len());
+                let frame = zstd::encode_all(entry.as_bytes(), 0)?;
+                let path = self.dir.join(format!("{day}.log.zst"));
+                OpenOptions::new().create(true).append(true).open(path)?.write_all(&frame)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes (or with `dry_run`, counts) the days archived before `cutoff`.
+    #[cfg(feature = "sqlite")]
+    pub fn prune(&self, cutoff: NaiveDate, dry_run: bool) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if archive_day(&path).is_none_or(|day| day >= cutoff) {
+                continue;
+            }
+            if !dry_run {
+                if path.is_dir() {
+                    fs::remove_dir_all(&path)?;
+                } else {
+                    fs::remove_file(&path)?;
+                }
+            }
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
+
+/// The day an archive entry (`2025-03-14/` or `2025-03-14.log.zst`) holds.
+#[cfg(feature = "sqlite")]
+fn archive_day(path: &Path) -> Option<NaiveDate> {
+    let name = path.file_name()?.to_str()?;
+    NaiveDate::parse_from_str(name.get(..10)?, "%Y-%m-%d").ok()
+}
@@ -0,0 +1,172 @@
+//! A self-imposed cap on scheduler API requests per hour and per day, held
+//! no matter what the rest of the config asks for.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+const HOUR: Duration = Duration::from_secs(3600);
+const DAY: Duration = Duration::from_secs(86_400);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BudgetConfig {
+    #[serde(default)]
+    pub per_hour: Option<u32>,
+    #[serde(default)]
+    pub per_day: Option<u32>,
+}
+
+impl BudgetConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.per_hour, self.per_day) {
+            (None, None) => Err("set per_hour, per_day or both".to_string()),
+            (Some(0), _) | (_, Some(0)) => Err("limits must be at least 1".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The limits that apply, with their window and name.
+    fn limits(&self) -> impl Iterator<Item = (u32, Duration, &'static str)> {
+        [(self.per_hour, HOUR, "hour"), (self.per_day, DAY, "day")]
+            .into_iter()
+            .filter_map(|(limit, window, name)| Some((limit?, window, name)))
+    }
+
+    /// Why scanning `dates` dates for `providers` providers every `minutes`
+    /// minutes would outrun the budget, with what would fit, or `None` if it
+    /// fits. Retries, watches and directory updates come on top.
+    pub fn plan_warning(&self, dates: usize, providers: usize, minutes: u64) -> Option<String> {
+        let what = format!("Checking {dates} date(s) for {providers} provider(s)");
+        self.warning((dates * providers) as u64, minutes, &what, |fits| {
+            let fits = fits / providers.max(1) as u64;
+            (fits > 0).then(|| format!("check at most {fits} date(s) (a shorter date_range or window_days, or only_weekdays)"))
+        })
+    }
+
+    /// [`BudgetConfig::plan_warning`] for `scan_mode: snapshot`: one
+    /// nationwide request a cycle, plus up to `lookups` for each location's
+    /// soonest date with `snapshot_lookups`.
+    pub fn snapshot_warning(&self, lookups: usize, minutes: u64) -> Option<String> {
+        let what = format!("A nationwide snapshot and up to {lookups} location lookup(s) each cycle");
+        self.warning(1 + lookups as u64, minutes, &what, |_| (lookups > 0).then(|| "turn off snapshot_lookups".to_string()))
+    }
+
+    /// The warning for `per_cycle` requests every `minutes` minutes, if they
+    /// outrun a limit; `fewer` suggests a fix given how many requests a
+    /// cycle could make instead.
+    fn warning(&self, per_cycle: u64, minutes: u64, what: &str, fewer: impl Fn(u64) -> Option<String>) -> Option<String> {
+        self.limits().find_map(|(limit, window, name)| {
+            let limit = u64::from(limit);
+            let cycles = if minutes == 0 { 1 } else { (window.as_secs() / 60).div_ceil(minutes) };
+            let planned = per_cycle * cycles;
+            if planned <= limit {
+                return None;
+            }
+            let mut text = format!(
+                "{what} takes about {planned} requests per {name}, \
+                 more than request_budget.per_{name} ({limit}); requests past it will be refused."
+            );
+            let mut fixes: Vec<String> = fewer(limit / cycles).into_iter().collect();
+            if minutes > 0 {
+                let interval = (window.as_secs() / 60 * per_cycle).div_ceil(limit);
+                fixes.push(format!("set fetch_interval_minutes to at least {interval}"));
+            }
+            if !fixes.is_empty() {
+                text.push_str(&format!(" To stay within it, {}.", fixes.join(" or ")));
+            }
+            Some(text)
+        })
+    }
+}
+
+/// The requests counted against a [`BudgetConfig`], shared by every client
+/// built from one config.
+#[derive(Debug)]
+pub struct RequestBudget {
+    config: BudgetConfig,
+    /// When each request in the last day was sent, oldest first.
+    sent: Mutex<VecDeque<Instant>>,
+    refused: AtomicU64,
+}
+
+impl RequestBudget {
+    pub fn new(config: BudgetConfig) -> Self {
+        RequestBudget {
+            config,
+            sent: Mutex::new(VecDeque::new()),
+            refused: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts one request against the budget, or says which limit it would break.
+    pub fn take(&self) -> Result<(), String> {
+        let now = Instant::now();
+        let mut sent = self.sent.lock().unwrap();
+        while sent.front().is_some_and(|t| now.duration_since(*t) >= DAY) {
+            sent.pop_front();
+        }
+        for (limit, window, name) in self.config.limits() {
+            let used = sent.iter().rev().take_while(|t| now.duration_since(**t) < window).count();
+            if used >= limit as usize {
+                self.refused.fetch_add(1, Ordering::Relaxed);
+                return Err(format!("{limit} per {name}"));
+            }
+        }
+        sent.push_back(now);
+        Ok(())
+    }
+
+    /// Requests refused since the last call.
+    pub fn take_refused(&self) -> u64 {
+        self.refused.swap(0, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(per_hour: Option<u32>, per_day: Option<u32>) -> BudgetConfig {
+        BudgetConfig { per_hour, per_day }
+    }
+
+    #[test]
+    fn plan_warning_suggests_what_fits() {
+        // 10 dates every 10 minutes is 60 requests an hour.
+        assert_eq!(budget(Some(60), None).plan_warning(10, 1, 10), None);
+        let text = budget(Some(30), None).plan_warning(10, 1, 10).unwrap();
+        assert!(text.contains("about 60 requests per hour"), "{text}");
+        assert!(text.contains("check at most 5 date(s)"), "{text}");
+        assert!(text.contains("fetch_interval_minutes to at least 20"), "{text}");
+        // A one-shot run is one cycle in any window.
+        assert_eq!(budget(None, Some(10)).plan_warning(10, 1, 0), None);
+        let text = budget(None, Some(10)).plan_warning(10, 2, 0).unwrap();
+        assert!(text.contains("per_day (10)") && !text.contains("fetch_interval_minutes"), "{text}");
+    }
+
+    #[test]
+    fn snapshot_plan_counts_each_lookup() {
+        // One request every 5 minutes is 12 an hour; with 9 lookups, 120.
+        assert_eq!(budget(Some(12), None).snapshot_warning(0, 5), None);
+        let text = budget(Some(60), None).snapshot_warning(9, 5).unwrap();
+        assert!(text.contains("about 120 requests per hour"), "{text}");
+        assert!(text.contains("turn off snapshot_lookups or set fetch_interval_minutes to at least 10"), "{text}");
+    }
+
+    #[test]
+    fn refuses_past_the_limit() {
+        let budget = RequestBudget::new(budget(Some(2), None));
+        assert!(budget.take().is_ok());
+        assert!(budget.take().is_ok());
+        assert_eq!(budget.take(), Err("2 per hour".to_string()));
+        assert_eq!(budget.take_refused(), 1);
+        assert_eq!(budget.take_refused(), 0);
+    }
+}
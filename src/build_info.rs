@@ -0,0 +1,28 @@
+//! What a binary was built from, as embedded by `build.rs`, so a bug report
+//! can say exactly which build it came from.
+
+use std::io::{self, Write};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short commit hash, `-dirty` if the tree had changes, or `unknown`.
+pub const GIT_HASH: &str = env!("JEFF_GIT_HASH");
+/// UTC date of the build.
+pub const BUILD_DATE: &str = env!("JEFF_BUILD_DATE");
+pub const TARGET: &str = env!("JEFF_TARGET");
+pub const PROFILE: &str = env!("JEFF_PROFILE");
+/// Enabled cargo features, comma-separated.
+pub const FEATURES: &str = env!("JEFF_FEATURES");
+
+/// The version, and with `verbose` one line per detail.
+pub fn print(out: &mut impl Write, verbose: bool) -> io::Result<()> {
+    writeln!(out, "{} {VERSION}", env!("CARGO_PKG_NAME"))?;
+    if verbose {
+        writeln!(out, "commit:   {GIT_HASH}")?;
+        writeln!(out, "built:    {BUILD_DATE}")?;
+        writeln!(out, "target:   {TARGET}")?;
+        writeln!(out, "profile:  {PROFILE}")?;
+        let features = if FEATURES.is_empty() { "none".to_string() } else { FEATURES.replace(',', ", ") };
+        writeln!(out, "features: {features}")?;
+    }
+    Ok(())
+}
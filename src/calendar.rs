@@ -0,0 +1,499 @@
+//! Conflicts between open dates and events on CalDAV calendars, so alerts
+//! flag, or leave out, days you couldn't make. The scan only knows which days
+//! a center has openings, not their times, so a conflict is any event during
+//! `interview_hours` on that day, and a day is blocked when one event covers
+//! those hours entirely. Both are judged on the center's clock: events with a
+//! `TZID` or in UTC are moved into its `tz_data` zone, while all-day and
+//! floating events count as the same wall-clock times everywhere.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use reqwest::{header::CONTENT_TYPE, Client, Method};
+use serde::Deserialize;
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+use crate::{format::DisplayOptions, redact::Secret, tz::Zone, AppError};
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarConfig {
+    /// Whose calendar this is, used in flags; handy with one per person.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The calendar collection, e.g.
+    /// `https://caldav.fastmail.com/dav/calendars/user/me@fastmail.com/Default/`.
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<Secret>,
+    #[serde(default)]
+    pub mode: ConflictMode,
+    /// When interviews happen, as `HH:MM-HH:MM`.
+    #[serde(default = "default_interview_hours")]
+    pub interview_hours: String,
+    /// How long fetched events are reused before asking the server again.
+    #[serde(default = "default_refresh_minutes")]
+    pub refresh_minutes: u64,
+}
+
+fn default_interview_hours() -> String {
+    "08:00-16:30".to_string()
+}
+
+fn default_refresh_minutes() -> u64 {
+    15
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictMode {
+    /// Note conflicts under each location.
+    #[default]
+    Flag,
+    /// Also leave out days the calendar blocks entirely.
+    Filter,
+}
+
+impl CalendarConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.url.starts_with("https://") && !self.url.starts_with("http://") {
+            return Err(format!("url {:?} is not an http(s) URL", self.url));
+        }
+        self.hours()?;
+        Ok(())
+    }
+
+    fn hours(&self) -> Result<(NaiveTime, NaiveTime), String> {
+        let invalid = || format!("interview_hours {:?} is not HH:MM-HH:MM", self.interview_hours);
+        let (start, end) = self.interview_hours.split_once('-').ok_or_else(invalid)?;
+        let parse = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M").map_err(|_| invalid());
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start >= end {
+            return Err(format!("interview_hours {:?} ends before it starts", self.interview_hours));
+        }
+        Ok((start, end))
+    }
+}
+
+/// One event. All-day events run from midnight to midnight.
+#[derive(Debug, Clone)]
+struct Event {
+    summary: String,
+    start: Time,
+    end: Time,
+    all_day: bool,
+}
+
+/// When an event starts or ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Time {
+    /// A date or a time without a zone, the same wall-clock time anywhere.
+    Floating(NaiveDateTime),
+    At(DateTime<Utc>),
+}
+
+impl Time {
+    /// The wall-clock time in `zone`, or on this machine when the center's
+    /// zone isn't known.
+    fn local(self, zone: Option<&Zone>) -> NaiveDateTime {
+        match (self, zone) {
+            (Time::Floating(time), _) => time,
+            (Time::At(at), Some(zone)) => zone.local(at),
+            (Time::At(at), None) => at.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    fn plus(self, duration: chrono::Duration) -> Time {
+        match self {
+            Time::Floating(time) => Time::Floating(time + duration),
+            Time::At(at) => Time::At(at + duration),
+        }
+    }
+}
+
+/// A day at a center, in its `tz_data` zone.
+type Day = (NaiveDate, Option<String>);
+
+/// A day's events during interview hours, from every calendar.
+#[derive(Debug, Default)]
+pub struct Conflicts {
+    by_day: BTreeMap<Day, Vec<String>>,
+    /// Days a filtering calendar blocks entirely.
+    blocked: Vec<Day>,
+}
+
+impl Conflicts {
+    pub fn is_blocked(&self, date: NaiveDate, tz: Option<&str>) -> bool {
+        self.blocked.iter().any(|(d, z)| *d == date && z.as_deref() == tz)
+    }
+
+    /// A flag for each conflicting event on `date` at a center in `tz`, e.g.
+    /// `2025-03-14 conflicts with 'Dentist' (10:00–11:00)`, the date as
+    /// `date_format` asks.
+    pub fn flags(&self, date: NaiveDate, tz: Option<&str>) -> &[String] {
+        self.by_day.get(&(date, tz.map(str::to_string))).map_or(&[], Vec::as_slice)
+    }
+}
+
+pub struct Calendars<'a> {
+    client: Client,
+    calendars: Vec<Calendar<'a>>,
+}
+
+struct Calendar<'a> {
+    config: &'a CalendarConfig,
+    cache: Mutex<Option<Fetched>>,
+}
+
+/// The last events fetched, when, and for which days.
+struct Fetched {
+    at: Instant,
+    range: (NaiveDate, NaiveDate),
+    events: Vec<Event>,
+}
+
+impl<'a> Calendars<'a> {
+    pub fn new(client: &Client, configs: &'a [CalendarConfig]) -> Option<Self> {
+        if configs.is_empty() {
+            return None;
+        }
+        Some(Calendars {
+            client: client.clone(),
+            calendars: configs
+                .iter()
+                .map(|config| Calendar {
+                    config,
+                    cache: Mutex::new(None),
+                })
+                .collect(),
+        })
+    }
+
+    /// Conflicts on `days`, each a date and its center's `tz_data`. A
+    /// calendar that can't be read is logged and reuses its last events, so
+    /// alerts still go out.
+    pub async fn conflicts(&self, days: &[(NaiveDate, Option<&str>)], display: &DisplayOptions) -> Conflicts {
+        let (Some(first), Some(last)) = (days.iter().map(|d| d.0).min(), days.iter().map(|d| d.0).max()) else {
+            return Conflicts::default();
+        };
+        let mut conflicts = Conflicts::default();
+        for calendar in &self.calendars {
+            let events = calendar.events(&self.client, first, last).await;
+            add_conflicts(&mut conflicts, calendar.config, &events, days, display);
+        }
+        conflicts
+    }
+}
+
+/// Adds the conflicts one calendar's `events` make on `days`.
+fn add_conflicts(
+    conflicts: &mut Conflicts,
+    config: &CalendarConfig,
+    events: &[Event],
+    days: &[(NaiveDate, Option<&str>)],
+    display: &DisplayOptions,
+) {
+    let Ok((open, close)) = config.hours() else {
+        return;
+    };
+    let mut zones: BTreeMap<&str, Option<Zone>> = BTreeMap::new();
+    for &(date, tz) in days {
+        let zone = tz.and_then(|tz| zones.entry(tz).or_insert_with(|| Zone::named(tz)).as_ref());
+        let (open, close) = (date.and_time(open), date.and_time(close));
+        for event in events {
+            let (start, end) = (event.start.local(zone), event.end.local(zone));
+            if start >= close || end <= open {
+                continue;
+            }
+            let when = if event.all_day {
+                "all day".to_string()
+            } else {
+                format!("{}–{}", start.format("%H:%M"), end.format("%H:%M"))
+            };
+            let whose = config.name.as_deref().map(|name| format!("{name}'s ")).unwrap_or_default();
+            let day = (date, tz.map(str::to_string));
+            conflicts.by_day.entry(day.clone()).or_default().push(format!(
+                "{} conflicts with {whose}'{}' ({when})",
+                display.date(date),
+                event.summary
+            ));
+            let covers = start <= open && end >= close;
+            if config.mode == ConflictMode::Filter && covers && !conflicts.blocked.contains(&day) {
+                conflicts.blocked.push(day);
+            }
+        }
+    }
+}
+
+impl Calendar<'_> {
+    async fn events(&self, client: &Client, first: NaiveDate, last: NaiveDate) -> Vec<Event> {
+        let mut cache = self.cache.lock().await;
+        let refresh = Duration::from_secs(self.config.refresh_minutes * 60);
+        let fresh = |f: &&Fetched| f.at.elapsed() < refresh && f.range == (first, last);
+        if let Some(fetched) = cache.as_ref().filter(fresh) {
+            debug!("Reusing calendar events from {}s ago", fetched.at.elapsed().as_secs());
+            return fetched.events.clone();
+        }
+        match fetch(client, self.config, first, last).await {
+            Ok(events) => {
+                debug!("Read {} event(s) from {}", events.len(), self.config.url);
+                *cache = Some(Fetched {
+                    at: Instant::now(),
+                    range: (first, last),
+                    events: events.clone(),
+                });
+                events
+            }
+            Err(e) => {
+                warn!("Error reading calendar {}: {e}", self.config.url);
+                cache.as_ref().map(|f| f.events.clone()).unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// Every event from `first` through `last`, with recurring ones expanded by
+/// the server.
+async fn fetch(client: &Client, config: &CalendarConfig, first: NaiveDate, last: NaiveDate) -> Result<Vec<Event>, AppError> {
+    // A day wider each side, as the centers' days may fall anywhere in UTC.
+    let utc = |date: Option<NaiveDate>| date.map(|d| d.and_time(NaiveTime::MIN).and_utc().format("%Y%m%dT%H%M%SZ"));
+    let (Some(start), Some(end)) = (utc(first.pred_opt()), utc(last.checked_add_days(chrono::Days::new(2)))) else {
+        return Ok(Vec::new());
+    };
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <c:calendar-data><c:expand start="{start}" end="{end}"/></c:calendar-data>
+  </d:prop>
+  <c:filter>
+    <c:comp-filter name="VCALENDAR">
+      <c:comp-filter name="VEVENT">
+        <c:time-range start="{start}" end="{end}"/>
+      </c:comp-filter>
+    </c:comp-filter>
+  </c:filter>
+</c:calendar-query>"#
+    );
+    let mut request = client
+        .request(Method::from_bytes(b"REPORT").expect("valid method"), &config.url)
+        .header("Depth", "1")
+        .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(body);
+    if let Some(username) = &config.username {
+        request = request.basic_auth(username, config.password.as_ref().map(Secret::expose));
+    }
+    let text = request.send().await?.error_for_status()?.text().await?;
+    Ok(calendar_data(&text).iter().flat_map(|ics| parse_events(ics)).collect())
+}
+
+/// The iCalendar text in each `calendar-data` element of a multistatus
+/// response, whatever its namespace prefix.
+fn calendar_data(xml: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let Some(close) = rest.find('>') else { break };
+        let tag = &rest[..close];
+        rest = &rest[close + 1..];
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        if name.starts_with('/') || tag.ends_with('/') || name.rsplit(':').next() != Some("calendar-data") {
+            continue;
+        }
+        let end = rest.find("</").unwrap_or(rest.len());
+        let content = rest[..end].trim();
+        found.push(match content.strip_prefix("<![CDATA[").and_then(|c| c.strip_suffix("]]>")) {
+            Some(cdata) => cdata.to_string(),
+            None => unescape_xml(content),
+        });
+        rest = &rest[end..];
+    }
+    found
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&amp;", "&")
+}
+
+/// The busy events in one iCalendar object; cancelled and free ("transparent")
+/// ones are left out.
+fn parse_events(ics: &str) -> Vec<Event> {
+    let unfolded = ics.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    let mut events = Vec::new();
+    let mut props: Option<Vec<(&str, &str, &str)>> = None;
+    for line in unfolded.lines() {
+        match line {
+            "BEGIN:VEVENT" => props = Some(Vec::new()),
+            "END:VEVENT" => {
+                if let Some(event) = props.take().and_then(|props| event(&props)) {
+                    events.push(event);
+                }
+            }
+            _ => {
+                let (Some(props), Some((key, value))) = (props.as_mut(), line.split_once(':')) else {
+                    continue;
+                };
+                let (name, params) = key.split_once(';').unwrap_or((key, ""));
+                props.push((name, params, value));
+            }
+        }
+    }
+    events
+}
+
+/// An event from its properties: name, parameters and value.
+fn event(props: &[(&str, &str, &str)]) -> Option<Event> {
+    let get = |name: &str| props.iter().find(|(n, _, _)| n.eq_ignore_ascii_case(name));
+    if get("STATUS").is_some_and(|(_, _, v)| v.eq_ignore_ascii_case("CANCELLED"))
+        || get("TRANSP").is_some_and(|(_, _, v)| v.eq_ignore_ascii_case("TRANSPARENT"))
+    {
+        return None;
+    }
+    let (_, params, value) = get("DTSTART")?;
+    let (start, all_day) = timestamp(params, value)?;
+    let end = match (get("DTEND"), get("DURATION")) {
+        (Some((_, params, value)), _) => timestamp(params, value)?.0,
+        (None, Some((_, _, value))) => start.plus(duration(value)?),
+        (None, None) if all_day => start.plus(chrono::Duration::days(1)),
+        (None, None) => start,
+    };
+    let summary = get("SUMMARY").map_or("busy", |(_, _, v)| v);
+    let summary = summary.replace("\\n", " ").replace("\\N", " ").replace("\\,", ",").replace("\\;", ";");
+    Some(Event {
+        summary: summary.replace("\\\\", "\\"),
+        start,
+        end,
+        all_day,
+    })
+}
+
+/// A `DTSTART` or `DTEND`, and whether it's a whole day. Times with a `TZID`
+/// this machine doesn't know are taken as floating.
+fn timestamp(params: &str, value: &str) -> Option<(Time, bool)> {
+    if params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((Time::Floating(date.and_time(NaiveTime::MIN)), true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Time::At(utc.and_utc()), false));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let tzid = params.split(';').find_map(|param| param.strip_prefix("TZID="));
+    match tzid.and_then(|tzid| Zone::named(tzid.trim_matches('"'))) {
+        Some(zone) => Some((Time::At(zone.to_utc(time)), false)),
+        None => Some((Time::Floating(time), false)),
+    }
+}
+
+/// An iCalendar `DURATION` such as `PT1H30M` or `P1D`.
+fn duration(value: &str) -> Option<chrono::Duration> {
+    let mut total = chrono::Duration::zero();
+    let mut number = String::new();
+    let mut time = false;
+    for c in value.trim_start_matches(['+', 'P']).chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => time = true,
+            _ => {
+                let n: i64 = std::mem::take(&mut number).parse().ok()?;
+                total += match (c, time) {
+                    ('W', false) => chrono::Duration::weeks(n),
+                    ('D', false) => chrono::Duration::days(n),
+                    ('H', true) => chrono::Duration::hours(n),
+                    ('M', true) => chrono::Duration::minutes(n),
+                    ('S', true) => chrono::Duration::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_events_from_multistatus() {
+        let xml = "<d:multistatus xmlns:d=\"DAV:\" xmlns:C=\"urn:ietf:params:xml:ns:caldav\"><d:response><d:propstat><d:prop>\
+                   <C:calendar-data>BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;TZID=America/New_York:20250314T100000\r\n\
+                   DURATION:PT1H30M\r\nSUMMARY:Dentist\\, cleaning &amp; x-\r\n ray\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\n\
+                   DTSTART;VALUE=DATE:20250315\r\nSUMMARY:Off\r\nSTATUS:CANCELLED\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n\
+                   </C:calendar-data></d:prop></d:propstat></d:response></d:multistatus>";
+        let events: Vec<Event> = calendar_data(xml).iter().flat_map(|ics| parse_events(ics)).collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, "Dentist, cleaning & x-ray");
+        let zone = Zone::named("America/New_York");
+        if zone.is_some() {
+            assert_eq!(events[0].start, Time::At("2025-03-14T14:00:00Z".parse().unwrap()));
+        }
+        assert_eq!(events[0].start.local(zone.as_ref()).to_string(), "2025-03-14 10:00:00");
+        assert_eq!(events[0].end.local(zone.as_ref()).to_string(), "2025-03-14 11:30:00");
+        assert!(!events[0].all_day);
+    }
+
+    #[test]
+    fn conflicts_are_judged_on_each_centers_clock() {
+        if Zone::named("America/New_York").is_none() {
+            eprintln!("No tz database here; skipping");
+            return;
+        }
+        let events = parse_events(
+            "BEGIN:VEVENT\nDTSTART;TZID=America/New_York:20250314T100000\nDTEND;TZID=America/New_York:20250314T113000\n\
+             SUMMARY:Dentist\nEND:VEVENT\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20250315\nSUMMARY:Off\nEND:VEVENT\n\
+             BEGIN:VEVENT\nDTSTART:20250317T150000Z\nDURATION:PT1H\nSUMMARY:Call\nTRANSP:TRANSPARENT\nEND:VEVENT\n",
+        );
+        let config: CalendarConfig =
+            serde_json::from_str(r#"{"url": "https://dav.example/", "mode": "filter"}"#).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 3, day).unwrap();
+        let (east, west, tokyo) = (Some("America/New_York"), Some("America/Los_Angeles"), Some("Asia/Tokyo"));
+        let days = [(date(14), east), (date(14), west), (date(14), tokyo), (date(15), west), (date(17), east)];
+        let display = DisplayOptions {
+            date_format: crate::format::DateFormat::Short,
+            ..Default::default()
+        };
+        let mut conflicts = Conflicts::default();
+        add_conflicts(&mut conflicts, &config, &events, &days, &display);
+
+        assert_eq!(conflicts.flags(date(14), east), ["Mar 14 (Fri) conflicts with 'Dentist' (10:00–11:30)"]);
+        // 07:00-08:30 in Los Angeles overlaps interview hours from 08:00.
+        assert_eq!(conflicts.flags(date(14), west), ["Mar 14 (Fri) conflicts with 'Dentist' (07:00–08:30)"]);
+        // Late evening in Tokyo.
+        assert!(conflicts.flags(date(14), tokyo).is_empty());
+        assert!(!conflicts.is_blocked(date(14), east));
+        assert_eq!(conflicts.flags(date(15), west), ["Mar 15 (Sat) conflicts with 'Off' (all day)"]);
+        assert!(conflicts.is_blocked(date(15), west));
+        assert!(!conflicts.is_blocked(date(15), east));
+        assert!(conflicts.flags(date(17), east).is_empty());
+    }
+
+    #[test]
+    fn flagging_calendars_never_block() {
+        let events =
+            parse_events("BEGIN:VEVENT\nDTSTART;VALUE=DATE:20250315\nDTEND;VALUE=DATE:20250317\nSUMMARY:Trip\nEND:VEVENT\n");
+        let config: CalendarConfig =
+            serde_json::from_str(r#"{"url": "https://dav.example/", "name": "Sam", "interview_hours": "09:00-12:00"}"#)
+                .unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 3, day).unwrap();
+        let days = [(date(15), None), (date(16), None), (date(17), None)];
+        let mut conflicts = Conflicts::default();
+        add_conflicts(&mut conflicts, &config, &events, &days, &DisplayOptions::default());
+        assert_eq!(conflicts.flags(date(16), None), ["2025-03-16 conflicts with Sam's 'Trip' (all day)"]);
+        assert!(conflicts.flags(date(17), None).is_empty());
+        assert!(!conflicts.is_blocked(date(16), None));
+    }
+}
@@ -0,0 +1,482 @@
+//! The command-line front end; `main.rs` only hands over to [`main`].
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+};
+
+use chrono::{Local, NaiveDate};
+use clap::{Parser, Subcommand};
+#[cfg(feature = "tray")]
+use tracing::error;
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    api_client, build_info, clock,
+    directory::Directory,
+    dns::Dns,
+    filters,
+    history::History,
+    instance::InstanceLock,
+    load_config, preflight, preview,
+    providers::{self, GLOBAL_ENTRY_ID},
+    query,
+    redact::Scrubbed,
+    run, schema,
+    state::StateStore,
+    status,
+    ttp_client::TtpApi,
+    AppError, Controls, Exit, JeffConfig,
+};
+#[cfg(feature = "sqlite")]
+use crate::{import, retention};
+#[cfg(feature = "tray")]
+use crate::tray;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Finds open Global Entry interview appointments")]
+struct Cli {
+    /// Path to the config file
+    #[arg(long, default_value = ".jeff")]
+    config: PathBuf,
+    /// Log full API response bodies at debug level (they may contain personal data)
+    #[arg(long, global = true)]
+    log_bodies: bool,
+    /// Run even if another instance is running against the same state file
+    #[arg(long, global = true)]
+    force: bool,
+    /// Run the first cycle right away, even with `run_at_startup` off
+    #[arg(long, global = true)]
+    immediate: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Fetch appointments once or on the configured interval (default)
+    Run,
+    /// Export the stored history to a Parquet file
+    ExportHistory {
+        #[arg(default_value = "history.parquet")]
+        output: PathBuf,
+    },
+    /// Inspect or refresh the enrollment center directory
+    Locations {
+        #[command(subcommand)]
+        action: LocationsCommand,
+    },
+    /// Backfill the history store from earlier CSV/JSON exports
+    Import {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Check the configured filters against recorded data
+    Filters {
+        #[command(subcommand)]
+        action: FiltersCommand,
+    },
+    /// Report the finder's health, last cycle and stored data
+    Status {
+        #[arg(long, value_enum, default_value = "text")]
+        format: status::Format,
+    },
+    /// Run a read-only SQL query over the stored history
+    Query {
+        sql: String,
+        #[arg(long, value_enum, default_value = "table")]
+        format: query::OutputFormat,
+    },
+    /// Roll old history into daily summaries and shrink the database
+    Prune {
+        /// Days of raw history to keep (defaults to `retention.raw_days`, or 90)
+        #[arg(long)]
+        days: Option<u32>,
+        /// Report what would be pruned without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Silence alerts for slots or whole locations, or lift that, while running
+    Suppress {
+        #[command(subcommand)]
+        action: SuppressCommand,
+    },
+    /// Check channel credentials, output paths and the API as `run` does
+    /// before starting, and report every result
+    Check,
+    /// Print, without sending, the message each channel would get for sample
+    /// results or those of the latest cycle
+    Preview {
+        /// Only this channel, e.g. `slack` or `email`; repeat for more
+        #[arg(long = "notifier")]
+        notifiers: Vec<String>,
+        /// An export or saved API response to use instead of the latest cycle
+        #[arg(long)]
+        fixture: Option<PathBuf>,
+    },
+    /// Maintenance tasks for working on the finder itself
+    Dev {
+        #[command(subcommand)]
+        action: DevCommand,
+    },
+    /// Run on the configured interval with a desktop tray icon
+    #[cfg(feature = "tray")]
+    Watch,
+    /// Print the version; needs no config file
+    Version {
+        /// Also print the commit, build date, target and enabled features
+        #[arg(long)]
+        verbose: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum DevCommand {
+    /// Fetch live API responses and compare their shape with the committed
+    /// schemas; exits 6 when they differ
+    CaptureSchema {
+        /// Rewrite `tests/schema` and the `live_*` fixtures from the responses
+        #[arg(long)]
+        update: bool,
+        /// Repository checkout to read and write (defaults to the current directory)
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum LocationsCommand {
+    /// Download the current directory from CBP
+    Update {
+        /// Where to write it (defaults to `directory_path`); use
+        /// `data/locations.json` to refresh the snapshot built into the binary
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the soonest open slots at one location
+    Slots {
+        id: usize,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Print known enrollment centers
+    List {
+        /// Only show centers in this state
+        #[arg(long)]
+        state: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SuppressCommand {
+    /// Stop alerting on slots (`5140@2025-03-14`) or on every date at a
+    /// location (`5140`)
+    Add {
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// Only for this many hours, as a mute link in an alert does
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        hours: Option<u32>,
+    },
+    /// Alert on these slots or locations again
+    Remove {
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Print what's suppressed
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum FiltersCommand {
+    /// Report which recorded locations the filters keep, and why others are dropped
+    Test {
+        /// Saved API responses (e.g. from `body_log.capture_dir`), exports, or
+        /// directories of them
+        #[arg(long, required = true, num_args = 1..)]
+        against: Vec<PathBuf>,
+    },
+}
+
+/// Parses the command line, runs the command and returns the exit code.
+pub async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(Scrubbed(std::io::stdout))
+        .init();
+
+    // Usage errors exit with 2 (`Exit::Config`) from clap itself.
+    let cli = Cli::parse();
+    // `version` must work without a config, e.g. in a bug report.
+    if let Some(Command::Version { verbose }) = cli.command {
+        return match build_info::print(&mut std::io::stdout().lock(), verbose) {
+            Ok(()) => Exit::Ok.into(),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                Exit::Failure.into()
+            }
+        };
+    }
+    let mut config = match load_config(&cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return Exit::Config.into();
+        }
+    };
+    config.run_at_startup |= cli.immediate;
+    config.body_log.log_bodies |= cli.log_bodies;
+    let config = Arc::new(config);
+
+    match execute(cli.command.unwrap_or(Command::Run), config, cli.force).await {
+        Ok(exit) => exit.into(),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            e.exit().into()
+        }
+    }
+}
+
+async fn execute(command: Command, config: Arc<JeffConfig>, force: bool) -> Result<Exit, AppError> {
+    match command {
+        Command::Run => {
+            let _lock = InstanceLock::acquire(&config.state_path, force)?;
+            run(config, Arc::new(Controls::new())).await
+        }
+        #[cfg(feature = "parquet")]
+        Command::ExportHistory { output } => {
+            let history = History::open(&config.history_path)?;
+            crate::export::parquet::export_history(&history, output)?;
+            Ok(Exit::Ok)
+        }
+        #[cfg(not(feature = "parquet"))]
+        Command::ExportHistory { .. } => Err(AppError::Config(
+            "export-history: this build doesn't include Parquet support; rebuild with `--features parquet`".to_string(),
+        )),
+        Command::Status { format } => {
+            status::print(&config, format, &mut std::io::stdout().lock())?;
+            Ok(Exit::Ok)
+        }
+        Command::Locations { action } => {
+            locations(&config, action).await?;
+            Ok(Exit::Ok)
+        }
+        #[cfg(feature = "sqlite")]
+        Command::Import { files } => {
+            let files = files
+                .iter()
+                .map(|path| import::read(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            let count = files.len();
+            let rows = import::import(&mut History::open(&config.history_path)?, files)?;
+            println!("Imported {rows} rows from {count} file(s) into {}", config.history_path);
+            Ok(Exit::Ok)
+        }
+        Command::Filters {
+            action: FiltersCommand::Test { against },
+        } => {
+            filters::test(&config, &against, &mut std::io::stdout().lock())?;
+            Ok(Exit::Ok)
+        }
+        #[cfg(feature = "sqlite")]
+        Command::Query { sql, format } => {
+            let history = History::open_read_only(&config.history_path)?;
+            query::run(&history, &sql, format, &mut std::io::stdout().lock())?;
+            Ok(Exit::Ok)
+        }
+        #[cfg(feature = "sqlite")]
+        Command::Prune { days, dry_run } => {
+            retention::run(&config, days, dry_run, &mut std::io::stdout().lock())?;
+            Ok(Exit::Ok)
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Command::Import { .. } | Command::Query { .. } | Command::Prune { .. } => Err(AppError::Config(
+            "this build doesn't include the history database; rebuild with `--features sqlite`".to_string(),
+        )),
+        Command::Suppress { action } => {
+            suppress(&config, action)?;
+            Ok(Exit::Ok)
+        }
+        Command::Check => preflight::print(&config, &mut std::io::stdout().lock()).await,
+        Command::Preview { notifiers, fixture } => {
+            preview::print(&config, &notifiers, fixture.as_deref(), &mut std::io::stdout().lock())?;
+            Ok(Exit::Ok)
+        }
+        Command::Dev {
+            action: DevCommand::CaptureSchema { update, root },
+        } => {
+            let drifted = schema::capture(&config, &root, update, &mut std::io::stdout().lock()).await?;
+            Ok(if drifted { Exit::SchemaDrift } else { Exit::Ok })
+        }
+        #[cfg(feature = "tray")]
+        Command::Watch => {
+            let _lock = InstanceLock::acquire(&config.state_path, force)?;
+            let controls = Arc::new(Controls::new());
+            tokio::spawn({
+                let (config, controls) = (Arc::clone(&config), Arc::clone(&controls));
+                async move {
+                    if let Err(e) = run(config, controls).await {
+                        error!("{e}");
+                    }
+                }
+            });
+            tray::run(config, controls)
+        }
+        Command::Version { .. } => unreachable!("handled before the config is loaded"),
+    }
+}
+
+async fn locations(config: &JeffConfig, action: LocationsCommand) -> Result<(), AppError> {
+    match action {
+        LocationsCommand::Update { output } => {
+            let path = output.unwrap_or_else(|| PathBuf::from(&config.directory_path));
+            let dns = Dns::new(config.dns.as_ref()).low_memory(config.low_memory);
+            let directory = Directory::update(&api_client(config, &dns, dns.builder().build()?)?, &path).await?;
+            println!("Saved {} locations to {}", directory.centers().len(), path.display());
+        }
+        LocationsCommand::Slots { id, limit } => {
+            let dns = Dns::new(config.dns.as_ref()).low_memory(config.low_memory);
+            let api = api_client(config, &dns, dns.builder().build()?)?;
+            for slot in api.slots_for_location(id, limit).await?.iter().filter(|s| s.active) {
+                println!(
+                    "{} - {} ({} min)",
+                    slot.start_timestamp.format("%Y-%m-%d %H:%M"),
+                    slot.end_timestamp.format("%H:%M"),
+                    slot.duration
+                );
+            }
+        }
+        LocationsCommand::List { state } => {
+            let directory = Directory::load(&config.directory_path)?;
+            if directory.is_empty() {
+                println!("Location directory is empty; run `locations update` first.");
+            }
+            for c in directory.centers() {
+                if state.as_ref().is_some_and(|s| !s.eq_ignore_ascii_case(&c.state)) {
+                    continue;
+                }
+                let tz = directory.timezone(c.id).unwrap_or("");
+                println!("{:>6}  {}  {:<20} {:<20} {}", c.id, c.state, c.city, tz, c.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn suppress(config: &JeffConfig, action: SuppressCommand) -> Result<(), AppError> {
+    let path = &config.state_path;
+    match action {
+        SuppressCommand::Add { keys, hours: None } => {
+            let (slots, locations) = suppression_keys(&keys)?;
+            let added = StateStore::edit(path, |store| store.suppress(&slots) + store.suppress_locations(&locations))?;
+            println!("Suppressed {added}{}", already(keys.len() - added, "already suppressed"));
+        }
+        SuppressCommand::Add { keys, hours: Some(hours) } => {
+            suppression_keys(&keys)?;
+            let until = clock::now() + chrono::Duration::hours(i64::from(hours));
+            let added = StateStore::edit(path, |store| store.mute(&keys, until))?;
+            println!(
+                "Muted {added} until {}{}",
+                until.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                already(keys.len() - added, "already muted")
+            );
+        }
+        SuppressCommand::Remove { keys } => {
+            let parsed = keys
+                .iter()
+                .map(|key| suppression_keys(std::slice::from_ref(key)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let removed = StateStore::edit(path, |store| {
+                // A key can be both suppressed and muted; count it once.
+                keys.iter()
+                    .zip(&parsed)
+                    .filter(|(key, (slots, locations))| {
+                        let lifted = store.unsuppress(slots)
+                            + store.unsuppress_locations(locations)
+                            + store.unmute(std::slice::from_ref(*key));
+                        lifted > 0
+                    })
+                    .count()
+            })?;
+            println!("Lifted {removed}{}", already(keys.len() - removed, "not suppressed"));
+        }
+        SuppressCommand::List => {
+            let store = StateStore::load(path)?;
+            // Names of locations seen before; the rest are left blank.
+            let names = if Path::new(&config.history_path).exists() {
+                History::open_read_only(&config.history_path)?.location_names()?
+            } else {
+                Default::default()
+            };
+            let name = |key: &str| names.get(key.split('@').next().unwrap_or(key)).map_or("", String::as_str);
+            let locations: Vec<&str> = store.suppressed_locations().collect();
+            let slots: Vec<&str> = store.suppressed_slots().collect();
+            let muted: Vec<_> = store.muted().collect();
+            if locations.is_empty() && slots.is_empty() && muted.is_empty() {
+                println!("Nothing is suppressed.");
+            }
+            if !locations.is_empty() {
+                println!("Locations (every date):");
+                for key in locations {
+                    println!("  {key:<20} {}", name(key));
+                }
+            }
+            if !slots.is_empty() {
+                println!("Slots:");
+                for key in slots {
+                    println!("  {key:<20} {}", name(key));
+                }
+            }
+            if !muted.is_empty() {
+                println!("Muted for now:");
+                for (key, until) in muted {
+                    let until = until.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+                    println!("  {key:<20} until {until}  {}", name(key));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn already(n: usize, what: &str) -> String {
+    if n == 0 {
+        String::new()
+    } else {
+        format!(" ({n} {what})")
+    }
+}
+
+/// Sorts `keys` into slot keys (`5140@2025-03-14`) and location keys
+/// (`5140`), rejecting anything else.
+fn suppression_keys(keys: &[String]) -> Result<(Vec<String>, Vec<String>), AppError> {
+    let (mut slots, mut locations) = (Vec::new(), Vec::new());
+    for key in keys {
+        let invalid = |why: &str| {
+            AppError::General(format!(
+                "{key:?}: {why}; expected a location such as 5140, optionally followed by @YYYY-MM-DD"
+            ))
+        };
+        let (location, date) = match key.split_once('@') {
+            Some((location, date)) => (location, Some(date)),
+            None => (key.as_str(), None),
+        };
+        let id = match location.split_once(':') {
+            Some((provider, id)) if provider != GLOBAL_ENTRY_ID && providers::display_name(provider) != "Unknown" => id,
+            Some((provider, _)) => return Err(invalid(&format!("unknown provider {provider:?}"))),
+            None => location,
+        };
+        if id.parse::<usize>().is_err() {
+            return Err(invalid("not a location ID"));
+        }
+        match date {
+            Some(date) => {
+                NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid("not a date"))?;
+                slots.push(key.clone());
+            }
+            None => locations.push(key.clone()),
+        }
+    }
+    Ok((slots, locations))
+}
@@ -0,0 +1,183 @@
+//! Where the finder reads the wall clock. Sleeps, intervals and backoffs use
+//! `tokio::time`, which tests can pause and advance; [`SimulatedClock`]
+//! derives the date and time from that same clock, so a paused test can let
+//! days pass and watch quiet hours, digests and date windows roll over.
+//! [`SkewCheck`] compares the wall clock with the scheduler API's, since a
+//! drifting clock quietly shifts date windows and "today".
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use reqwest::{
+    header::{HeaderMap, DATE},
+    Response,
+};
+use tracing::{info, warn};
+
+use crate::ttp_client::RequestDecorator;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Starts at a fixed time and moves with `tokio::time`, so under
+/// `#[tokio::test(start_paused = true)]` it only moves when the test sleeps
+/// or calls `tokio::time::advance`.
+pub struct SimulatedClock {
+    start: DateTime<Utc>,
+    at: tokio::time::Instant,
+}
+
+impl SimulatedClock {
+    pub fn starting_at(start: DateTime<Utc>) -> SimulatedClock {
+        SimulatedClock {
+            start,
+            at: tokio::time::Instant::now(),
+        }
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = chrono::Duration::from_std(self.at.elapsed()).unwrap_or(chrono::Duration::MAX);
+        self.start + elapsed
+    }
+}
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Puts `clock` in place of the system's on this thread until the guard is
+/// dropped. A `#[tokio::test]` runs on one thread, so this covers the whole
+/// test without touching others running alongside it.
+pub fn install(clock: impl Clock + 'static) -> Installed {
+    let previous = OVERRIDE.with(|o| o.replace(Some(Arc::new(clock))));
+    Installed { previous }
+}
+
+pub struct Installed {
+    previous: Option<Arc<dyn Clock>>,
+}
+
+impl Drop for Installed {
+    fn drop(&mut self) {
+        OVERRIDE.with(|o| *o.borrow_mut() = self.previous.take());
+    }
+}
+
+pub fn now() -> DateTime<Utc> {
+    OVERRIDE.with(|o| o.borrow().as_ref().map(|clock| clock.now())).unwrap_or_else(|| SystemClock.now())
+}
+
+pub fn local_now() -> DateTime<Local> {
+    now().with_timezone(&Local)
+}
+
+/// Today's date in UTC, which is how the scheduler API dates slots.
+pub fn today() -> NaiveDate {
+    now().date_naive()
+}
+
+/// The largest `max_clock_skew_seconds`: a day. Clocks further apart than
+/// that put every date window off anyway.
+pub const MAX_SKEW_SECONDS: u64 = 86_400;
+
+/// Warns when the `Date` header of API responses and [`now`] differ by more
+/// than a threshold, once until they agree again. The header has whole
+/// seconds and is stamped before the response travels, so small thresholds
+/// will trip on latency alone.
+pub struct SkewCheck {
+    max: chrono::Duration,
+    warned: AtomicBool,
+}
+
+impl SkewCheck {
+    pub fn new(max_seconds: u64) -> SkewCheck {
+        SkewCheck {
+            max: i64::try_from(max_seconds)
+                .ok()
+                .and_then(chrono::Duration::try_seconds)
+                .unwrap_or(chrono::Duration::MAX),
+            warned: AtomicBool::new(false),
+        }
+    }
+}
+
+/// How far `now` is ahead of an HTTP `Date` header; negative if behind.
+fn skew(date: &str, now: DateTime<Utc>) -> Option<chrono::Duration> {
+    let server = DateTime::parse_from_rfc2822(date).ok()?;
+    Some(now - server.with_timezone(&Utc))
+}
+
+impl RequestDecorator for SkewCheck {
+    fn decorate(&self, _headers: &mut HeaderMap) {}
+
+    fn observe(&self, response: &Response) {
+        let date = response.headers().get(DATE).and_then(|v| v.to_str().ok());
+        let Some(skew) = date.and_then(|date| skew(date, now())) else {
+            return;
+        };
+        if skew.abs() <= self.max {
+            if self.warned.swap(false, Ordering::Relaxed) {
+                info!("System clock agrees with the scheduler API again");
+            }
+            return;
+        }
+        if !self.warned.swap(true, Ordering::Relaxed) {
+            let direction = if skew > chrono::Duration::zero() { "ahead of" } else { "behind" };
+            warn!(
+                "System clock is {}s {direction} the scheduler API's; date windows and \"today\" may be off. \
+                 Check that NTP is running.",
+                skew.num_seconds().abs()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn simulated_days_pass_with_paused_time() {
+        let _clock = install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2026, 3, 13, 23, 0, 0).unwrap()));
+        assert_eq!(today(), NaiveDate::from_ymd_opt(2026, 3, 13).unwrap());
+        tokio::time::sleep(Duration::from_secs(2 * 3600)).await;
+        assert_eq!(today(), NaiveDate::from_ymd_opt(2026, 3, 14).unwrap());
+    }
+
+    #[test]
+    fn skew_threshold_saturates_instead_of_panicking() {
+        assert_eq!(SkewCheck::new(120).max, chrono::Duration::minutes(2));
+        assert_eq!(SkewCheck::new(u64::MAX).max, chrono::Duration::MAX);
+        assert_eq!(SkewCheck::new(i64::MAX as u64).max, chrono::Duration::MAX);
+    }
+
+    #[test]
+    fn skew_from_date_header() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 13, 12, 5, 0).unwrap();
+        assert_eq!(skew("Fri, 13 Mar 2026 12:00:00 GMT", now), Some(chrono::Duration::minutes(5)));
+        assert_eq!(skew("Fri, 13 Mar 2026 12:06:30 GMT", now), Some(chrono::Duration::seconds(-90)));
+        assert_eq!(skew("yesterday", now), None);
+    }
+}
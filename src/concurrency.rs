@@ -0,0 +1,184 @@
+//! Adaptive concurrency for the date scan. Like TCP's congestion window, the
+//! number of fetches in flight grows by one for each round of healthy fetches
+//! and is halved when the API shows strain (throttling, error pages, failed
+//! requests or slow answers), staying within `min` and `max`. The limit
+//! carries over from cycle to cycle, so each scan starts where the last left
+//! off rather than at `max_concurrent_fetches`.
+
+use std::{sync::Mutex, time::Duration};
+
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::{ttp_client::TtpError, AppError};
+
+/// What the limit is multiplied by when the API shows strain.
+const DECREASE: f64 = 0.5;
+
+#[derive(Debug, Deserialize)]
+pub struct AdaptiveConcurrencyConfig {
+    /// Fewest fetches kept in flight, however badly the API is doing.
+    #[serde(default = "default_min")]
+    pub min: usize,
+    /// Most fetches in flight, however well the API is doing.
+    pub max: usize,
+    /// A fetch slower than this, retries included, counts as strain.
+    #[serde(default = "default_latency_ms")]
+    pub latency_ms: u64,
+}
+
+fn default_min() -> usize {
+    1
+}
+
+fn default_latency_ms() -> u64 {
+    5000
+}
+
+#[derive(Debug)]
+struct Window {
+    limit: f64,
+    /// Fetches still to finish before strain can cut the limit again, so
+    /// that the ones already in flight when it was cut don't cut it again.
+    hold: usize,
+}
+
+impl AdaptiveConcurrencyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min == 0 {
+            return Err("min must be at least 1".to_string());
+        }
+        if self.max < self.min {
+            return Err(format!("max ({}) is below min ({})", self.max, self.min));
+        }
+        Ok(())
+    }
+}
+
+/// The limit itself, kept for the life of the process.
+#[derive(Debug)]
+pub struct AdaptiveConcurrency {
+    min: usize,
+    max: usize,
+    latency: Duration,
+    window: Mutex<Window>,
+}
+
+impl AdaptiveConcurrency {
+    /// A limit between `config`'s bounds, starting from `start`
+    /// (`max_concurrent_fetches`).
+    pub fn new(config: &AdaptiveConcurrencyConfig, start: usize) -> Self {
+        AdaptiveConcurrency {
+            min: config.min,
+            max: config.max,
+            latency: Duration::from_millis(config.latency_ms),
+            window: Mutex::new(Window {
+                limit: start.clamp(config.min, config.max) as f64,
+                hold: 0,
+            }),
+        }
+    }
+
+    /// How many fetches to keep in flight now.
+    pub fn limit(&self) -> usize {
+        self.window.lock().unwrap().limit as usize
+    }
+
+    /// Adjusts the limit for a fetch that took `latency` and ended in `result`.
+    pub fn record<T>(&self, latency: Duration, result: &Result<T, AppError>) {
+        let strained = match result {
+            Ok(_) => latency > self.latency,
+            Err(AppError::TtpError(TtpError::Unavailable { .. } | TtpError::Http(_)) | AppError::HttpError(_)) => true,
+            // A refused budget or a malformed body says nothing about load.
+            Err(_) => return,
+        };
+        let mut window = self.window.lock().unwrap();
+        let before = window.limit as usize;
+        let holding = window.hold > 0;
+        window.hold = window.hold.saturating_sub(1);
+        if !strained {
+            window.limit = (window.limit + 1.0 / window.limit).min(self.max as f64);
+        } else if !holding {
+            window.limit = (window.limit * DECREASE).max(self.min as f64);
+            window.hold = before;
+        }
+        let after = window.limit as usize;
+        match result {
+            _ if after == before => {}
+            _ if !strained => debug!("Concurrent fetches: {before} -> {after}"),
+            Err(e) => info!("Concurrent fetches: {before} -> {after} after: {e}"),
+            Ok(_) => info!(
+                "Concurrent fetches: {before} -> {after} after a {:.1}s fetch",
+                latency.as_secs_f64()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(min: usize, max: usize, start: usize) -> AdaptiveConcurrency {
+        let config = AdaptiveConcurrencyConfig {
+            min,
+            max,
+            latency_ms: 1000,
+        };
+        AdaptiveConcurrency::new(&config, start)
+    }
+
+    fn fast() -> (Duration, Result<(), AppError>) {
+        (Duration::from_millis(100), Ok(()))
+    }
+
+    #[test]
+    fn grows_by_about_one_per_healthy_round() {
+        let adaptive = limiter(1, 8, 2);
+        assert_eq!(adaptive.limit(), 2);
+        // Each fetch adds 1/limit, so a round of about as many fetches as
+        // the limit adds one: 2 -> 2.5 -> 2.9 -> 3.24.
+        for _ in 0..3 {
+            let (latency, result) = fast();
+            adaptive.record(latency, &result);
+        }
+        assert_eq!(adaptive.limit(), 3);
+        for _ in 0..100 {
+            let (latency, result) = fast();
+            adaptive.record(latency, &result);
+        }
+        assert_eq!(adaptive.limit(), 8);
+    }
+
+    #[test]
+    fn halves_once_per_round_of_strain_and_stops_at_min() {
+        let adaptive = limiter(2, 16, 16);
+        let outage: Result<(), AppError> = Err(AppError::TtpError(TtpError::Unavailable {
+            outage: crate::ttp_client::Outage::RateLimited,
+            status: 429,
+            retry_after: None,
+        }));
+        adaptive.record(Duration::from_millis(100), &outage);
+        assert_eq!(adaptive.limit(), 8);
+        // The 16 fetches in flight when it was cut don't cut it again.
+        for _ in 0..16 {
+            adaptive.record(Duration::from_millis(100), &outage);
+        }
+        assert_eq!(adaptive.limit(), 8);
+        adaptive.record(Duration::from_secs(3), &Ok::<_, AppError>(()));
+        assert_eq!(adaptive.limit(), 4);
+        for _ in 0..40 {
+            adaptive.record(Duration::from_secs(3), &Ok::<_, AppError>(()));
+        }
+        assert_eq!(adaptive.limit(), 2);
+    }
+
+    #[test]
+    fn starts_within_bounds_and_ignores_unrelated_errors() {
+        assert_eq!(limiter(2, 4, 10).limit(), 4);
+        assert_eq!(limiter(2, 4, 1).limit(), 2);
+        let adaptive = limiter(1, 8, 4);
+        adaptive.record(Duration::from_secs(30), &Err::<(), _>(AppError::General("budget".to_string())));
+        assert_eq!(adaptive.limit(), 4);
+    }
+}
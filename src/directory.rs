@@ -0,0 +1,208 @@
+use std::path::Path;
+
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::{
+    ttp_client::{CenterInfo, TtpApi, GLOBAL_ENTRY},
+    AppError,
+};
+
+/// Directory snapshot compiled into the binary, used until a fresher copy is cached.
+const EMBEDDED_SNAPSHOT: &str = include_str!("../data/locations.json");
+
+/// Two-letter codes for US states, DC and territories, with their names.
+pub const US_STATES: &[(&str, &str)] = &[
+    ("AL", "Alabama"), ("AK", "Alaska"), ("AZ", "Arizona"),
+    ("AR", "Arkansas"), ("CA", "California"), ("CO", "Colorado"),
+    ("CT", "Connecticut"), ("DE", "Delaware"), ("FL", "Florida"),
+    ("GA", "Georgia"), ("HI", "Hawaii"), ("ID", "Idaho"),
+    ("IL", "Illinois"), ("IN", "Indiana"), ("IA", "Iowa"),
+    ("KS", "Kansas"), ("KY", "Kentucky"), ("LA", "Louisiana"),
+    ("ME", "Maine"), ("MD", "Maryland"), ("MA", "Massachusetts"),
+    ("MI", "Michigan"), ("MN", "Minnesota"), ("MS", "Mississippi"),
+    ("MO", "Missouri"), ("MT", "Montana"), ("NE", "Nebraska"),
+    ("NV", "Nevada"), ("NH", "New Hampshire"), ("NJ", "New Jersey"),
+    ("NM", "New Mexico"), ("NY", "New York"), ("NC", "North Carolina"),
+    ("ND", "North Dakota"), ("OH", "Ohio"), ("OK", "Oklahoma"),
+    ("OR", "Oregon"), ("PA", "Pennsylvania"), ("RI", "Rhode Island"),
+    ("SC", "South Carolina"), ("SD", "South Dakota"), ("TN", "Tennessee"),
+    ("TX", "Texas"), ("UT", "Utah"), ("VT", "Vermont"),
+    ("VA", "Virginia"), ("WA", "Washington"), ("WV", "West Virginia"),
+    ("WI", "Wisconsin"), ("WY", "Wyoming"), ("DC", "District of Columbia"),
+    ("PR", "Puerto Rico"), ("GU", "Guam"), ("VI", "U.S. Virgin Islands"),
+    ("AS", "American Samoa"), ("MP", "Northern Mariana Islands"),
+];
+
+/// AP-style abbreviations that aren't just the code with dots, e.g. in
+/// addresses copied from a website.
+const STATE_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("AL", "Ala."), ("AZ", "Ariz."), ("AR", "Ark."), ("CA", "Calif."),
+    ("CO", "Colo."), ("CT", "Conn."), ("DE", "Del."), ("FL", "Fla."),
+    ("GA", "Ga."), ("IL", "Ill."), ("IN", "Ind."), ("KS", "Kan."),
+    ("KY", "Ky."), ("LA", "La."), ("MD", "Md."), ("MA", "Mass."),
+    ("MI", "Mich."), ("MN", "Minn."), ("MS", "Miss."), ("MO", "Mo."),
+    ("MT", "Mont."), ("NE", "Neb."), ("NV", "Nev."), ("OK", "Okla."),
+    ("OR", "Ore."), ("PA", "Pa."), ("TN", "Tenn."), ("VT", "Vt."),
+    ("VA", "Va."), ("WA", "Wash."), ("WV", "W.Va."), ("WI", "Wis."),
+    ("WY", "Wyo."), ("DC", "D.C."), ("DC", "Washington D.C."),
+];
+
+pub fn is_state_code(code: &str) -> bool {
+    US_STATES.iter().any(|(c, _)| *c == code)
+}
+
+/// The code for a state given by code, name or abbreviation, ignoring case,
+/// dots and commas: `"California"`, `"calif."` and `"ca"` are all `"CA"`.
+pub fn state_code(input: &str) -> Option<&'static str> {
+    let fold = |s: &str| {
+        let s: String = s.chars().filter(|c| !matches!(c, '.' | ',')).collect();
+        s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    };
+    let input = fold(input);
+    US_STATES
+        .iter()
+        .flat_map(|&(code, name)| [(code, code), (code, name)])
+        .chain(STATE_ABBREVIATIONS.iter().copied())
+        .find(|(_, alias)| fold(alias) == input)
+        .map(|(code, _)| code)
+}
+
+/// The state code `input` most likely means, matching it against codes and
+/// names, e.g. `"CA"` for `"Californa"` or `"ca"`.
+pub fn suggest_state(input: &str) -> Option<(&'static str, &'static str)> {
+    let input = input.trim();
+    US_STATES
+        .iter()
+        .map(|&(code, name)| {
+            let score = strsim::jaro_winkler(&input.to_uppercase(), code)
+                .max(strsim::jaro_winkler(&input.to_lowercase(), &name.to_lowercase()));
+            (score, (code, name))
+        })
+        .filter(|(score, _)| *score >= 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, state)| state)
+}
+
+/// The set of known enrollment centers.
+#[derive(Debug, Default)]
+pub struct Directory {
+    centers: Vec<CenterInfo>,
+}
+
+impl Directory {
+    /// Loads the cached copy at `path` if present, otherwise the embedded
+    /// snapshot. An empty cached copy is an error, since a download produced
+    /// it; an empty embedded snapshot (a source build that never ran
+    /// `locations update`) is only a warning, and the checks against it are
+    /// skipped.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let path = path.as_ref();
+        if path.exists() {
+            debug!("Loading location directory from {}", path.display());
+            let directory = Self::parse(&std::fs::read_to_string(path)?)?;
+            if directory.is_empty() {
+                return Err(AppError::Config(format!(
+                    "the location directory at {} is empty; run `locations update` to download it again",
+                    path.display()
+                )));
+            }
+            Ok(directory)
+        } else {
+            debug!("Using embedded location directory snapshot");
+            let directory = Self::parse(EMBEDDED_SNAPSHOT)?;
+            if directory.is_empty() {
+                warn!(
+                    "This build has no location directory; skipping the checks against it. Run `locations update` to download it to {}",
+                    path.display()
+                );
+            }
+            Ok(directory)
+        }
+    }
+
+    fn parse(json: &str) -> Result<Self, AppError> {
+        let values: Vec<Value> = serde_json::from_str(json)?;
+        let centers = values
+            .into_iter()
+            .filter_map(|v| match serde_json::from_value::<CenterInfo>(v) {
+                Ok(center) => Some(center),
+                Err(e) => {
+                    warn!("Skipping directory entry: {e}");
+                    None
+                }
+            })
+            .collect();
+        Ok(Directory { centers })
+    }
+
+    /// Downloads the current directory and writes it verbatim to `path`.
+    pub async fn update(api: &impl TtpApi, path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let entries = api.locations(GLOBAL_ENTRY).await?;
+        let raw: Vec<&Value> = entries.iter().map(|e| &e.raw).collect();
+        std::fs::write(path.as_ref(), serde_json::to_string_pretty(&raw)?)?;
+        info!("Saved {} locations to {}", raw.len(), path.as_ref().display());
+        Ok(Directory {
+            centers: entries.into_iter().map(|e| e.value).collect(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    pub fn centers(&self) -> &[CenterInfo] {
+        &self.centers
+    }
+
+    pub fn get(&self, id: usize) -> Option<&CenterInfo> {
+        self.centers.iter().find(|c| c.id == id)
+    }
+
+    /// Whether any center in `state` offers `service`. Centers without a
+    /// service list are assumed to offer everything.
+    pub fn offers_in_state(&self, service: &str, state: &str) -> bool {
+        self.centers.iter().any(|c| {
+            c.state == state && (c.services.is_empty() || c.services.iter().any(|s| s.name == service))
+        })
+    }
+
+    /// States with at least one center offering `service`, sorted.
+    pub fn states_offering(&self, service: &str) -> Vec<&str> {
+        let mut states: Vec<&str> = self
+            .centers
+            .iter()
+            .filter(|c| self.offers_in_state(service, &c.state))
+            .map(|c| c.state.as_str())
+            .collect();
+        states.sort();
+        states.dedup();
+        states
+    }
+
+    /// IANA timezone of the center, e.g. `America/New_York`.
+    pub fn timezone(&self, id: usize) -> Option<&str> {
+        self.get(id).and_then(|c| c.tz_data.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_cached_copy_is_an_error() {
+        let path = crate::notify::tests::state_path("directory");
+        std::fs::write(&path, "[]").unwrap();
+        let err = Directory::load(&path).err().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+        assert!(err.contains("locations update"), "{err}");
+    }
+
+    #[test]
+    fn without_a_cached_copy_the_embedded_snapshot_loads_even_if_empty() {
+        let path = crate::notify::tests::state_path("directory-missing");
+        let directory = Directory::load(&path).unwrap();
+        assert_eq!(directory.is_empty(), EMBEDDED_SNAPSHOT.trim() == "[]");
+    }
+}
@@ -0,0 +1,121 @@
+//! DNS pinning and a caching resolver that keeps using the last good answer
+//! when a lookup fails, so a flaky resolver doesn't fail requests.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    ClientBuilder,
+};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+#[derive(Debug, Deserialize)]
+pub struct DnsConfig {
+    /// Fixed addresses per host, e.g. `{"ttp.cbp.dhs.gov": ["23.45.67.89"]}`.
+    #[serde(default)]
+    pub overrides: HashMap<String, Vec<IpAddr>>,
+    /// How long a successful lookup is reused before asking the resolver again.
+    #[serde(default = "default_cache_seconds")]
+    pub cache_seconds: u64,
+}
+
+fn default_cache_seconds() -> u64 {
+    300
+}
+
+/// Builds HTTP clients that share one DNS cache and the configured overrides.
+#[derive(Clone, Default)]
+pub struct Dns {
+    overrides: Vec<(String, Vec<SocketAddr>)>,
+    resolver: Option<Arc<CachingResolver>>,
+    /// Keep at most one idle connection per host.
+    low_memory: bool,
+}
+
+impl Dns {
+    /// Plain system DNS when `config` is `None`.
+    pub fn new(config: Option<&DnsConfig>) -> Self {
+        let Some(config) = config else {
+            return Dns::default();
+        };
+        Dns {
+            // Port 0: reqwest connects to the URL's port regardless.
+            overrides: config
+                .overrides
+                .iter()
+                .map(|(host, ips)| (host.clone(), ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect()))
+                .collect(),
+            resolver: Some(Arc::new(CachingResolver {
+                ttl: Duration::from_secs(config.cache_seconds),
+                cache: Default::default(),
+            })),
+            low_memory: false,
+        }
+    }
+
+    pub fn low_memory(self, low_memory: bool) -> Self {
+        Dns { low_memory, ..self }
+    }
+
+    pub fn builder(&self) -> ClientBuilder {
+        let mut builder = ClientBuilder::new();
+        if let Some(resolver) = &self.resolver {
+            builder = builder.dns_resolver(Arc::clone(resolver));
+        }
+        for (host, addrs) in &self.overrides {
+            builder = builder.resolve_to_addrs(host, addrs);
+        }
+        if self.low_memory {
+            builder = builder.pool_max_idle_per_host(1);
+        }
+        builder
+    }
+}
+
+type Cache = Arc<Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>>;
+
+struct CachingResolver {
+    ttl: Duration,
+    cache: Cache,
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let stale = match self.cache.lock().unwrap().get(&host) {
+            Some((at, addrs)) if at.elapsed() < self.ttl => {
+                let addrs = addrs.clone();
+                return Box::pin(async move { Ok(Box::new(addrs.into_iter()) as Addrs) });
+            }
+            cached => cached.map(|(_, addrs)| addrs.clone()),
+        };
+
+        let cache = Arc::clone(&self.cache);
+        Box::pin(async move {
+            let lookup = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            match lookup {
+                Ok(addrs) => {
+                    debug!("Resolved {host}: {addrs:?}");
+                    cache.lock().unwrap().insert(host, (Instant::now(), addrs.clone()));
+                    Ok(Box::new(addrs.into_iter()) as Addrs)
+                }
+                Err(e) => match stale {
+                    Some(addrs) => {
+                        warn!("DNS lookup for {host} failed ({e}); using cached addresses");
+                        Ok(Box::new(addrs.into_iter()) as Addrs)
+                    }
+                    None => Err(e.into()),
+                },
+            }
+        })
+    }
+}
@@ -0,0 +1,198 @@
+//! Tracks how long each watched state has gone without any availability, so a
+//! quiet channel can be told apart from a broken pipeline.
+
+use std::{collections::HashSet, sync::Mutex};
+
+use chrono::{NaiveDate, Utc, Weekday};
+use reqwest::Client;
+use rusqlite::params;
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    clock,
+    format::DisplayOptions,
+    history::History,
+    notify::{Action, EventKind},
+    report::{default_hour, default_weekday, last_scheduled},
+    slack,
+    state::{DigestLine, StateStore},
+    AppError, JeffConfig,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct DroughtNoteConfig {
+    #[serde(default = "default_weekday")]
+    pub weekday: Weekday,
+    /// Local hour of day (0-23) at which the note becomes due.
+    #[serde(default = "default_hour")]
+    pub hour: u32,
+    /// Only mention states that have been dry for at least this many days.
+    #[serde(default = "default_min_days")]
+    pub min_days: usize,
+}
+
+fn default_min_days() -> usize {
+    7
+}
+
+/// Zero-availability streaks for one state, counted in days on which at least
+/// one fetch succeeded (days the finder wasn't running don't count).
+#[derive(Debug)]
+pub struct Drought {
+    pub state: String,
+    pub current_days: usize,
+    pub longest_days: usize,
+    pub last_available: Option<NaiveDate>,
+}
+
+/// Overall fetch health since history began, quoted alongside droughts.
+#[derive(Debug)]
+pub struct PipelineHealth {
+    pub cycles: i64,
+    pub dates_scanned: i64,
+    pub dates_failed: i64,
+}
+
+impl History {
+    pub fn droughts(&self, states: &[String]) -> Result<Vec<Drought>, AppError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT date(finished_at) FROM cycles
+             WHERE dates_scanned > dates_failed ORDER BY 1",
+        )?;
+        let tracked_days = stmt
+            .query_map([], |r| r.get::<_, String>(0))?
+            .map(|day| parse_day(&day?))
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        let mut stmt = conn.prepare("SELECT DISTINCT date(observed_at) FROM observations WHERE state = ?1")?;
+        let mut out = Vec::new();
+        for state in states {
+            let available: HashSet<NaiveDate> = stmt
+                .query_map(params![state], |r| r.get::<_, String>(0))?
+                .map(|day| parse_day(&day?))
+                .collect::<Result<_, AppError>>()?;
+
+            let (mut current, mut longest) = (0, 0);
+            for day in &tracked_days {
+                if available.contains(day) {
+                    current = 0;
+                } else {
+                    current += 1;
+                    longest = longest.max(current);
+                }
+            }
+            out.push(Drought {
+                state: state.clone(),
+                current_days: current,
+                longest_days: longest,
+                last_available: available.into_iter().max(),
+            });
+        }
+        Ok(out)
+    }
+
+    pub fn pipeline_health(&self) -> Result<PipelineHealth, AppError> {
+        Ok(self.conn().query_row(
+            "SELECT COUNT(*), COALESCE(SUM(dates_scanned), 0), COALESCE(SUM(dates_failed), 0) FROM cycles",
+            [],
+            |r| {
+                Ok(PipelineHealth {
+                    cycles: r.get(0)?,
+                    dates_scanned: r.get(1)?,
+                    dates_failed: r.get(2)?,
+                })
+            },
+        )?)
+    }
+}
+
+fn parse_day(day: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|e| AppError::General(format!("Bad date {day}: {e}")))
+}
+
+/// Renders the note, or `None` when no state has been dry for `min_days`.
+pub fn render(droughts: &[Drought], health: &PipelineHealth, min_days: usize, display: &DisplayOptions) -> Option<String> {
+    let dry: Vec<&Drought> = droughts.iter().filter(|d| d.current_days >= min_days).collect();
+    if dry.is_empty() {
+        return None;
+    }
+
+    let mut msg = String::from("*Availability drought*\n\n");
+    for d in dry {
+        let last = d
+            .last_available
+            .map(|day| format!("last seen {}", display.date(day)))
+            .unwrap_or_else(|| "never seen".to_string());
+        msg.push_str(&format!(
+            "Still nothing in {} after {} days ({last}), longest drought {} days\n",
+            d.state, d.current_days, d.longest_days
+        ));
+    }
+    let ok = health.dates_scanned - health.dates_failed;
+    msg.push_str(&format!(
+        "\nThe finder is running: {} cycles, {ok} of {} date fetches succeeded.\n",
+        health.cycles, health.dates_scanned
+    ));
+    Some(msg)
+}
+
+/// Posts the drought note to Slack if one is due.
+pub async fn maybe_send_note(
+    client: &Client,
+    config: &JeffConfig,
+    state: &Mutex<StateStore>,
+    history: &Mutex<History>,
+) -> Result<(), AppError> {
+    let Some(note_cfg) = &config.drought_note else {
+        return Ok(());
+    };
+    if !config.enable_slack {
+        warn!("drought_note is set but enable_slack is false");
+        return Ok(());
+    }
+    let Some(due) = last_scheduled(clock::local_now(), note_cfg.weekday, note_cfg.hour) else {
+        return Ok(());
+    };
+    let due = due.with_timezone(&Utc);
+    if state.lock().unwrap().last_drought_note().is_some_and(|last| last >= due) {
+        return Ok(());
+    }
+
+    let (droughts, health) = {
+        let history = history.lock().unwrap();
+        (history.droughts(&config.search_states)?, history.pipeline_health()?)
+    };
+    if let Some(text) = render(&droughts, &health, note_cfg.min_days, &config.display) {
+        let throttle = slack::Throttle::default();
+        for channel in &config.slack_channel_id {
+            let policy = config.notification_policy.as_ref();
+            match policy.map_or(Action::Send, |p| p.action(EventKind::DroughtReport, "slack", channel)) {
+                Action::Send => {
+                    throttle.wait().await;
+                    match slack::post_to_slack(client.clone(), config.slack_token.expose(), channel, &text).await {
+                        Ok(_) => info!("Posted drought note to Slack channel {channel}"),
+                        Err(e) => error!("Error posting drought note to Slack channel {channel}: {e}"),
+                    }
+                }
+                Action::Queue => {
+                    let line = DigestLine {
+                        slot: String::new(),
+                        line: text.clone(),
+                    };
+                    let mut store = state.lock().unwrap();
+                    store.queue_digest(channel, vec![line]);
+                    if let Err(e) = store.save() {
+                        error!("Error saving state: {e}");
+                    }
+                }
+                Action::Suppress => info!("Not posting drought note to {channel}: notification_policy suppresses it"),
+            }
+        }
+    }
+
+    let mut store = state.lock().unwrap();
+    store.set_last_drought_note(due);
+    store.save()
+}
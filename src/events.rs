@@ -0,0 +1,190 @@
+//! The finder as a library: a stream of availability changes for bots, GUIs
+//! and other Rust apps that want the polling, retries and diffing without the
+//! notification channels, history or state files.
+
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use chrono::NaiveDate;
+use futures::{stream, Stream, StreamExt};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    build_providers,
+    clock,
+    concurrency::AdaptiveConcurrency,
+    providers::Provider,
+    scan,
+    ttp_client::{Location, Outage},
+    AppError, CycleReport, JeffConfig, LocationAvailability, Scan, MAX_OUTAGE_BACKOFF,
+};
+
+/// One open slot: a day with availability at one location.
+#[derive(Debug, Clone)]
+pub struct Availability {
+    /// Identifies the slot across cycles, e.g. `5140@2025-01-14`.
+    pub slot: String,
+    pub date: NaiveDate,
+    /// The location as the API described it, with `provider` set.
+    pub location: Location,
+}
+
+#[derive(Debug)]
+pub enum AvailabilityEvent {
+    /// A slot that wasn't open after the previous cycle; after the first
+    /// cycle, every open slot.
+    Opened(Availability),
+    /// A slot that was open and is gone: its date loaded without it, or the
+    /// date has passed. Slots on dates that failed to load stay open.
+    Closed(Availability),
+    /// A cycle finished; its `Opened` and `Closed` events come first.
+    CycleFinished(CycleReport),
+    /// The watcher couldn't start, e.g. a bad `session_path`; nothing follows.
+    Failed(AppError),
+}
+
+/// Scans as configured and yields what changed, one cycle every
+/// `fetch_interval_minutes`, or a single cycle when that's 0. The stream ends
+/// once `booked_date` has passed. Must be polled within a Tokio runtime.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), jeff::AppError> {
+/// use futures::StreamExt;
+/// use jeff::AvailabilityEvent;
+///
+/// let events = jeff::watch(jeff::load_config(".jeff")?);
+/// futures::pin_mut!(events);
+/// while let Some(event) = events.next().await {
+///     if let AvailabilityEvent::Opened(slot) = event {
+///         println!("{} open at {}", slot.date, slot.location.name);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn watch(config: JeffConfig) -> impl Stream<Item = AvailabilityEvent> + Send {
+    let config = Arc::new(config);
+    stream::once(async move { Watcher::new(config) }).flat_map(|watcher| match watcher {
+        Ok(watcher) => stream::unfold(watcher, |mut watcher| async move {
+            let events = watcher.next().await?;
+            Some((stream::iter(events), watcher))
+        })
+        .flatten()
+        .left_stream(),
+        Err(e) => stream::iter([AvailabilityEvent::Failed(e)]).right_stream(),
+    })
+}
+
+struct Watcher {
+    config: Arc<JeffConfig>,
+    providers: Vec<Arc<dyn Provider>>,
+    concurrency: Option<Arc<AdaptiveConcurrency>>,
+    /// Slots open after the last cycle, by slot key.
+    open: BTreeMap<String, Availability>,
+    cycles: u64,
+    /// Intervals to wait before the next cycle; grows while the API is down.
+    backoff: u32,
+}
+
+impl Watcher {
+    fn new(config: Arc<JeffConfig>) -> Result<Self, AppError> {
+        Ok(Watcher {
+            providers: build_providers(&config)?,
+            concurrency: config
+                .adaptive_concurrency
+                .as_ref()
+                .map(|adaptive| Arc::new(AdaptiveConcurrency::new(adaptive, config.max_concurrent_fetches))),
+            config,
+            open: BTreeMap::new(),
+            cycles: 0,
+            backoff: 1,
+        })
+    }
+
+    /// Waits for and runs the next cycle, returning its events, or `None`
+    /// when there are no more cycles.
+    async fn next(&mut self) -> Option<Vec<AvailabilityEvent>> {
+        if self.cycles > 0 {
+            if self.config.fetch_interval_minutes == 0 {
+                return None;
+            }
+            let interval = Duration::from_secs(self.config.fetch_interval_minutes * 60);
+            sleep(interval * self.backoff).await;
+        }
+        let today = clock::today();
+        if self.config.booking_passed(today) {
+            info!("Booked appointment has passed; stopping");
+            return None;
+        }
+        self.cycles += 1;
+
+        let started_at = clock::now();
+        let dates = self.config.scan_dates(today);
+        let scan = scan(&self.providers, &self.config, self.concurrency.as_ref(), &dates).await;
+        self.backoff = if scan.outages.iter().any(|o| *o != Outage::Unrecognized) {
+            let backoff = (self.backoff * 2).min(MAX_OUTAGE_BACKOFF);
+            warn!("API unavailable; waiting {backoff} intervals before the next cycle");
+            backoff
+        } else {
+            1
+        };
+        let mut report = CycleReport {
+            started_at,
+            finished_at: clock::now(),
+            dates_scanned: scan.scanned_ok.len() + scan.dates_failed,
+            dates_failed: scan.dates_failed,
+            slots_found: scan.locations.len(),
+            new_slots: None,
+            states: scan.locations.iter().map(|item| item.loc.state.clone()).collect(),
+            slots_alerted: 0,
+            outages: scan.outages.clone(),
+            notify_failures: 0,
+            stale: false,
+        };
+        let mut events = diff(&mut self.open, scan, today);
+        report.new_slots = Some(events.iter().filter(|e| matches!(e, AvailabilityEvent::Opened(_))).count());
+        events.push(AvailabilityEvent::CycleFinished(report));
+        Some(events)
+    }
+}
+
+/// Updates `open` to this scan's slots, returning what opened and closed.
+pub fn diff(open: &mut BTreeMap<String, Availability>, scan: Scan, today: NaiveDate) -> Vec<AvailabilityEvent> {
+    let found: BTreeMap<String, Availability> = LocationAvailability::merge(&scan.locations)
+        .into_iter()
+        // A slot known only to be before some date can't be opened or closed on one.
+        .filter(|group| !group.undated)
+        .flat_map(|LocationAvailability { loc, dates, .. }| {
+            let key = loc.key();
+            dates.into_iter().map(move |date| {
+                let slot = crate::slot_key(&key, date);
+                (slot.clone(), Availability { slot, date, location: loc.clone() })
+            })
+        })
+        .collect();
+
+    let mut events = Vec::new();
+    let gone: Vec<String> = open
+        .iter()
+        .filter(|(slot, was)| {
+            let loaded = scan
+                .scanned_ok
+                .iter()
+                .any(|(provider, date)| *provider == was.location.provider && *date == was.date);
+            !found.contains_key(*slot) && (loaded || was.date < today)
+        })
+        .map(|(slot, _)| slot.clone())
+        .collect();
+    for slot in gone {
+        if let Some(was) = open.remove(&slot) {
+            events.push(AvailabilityEvent::Closed(was));
+        }
+    }
+    for (slot, now) in found {
+        if !open.contains_key(&slot) {
+            events.push(AvailabilityEvent::Opened(now.clone()));
+        }
+        open.insert(slot, now);
+    }
+    events
+}
@@ -0,0 +1,127 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+
+use crate::{
+    clock,
+    format::{escape_html, DisplayOptions}, history::History, AppError, FetchedLocation,
+};
+
+/// How far back the "over time" chart reaches.
+const HISTORY_DAYS: i64 = 30;
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Global Entry Availability</title>
+<script src="https://cdn.jsdelivr.net/npm/vega@5"></script>
+<script src="https://cdn.jsdelivr.net/npm/vega-lite@5"></script>
+<script src="https://cdn.jsdelivr.net/npm/vega-embed@6"></script>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+</style>
+</head>
+<body>
+<h1>Global Entry Availability</h1>
+<p>Generated __GENERATED__</p>
+<h2>Open days per location (last __DAYS__ days)</h2>
+<div id="history"></div>
+<h2>Current availability</h2>
+<div id="current"></div>
+__TABLE__
+<script>
+vegaEmbed("#history", __HISTORY_SPEC__);
+vegaEmbed("#current", __CURRENT_SPEC__);
+</script>
+</body>
+</html>
+"##;
+
+/// Renders a standalone page charting current results and recent history.
+pub fn render(
+    fetched_locations: &[FetchedLocation],
+    history: &History,
+    display: &DisplayOptions,
+) -> Result<String, AppError> {
+    let since = clock::now() - Duration::days(HISTORY_DAYS);
+    let history_values: Vec<Value> = history
+        .availability_by_day(since)?
+        .into_iter()
+        .map(|(day, name, open_days)| json!({ "day": day, "location": name, "open_days": open_days }))
+        .collect();
+
+    let history_spec = json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "width": 700,
+        "height": 300,
+        "data": { "values": history_values },
+        "mark": { "type": "line", "point": true },
+        "encoding": {
+            "x": { "field": "day", "type": "temporal", "title": "Observed on" },
+            "y": { "field": "open_days", "type": "quantitative", "title": "Open days" },
+            "color": { "field": "location", "type": "nominal" },
+            "tooltip": [
+                { "field": "location" },
+                { "field": "day", "type": "temporal" },
+                { "field": "open_days" }
+            ]
+        }
+    });
+
+    let current_values: Vec<Value> = fetched_locations
+        .iter()
+        .map(|item| {
+            json!({ "date": item.date.to_string(), "location": display.name(&item.loc), "state": item.loc.state })
+        })
+        .collect();
+
+    let current_spec = json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "width": 700,
+        "data": { "values": current_values },
+        "mark": { "type": "square", "size": 120 },
+        "encoding": {
+            "x": { "field": "date", "type": "temporal", "title": "Appointment date" },
+            "y": { "field": "location", "type": "nominal", "title": null },
+            "color": { "field": "state", "type": "nominal" },
+            "tooltip": [{ "field": "location" }, { "field": "date", "type": "temporal" }]
+        }
+    });
+
+    Ok(TEMPLATE
+        .replace("__GENERATED__", &clock::now().format("%Y-%m-%d %H:%M UTC").to_string())
+        .replace("__DAYS__", &HISTORY_DAYS.to_string())
+        .replace("__TABLE__", &table(fetched_locations, display))
+        .replace("__HISTORY_SPEC__", &script_json(&history_spec))
+        .replace("__CURRENT_SPEC__", &script_json(&current_spec)))
+}
+
+fn table(fetched_locations: &[FetchedLocation], display: &DisplayOptions) -> String {
+    if fetched_locations.is_empty() {
+        return "<p>No Global Entry appointments found.</p>".to_string();
+    }
+
+    let mut out = String::from(
+        "<table>\n<tr><th>Date</th><th>Location</th><th>City</th><th>State</th><th>Phone</th></tr>\n",
+    );
+    for item in fetched_locations {
+        let loc = &item.loc;
+        out.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            item.date,
+            escape_html(display.name(loc)),
+            escape_html(&loc.city),
+            escape_html(&loc.state),
+            escape_html(loc.phone_number.as_deref().unwrap_or("N/A")),
+        ));
+    }
+    out.push_str("</table>");
+    out
+}
+
+/// Serializes `value` so it can't terminate the surrounding `<script>` element.
+fn script_json(value: &Value) -> String {
+    value.to_string().replace("</", "<\\/")
+}
@@ -0,0 +1,209 @@
+mod html;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+
+use std::{fs::File, io::Write, sync::Mutex};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    format::DisplayOptions,
+    history::History,
+    AppError, FetchedLocation, JeffConfig, LocationAvailability,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Html,
+    Json,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn path(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "appointments.csv",
+            ExportFormat::Html => "appointments.html",
+            ExportFormat::Json => "appointments.json",
+            ExportFormat::Xlsx => "appointments.xlsx",
+        }
+    }
+}
+
+/// Row layout for CSV and JSON exports.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportLayout {
+    /// One row per (date, location) pair.
+    #[default]
+    Flat,
+    /// One row per location with all of its dates.
+    Grouped,
+}
+
+/// Writes `fetched_locations` in `format` to its default path.
+pub fn export(
+    format: ExportFormat,
+    fetched_locations: &[FetchedLocation],
+    history: &Mutex<History>,
+    config: &JeffConfig,
+) -> Result<(), AppError> {
+    let display = &config.display;
+    let path = format.path();
+    match (format, config.export_layout) {
+        (ExportFormat::Csv, layout) => write_csv(fetched_locations, layout, display, File::create(path)?),
+        (ExportFormat::Json, layout) => {
+            let value = export_json(fetched_locations, layout, display)?;
+            std::fs::write(path, serde_json::to_string_pretty(&value)?)?;
+            Ok(())
+        }
+        (ExportFormat::Html, _) => {
+            let page = html::render(fetched_locations, &history.lock().unwrap(), display)?;
+            std::fs::write(path, page)?;
+            Ok(())
+        }
+        #[cfg(feature = "xlsx")]
+        (ExportFormat::Xlsx, _) => xlsx::write(fetched_locations, display, path),
+        // Refused by `load_config`.
+        #[cfg(not(feature = "xlsx"))]
+        (ExportFormat::Xlsx, _) => Err(AppError::Config("exports: this build doesn't include xlsx support".to_string())),
+    }
+}
+
+/// `fetched_locations` as a CSV export, e.g. to attach to a message.
+pub fn csv_bytes(
+    fetched_locations: &[FetchedLocation],
+    layout: ExportLayout,
+    display: &DisplayOptions,
+) -> Result<Vec<u8>, AppError> {
+    let mut bytes = Vec::new();
+    write_csv(fetched_locations, layout, display, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_csv(
+    fetched_locations: &[FetchedLocation],
+    layout: ExportLayout,
+    display: &DisplayOptions,
+    out: impl Write,
+) -> Result<(), AppError> {
+    match layout {
+        ExportLayout::Flat => export_to_csv(fetched_locations, out, display),
+        ExportLayout::Grouped => export_grouped_csv(fetched_locations, out, display),
+    }
+}
+
+fn export_json(
+    fetched_locations: &[FetchedLocation],
+    layout: ExportLayout,
+    display: &DisplayOptions,
+) -> Result<Value, AppError> {
+    let rows = match layout {
+        ExportLayout::Flat => fetched_locations
+            .iter()
+            .map(|item| {
+                Ok(json!({
+                    "date": item.date,
+                    "name": display.name(&item.loc),
+                    "location": serde_json::from_str::<Value>(&item.raw_json)?,
+                }))
+            })
+            .collect::<Result<Vec<_>, AppError>>()?,
+        ExportLayout::Grouped => LocationAvailability::merge(fetched_locations)
+            .iter()
+            .map(|group| {
+                json!({
+                    "id": group.loc.id,
+                    "name": display.name(&group.loc),
+                    "state": group.loc.state,
+                    "city": group.loc.city,
+                    "short_name": group.loc.short_name,
+                    "tz_data": group.loc.tz_data,
+                    "services": group.loc.service_names(),
+                    "dates": group.dates,
+                })
+            })
+            .collect(),
+    };
+    Ok(Value::Array(rows))
+}
+
+/// Write one CSV row per location, with its dates joined by `;`.
+fn export_grouped_csv(
+    fetched_locations: &[FetchedLocation],
+    out: impl Write,
+    display: &DisplayOptions,
+) -> Result<(), AppError> {
+    let mut wtr = csv::Writer::from_writer(out);
+    wtr.write_record(["ID", "Name", "State", "City", "EarliestDate", "DateCount", "Dates"])?;
+
+    for group in LocationAvailability::merge(fetched_locations) {
+        let dates: Vec<String> = group.dates.iter().map(|d| d.to_string()).collect();
+        wtr.write_record(&[
+            group.loc.id.to_string(),
+            display.name(&group.loc).to_string(),
+            group.loc.state.to_string(),
+            group.loc.city.to_string(),
+            dates[0].clone(),
+            dates.len().to_string(),
+            dates.join(";"),
+        ])?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Write CSV including the entire raw JSON for each location.
+fn export_to_csv(
+    fetched_locations: &[FetchedLocation],
+    out: impl Write,
+    display: &DisplayOptions,
+) -> Result<(), AppError> {
+    let mut wtr = csv::Writer::from_writer(out);
+
+    // We now include a column for "RawJSON"
+    wtr.write_record([
+        "Date", 
+        "ID", 
+        "Name", 
+        "State", 
+        "City", 
+        "Address", 
+        "PostalCode", 
+        "Phone", 
+        "ShortName",
+        "TimeZone",
+        "Operational",
+        "Services",
+        "RawJSON"
+    ])?;
+
+    for item in fetched_locations {
+        let loc = &item.loc;
+        let phone = loc.phone_number.as_deref().unwrap_or("N/A");
+        wtr.write_record(&[
+            item.date.to_string(),
+            loc.id.to_string(),
+            display.name(loc).to_string(),
+            loc.state.to_string(),
+            loc.city.to_string(),
+            loc.address.to_string(),
+            loc.postal_code.to_string(),
+            phone.to_string(),
+            loc.short_name.clone().unwrap_or_default(),
+            loc.tz_data.clone().unwrap_or_default(),
+            loc.operational.map(|o| o.to_string()).unwrap_or_default(),
+            loc.service_names().join(";"),
+            item.raw_json.to_string(),
+        ])?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use ::parquet::{
+    basic::Compression,
+    data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use tracing::info;
+
+use crate::{
+    history::{History, TS_FORMAT},
+    AppError,
+};
+
+const SCHEMA: &str = "
+message observation {
+    REQUIRED INT64 cycle_id;
+    REQUIRED INT64 observed_at (TIMESTAMP(MILLIS, true));
+    REQUIRED INT32 slot_date (DATE);
+    REQUIRED INT64 location_id;
+    REQUIRED BYTE_ARRAY name (UTF8);
+    REQUIRED BYTE_ARRAY state (UTF8);
+    REQUIRED BYTE_ARRAY city (UTF8);
+    REQUIRED BYTE_ARRAY raw_json (UTF8);
+    REQUIRED BYTE_ARRAY provider (UTF8);
+}
+";
+
+/// Rows buffered per Parquet row group, bounding memory on large histories.
+const ROW_GROUP_SIZE: usize = 50_000;
+
+#[derive(Default)]
+struct Columns {
+    cycle_id: Vec<i64>,
+    observed_at: Vec<i64>,
+    slot_date: Vec<i32>,
+    location_id: Vec<i64>,
+    name: Vec<ByteArray>,
+    state: Vec<ByteArray>,
+    city: Vec<ByteArray>,
+    raw_json: Vec<ByteArray>,
+    provider: Vec<ByteArray>,
+}
+
+impl Columns {
+    fn len(&self) -> usize {
+        self.cycle_id.len()
+    }
+}
+
+/// Writes every stored observation to a Parquet file at `path`.
+pub fn export_history(history: &History, path: impl AsRef<Path>) -> Result<(), AppError> {
+    let schema = Arc::new(parse_message_type(SCHEMA)?);
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let mut writer = SerializedFileWriter::new(File::create(path.as_ref())?, schema, props)?;
+
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let mut stmt = history.conn().prepare(
+        "SELECT cycle_id, observed_at, slot_date, location_id, name, state, city, raw_json, provider
+         FROM observations ORDER BY rowid",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut cols = Columns::default();
+    let mut total = 0;
+    while let Some(row) = rows.next()? {
+        let observed_at: String = row.get(1)?;
+        let observed_at = NaiveDateTime::parse_from_str(&observed_at, TS_FORMAT)
+            .map_err(|e| AppError::General(format!("Bad observed_at {observed_at}: {e}")))?;
+        let slot_date: String = row.get(2)?;
+        let slot_date = NaiveDate::parse_from_str(&slot_date, "%Y-%m-%d")
+            .map_err(|e| AppError::General(format!("Bad slot_date {slot_date}: {e}")))?;
+
+        cols.cycle_id.push(row.get(0)?);
+        cols.observed_at.push(observed_at.and_utc().timestamp_millis());
+        cols.slot_date.push((slot_date - epoch).num_days() as i32);
+        cols.location_id.push(row.get(3)?);
+        cols.name.push(row.get::<_, String>(4)?.as_str().into());
+        cols.state.push(row.get::<_, String>(5)?.as_str().into());
+        cols.city.push(row.get::<_, String>(6)?.as_str().into());
+        cols.raw_json.push(row.get::<_, String>(7)?.as_str().into());
+        cols.provider.push(row.get::<_, String>(8)?.as_str().into());
+
+        if cols.len() >= ROW_GROUP_SIZE {
+            total += cols.len();
+            write_row_group(&mut writer, std::mem::take(&mut cols))?;
+        }
+    }
+    if cols.len() > 0 {
+        total += cols.len();
+        write_row_group(&mut writer, cols)?;
+    }
+
+    writer.close()?;
+    info!("Wrote {total} observations to {}", path.as_ref().display());
+    Ok(())
+}
+
+fn write_row_group(writer: &mut SerializedFileWriter<File>, cols: Columns) -> Result<(), AppError> {
+    let mut rg = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut col) = rg.next_column()? {
+        match index {
+            0 => col.typed::<Int64Type>().write_batch(&cols.cycle_id, None, None)?,
+            1 => col.typed::<Int64Type>().write_batch(&cols.observed_at, None, None)?,
+            2 => col.typed::<Int32Type>().write_batch(&cols.slot_date, None, None)?,
+            3 => col.typed::<Int64Type>().write_batch(&cols.location_id, None, None)?,
+            4 => col.typed::<ByteArrayType>().write_batch(&cols.name, None, None)?,
+            5 => col.typed::<ByteArrayType>().write_batch(&cols.state, None, None)?,
+            6 => col.typed::<ByteArrayType>().write_batch(&cols.city, None, None)?,
+            7 => col.typed::<ByteArrayType>().write_batch(&cols.raw_json, None, None)?,
+            _ => col.typed::<ByteArrayType>().write_batch(&cols.provider, None, None)?,
+        };
+        col.close()?;
+        index += 1;
+    }
+    rg.close()?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! `appointments.xlsx`: one sheet per state, one row per location, with a
+//! column for each open date, for opening in Excel or Numbers.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use rust_xlsxwriter::{Color, Format, FormatAlign, FormatBorder, Workbook, Worksheet};
+
+use crate::{directory::US_STATES, format::DisplayOptions, AppError, FetchedLocation, LocationAvailability};
+
+/// Columns before the per-date ones.
+const HEADERS: [&str; 6] = ["Location", "ID", "City", "Address", "Phone", "Earliest"];
+
+const DATE_FORMAT: &str = "ddd, mmm d yyyy";
+
+struct Formats {
+    header: Format,
+    date_header: Format,
+    date: Format,
+    open: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        let header = Format::new()
+            .set_bold()
+            .set_font_color(Color::White)
+            .set_background_color(Color::RGB(0x1F4E79))
+            .set_border_bottom(FormatBorder::Thin);
+        Formats {
+            date_header: header.clone().set_num_format("ddd mmm d").set_align(FormatAlign::Center),
+            header,
+            date: Format::new().set_num_format(DATE_FORMAT).set_align(FormatAlign::Left),
+            open: Format::new()
+                .set_align(FormatAlign::Center)
+                .set_background_color(Color::RGB(0xC6EFCE)),
+        }
+    }
+}
+
+/// Writes `fetched_locations` to `path`.
+pub fn write(fetched_locations: &[FetchedLocation], display: &DisplayOptions, path: &str) -> Result<(), AppError> {
+    let mut by_state: BTreeMap<&str, Vec<LocationAvailability>> = BTreeMap::new();
+    for group in LocationAvailability::merge(fetched_locations) {
+        let state = US_STATES.iter().find(|(code, _)| *code == group.loc.state).map_or("Other", |(_, name)| name);
+        by_state.entry(state).or_default().push(group);
+    }
+
+    let formats = Formats::new();
+    let mut workbook = Workbook::new();
+    if by_state.is_empty() {
+        let sheet = workbook.add_worksheet().set_name("No appointments")?;
+        sheet.write_string(0, 0, "No appointments were found.")?;
+    }
+    for (state, groups) in &by_state {
+        let sheet = workbook.add_worksheet().set_name(*state)?;
+        write_state(sheet, groups, display, &formats)?;
+    }
+    workbook.save(path)?;
+    Ok(())
+}
+
+fn write_state(
+    sheet: &mut Worksheet,
+    groups: &[LocationAvailability],
+    display: &DisplayOptions,
+    formats: &Formats,
+) -> Result<(), AppError> {
+    let mut dates: Vec<NaiveDate> = groups.iter().flat_map(|g| g.dates.iter().copied()).collect();
+    dates.sort();
+    dates.dedup();
+
+    for (col, header) in (0..).zip(HEADERS) {
+        sheet.write_string_with_format(0, col, header, &formats.header)?;
+    }
+    let first_date_col = HEADERS.len() as u16;
+    for (col, date) in (first_date_col..).zip(&dates) {
+        sheet.write_datetime_with_format(0, col, date, &formats.date_header)?;
+    }
+
+    for (row, group) in (1..).zip(groups) {
+        let loc = &group.loc;
+        let address = match &loc.address_additional {
+            Some(extra) if !extra.is_empty() => format!("{} {extra}, {}", loc.address, loc.postal_code),
+            _ => format!("{}, {}", loc.address, loc.postal_code),
+        };
+        sheet.write_string(row, 0, display.name(loc))?;
+        sheet.write_number(row, 1, loc.id as f64)?;
+        sheet.write_string(row, 2, &loc.city)?;
+        sheet.write_string(row, 3, address)?;
+        sheet.write_string(row, 4, loc.phone_number.as_deref().unwrap_or(""))?;
+        sheet.write_datetime_with_format(row, 5, group.earliest(), &formats.date)?;
+        for (col, date) in (first_date_col..).zip(&dates) {
+            if group.dates.contains(date) {
+                sheet.write_string_with_format(row, col, "open", &formats.open)?;
+            }
+        }
+    }
+
+    let last_col = first_date_col + dates.len() as u16 - 1;
+    sheet.set_freeze_panes(1, 1)?;
+    sheet.autofilter(0, 0, groups.len() as u32, last_col)?;
+    sheet.autofit();
+    Ok(())
+}
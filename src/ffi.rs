@@ -0,0 +1,355 @@
+//! A small C ABI over the scheduler client and filters, for non-Rust tooling
+//! such as a Swift menu-bar app. Results are JSON strings; see `include/jeff.h`.
+//!
+//! Functions returning a pointer return null on failure, and
+//! [`jeff_last_error`] then describes what went wrong on that thread. A panic
+//! never crosses into the caller: it's a failure like any other.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+};
+
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+
+use crate::{
+    build_providers,
+    clock,
+    concurrency::AdaptiveConcurrency,
+    dns::Dns,
+    load_config,
+    providers::{Provider, GLOBAL_ENTRY_ID},
+    scan,
+    ttp_client::{parse_entries, Location, TtpApi},
+    api_client, validate, AppError, JeffConfig,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A loaded config with the runtime its requests run on.
+pub struct Jeff {
+    config: Arc<JeffConfig>,
+    runtime: Runtime,
+    providers: Vec<Arc<dyn Provider>>,
+    concurrency: Option<Arc<AdaptiveConcurrency>>,
+}
+
+impl Jeff {
+    fn new(config: JeffConfig) -> Result<Box<Jeff>, AppError> {
+        let runtime = Runtime::new()?;
+        // Clients must be built inside the runtime that drives them.
+        let providers = runtime.block_on(async { build_providers(&config) })?;
+        let concurrency = config
+            .adaptive_concurrency
+            .as_ref()
+            .map(|adaptive| Arc::new(AdaptiveConcurrency::new(adaptive, config.max_concurrent_fetches)));
+        Ok(Box::new(Jeff {
+            config: Arc::new(config),
+            runtime,
+            providers,
+            concurrency,
+        }))
+    }
+}
+
+fn set_error(e: impl std::fmt::Display) {
+    let message = CString::new(e.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Runs an entry point's body, turning a panic into `failed` with the error
+/// recorded, since unwinding out of an `extern "C"` function aborts.
+fn guard<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        set_error(format!("panicked: {message}"));
+        failed
+    })
+}
+
+/// `value` as a string the caller frees with [`jeff_string_free`], or null
+/// with the error recorded.
+fn to_c(value: Result<Value, AppError>) -> *mut c_char {
+    match value.and_then(|v| Ok(serde_json::to_string(&v)?)) {
+        Ok(text) => CString::new(text).map(CString::into_raw).unwrap_or(ptr::null_mut()),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// `s` must be null or a valid NUL-terminated string.
+unsafe fn from_c<'a>(s: *const c_char, what: &str) -> Result<&'a str, AppError> {
+    if s.is_null() {
+        return Err(AppError::General(format!("{what} is null")));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| AppError::General(format!("{what}: {e}")))
+}
+
+fn open(config: Result<JeffConfig, AppError>) -> *mut Jeff {
+    match config.and_then(Jeff::new) {
+        Ok(jeff) => Box::into_raw(jeff),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Loads and validates the config file at `path`, as the command line does.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn jeff_open(path: *const c_char) -> *mut Jeff {
+    guard(ptr::null_mut(), || open(from_c(path, "path").and_then(load_config)))
+}
+
+/// Like [`jeff_open`], from the config's JSON text; `include` isn't supported.
+///
+/// # Safety
+///
+/// `json` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn jeff_open_json(json: *const c_char) -> *mut Jeff {
+    guard(ptr::null_mut(), || {
+        open(from_c(json, "json").and_then(|json| {
+            let mut config: JeffConfig = serde_json::from_str(json)?;
+            validate(&config)?;
+            config.load_extensions()?;
+            Ok(config)
+        }))
+    })
+}
+
+/// Frees a handle from [`jeff_open`] or [`jeff_open_json`].
+///
+/// # Safety
+///
+/// `jeff` must be null or a handle not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn jeff_close(jeff: *mut Jeff) {
+    guard((), || {
+        if !jeff.is_null() {
+            drop(Box::from_raw(jeff));
+        }
+    })
+}
+
+/// Runs the configured filters over a `slots/asLocations` response, returning
+/// `[{"keep": bool, "reason": string|null, "location": {...}}]`.
+///
+/// # Safety
+///
+/// `jeff` must be a live handle and `response` a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn jeff_filter(jeff: *const Jeff, response: *const c_char) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let Some(jeff) = jeff.as_ref() else {
+            set_error("handle is null");
+            return ptr::null_mut();
+        };
+        to_c(from_c(response, "response").and_then(|body| {
+            let verdicts = parse_entries::<Location>(body)?
+                .into_iter()
+                .map(|mut entry| {
+                    entry.value.provider = GLOBAL_ENTRY_ID;
+                    let reason = jeff.config.rejection(&entry.value);
+                    json!({ "keep": reason.is_none(), "reason": reason, "location": entry.raw })
+                })
+                .collect();
+            Ok(Value::Array(verdicts))
+        }))
+    })
+}
+
+/// Scans every configured date once, blocking until done, and returns
+/// `{"dates_scanned": n, "dates_failed": n, "slots": [{"slot", "date",
+/// "provider", "location_id", "name", "city", "state", "location"}]}`.
+///
+/// # Safety
+///
+/// `jeff` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn jeff_scan(jeff: *const Jeff) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let Some(jeff) = jeff.as_ref() else {
+            set_error("handle is null");
+            return ptr::null_mut();
+        };
+        let dates = jeff.config.scan_dates(clock::today());
+        let scan = jeff.runtime.block_on(scan(&jeff.providers, &jeff.config, jeff.concurrency.as_ref(), &dates));
+        let slots: Vec<Value> = scan
+            .locations
+            .iter()
+            .map(|item| {
+                json!({
+                    "slot": item.slot_key(),
+                    "date": item.date,
+                    "provider": item.loc.provider,
+                    "location_id": item.loc.id,
+                    "name": jeff.config.display.name(&item.loc),
+                    "city": item.loc.city,
+                    "state": item.loc.state,
+                    "location": serde_json::from_str::<Value>(&item.raw_json).unwrap_or_default(),
+                })
+            })
+            .collect();
+        to_c(Ok(json!({
+            "dates_scanned": scan.scanned_ok.len() + scan.dates_failed,
+            "dates_failed": scan.dates_failed,
+            "slots": slots,
+        })))
+    })
+}
+
+/// The soonest open interview times at one location, as
+/// `[{"start": "2025-01-14T08:15:00", "end": ..., "duration": minutes}]`.
+///
+/// # Safety
+///
+/// `jeff` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn jeff_location_slots(jeff: *const Jeff, location_id: usize, limit: usize) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let Some(jeff) = jeff.as_ref() else {
+            set_error("handle is null");
+            return ptr::null_mut();
+        };
+        to_c(jeff.runtime.block_on(async {
+            let dns = Dns::new(jeff.config.dns.as_ref()).low_memory(jeff.config.low_memory);
+            let api = api_client(&jeff.config, &dns, dns.builder().build()?)?;
+            let slots = api
+                .slots_for_location(location_id, limit)
+                .await?
+                .into_iter()
+                .filter(|s| s.active)
+                .map(|s| json!({ "start": s.start_timestamp, "end": s.end_timestamp, "duration": s.duration }))
+                .collect();
+            Ok(Value::Array(slots))
+        }))
+    })
+}
+
+/// The last error on this thread, valid until the next call on it; null if none.
+#[no_mangle]
+pub extern "C" fn jeff_last_error() -> *const c_char {
+    guard(ptr::null(), || LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr())))
+}
+
+/// Frees a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be null or a string from this library not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn jeff_string_free(s: *mut c_char) {
+    guard((), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let error = jeff_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }.to_str().unwrap().to_string()
+    }
+
+    /// Takes a string this library returned, freeing it.
+    fn take(s: *mut c_char) -> Value {
+        assert!(!s.is_null(), "{}", last_error());
+        let value = serde_json::from_str(unsafe { CStr::from_ptr(s) }.to_str().unwrap()).unwrap();
+        unsafe { jeff_string_free(s) };
+        value
+    }
+
+    #[test]
+    fn a_panic_is_a_failure_with_its_message() {
+        let result: *mut c_char = guard(ptr::null_mut(), || panic!("boom"));
+        assert!(result.is_null());
+        assert_eq!(last_error(), "panicked: boom");
+        let result: *mut c_char = guard(ptr::null_mut(), || panic!("{} {}", "formatted", 1));
+        assert!(result.is_null());
+        assert_eq!(last_error(), "panicked: formatted 1");
+    }
+
+    #[test]
+    fn filters_a_response_with_the_configs_rules() {
+        let config = CString::new(
+            json!({
+                "enable_slack": false,
+                "slack_token": "",
+                "slack_channel_id": "",
+                "fetch_interval_minutes": 0,
+                "search_states": ["NY"],
+                "window_mode": "rolling",
+                "window_days": 30,
+                "api_rate_limit_seconds": 1.0,
+                "max_concurrent_fetches": 5,
+                "max_retries": 3,
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let jeff = unsafe { jeff_open_json(config.as_ptr()) };
+        assert!(!jeff.is_null(), "{}", last_error());
+
+        let response = CString::new(
+            json!([
+                { "id": 5140, "name": "JFK", "state": "NY", "city": "Jamaica", "address": "Terminal 4",
+                  "postalCode": "11430" },
+                { "id": 5446, "name": "SFO", "state": "CA", "city": "San Francisco", "address": "Terminal A",
+                  "postalCode": "94128" },
+            ])
+            .to_string(),
+        )
+        .unwrap();
+        let verdicts = take(unsafe { jeff_filter(jeff, response.as_ptr()) });
+        assert_eq!(verdicts[0]["keep"], true);
+        assert_eq!(verdicts[0]["location"]["id"], 5140);
+        assert_eq!(verdicts[1]["keep"], false);
+        assert!(verdicts[1]["reason"].is_string());
+
+        let bad = CString::new("not json").unwrap();
+        assert!(unsafe { jeff_filter(jeff, bad.as_ptr()) }.is_null());
+        assert!(unsafe { jeff_filter(jeff, ptr::null()) }.is_null());
+        assert_eq!(last_error(), "General: response is null");
+        unsafe { jeff_close(jeff) };
+    }
+
+    #[test]
+    fn bad_input_is_an_error_not_a_crash() {
+        assert!(unsafe { jeff_scan(ptr::null()) }.is_null());
+        assert_eq!(last_error(), "handle is null");
+        let json = CString::new("{}").unwrap();
+        assert!(unsafe { jeff_open_json(json.as_ptr()) }.is_null());
+        assert!(last_error().contains("missing field"));
+        assert!(unsafe { jeff_open(ptr::null()) }.is_null());
+        unsafe {
+            jeff_close(ptr::null_mut());
+            jeff_string_free(ptr::null_mut());
+        }
+    }
+}
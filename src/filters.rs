@@ -0,0 +1,110 @@
+//! `filters test`: runs the configured location filters over recorded API
+//! responses or exports and reports what would match, and why the rest wouldn't.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+use serde_json::Value;
+
+use crate::{
+    import,
+    providers::GLOBAL_ENTRY_ID,
+    ttp_client::{parse_entries, Location},
+    AppError, JeffConfig,
+};
+
+/// One recorded location, with its date when the source has one.
+struct Record {
+    date: Option<NaiveDate>,
+    loc: Location,
+}
+
+/// Reports filter results for every file in `paths`; directories are expanded
+/// one level. Unreadable files are reported and skipped.
+pub fn test(config: &JeffConfig, paths: &[PathBuf], out: &mut impl Write) -> Result<(), AppError> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+                .map(|e| e.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            entries.retain(|p| p.is_file());
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let (mut matched, mut total) = (0, 0);
+    for file in &files {
+        writeln!(out, "{}", file.display())?;
+        let records = match load(file) {
+            Ok(records) => records,
+            Err(e) => {
+                writeln!(out, "  error: {e}")?;
+                continue;
+            }
+        };
+        let mut file_matched = 0;
+        for record in &records {
+            let date = record.date.map(|d| format!("{d}  ")).unwrap_or_default();
+            let loc = &record.loc;
+            let what = format!("{date}{} {} ({}, {})", loc.id, loc.name, loc.city, loc.state);
+            match config.rejection(loc) {
+                None => {
+                    file_matched += 1;
+                    writeln!(out, "  match  {what}")?;
+                }
+                Some(reason) => writeln!(out, "  drop   {what}: {reason}")?,
+            }
+        }
+        writeln!(out, "  {file_matched} of {} match", records.len())?;
+        matched += file_matched;
+        total += records.len();
+    }
+    if files.len() > 1 {
+        writeln!(out, "Total: {matched} of {total} match across {} files", files.len())?;
+    }
+    Ok(())
+}
+
+/// Reads a raw API response (such as a `body_log.capture_dir` file or a test
+/// fixture) or a CSV/JSON export.
+fn load(path: &Path) -> Result<Vec<Record>, AppError> {
+    let is = |ext: &str| path.extension().is_some_and(|e| e == ext);
+    if is("csv") {
+        return from_export(path);
+    }
+    let text = std::fs::read_to_string(path)?;
+    let values: Vec<Value> = serde_json::from_str(&text)
+        .map_err(|e| AppError::General(format!("not an API response or export: {e}")))?;
+    let is_export = values
+        .first()
+        .is_some_and(|v| v.get("location").is_some() || v.get("dates").is_some());
+    if is_export && is("json") {
+        return from_export(path);
+    }
+    Ok(parse_entries::<Location>(&text)?
+        .into_iter()
+        .map(|entry| {
+            let mut loc = entry.value;
+            loc.provider = GLOBAL_ENTRY_ID;
+            Record { date: None, loc }
+        })
+        .collect())
+}
+
+fn from_export(path: &Path) -> Result<Vec<Record>, AppError> {
+    Ok(import::read(path)?
+        .locations
+        .into_iter()
+        .map(|item| Record {
+            date: Some(item.date),
+            loc: item.loc,
+        })
+        .collect())
+}
@@ -0,0 +1,538 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use chrono::{DateTime, Datelike, Locale, NaiveDate, TimeZone, Weekday};
+use serde::Deserialize;
+
+use crate::{ttp_client::CenterInfo, FetchedLocation, Location, LocationAvailability};
+
+/// How many dates a grouped line spells out before "+N more days".
+const MAX_GROUP_DATES: usize = 3;
+
+/// Location fields that can be left out of notification messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Id,
+    Address,
+    PostalCode,
+    Phone,
+}
+
+/// How locations are presented in notifications and exports.
+#[derive(Debug, Default, Deserialize)]
+pub struct DisplayOptions {
+    /// Friendly names keyed by location, e.g. `{"5446": "SFO ground floor"}`.
+    #[serde(default)]
+    pub location_aliases: HashMap<String, String>,
+    #[serde(default)]
+    pub hidden_fields: Vec<Field>,
+    /// List each location once with its dates instead of one entry per date.
+    #[serde(default)]
+    pub group_by_location: bool,
+    #[serde(default)]
+    pub summarize: Summarize,
+    /// Annotate each location's earliest date with its change since yesterday.
+    #[serde(default)]
+    pub show_trends: bool,
+    /// Label locations with how quickly their slots usually disappear.
+    #[serde(default)]
+    pub show_urgency: bool,
+    /// Add the location directory's hours, closure flag and notes, with a
+    /// warning when a center interviews only on some weekdays.
+    #[serde(default)]
+    pub show_center_info: bool,
+    #[serde(default)]
+    pub date_format: DateFormat,
+    #[serde(default)]
+    pub time_format: TimeFormat,
+    /// Language for month and weekday names and `date_format: locale`, e.g.
+    /// `de_DE` or `fr-FR`. Default: English.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// How much each channel's alerts spell out.
+    #[serde(default)]
+    pub verbosity: VerbosityRules,
+}
+
+/// How much of each result an alert spells out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verbosity {
+    /// One line per result and only the first two results, for SMS and
+    /// lock screens.
+    Terse,
+    /// Contact fields, urgency and notes for up to five results.
+    #[default]
+    Normal,
+    /// Every result and every date, with the center's code and time zone.
+    Detailed,
+}
+
+/// Presets by channel kind or target, or `*` for the rest; `normal` where
+/// none applies.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct VerbosityRules(BTreeMap<String, Verbosity>);
+
+impl VerbosityRules {
+    /// The preset for a channel's `target`, else its `kind`, else `*`.
+    pub fn for_channel(&self, kind: &str, target: &str) -> Verbosity {
+        [target, kind, "*"].iter().find_map(|key| self.0.get(*key)).copied().unwrap_or_default()
+    }
+
+    /// Every preset some channel may get.
+    pub fn in_use(&self) -> BTreeSet<Verbosity> {
+        self.0.values().copied().chain([Verbosity::Normal]).collect()
+    }
+
+    /// Keys other than `*`, to check against the configured channels.
+    pub fn channels(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str).filter(|key| *key != "*")
+    }
+}
+
+/// How slot dates are written in messages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateFormat {
+    /// `2025-03-14`.
+    #[default]
+    Iso,
+    /// `Mar 14 (Fri)`.
+    Short,
+    /// The locale's own numeric format, e.g. `03/14/2025` or `14.03.2025`.
+    Locale,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TimeFormat {
+    /// `14:30`.
+    #[default]
+    #[serde(rename = "24h")]
+    H24,
+    /// `2:30 PM`.
+    #[serde(rename = "12h")]
+    H12,
+}
+
+/// How much of each cycle's results goes into a notification.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Summarize {
+    /// Every matching (date, location) pair.
+    #[default]
+    All,
+    /// Only the soonest date at each location.
+    EarliestPerLocation,
+}
+
+impl DisplayOptions {
+    /// The alias for `loc` if one is configured, otherwise its API name.
+    pub fn name<'a>(&'a self, loc: &'a Location) -> &'a str {
+        self.location_aliases
+            .get(&loc.key())
+            .map(String::as_str)
+            .unwrap_or(&loc.name)
+    }
+
+    pub fn shows(&self, field: Field) -> bool {
+        !self.hidden_fields.contains(&field)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.locale {
+            Some(locale) if parse_locale(locale).is_none() => {
+                Err(format!("locale: unknown locale {locale:?}; use a code such as en_US or de_DE"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn chrono_locale(&self) -> Locale {
+        self.locale.as_deref().and_then(parse_locale).unwrap_or(Locale::en_US)
+    }
+
+    /// `date` as `date_format` asks.
+    pub fn date(&self, date: NaiveDate) -> String {
+        let format = match self.date_format {
+            DateFormat::Iso => return date.to_string(),
+            DateFormat::Short => "%b %-d (%a)",
+            DateFormat::Locale => "%x",
+        };
+        date.format_localized(format, self.chrono_locale()).to_string()
+    }
+
+    /// A date without its year, with or without the month, as in date lists.
+    fn day(&self, date: NaiveDate, with_month: bool) -> String {
+        date.format_localized(if with_month { "%b %-d" } else { "%-d" }, self.chrono_locale()).to_string()
+    }
+
+    /// `at` as `date_format` and `time_format` ask, e.g. `2025-03-14 14:30`.
+    pub fn date_time<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let time = match self.time_format {
+            TimeFormat::H24 => at.format("%H:%M").to_string(),
+            TimeFormat::H12 => at.format_localized("%-I:%M %p", self.chrono_locale()).to_string(),
+        };
+        format!("{} {}", self.date(at.date_naive()), time.trim_end())
+    }
+
+    /// ` ▼ (was Mar 12)` and the like, after a location's earliest date.
+    pub fn trend(&self, trend: Trend) -> String {
+        match trend {
+            Trend::New => " NEW".to_string(),
+            Trend::Earlier(prev) => format!(" ▼ (was {})", self.date(prev)),
+            Trend::Later(prev) => format!(" ▲ (was {})", self.date(prev)),
+            Trend::Same => String::new(),
+        }
+    }
+}
+
+/// `de_DE`, `de-DE` and the like.
+fn parse_locale(code: &str) -> Option<Locale> {
+    Locale::try_from(code.replace('-', "_").as_str()).ok()
+}
+
+/// One-line description of a slot for digests, e.g. `2025-03-14  SFO ground floor (San Francisco, CA)`.
+pub fn digest_line(item: &FetchedLocation, display: &DisplayOptions) -> String {
+    format!("{}  {} ({}, {})", slot_date(item, display), display.name(&item.loc), item.loc.city, item.loc.state)
+}
+
+/// The slot's date, or `by 2025-03-14` when only that bound is known.
+pub fn slot_date(item: &FetchedLocation, display: &DisplayOptions) -> String {
+    if item.undated {
+        format!("by {}", display.date(item.date))
+    } else {
+        display.date(item.date)
+    }
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Compact date list such as `Mar 14, 15, 18, +9 more days`.
+pub fn date_list(dates: &[NaiveDate], display: &DisplayOptions) -> String {
+    let mut parts = Vec::new();
+    let mut prev: Option<NaiveDate> = None;
+    for date in dates.iter().take(MAX_GROUP_DATES) {
+        let same_month = prev.is_some_and(|p| p.year() == date.year() && p.month() == date.month());
+        parts.push(display.day(*date, !same_month));
+        prev = Some(*date);
+    }
+    if dates.len() > MAX_GROUP_DATES {
+        let more = dates.len() - MAX_GROUP_DATES;
+        parts.push(format!("+{more} more day{}", if more == 1 { "" } else { "s" }));
+    }
+    parts.join(", ")
+}
+
+/// Applies `mode`, keeping results ordered by date.
+pub fn summarize(mut fetched_locations: Vec<FetchedLocation>, mode: Summarize) -> Vec<FetchedLocation> {
+    if mode == Summarize::EarliestPerLocation {
+        fetched_locations.sort_by_key(|item| item.date);
+        let mut seen = Vec::new();
+        fetched_locations.retain(|item| {
+            let key = (item.loc.provider, item.loc.id);
+            if seen.contains(&key) {
+                false
+            } else {
+                seen.push(key);
+                true
+            }
+        });
+    }
+    fetched_locations
+}
+
+/// How a location's earliest date compares to the previous day's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    New,
+    Earlier(NaiveDate),
+    Later(NaiveDate),
+    Same,
+}
+
+/// Each location's current earliest date and its trend, keyed by `Location::key`.
+pub type Trends = HashMap<String, (NaiveDate, Trend)>;
+
+pub fn trends(fetched_locations: &[FetchedLocation], yesterday: &HashMap<String, NaiveDate>) -> Trends {
+    LocationAvailability::merge(fetched_locations)
+        .into_iter()
+        // Without a date there's nothing to compare.
+        .filter(|m| !m.undated)
+        .map(|m| (m.loc.key(), m.earliest()))
+        .map(|(id, now)| {
+            let trend = match yesterday.get(&id) {
+                None => Trend::New,
+                Some(&prev) if now < prev => Trend::Earlier(prev),
+                Some(&prev) if now > prev => Trend::Later(prev),
+                Some(_) => Trend::Same,
+            };
+            (id, (now, trend))
+        })
+        .collect()
+}
+
+/// Median slot lifetime in minutes, keyed by `Location::key`.
+pub type Lifetimes = HashMap<String, f64>;
+
+/// Below this median lifetime a location is flagged as "book fast".
+const BOOK_FAST_MINUTES: f64 = 60.0;
+
+/// Human-readable urgency for a location whose slots last `minutes` (median).
+pub fn urgency(minutes: f64) -> String {
+    let within = if minutes < 1.5 {
+        "a minute".to_string()
+    } else if minutes < 90.0 {
+        format!("{minutes:.0} minutes")
+    } else if minutes < 36.0 * 60.0 {
+        format!("{:.0} hours", minutes / 60.0)
+    } else {
+        format!("{:.0} days", minutes / 1440.0)
+    };
+    if minutes < BOOK_FAST_MINUTES {
+        format!("Book fast: slots here typically vanish within {within}")
+    } else {
+        format!("Slots here typically last about {within}")
+    }
+}
+
+/// Warnings and remarks from the location directory, keyed by `Location::key`,
+/// and calendar conflicts, keyed by slot key.
+pub type CenterNotes = HashMap<String, Vec<String>>;
+
+/// Longest directory note quoted before it's cut short.
+const MAX_NOTE_CHARS: usize = 200;
+
+/// What's worth knowing about `center` before booking there, warnings first.
+pub fn center_notes(center: &CenterInfo) -> Vec<String> {
+    let mut notes = Vec::new();
+    if center.tentatively_closed == Some(true) {
+        notes.push("⚠️ This center is tentatively closed; check before booking".to_string());
+    }
+    let hours = center.hours.as_deref().map(str::trim).filter(|h| !h.is_empty());
+    if let Some(days) = hours.and_then(interview_days) {
+        let weekdays = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+        if !weekdays.iter().all(|d| days.contains(d)) {
+            notes.push(format!("⚠️ This center only does interviews {}", day_list(&days)));
+        }
+    }
+    if let Some(hours) = hours {
+        notes.push(format!("Hours: {hours}"));
+    }
+    if let Some(note) = center.notes.as_deref() {
+        let note = note.split_whitespace().collect::<Vec<_>>().join(" ");
+        if note.chars().count() > MAX_NOTE_CHARS {
+            let cut: String = note.chars().take(MAX_NOTE_CHARS).collect();
+            notes.push(format!("Note: {}…", cut.trim_end()));
+        } else if !note.is_empty() {
+            notes.push(format!("Note: {note}"));
+        }
+    }
+    notes
+}
+
+/// The weekdays named in free-text opening hours such as `Tue-Thu 8am-4pm` or
+/// `Monday through Wednesday, Friday`, in week order; `None` if it names none.
+fn interview_days(hours: &str) -> Option<Vec<Weekday>> {
+    let text = hours.to_lowercase().replace(['–', '—', '-'], " - ");
+    let mut days: Vec<Weekday> = Vec::new();
+    let mut range_from: Option<Weekday> = None;
+    let mut last: Option<Weekday> = None;
+    for word in text.split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '/' | '&' | '.' | ':')) {
+        if matches!(word, "-" | "to" | "through" | "thru") {
+            range_from = last;
+            continue;
+        }
+        let Some(day) = weekday(word) else {
+            // A range only joins two days, not e.g. a time and a day.
+            if !word.is_empty() {
+                range_from = None;
+                last = None;
+            }
+            continue;
+        };
+        match range_from.take() {
+            Some(from) => {
+                let mut d = from;
+                while d != day {
+                    d = d.succ();
+                    days.push(d);
+                }
+            }
+            None => days.push(day),
+        }
+        last = Some(day);
+    }
+    days.sort_by_key(Weekday::num_days_from_monday);
+    days.dedup();
+    (!days.is_empty()).then_some(days)
+}
+
+/// `word` as a weekday if it's a day name or an abbreviation of one (`tues`).
+fn weekday(word: &str) -> Option<Weekday> {
+    const NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+    if word.len() < 3 {
+        return None;
+    }
+    let i = NAMES.iter().position(|name| name.starts_with(word))?;
+    Weekday::try_from(i as u8).ok()
+}
+
+/// `[Tue, Wed, Thu, Sat]` as `Tue–Thu, Sat`.
+fn day_list(days: &[Weekday]) -> String {
+    let mut runs: Vec<(Weekday, Weekday)> = Vec::new();
+    for &day in days {
+        match runs.last_mut() {
+            Some((_, end)) if end.succ() == day => *end = day,
+            _ => runs.push((day, day)),
+        }
+    }
+    runs.iter()
+        .map(|(start, end)| match end.num_days_from_monday() - start.num_days_from_monday() {
+            0 => start.to_string(),
+            1 => format!("{start}, {end}"),
+            _ => format!("{start}–{end}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::providers::GLOBAL_ENTRY_ID;
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn days(dates: &[&str]) -> Vec<NaiveDate> {
+        dates.iter().map(|d| day(d)).collect()
+    }
+
+    /// A slot at location `id` on `date`.
+    fn found(id: usize, date: &str) -> FetchedLocation {
+        let mut loc: Location = serde_json::from_value(serde_json::json!({
+            "id": id, "name": format!("Center {id}"), "shortName": format!("C{id}"), "state": "NY", "city": "Jamaica",
+            "address": "Terminal 4", "postalCode": "11430", "phoneNumber": "555-0100", "tzData": "America/New_York",
+        }))
+        .unwrap();
+        loc.provider = GLOBAL_ENTRY_ID;
+        FetchedLocation {
+            date: day(date),
+            loc,
+            raw_json: String::new(),
+            fetched_at: Utc::now(),
+            undated: false,
+        }
+    }
+
+    #[test]
+    fn date_lists_spell_out_three_dates_and_count_the_rest() {
+        let display = DisplayOptions::default();
+        assert_eq!(date_list(&days(&["2025-03-14"]), &display), "Mar 14");
+        assert_eq!(date_list(&days(&["2025-03-31", "2025-04-01", "2025-04-02"]), &display), "Mar 31, Apr 1, 2");
+        assert_eq!(
+            date_list(&days(&["2025-03-14", "2025-03-15", "2025-03-18", "2025-03-20"]), &display),
+            "Mar 14, 15, 18, +1 more day"
+        );
+        let twelve: Vec<NaiveDate> = day("2025-03-14").iter_days().take(12).collect();
+        assert_eq!(date_list(&twelve, &display), "Mar 14, 15, 16, +9 more days");
+        // A new year starts a new month.
+        assert_eq!(date_list(&days(&["2025-12-31", "2026-01-01"]), &display), "Dec 31, Jan 1");
+    }
+
+    #[test]
+    fn grouping_merges_each_location_sorted_by_its_earliest_date() {
+        let groups = LocationAvailability::merge(&[
+            found(1, "2025-03-18"),
+            found(2, "2025-03-15"),
+            found(1, "2025-03-14"),
+            found(1, "2025-03-18"),
+        ]);
+        let groups: Vec<(usize, Vec<NaiveDate>)> = groups.iter().map(|g| (g.loc.id, g.dates.clone())).collect();
+        assert_eq!(groups, [(1, days(&["2025-03-14", "2025-03-18"])), (2, days(&["2025-03-15"]))]);
+    }
+
+    #[test]
+    fn digest_lines_use_the_alias_and_date_format() {
+        let item = found(5446, "2025-03-14");
+        assert_eq!(digest_line(&item, &DisplayOptions::default()), "2025-03-14  Center 5446 (Jamaica, NY)");
+
+        let display = DisplayOptions {
+            location_aliases: HashMap::from([("5446".to_string(), "SFO ground floor".to_string())]),
+            date_format: DateFormat::Short,
+            ..DisplayOptions::default()
+        };
+        assert_eq!(digest_line(&item, &display), "Mar 14 (Fri)  SFO ground floor (Jamaica, NY)");
+
+        let undated = FetchedLocation { undated: true, ..item };
+        assert_eq!(digest_line(&undated, &display), "by Mar 14 (Fri)  SFO ground floor (Jamaica, NY)");
+    }
+
+    #[test]
+    fn earliest_per_location_keeps_each_locations_soonest_date_in_date_order() {
+        let found = vec![
+            found(1, "2025-03-20"),
+            found(2, "2025-03-18"),
+            found(1, "2025-03-14"),
+            found(2, "2025-03-19"),
+        ];
+        let keys = |found: &[FetchedLocation]| -> Vec<String> { found.iter().map(FetchedLocation::slot_key).collect() };
+        assert_eq!(keys(&summarize(found.clone(), Summarize::All)), keys(&found));
+        assert_eq!(keys(&summarize(found, Summarize::EarliestPerLocation)), ["1@2025-03-14", "2@2025-03-18"]);
+    }
+
+    #[test]
+    fn a_channels_target_outranks_its_kind_and_the_default() {
+        let rules: VerbosityRules =
+            serde_json::from_value(serde_json::json!({"*": "detailed", "sms": "terse", "C0123": "normal"})).unwrap();
+        assert_eq!(rules.for_channel("sms", "+15555550100"), Verbosity::Terse);
+        assert_eq!(rules.for_channel("slack", "C0123"), Verbosity::Normal);
+        assert_eq!(rules.for_channel("email", "me@example.com"), Verbosity::Detailed);
+        assert_eq!(VerbosityRules::default().for_channel("sms", "+15555550100"), Verbosity::Normal);
+        assert_eq!(rules.channels().collect::<Vec<_>>(), ["C0123", "sms"]);
+        assert_eq!(rules.in_use().len(), 3);
+    }
+
+    #[test]
+    fn each_preset_spells_out_as_much_as_it_promises() {
+        let found: Vec<FetchedLocation> = (1..=6).map(|id| found(id, &format!("2025-03-1{id}"))).collect();
+        let display = DisplayOptions::default();
+        let message = |verbosity| {
+            let (trends, lifetimes, notes) = (Trends::new(), Lifetimes::new(), CenterNotes::new());
+            crate::slack::build_slack_message(&found, "Global Entry", &display, verbosity, &trends, &lifetimes, &notes)
+        };
+
+        let (terse, slots) = message(Verbosity::Terse);
+        assert_eq!(
+            terse,
+            "1. 2025-03-11 *Center 1*, Jamaica, NY\n2. 2025-03-12 *Center 2*, Jamaica, NY\n...and 4 more.\n"
+        );
+        assert_eq!(slots, ["1@2025-03-11", "2@2025-03-12"]);
+
+        let (normal, slots) = message(Verbosity::Normal);
+        assert!(normal.starts_with("*Global Entry Availability*\n\n1. (Date: 2025-03-11) *Center 1* (ID: 1) in Jamaica, NY\n"));
+        assert!(normal.contains("Phone: 555-0100\n") && !normal.contains("Code:"), "{normal}");
+        assert!(normal.ends_with("...and 1 more.\n"), "{normal}");
+        assert_eq!(slots.len(), 5);
+
+        let (detailed, slots) = message(Verbosity::Detailed);
+        assert!(detailed.contains("Code: C6\nTime zone: America/New_York\n"), "{detailed}");
+        assert!(!detailed.contains("more."), "{detailed}");
+        assert_eq!(slots.len(), 6);
+    }
+}
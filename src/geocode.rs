@@ -0,0 +1,538 @@
+//! Coordinates for `home` and for the locations a cycle finds, from a
+//! geocoding service, so results can be limited to those within a distance.
+//! Every answer, including "no match", is kept in a cache file, so each
+//! address is only ever looked up once. Lookups run on a background task
+//! and cycles only read the cache, so a slow or failing service never holds
+//! up or fails a cycle; it just leaves results unfiltered.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{header::USER_AGENT, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    sync::Notify,
+    time::{sleep, Duration},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{AppError, FetchedLocation, Location};
+
+/// Nominatim's usage policy allows one request a second.
+const LOOKUP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait before asking again for home after the service failed.
+const HOME_RETRY_INTERVAL: Duration = Duration::from_secs(600);
+
+const EARTH_RADIUS_MILES: f64 = 3958.8;
+
+#[derive(Debug, Deserialize)]
+pub struct HomeConfig {
+    /// A street address or place, e.g. `350 5th Ave, New York, NY 10118`.
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Coordinates, used instead of looking up `address`.
+    #[serde(default)]
+    pub lat: Option<f64>,
+    #[serde(default)]
+    pub lon: Option<f64>,
+    /// Drops locations farther than this from home, as the crow flies.
+    #[serde(default)]
+    pub max_distance_miles: Option<f64>,
+    #[serde(default)]
+    pub geocoder: GeocoderConfig,
+    #[serde(default = "default_cache_path")]
+    pub cache_path: PathBuf,
+}
+
+fn default_cache_path() -> PathBuf {
+    PathBuf::from("geocode_cache.json")
+}
+
+impl HomeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.address, self.lat, self.lon) {
+            (_, Some(lat), Some(lon)) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err(format!("lat {lat}, lon {lon} are not coordinates"));
+                }
+            }
+            (_, Some(_), None) | (_, None, Some(_)) => return Err("set both lat and lon, or neither".to_string()),
+            (Some(address), None, None) if !address.trim().is_empty() => {}
+            _ => return Err("set address, or lat and lon".to_string()),
+        }
+        if self.max_distance_miles.is_some_and(|miles| miles <= 0.0) {
+            return Err("max_distance_miles must be above 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Which service looks up addresses.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum GeocoderConfig {
+    /// OpenStreetMap's Nominatim, or your own instance of it.
+    Nominatim {
+        #[serde(default = "default_nominatim_url")]
+        url: String,
+        /// Sent with each request, as Nominatim's usage policy asks of
+        /// heavy users.
+        #[serde(default)]
+        email: Option<String>,
+    },
+    /// The US Census Bureau's geocoder; US street addresses only.
+    Census {
+        #[serde(default = "default_census_url")]
+        url: String,
+    },
+}
+
+impl Default for GeocoderConfig {
+    fn default() -> Self {
+        GeocoderConfig::Nominatim {
+            url: default_nominatim_url(),
+            email: None,
+        }
+    }
+}
+
+fn default_nominatim_url() -> String {
+    "https://nominatim.openstreetmap.org".to_string()
+}
+
+fn default_census_url() -> String {
+    "https://geocoding.geo.census.gov".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Point {
+    /// Great-circle distance.
+    pub fn miles_to(&self, other: &Point) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_MILES * a.sqrt().asin()
+    }
+}
+
+/// Turns an address into coordinates, or `None` if the service has no match.
+pub trait Geocoder: Send + Sync {
+    fn lookup<'a>(&'a self, client: &'a Client, query: &'a str) -> BoxFuture<'a, Result<Option<Point>, AppError>>;
+}
+
+struct Nominatim {
+    url: String,
+    email: Option<String>,
+}
+
+impl Geocoder for Nominatim {
+    fn lookup<'a>(&'a self, client: &'a Client, query: &'a str) -> BoxFuture<'a, Result<Option<Point>, AppError>> {
+        async move {
+            let mut params = vec![("q", query), ("format", "jsonv2"), ("limit", "1")];
+            if let Some(email) = &self.email {
+                params.push(("email", email));
+            }
+            let found: Vec<Value> = client
+                .get(format!("{}/search", self.url.trim_end_matches('/')))
+                // Nominatim refuses requests without an identifying agent.
+                .header(USER_AGENT, concat!("global-entry-appointment-finder/", env!("CARGO_PKG_VERSION")))
+                .query(&params)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            // Coordinates come back as strings.
+            let coordinate = |value: &Value, key: &str| value[key].as_str().and_then(|s| s.parse().ok());
+            Ok(found.first().and_then(|place| {
+                Some(Point {
+                    lat: coordinate(place, "lat")?,
+                    lon: coordinate(place, "lon")?,
+                })
+            }))
+        }
+        .boxed()
+    }
+}
+
+struct Census {
+    url: String,
+}
+
+impl Geocoder for Census {
+    fn lookup<'a>(&'a self, client: &'a Client, query: &'a str) -> BoxFuture<'a, Result<Option<Point>, AppError>> {
+        async move {
+            let found: Value = client
+                .get(format!("{}/geocoder/locations/onelineaddress", self.url.trim_end_matches('/')))
+                .query(&[("address", query), ("benchmark", "Public_AR_Current"), ("format", "json")])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let coordinates = &found["result"]["addressMatches"][0]["coordinates"];
+            Ok(coordinates["y"].as_f64().zip(coordinates["x"].as_f64()).map(|(lat, lon)| Point { lat, lon }))
+        }
+        .boxed()
+    }
+}
+
+fn build(config: &GeocoderConfig) -> Box<dyn Geocoder> {
+    match config {
+        GeocoderConfig::Nominatim { url, email } => Box::new(Nominatim {
+            url: url.clone(),
+            email: email.clone(),
+        }),
+        GeocoderConfig::Census { url } => Box::new(Census { url: url.clone() }),
+    }
+}
+
+/// Lookups so far, by query; `None` where the service had no match.
+struct Cache {
+    path: PathBuf,
+    entries: BTreeMap<String, Option<Point>>,
+}
+
+impl Cache {
+    fn load(path: &Path) -> Self {
+        let entries = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable geocode cache {}: {e}", path.display());
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Cache {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    fn save(&self) {
+        let written = serde_json::to_string_pretty(&self.entries)
+            .map_err(AppError::from)
+            .and_then(|json| Ok(std::fs::write(&self.path, json)?));
+        if let Err(e) = written {
+            warn!("Error saving geocode cache {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Where home is, and how far found locations are from it. Lookups happen
+/// on a task of their own (see [`Home::warm`]); cycles only read the cache.
+pub struct Home {
+    address: Option<String>,
+    /// Set once home has been given or looked up.
+    point: OnceLock<Point>,
+    max_distance_miles: Option<f64>,
+    client: Client,
+    geocoder: Box<dyn Geocoder>,
+    cache: Mutex<Cache>,
+    /// Locations cycles found that aren't in the cache yet.
+    queue: Mutex<Vec<Location>>,
+    queued: Notify,
+}
+
+impl Home {
+    pub fn new(config: &HomeConfig, client: &Client) -> Self {
+        Home::with_geocoder(config, client, build(&config.geocoder))
+    }
+
+    fn with_geocoder(config: &HomeConfig, client: &Client, geocoder: Box<dyn Geocoder>) -> Self {
+        let point = OnceLock::new();
+        if let (Some(lat), Some(lon)) = (config.lat, config.lon) {
+            let _ = point.set(Point { lat, lon });
+        }
+        Home {
+            address: config.address.clone(),
+            point,
+            max_distance_miles: config.max_distance_miles,
+            client: client.clone(),
+            geocoder,
+            cache: Mutex::new(Cache::load(&config.cache_path)),
+            queue: Mutex::new(Vec::new()),
+            queued: Notify::new(),
+        }
+    }
+
+    /// Locates home, then looks up `seen` (say, the last cycle's locations)
+    /// and whatever cycles queue, a second apart. Runs for the life of the
+    /// process, or stops if home can't be found.
+    pub async fn warm(self: Arc<Self>, seen: Vec<Location>) {
+        if !self.locate().await {
+            return;
+        }
+        let mut pending = seen;
+        loop {
+            for loc in pending.drain(..) {
+                if self.place(&loc).await.1 {
+                    sleep(LOOKUP_INTERVAL).await;
+                }
+            }
+            self.queued.notified().await;
+            pending = std::mem::take(&mut *self.queue.lock().unwrap());
+        }
+    }
+
+    /// Waits for home's coordinates, asking again while the service fails.
+    /// `false` if the service can't place `address`.
+    async fn locate(&self) -> bool {
+        if self.point.get().is_some() {
+            return true;
+        }
+        let Some(address) = &self.address else {
+            return false;
+        };
+        loop {
+            match self.lookup(address).await {
+                Ok((Some(point), _)) => {
+                    info!("Home is at {:.4}, {:.4}", point.lat, point.lon);
+                    let _ = self.point.set(point);
+                    return true;
+                }
+                Ok((None, _)) => {
+                    error!("home: {address:?} wasn't found, so nothing is left out by distance; check it, or set lat and lon");
+                    return false;
+                }
+                Err(e) => {
+                    warn!("Error geocoding home, so nothing is left out by distance until it works: {e}");
+                    sleep(HOME_RETRY_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    /// The cached answer for `query`, or the service's, and whether the
+    /// service was asked.
+    async fn lookup(&self, query: &str) -> Result<(Option<Point>, bool), AppError> {
+        if let Some(&found) = self.cache.lock().unwrap().entries.get(query) {
+            return Ok((found, false));
+        }
+        let found = self.geocoder.lookup(&self.client, query).await?;
+        debug!("Geocoded {query:?}: {found:?}");
+        let mut cache = self.cache.lock().unwrap();
+        cache.entries.insert(query.to_string(), found);
+        cache.save();
+        Ok((found, true))
+    }
+
+    /// Where `loc` is: its street address, or failing that its city and
+    /// postal code. `None` if neither can be placed or the service failed.
+    async fn place(&self, loc: &Location) -> (Option<Point>, bool) {
+        let mut asked = false;
+        for query in queries(loc) {
+            if asked {
+                sleep(LOOKUP_INTERVAL).await;
+            }
+            match self.lookup(&query).await {
+                Ok((Some(point), looked_up)) => return (Some(point), asked || looked_up),
+                Ok((None, looked_up)) => asked |= looked_up,
+                Err(e) => {
+                    warn!("Error geocoding {query:?}: {e}");
+                    return (None, true);
+                }
+            }
+        }
+        (None, asked)
+    }
+
+    /// Where the cache places `loc`: `Some(None)` if it can't be placed,
+    /// `None` if it hasn't been looked up yet.
+    fn cached(cache: &Cache, loc: &Location) -> Option<Option<Point>> {
+        for query in queries(loc) {
+            match cache.entries.get(&query)? {
+                Some(point) => return Some(Some(*point)),
+                None => continue,
+            }
+        }
+        Some(None)
+    }
+
+    /// Miles from home to each location in `found` the cache places, by
+    /// `Location::key`, queueing those not looked up yet.
+    fn distances(&self, home: &Point, found: &[FetchedLocation]) -> HashMap<String, f64> {
+        let mut distances = HashMap::new();
+        let mut unknown: Vec<Location> = Vec::new();
+        {
+            let cache = self.cache.lock().unwrap();
+            for item in found {
+                let key = item.loc.key();
+                if distances.contains_key(&key) || unknown.iter().any(|loc| loc.key() == key) {
+                    continue;
+                }
+                match Home::cached(&cache, &item.loc) {
+                    Some(Some(point)) => {
+                        distances.insert(key, home.miles_to(&point));
+                    }
+                    Some(None) => {}
+                    None => unknown.push(item.loc.clone()),
+                }
+            }
+        }
+        if !unknown.is_empty() {
+            let mut queue = self.queue.lock().unwrap();
+            for loc in unknown {
+                if !queue.iter().any(|queued| queued.key() == loc.key()) {
+                    queue.push(loc);
+                }
+            }
+            self.queued.notify_one();
+        }
+        distances
+    }
+
+    /// Drops results farther than `max_distance_miles`. Until home is
+    /// located nothing is dropped, and locations not looked up yet or that
+    /// can't be placed are kept, so a geocoding gap never hides a slot.
+    pub fn retain_nearby(&self, found: &mut Vec<FetchedLocation>) {
+        let (Some(max), Some(home)) = (self.max_distance_miles, self.point.get()) else {
+            return;
+        };
+        let distances = self.distances(home, found);
+        let before = found.len();
+        found.retain(|item| distances.get(&item.loc.key()).is_none_or(|&miles| miles <= max));
+        if found.len() < before {
+            debug!("Dropped {} result(s) over {max} miles from home", before - found.len());
+        }
+    }
+}
+
+/// What to ask the service for `loc`, best first.
+fn queries(loc: &Location) -> [String; 2] {
+    [
+        format!("{}, {}, {} {}", loc.address, loc.city, loc.state, loc.postal_code),
+        format!("{}, {} {}", loc.city, loc.state, loc.postal_code),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Knows the places in `places`, counts lookups, and fails while `down`
+    /// is set.
+    struct Stub {
+        places: Vec<(&'static str, Point)>,
+        asked: Arc<AtomicUsize>,
+        down: Arc<AtomicBool>,
+    }
+
+    impl Geocoder for Stub {
+        fn lookup<'a>(&'a self, _: &'a Client, query: &'a str) -> BoxFuture<'a, Result<Option<Point>, AppError>> {
+            async move {
+                self.asked.fetch_add(1, Ordering::Relaxed);
+                if self.down.load(Ordering::Relaxed) {
+                    return Err(AppError::General("geocoder is down".to_string()));
+                }
+                Ok(self.places.iter().find(|(place, _)| query.starts_with(place)).map(|(_, point)| *point))
+            }
+            .boxed()
+        }
+    }
+
+    const MANHATTAN: Point = Point { lat: 40.7484, lon: -73.9857 };
+    const JFK: Point = Point { lat: 40.6413, lon: -73.7781 };
+    const SFO: Point = Point { lat: 37.6213, lon: -122.3790 };
+
+    fn home(name: &str, home: serde_json::Value, down: bool) -> (Arc<Home>, Arc<AtomicUsize>, Arc<AtomicBool>) {
+        let cache_path = crate::notify::tests::state_path(&format!("geocode-{name}"));
+        let _ = std::fs::remove_file(&cache_path);
+        let mut config = home;
+        config["max_distance_miles"] = 50.into();
+        config["cache_path"] = cache_path.to_string_lossy().into();
+        let config: HomeConfig = serde_json::from_value(config).unwrap();
+        let (asked, down) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicBool::new(down)));
+        let stub = Stub {
+            places: vec![("350 5th Ave", MANHATTAN), ("Terminal 4", JFK), ("Terminal A", SFO)],
+            asked: Arc::clone(&asked),
+            down: Arc::clone(&down),
+        };
+        (Arc::new(Home::with_geocoder(&config, &Client::new(), Box::new(stub))), asked, down)
+    }
+
+    fn found(id: usize, address: &str) -> FetchedLocation {
+        let loc: Location = serde_json::from_value(serde_json::json!({
+            "id": id, "name": "Center", "state": "XX", "city": "Nowhere", "address": address, "postalCode": "00000",
+        }))
+        .unwrap();
+        FetchedLocation {
+            date: chrono::NaiveDate::from_ymd_opt(2025, 3, 14).unwrap(),
+            loc,
+            raw_json: String::new(),
+            fetched_at: chrono::Utc::now(),
+            undated: false,
+        }
+    }
+
+    fn ids(found: &[FetchedLocation]) -> Vec<usize> {
+        found.iter().map(|item| item.loc.id).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cycles_only_filter_on_what_the_background_task_placed() {
+        let (home, asked, _) = home("placed", serde_json::json!({ "lat": MANHATTAN.lat, "lon": MANHATTAN.lon }), false);
+        let all = vec![found(1, "Terminal 4"), found(2, "Terminal A"), found(3, "Unknown Rd")];
+        let mut results = all.clone();
+        home.retain_nearby(&mut results);
+        assert_eq!(ids(&results), [1, 2, 3]);
+        assert_eq!(asked.load(Ordering::Relaxed), 0);
+
+        tokio::spawn(Arc::clone(&home).warm(Vec::new()));
+        sleep(Duration::from_secs(30)).await;
+        // Two queries each for the unknown place, one for the others.
+        assert_eq!(asked.load(Ordering::Relaxed), 4);
+        let mut results = all.clone();
+        home.retain_nearby(&mut results);
+        assert_eq!(ids(&results), [1, 3]);
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(asked.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failing_service_leaves_results_unfiltered_until_it_recovers() {
+        let (home, asked, down) = home("down", serde_json::json!({ "address": "350 5th Ave, New York" }), true);
+        let task = tokio::spawn(Arc::clone(&home).warm(vec![found(1, "Terminal 4").loc, found(2, "Terminal A").loc]));
+        sleep(Duration::from_secs(30)).await;
+        let mut results = vec![found(1, "Terminal 4"), found(2, "Terminal A")];
+        home.retain_nearby(&mut results);
+        assert_eq!(ids(&results), [1, 2]);
+        assert_eq!(asked.load(Ordering::Relaxed), 1);
+
+        down.store(false, Ordering::Relaxed);
+        sleep(HOME_RETRY_INTERVAL).await;
+        home.retain_nearby(&mut results);
+        assert_eq!(ids(&results), [1]);
+        assert!(!task.is_finished());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_home_the_service_cant_place_turns_the_filter_off() {
+        let (home, _, _) = home("lost", serde_json::json!({ "address": "1 Nowhere Ln" }), false);
+        Arc::clone(&home).warm(vec![found(2, "Terminal A").loc]).await;
+        let mut results = vec![found(2, "Terminal A")];
+        home.retain_nearby(&mut results);
+        assert_eq!(ids(&results), [2]);
+    }
+
+    #[test]
+    fn distance_between_airports() {
+        let jfk = Point { lat: 40.6413, lon: -73.7781 };
+        let sfo = Point { lat: 37.6213, lon: -122.3790 };
+        // About 2,580 miles on a spherical Earth.
+        assert!((jfk.miles_to(&sfo) - 2580.0).abs() < 5.0, "{}", jfk.miles_to(&sfo));
+    }
+}
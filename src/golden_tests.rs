@@ -0,0 +1,202 @@
+//! Golden-file tests over the sanitized API responses in `tests/fixtures/api`.
+//! Each test renders its result as text and compares it with the matching file
+//! in `tests/golden`; run with `UPDATE_GOLDEN=1` to accept intentional changes.
+
+use std::{collections::HashMap, fmt::Write, path::PathBuf};
+
+use chrono::NaiveDate;
+use reqwest::StatusCode;
+
+use crate::{
+    clock,
+    events::{self, AvailabilityEvent},
+    format::{DisplayOptions, Verbosity},
+    providers::GLOBAL_ENTRY_ID,
+    slack,
+    ttp_client::{classify, parse_entries, Location},
+    FetchedLocation, JeffConfig, Scan,
+};
+
+const FIXTURES: &[&str] = &[
+    "empty.json",
+    "typical.json",
+    "missing_phone.json",
+    "extra_fields.json",
+    "malformed_element.json",
+    "error_object.json",
+    "html_error.html",
+    "maintenance.html",
+    "rate_limited.html",
+    "waf_block.html",
+];
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(root().join("fixtures/api").join(name)).unwrap()
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = root().join("golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {e}; run with UPDATE_GOLDEN=1 to create it", path.display()));
+    assert_eq!(
+        expected, actual,
+        "output differs from {}; run with UPDATE_GOLDEN=1 if the change is intended",
+        path.display()
+    );
+}
+
+fn describe(loc: &Location) -> String {
+    format!(
+        "{} | {} | {}, {} | {} {} {} | phone {:?} | short {:?} tz {:?} operational {:?} services {:?} | extras {:?}",
+        loc.id,
+        loc.name,
+        loc.city,
+        loc.state,
+        loc.address,
+        loc.address_additional.as_deref().unwrap_or("-"),
+        loc.postal_code,
+        loc.phone_number,
+        loc.short_name,
+        loc.tz_data,
+        loc.operational,
+        loc.service_names(),
+        loc.extras.keys().collect::<Vec<_>>(),
+    )
+}
+
+/// Every fixture that parses, as global_entry locations on two fixed dates.
+fn fetched() -> Vec<FetchedLocation> {
+    let dates = [
+        NaiveDate::from_ymd_opt(2025, 3, 14).unwrap(),
+        NaiveDate::from_ymd_opt(2025, 3, 21).unwrap(),
+    ];
+    let mut out = Vec::new();
+    for date in dates {
+        for name in FIXTURES {
+            let Ok(entries) = parse_entries::<Location>(&fixture(name)) else {
+                continue;
+            };
+            for entry in entries {
+                let mut loc = entry.value;
+                loc.provider = GLOBAL_ENTRY_ID;
+                out.push(FetchedLocation {
+                    date,
+                    loc,
+                    raw_json: entry.raw.to_string(),
+                    fetched_at: clock::now(),
+                    undated: false,
+                });
+            }
+        }
+    }
+    out
+}
+
+fn fixture_config() -> JeffConfig {
+    serde_json::from_str(&std::fs::read_to_string(root().join("fixtures/config.json")).unwrap()).unwrap()
+}
+
+#[test]
+fn parsing() {
+    for name in FIXTURES {
+        let mut out = String::new();
+        match parse_entries::<Location>(&fixture(name)) {
+            Ok(entries) => {
+                writeln!(out, "{} location(s)", entries.len()).unwrap();
+                for entry in entries {
+                    writeln!(out, "{}", describe(&entry.value)).unwrap();
+                }
+            }
+            Err(e) => writeln!(out, "error: {e}").unwrap(),
+        }
+        assert_golden(&format!("parse_{}.txt", name.split('.').next().unwrap()), &out);
+    }
+}
+
+#[test]
+fn classification() {
+    let mut out = String::new();
+    for name in FIXTURES {
+        for status in [StatusCode::OK, StatusCode::FORBIDDEN, StatusCode::SERVICE_UNAVAILABLE] {
+            let verdict = match classify(status, &fixture(name)) {
+                Some(outage) => format!("{outage:?}"),
+                None => "api response".to_string(),
+            };
+            writeln!(out, "{name} {}: {verdict}", status.as_u16()).unwrap();
+        }
+    }
+    assert_golden("classify.txt", &out);
+}
+
+#[test]
+fn filtering() {
+    let config = fixture_config();
+    let mut out = String::new();
+    for item in fetched() {
+        let verdict = if config.wants(&item.loc) { "keep" } else { "drop" };
+        writeln!(out, "{verdict} {} {}", item.date, describe(&item.loc)).unwrap();
+    }
+    assert_golden("filter.txt", &out);
+}
+
+#[test]
+fn message_building() {
+    let config = fixture_config();
+    let kept: Vec<FetchedLocation> = fetched().into_iter().filter(|item| config.wants(&item.loc)).collect();
+    let mut grouped = fixture_config().display;
+    grouped.group_by_location = true;
+    let cases = [
+        ("message_flat.txt", &kept[..], &config.display, Verbosity::Normal),
+        ("message_grouped.txt", &kept[..], &grouped, Verbosity::Normal),
+        ("message_terse.txt", &kept[..], &config.display, Verbosity::Terse),
+        ("message_detailed.txt", &kept[..], &grouped, Verbosity::Detailed),
+        ("message_empty.txt", &[][..], &DisplayOptions::default(), Verbosity::Normal),
+    ];
+    for (name, items, display, verbosity) in cases {
+        let (text, slots) = slack::build_slack_message(items, "Global Entry", display, verbosity, &HashMap::new(), &HashMap::new(), &HashMap::new());
+        assert_golden(name, &format!("{text}\n--- slots\n{}\n", slots.join("\n")));
+    }
+}
+
+#[test]
+fn availability_events() {
+    let first = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+    let second = NaiveDate::from_ymd_opt(2025, 3, 21).unwrap();
+    let today = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    let scan = |dates: &[NaiveDate], failed: &[NaiveDate]| Scan {
+        locations: fetched().into_iter().filter(|item| dates.contains(&item.date)).collect(),
+        scanned_ok: dates.iter().map(|d| (GLOBAL_ENTRY_ID, *d)).collect(),
+        dates_failed: failed.len(),
+        outages: Default::default(),
+    };
+    let cycles = [
+        ("both dates open", scan(&[first, second], &[])),
+        ("second date failed to load", scan(&[first], &[second])),
+        ("second date loaded empty", Scan {
+            scanned_ok: vec![(GLOBAL_ENTRY_ID, first), (GLOBAL_ENTRY_ID, second)],
+            ..scan(&[first], &[])
+        }),
+    ];
+    let mut open = Default::default();
+    let mut out = String::new();
+    for (name, scan) in cycles {
+        writeln!(out, "# {name}").unwrap();
+        for event in events::diff(&mut open, scan, today) {
+            match event {
+                AvailabilityEvent::Opened(a) => writeln!(out, "opened {} {}", a.slot, a.location.name),
+                AvailabilityEvent::Closed(a) => writeln!(out, "closed {} {}", a.slot, a.location.name),
+                other => writeln!(out, "{other:?}"),
+            }
+            .unwrap();
+        }
+    }
+    assert_golden("events.txt", &out);
+}
@@ -0,0 +1,290 @@
+//! Renders recent availability as a heatmap, locations down the side and slot
+//! dates across, each cell shaded by the share of cycles the slot was open.
+
+use std::{collections::BTreeMap, path::Path, sync::Mutex};
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc, Weekday};
+use plotters::{prelude::*, style::RGBColor};
+use rusqlite::params;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    clock,
+    history::{History, TS_FORMAT},
+    notify::{last_daily, Attachment, Dispatcher},
+    state::StateStore,
+    AppError, CycleReport, JeffConfig,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct HeatmapConfig {
+    #[serde(default)]
+    pub every: HeatmapEvery,
+    /// Local hour of day (0-23) at which the daily heatmap becomes due.
+    #[serde(default = "default_hour")]
+    pub hour: u32,
+    /// Days of history each heatmap covers.
+    #[serde(default = "default_days")]
+    pub days: u32,
+    #[serde(default)]
+    pub format: HeatmapFormat,
+    #[serde(default = "default_output_dir")]
+    pub output_dir: String,
+    /// Also send the image to the channels that take files (Slack, email).
+    #[serde(default)]
+    pub attach: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatmapEvery {
+    /// After each cycle, overwriting `heatmap.<format>`; attached only when
+    /// the cycle sent an alert.
+    Cycle,
+    /// Once a day at `hour`, as `heatmap-YYYY-MM-DD.<format>`.
+    #[default]
+    Day,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeatmapFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl HeatmapFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            HeatmapFormat::Png => "png",
+            HeatmapFormat::Svg => "svg",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            HeatmapFormat::Png => "image/png",
+            HeatmapFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+fn default_hour() -> u32 {
+    8
+}
+
+fn default_days() -> u32 {
+    7
+}
+
+fn default_output_dir() -> String {
+    "heatmaps".to_string()
+}
+
+impl HeatmapConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.hour > 23 {
+            return Err(format!("hour must be 0-23, got {}", self.hour));
+        }
+        if self.days == 0 {
+            return Err("days must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Availability per location and slot date over a window of cycles.
+#[derive(Debug)]
+pub struct Grid {
+    pub cycles: i64,
+    /// Location names, in display order.
+    pub rows: Vec<String>,
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    /// Cycles each (row, date) was open in.
+    pub open: BTreeMap<(usize, NaiveDate), i64>,
+}
+
+impl Grid {
+    fn share(&self, row: usize, date: NaiveDate) -> f64 {
+        let open = self.open.get(&(row, date)).copied().unwrap_or(0);
+        open as f64 / self.cycles.max(1) as f64
+    }
+
+    /// Every date from the first open slot to the last, gaps included.
+    fn dates(&self) -> Vec<NaiveDate> {
+        self.first.iter_days().take_while(|d| *d <= self.last).collect()
+    }
+}
+
+impl History {
+    /// The grid for cycles finished in `[since, until)`, or `None` if no
+    /// slot was open in any of them.
+    pub fn heatmap_grid(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Option<Grid>, AppError> {
+        let (since, until) = (since.format(TS_FORMAT).to_string(), until.format(TS_FORMAT).to_string());
+        let cycles: i64 = self.conn().query_row(
+            "SELECT COUNT(*) FROM cycles WHERE finished_at >= ?1 AND finished_at < ?2",
+            params![since, until],
+            |row| row.get(0),
+        )?;
+        let mut stmt = self.conn().prepare(
+            "SELECT MAX(name), state, slot_date, COUNT(DISTINCT cycle_id) FROM observations
+             WHERE observed_at >= ?1 AND observed_at < ?2
+             GROUP BY provider, location_id, slot_date
+             ORDER BY state, MAX(name)",
+        )?;
+        let cells = stmt
+            .query_map(params![since, until], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut rows: Vec<String> = Vec::new();
+        let mut open = BTreeMap::new();
+        for (name, state, date, count) in cells {
+            let Ok(date) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+                continue;
+            };
+            let label = format!("{name} ({state})");
+            let row = match rows.iter().position(|r| *r == label) {
+                Some(i) => i,
+                None => {
+                    rows.push(label);
+                    rows.len() - 1
+                }
+            };
+            *open.entry((row, date)).or_default() += count;
+        }
+        let dates = || open.keys().map(|&(_, date)| date);
+        let (Some(first), Some(last)) = (dates().min(), dates().max()) else {
+            return Ok(None);
+        };
+        Ok(Some(Grid {
+            cycles,
+            rows,
+            first,
+            last,
+            open,
+        }))
+    }
+}
+
+const CELL: u32 = 14;
+const MARGIN: u32 = 10;
+const HEADER: u32 = 44;
+const TEXT: RGBColor = RGBColor(0x33, 0x33, 0x33);
+const CLOSED: RGBColor = RGBColor(0xee, 0xee, 0xee);
+const LEAST: RGBColor = RGBColor(0xc7, 0xe9, 0xc0);
+const MOST: RGBColor = RGBColor(0x00, 0x6d, 0x2c);
+
+/// Never-open cells are grey; open ones run from pale to dark green.
+fn shade(share: f64) -> RGBColor {
+    if share <= 0.0 {
+        return CLOSED;
+    }
+    let mix = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * share.min(1.0)).round() as u8;
+    RGBColor(mix(LEAST.0, MOST.0), mix(LEAST.1, MOST.1), mix(LEAST.2, MOST.2))
+}
+
+/// Draws `grid` to `path`, titled `title`.
+pub fn render(grid: &Grid, title: &str, format: HeatmapFormat, path: &Path) -> Result<(), AppError> {
+    let label_width = grid.rows.iter().map(|r| r.chars().count()).max().unwrap_or(0) as u32 * 7 + MARGIN;
+    let dates = grid.dates();
+    let size = (
+        (MARGIN + label_width + dates.len() as u32 * CELL).max(title.chars().count() as u32 * 8) + MARGIN,
+        HEADER + grid.rows.len() as u32 * CELL + MARGIN,
+    );
+    let drawn = match format {
+        HeatmapFormat::Png => draw(BitMapBackend::new(path, size).into_drawing_area(), grid, &dates, title, label_width)
+            .map_err(|e| e.to_string()),
+        HeatmapFormat::Svg => draw(SVGBackend::new(path, size).into_drawing_area(), grid, &dates, title, label_width)
+            .map_err(|e| e.to_string()),
+    };
+    drawn.map_err(|e| AppError::General(format!("Error drawing heatmap: {e}")))
+}
+
+fn draw<DB: DrawingBackend>(
+    area: DrawingArea<DB, plotters::coord::Shift>,
+    grid: &Grid,
+    dates: &[NaiveDate],
+    title: &str,
+    label_width: u32,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let font = |size| ("sans-serif", size).into_font().color(&TEXT);
+    let left = (MARGIN + label_width) as i32;
+    area.fill(&WHITE)?;
+    area.draw(&Text::new(title.to_string(), (MARGIN as i32, MARGIN as i32), font(14)))?;
+    for (i, date) in dates.iter().enumerate() {
+        if date.weekday() == Weekday::Mon || i == 0 {
+            let x = left + (i as u32 * CELL) as i32;
+            area.draw(&Text::new(date.format("%b %-d").to_string(), (x, HEADER as i32 - 14), font(11)))?;
+        }
+    }
+    for (row, label) in grid.rows.iter().enumerate() {
+        let y = (HEADER + row as u32 * CELL) as i32;
+        area.draw(&Text::new(label.clone(), (MARGIN as i32, y + 2), font(11)))?;
+        for (i, date) in dates.iter().enumerate() {
+            let x = left + (i as u32 * CELL) as i32;
+            let corners = [(x, y), (x + CELL as i32 - 1, y + CELL as i32 - 1)];
+            area.draw(&Rectangle::new(corners, shade(grid.share(row, *date)).filled()))?;
+        }
+    }
+    area.present()
+}
+
+/// Draws and sends whatever heatmap `heatmap` says is due after `report`.
+pub async fn maybe_render(
+    report: &CycleReport,
+    dispatcher: &Dispatcher,
+    config: &JeffConfig,
+    state: &Mutex<StateStore>,
+    history: &Mutex<History>,
+) -> Result<(), AppError> {
+    let Some(heatmap) = &config.heatmap else {
+        return Ok(());
+    };
+    let (until, filename, attach) = match heatmap.every {
+        HeatmapEvery::Cycle => (clock::now(), "heatmap".to_string(), heatmap.attach && report.slots_alerted > 0),
+        HeatmapEvery::Day => {
+            let Some(due) = last_daily(clock::local_now(), heatmap.hour) else {
+                return Ok(());
+            };
+            if state.lock().unwrap().last_heatmap().is_some_and(|last| last >= due) {
+                return Ok(());
+            }
+            (due, format!("heatmap-{}", due.with_timezone(&Local).format("%Y-%m-%d")), heatmap.attach)
+        }
+    };
+
+    let since = until - Duration::days(i64::from(heatmap.days));
+    let grid = history.lock().unwrap().heatmap_grid(since, until)?;
+    if let Some(grid) = grid {
+        let title = format!(
+            "Open slots, last {} day(s): share of {} cycle(s) each date was open",
+            heatmap.days, grid.cycles
+        );
+        std::fs::create_dir_all(&heatmap.output_dir)?;
+        let path = Path::new(&heatmap.output_dir).join(format!("{filename}.{}", heatmap.format.extension()));
+        render(&grid, &title, heatmap.format, &path)?;
+        info!("Wrote heatmap to {}", path.display());
+        if attach {
+            let file = Attachment {
+                filename: format!("{filename}.{}", heatmap.format.extension()),
+                content_type: heatmap.format.content_type().to_string(),
+                bytes: std::fs::read(&path)?,
+            };
+            dispatcher.send_file(&file, &title).await;
+        }
+    } else {
+        info!("No open slots in the last {} day(s); no heatmap to draw", heatmap.days);
+    }
+
+    if heatmap.every == HeatmapEvery::Day {
+        let mut store = state.lock().unwrap();
+        store.set_last_heatmap(until);
+        store.save()?;
+    }
+    Ok(())
+}
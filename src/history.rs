@@ -0,0 +1,663 @@
+use std::{collections::HashMap, path::Path};
+
+use chrono::{DateTime, NaiveDate, Utc};
+#[cfg(feature = "sqlite")]
+use chrono::NaiveDateTime;
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::{AppError, FetchedLocation};
+#[cfg(feature = "sqlite")]
+use crate::{
+    clock,
+    providers::{known_id, location_key, GLOBAL_ENTRY_ID},
+    Location,
+};
+
+/// Timestamp format used for every time column, so SQLite's date functions work on it.
+pub const TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Schema changes applied in order; `PRAGMA user_version` records how many have run.
+#[cfg(feature = "sqlite")]
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE IF NOT EXISTS cycles (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at    TEXT NOT NULL,
+    finished_at   TEXT NOT NULL,
+    dates_scanned INTEGER NOT NULL,
+    dates_failed  INTEGER NOT NULL,
+    slots_found   INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS observations (
+    cycle_id    INTEGER NOT NULL REFERENCES cycles(id),
+    observed_at TEXT NOT NULL,
+    slot_date   TEXT NOT NULL,
+    location_id INTEGER NOT NULL,
+    name        TEXT NOT NULL,
+    state       TEXT NOT NULL,
+    city        TEXT NOT NULL,
+    raw_json    TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS observations_observed_at ON observations(observed_at);
+CREATE TABLE IF NOT EXISTS slots (
+    location_id INTEGER NOT NULL,
+    slot_date   TEXT NOT NULL,
+    first_seen  TEXT NOT NULL,
+    last_seen   TEXT NOT NULL,
+    gone_at     TEXT,
+    PRIMARY KEY (location_id, slot_date)
+);
+CREATE TABLE IF NOT EXISTS alerts (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    sent_at    TEXT NOT NULL,
+    channel    TEXT NOT NULL,
+    slot_count INTEGER NOT NULL
+);
+",
+    "
+ALTER TABLE observations ADD COLUMN provider TEXT NOT NULL DEFAULT 'global_entry';
+CREATE TABLE slots_new (
+    provider    TEXT NOT NULL DEFAULT 'global_entry',
+    location_id INTEGER NOT NULL,
+    slot_date   TEXT NOT NULL,
+    first_seen  TEXT NOT NULL,
+    last_seen   TEXT NOT NULL,
+    gone_at     TEXT,
+    PRIMARY KEY (provider, location_id, slot_date)
+);
+INSERT INTO slots_new (location_id, slot_date, first_seen, last_seen, gone_at)
+    SELECT location_id, slot_date, first_seen, last_seen, gone_at FROM slots;
+DROP TABLE slots;
+ALTER TABLE slots_new RENAME TO slots;
+",
+    "
+CREATE TABLE daily_summaries (
+    day           TEXT NOT NULL,
+    provider      TEXT NOT NULL,
+    location_id   INTEGER NOT NULL,
+    name          TEXT NOT NULL,
+    state         TEXT NOT NULL,
+    city          TEXT NOT NULL,
+    observations  INTEGER NOT NULL,
+    slot_dates    INTEGER NOT NULL,
+    earliest_slot TEXT NOT NULL,
+    first_seen    TEXT NOT NULL,
+    last_seen     TEXT NOT NULL,
+    PRIMARY KEY (day, provider, location_id)
+);
+",
+    "
+CREATE TABLE alerted_slots (
+    provider    TEXT NOT NULL,
+    location_id INTEGER NOT NULL,
+    slot_date   TEXT NOT NULL,
+    alerted_at  TEXT NOT NULL,
+    gone_at     TEXT,
+    PRIMARY KEY (provider, location_id, slot_date, alerted_at)
+);
+",
+    "
+CREATE TABLE summary_slot_dates (
+    day         TEXT NOT NULL,
+    provider    TEXT NOT NULL,
+    location_id INTEGER NOT NULL,
+    slot_date   TEXT NOT NULL,
+    PRIMARY KEY (day, provider, location_id, slot_date)
+) WITHOUT ROWID;
+",
+];
+
+/// Outcome of one fetch cycle, as stored in the `cycles` table.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+pub struct CycleRecord {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Provider and date pairs whose fetch succeeded; only these can mark slots as gone.
+    pub scanned_ok: Vec<(&'static str, NaiveDate)>,
+    pub dates_failed: usize,
+}
+
+#[cfg(feature = "sqlite")]
+fn migrate(conn: &Connection) -> Result<(), AppError> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", i + 1)?;
+    }
+    Ok(())
+}
+
+/// SQLite-backed record of every cycle, observed slot and sent alert.
+#[cfg(feature = "sqlite")]
+pub struct History {
+    conn: Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl History {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let conn = Connection::open(path)?;
+        migrate(&conn)?;
+        Ok(History { conn })
+    }
+
+    /// Opens an existing database without allowing writes, for ad-hoc queries.
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(History { conn })
+    }
+
+    /// Whether every migration has run, so the current schema can be queried.
+    pub fn is_current(&self) -> Result<bool, AppError> {
+        let version: usize = self.conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        Ok(version >= MIGRATIONS.len())
+    }
+
+    /// Caps SQLite's page cache at 512 KiB, a quarter of its default.
+    pub fn shrink_cache(&self) -> Result<(), AppError> {
+        Ok(self.conn.execute_batch("PRAGMA cache_size = -512")?)
+    }
+
+    pub fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn conn_mut(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+
+    /// Stores a cycle with its observations and updates slot lifetimes,
+    /// returning how many slots appeared (or reappeared) in this cycle.
+    pub fn record_cycle(
+        &mut self,
+        cycle: &CycleRecord,
+        locations: &[FetchedLocation],
+    ) -> Result<usize, AppError> {
+        let observed_at = cycle.finished_at.format(TS_FORMAT).to_string();
+        let tx = self.conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO cycles (started_at, finished_at, dates_scanned, dates_failed, slots_found)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                cycle.started_at.format(TS_FORMAT).to_string(),
+                observed_at,
+                cycle.scanned_ok.len() + cycle.dates_failed,
+                cycle.dates_failed,
+                locations.len(),
+            ],
+        )?;
+        let cycle_id = tx.last_insert_rowid();
+
+        {
+            let mut insert_obs = tx.prepare(
+                "INSERT INTO observations
+                 (cycle_id, observed_at, slot_date, location_id, name, state, city, raw_json, provider)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            let mut upsert_slot = tx.prepare(
+                "INSERT INTO slots (provider, location_id, slot_date, first_seen, last_seen)
+                 VALUES (?1, ?2, ?3, ?4, ?4)
+                 ON CONFLICT (provider, location_id, slot_date) DO UPDATE SET
+                     last_seen = excluded.last_seen,
+                     first_seen = CASE WHEN gone_at IS NULL THEN first_seen ELSE excluded.first_seen END,
+                     gone_at = NULL",
+            )?;
+            // An undated result has no slot date to be filed under.
+            for item in locations.iter().filter(|item| !item.undated) {
+                let slot_date = item.date.to_string();
+                insert_obs.execute(params![
+                    cycle_id,
+                    observed_at,
+                    slot_date,
+                    item.loc.id,
+                    item.loc.name,
+                    item.loc.state,
+                    item.loc.city,
+                    item.raw_json,
+                    item.loc.provider,
+                ])?;
+                upsert_slot.execute(params![item.loc.provider, item.loc.id, slot_date, observed_at])?;
+            }
+
+            // Open slots on a successfully scanned date that we didn't see this time are gone.
+            let mut mark_gone = tx.prepare(
+                "UPDATE slots SET gone_at = ?1
+                 WHERE provider = ?2 AND slot_date = ?3 AND gone_at IS NULL AND last_seen < ?1",
+            )?;
+            for (provider, date) in &cycle.scanned_ok {
+                mark_gone.execute(params![observed_at, provider, date.to_string()])?;
+            }
+            tx.execute(
+                "UPDATE alerted_slots SET gone_at = ?1
+                 WHERE gone_at IS NULL AND EXISTS (
+                     SELECT 1 FROM slots s
+                     WHERE s.provider = alerted_slots.provider AND s.location_id = alerted_slots.location_id
+                       AND s.slot_date = alerted_slots.slot_date AND s.gone_at = ?1)",
+                params![observed_at],
+            )?;
+        }
+
+        let new_slots = tx.query_row(
+            "SELECT COUNT(*) FROM slots WHERE first_seen = ?1",
+            params![observed_at],
+            |r| r.get(0),
+        )?;
+        tx.commit()?;
+        Ok(new_slots)
+    }
+
+    /// Stores an alert, and when each of its still-open `slots` (slot keys)
+    /// was first alerted, so it's known how long they lasted afterwards.
+    pub fn record_alert(&self, channel: &str, slots: &[String]) -> Result<(), AppError> {
+        let sent_at = clock::now().format(TS_FORMAT).to_string();
+        self.conn.execute(
+            "INSERT INTO alerts (sent_at, channel, slot_count) VALUES (?1, ?2, ?3)",
+            params![sent_at, channel, slots.len()],
+        )?;
+        let mut insert = self.conn.prepare_cached(
+            "INSERT INTO alerted_slots (provider, location_id, slot_date, alerted_at)
+             SELECT provider, location_id, slot_date, ?1 FROM slots s
+             WHERE gone_at IS NULL
+               AND CASE provider WHEN ?3 THEN '' ELSE provider || ':' END || location_id || '@' || slot_date = ?2
+               AND NOT EXISTS (
+                   SELECT 1 FROM alerted_slots a
+                   WHERE a.provider = s.provider AND a.location_id = s.location_id
+                     AND a.slot_date = s.slot_date AND a.gone_at IS NULL)",
+        )?;
+        for slot in slots {
+            insert.execute(params![sent_at, slot, GLOBAL_ENTRY_ID])?;
+        }
+        Ok(())
+    }
+
+    /// Distinct open slot dates seen per location per day since `since`.
+    pub fn availability_by_day(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(String, String, i64)>, AppError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date(observed_at), name, COUNT(DISTINCT slot_date)
+             FROM observations WHERE observed_at >= ?1
+             GROUP BY 1, provider, location_id ORDER BY 1, 2",
+        )?;
+        let rows = stmt
+            .query_map(params![since.format(TS_FORMAT).to_string()], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Earliest open slot date per location key among observations made on `day` (UTC).
+    pub fn earliest_by_location_on(&self, day: NaiveDate) -> Result<HashMap<String, NaiveDate>, AppError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT provider, location_id, MIN(slot_date) FROM observations
+             WHERE date(observed_at) = ?1 GROUP BY provider, location_id",
+        )?;
+        let rows = stmt.query_map(params![day.to_string()], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, usize>(1)?, r.get::<_, String>(2)?))
+        })?;
+
+        let mut out = HashMap::new();
+        for row in rows {
+            let (provider, id, date) = row?;
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|e| AppError::General(format!("Bad slot_date {date}: {e}")))?;
+            out.insert(location_key(&provider, id), date);
+        }
+        Ok(out)
+    }
+
+    /// What the most recent cycle found, or `None` before the first cycle.
+    /// Rows from providers this build doesn't know are skipped.
+    pub fn latest_cycle(&self) -> Result<Option<Vec<FetchedLocation>>, AppError> {
+        let Some(cycle) = self.conn.query_row("SELECT MAX(id) FROM cycles", [], |r| r.get::<_, Option<i64>>(0))? else {
+            return Ok(None);
+        };
+        self.found(
+            "SELECT provider, slot_date, raw_json, observed_at FROM observations WHERE cycle_id = ?1 ORDER BY slot_date, rowid",
+            params![cycle],
+        )
+        .map(Some)
+    }
+
+    /// Every slot observed since `since`, as last seen, soonest first. Rows
+    /// from providers this build doesn't know are skipped.
+    pub fn seen_since(&self, since: DateTime<Utc>) -> Result<Vec<FetchedLocation>, AppError> {
+        self.found(
+            "SELECT provider, slot_date, raw_json, observed_at FROM observations WHERE rowid IN (
+                 SELECT MAX(rowid) FROM observations WHERE observed_at >= ?1
+                 GROUP BY provider, location_id, slot_date
+             ) ORDER BY slot_date, rowid",
+            params![since.format(TS_FORMAT).to_string()],
+        )
+    }
+
+    /// Runs `sql`, which selects `provider, slot_date, raw_json, observed_at`,
+    /// and rebuilds the results.
+    fn found(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<FetchedLocation>, AppError> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, String>(3)?,
+            ))
+        })?;
+        let mut found = Vec::new();
+        for row in rows {
+            let (provider, date, raw_json, observed_at) = row?;
+            let Some(provider) = known_id(&provider) else {
+                continue;
+            };
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|e| AppError::General(format!("Bad slot_date {date}: {e}")))?;
+            let fetched_at = NaiveDateTime::parse_from_str(&observed_at, TS_FORMAT)
+                .map_err(|e| AppError::General(format!("Bad observed_at {observed_at}: {e}")))?
+                .and_utc();
+            let mut loc: Location = serde_json::from_str(&raw_json)?;
+            loc.provider = provider;
+            found.push(FetchedLocation {
+                date,
+                loc,
+                raw_json,
+                fetched_at,
+                undated: false,
+            });
+        }
+        Ok(found)
+    }
+
+    /// The latest name recorded for each location key.
+    pub fn location_names(&self) -> Result<HashMap<String, String>, AppError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT provider, location_id, name FROM observations
+             WHERE rowid IN (SELECT MAX(rowid) FROM observations GROUP BY provider, location_id)",
+        )?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, usize>(1)?, r.get::<_, String>(2)?)))?;
+        let mut out = HashMap::new();
+        for row in rows {
+            let (provider, id, name) = row?;
+            out.insert(location_key(&provider, id), name);
+        }
+        Ok(out)
+    }
+
+    /// How many cycles started since `since`, and for each location key the
+    /// number of distinct open dates in each of those cycles that found any.
+    /// The latest cycle is left out, so it can be compared with the rest.
+    pub fn open_days_per_cycle(&self, since: DateTime<Utc>) -> Result<(usize, HashMap<String, Vec<usize>>), AppError> {
+        let since = since.format(TS_FORMAT).to_string();
+        let cycles: usize = self.conn.query_row(
+            "SELECT COUNT(*) FROM cycles WHERE started_at >= ?1 AND id < (SELECT MAX(id) FROM cycles)",
+            params![since],
+            |r| r.get(0),
+        )?;
+        let mut stmt = self.conn.prepare(
+            "SELECT provider, location_id, COUNT(DISTINCT slot_date) FROM observations
+             WHERE cycle_id IN (SELECT id FROM cycles WHERE started_at >= ?1 AND id < (SELECT MAX(id) FROM cycles))
+             GROUP BY cycle_id, provider, location_id",
+        )?;
+        let rows = stmt.query_map(params![since], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, usize>(1)?, r.get::<_, usize>(2)?))
+        })?;
+        let mut counts: HashMap<String, Vec<usize>> = HashMap::new();
+        for row in rows {
+            let (provider, id, days) = row?;
+            counts.entry(location_key(&provider, id)).or_default().push(days);
+        }
+        Ok((cycles, counts))
+    }
+
+    /// Median minutes between a slot first appearing and disappearing, per location
+    /// key, over slots gone since `since`. Locations with fewer than `min_samples`
+    /// such slots are left out.
+    pub fn median_lifetimes(
+        &self,
+        since: DateTime<Utc>,
+        min_samples: usize,
+    ) -> Result<HashMap<String, f64>, AppError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT provider, location_id, (julianday(gone_at) - julianday(first_seen)) * 1440
+             FROM slots WHERE gone_at >= ?1",
+        )?;
+        let rows = stmt.query_map(params![since.format(TS_FORMAT).to_string()], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, usize>(1)?, r.get::<_, f64>(2)?))
+        })?;
+
+        let mut samples: HashMap<String, Vec<f64>> = HashMap::new();
+        for row in rows {
+            let (provider, id, minutes) = row?;
+            samples.entry(location_key(&provider, id)).or_default().push(minutes);
+        }
+        Ok(samples
+            .into_iter()
+            .filter(|(_, m)| m.len() >= min_samples)
+            .map(|(key, mut m)| {
+                m.sort_by(f64::total_cmp);
+                let mid = m.len() / 2;
+                let median = if m.len() % 2 == 0 { (m[mid - 1] + m[mid]) / 2.0 } else { m[mid] };
+                (key, median)
+            })
+            .collect())
+    }
+}
+
+/// Stands in for the database in builds without the `sqlite` feature:
+/// nothing is recorded, and every lookback finds nothing.
+#[cfg(not(feature = "sqlite"))]
+pub struct History;
+
+#[cfg(not(feature = "sqlite"))]
+impl History {
+    pub fn open(_path: impl AsRef<Path>) -> Result<Self, AppError> {
+        Ok(History)
+    }
+
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        Self::open(path)
+    }
+
+    pub fn shrink_cache(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    pub fn record_alert(&self, _channel: &str, _slots: &[String]) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    pub fn availability_by_day(&self, _since: DateTime<Utc>) -> Result<Vec<(String, String, i64)>, AppError> {
+        Ok(Vec::new())
+    }
+
+    pub fn earliest_by_location_on(&self, _day: NaiveDate) -> Result<HashMap<String, NaiveDate>, AppError> {
+        Ok(HashMap::new())
+    }
+
+    pub fn latest_cycle(&self) -> Result<Option<Vec<FetchedLocation>>, AppError> {
+        Ok(None)
+    }
+
+    pub fn seen_since(&self, _since: DateTime<Utc>) -> Result<Vec<FetchedLocation>, AppError> {
+        Ok(Vec::new())
+    }
+
+    pub fn location_names(&self) -> Result<HashMap<String, String>, AppError> {
+        Ok(HashMap::new())
+    }
+
+    pub fn median_lifetimes(&self, _since: DateTime<Utc>, _min_samples: usize) -> Result<HashMap<String, f64>, AppError> {
+        Ok(HashMap::new())
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::clock::SimulatedClock;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap().and_utc()
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    /// Location `id` with a slot on `date`, as fetched at `time`.
+    fn found(id: usize, date: &str, time: &str) -> FetchedLocation {
+        let raw = json!({
+            "id": id, "name": format!("Center {id}"), "state": "NY", "city": "Jamaica", "address": "", "postalCode": "11430",
+        });
+        let mut loc: Location = serde_json::from_value(raw.clone()).unwrap();
+        loc.provider = GLOBAL_ENTRY_ID;
+        FetchedLocation {
+            date: day(date),
+            loc,
+            raw_json: raw.to_string(),
+            fetched_at: at(time),
+            undated: false,
+        }
+    }
+
+    /// Records a cycle finishing at `time` that loaded the `scanned` dates
+    /// and found `found`, returning how many slots were new.
+    fn cycle(history: &mut History, time: &str, scanned: &[&str], found: &[FetchedLocation]) -> usize {
+        let record = CycleRecord {
+            started_at: at(time),
+            finished_at: at(time),
+            scanned_ok: scanned.iter().map(|date| (GLOBAL_ENTRY_ID, day(date))).collect(),
+            dates_failed: 0,
+        };
+        history.record_cycle(&record, found).unwrap()
+    }
+
+    fn count(history: &History, sql: &str) -> i64 {
+        history.conn().query_row(sql, [], |r| r.get(0)).unwrap()
+    }
+
+    fn gone_at(history: &History, id: usize, date: &str) -> Option<String> {
+        history
+            .conn()
+            .query_row(
+                "SELECT gone_at FROM slots WHERE location_id = ?1 AND slot_date = ?2",
+                params![id, date],
+                |r| r.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn a_slot_is_gone_only_once_its_date_loads_without_it() {
+        let mut history = History::open(":memory:").unwrap();
+        let both = [found(1, "2025-02-03", "2025-01-10 08:00"), found(2, "2025-02-04", "2025-01-10 08:00")];
+        assert_eq!(cycle(&mut history, "2025-01-10 08:00", &["2025-02-03", "2025-02-04"], &both), 2);
+        assert_eq!(cycle(&mut history, "2025-01-10 08:05", &["2025-02-03", "2025-02-04"], &both), 0);
+
+        // 2025-02-04 failed to load, so its slot can't be called gone.
+        let one = [found(1, "2025-02-03", "2025-01-10 08:10")];
+        cycle(&mut history, "2025-01-10 08:10", &["2025-02-03"], &one);
+        assert_eq!(gone_at(&history, 2, "2025-02-04"), None);
+        cycle(&mut history, "2025-01-10 08:15", &["2025-02-03", "2025-02-04"], &one);
+        assert_eq!(gone_at(&history, 2, "2025-02-04").as_deref(), Some("2025-01-10 08:15:00"));
+
+        // Back again, it counts as new.
+        let back = [found(2, "2025-02-04", "2025-01-10 08:20")];
+        assert_eq!(cycle(&mut history, "2025-01-10 08:20", &["2025-02-04"], &back), 1);
+        assert_eq!(gone_at(&history, 2, "2025-02-04"), None);
+    }
+
+    #[test]
+    fn undated_results_are_not_recorded() {
+        let mut history = History::open(":memory:").unwrap();
+        let mut undated = found(1, "2025-02-28", "2025-01-10 08:00");
+        undated.undated = true;
+        assert_eq!(cycle(&mut history, "2025-01-10 08:00", &[], &[undated]), 0);
+        assert_eq!(count(&history, "SELECT COUNT(*) FROM observations"), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_alert_records_each_open_slot_once() {
+        let _clock = clock::install(SimulatedClock::starting_at(at("2025-01-10 08:01")));
+        let mut history = History::open(":memory:").unwrap();
+        cycle(&mut history, "2025-01-10 08:00", &["2025-02-03"], &[found(1, "2025-02-03", "2025-01-10 08:00")]);
+
+        // Location 9 was never seen, so only its alert is counted.
+        let slots = ["1@2025-02-03".to_string(), "9@2025-02-03".to_string()];
+        history.record_alert("C0123", &slots).unwrap();
+        history.record_alert("ops@example.com", &slots[..1]).unwrap();
+        assert_eq!(count(&history, "SELECT COUNT(*) FROM alerts"), 2);
+        assert_eq!(count(&history, "SELECT SUM(slot_count) FROM alerts"), 3);
+        assert_eq!(count(&history, "SELECT COUNT(*) FROM alerted_slots"), 1);
+
+        cycle(&mut history, "2025-01-10 08:10", &["2025-02-03"], &[]);
+        assert_eq!(count(&history, "SELECT COUNT(*) FROM alerted_slots WHERE gone_at = '2025-01-10 08:10:00'"), 1);
+    }
+
+    #[test]
+    fn seen_since_keeps_the_latest_sighting_of_each_slot() {
+        let mut history = History::open(":memory:").unwrap();
+        let dates = ["2025-02-03", "2025-02-04"];
+        cycle(
+            &mut history,
+            "2025-01-10 08:00",
+            &dates,
+            &[found(2, "2025-02-04", "2025-01-10 08:00"), found(1, "2025-02-03", "2025-01-10 08:00")],
+        );
+        cycle(&mut history, "2025-01-10 09:00", &dates, &[found(1, "2025-02-03", "2025-01-10 09:00")]);
+
+        let seen = |since| -> Vec<(usize, String)> {
+            history
+                .seen_since(at(since))
+                .unwrap()
+                .iter()
+                .map(|item| (item.loc.id, item.fetched_at.format("%H:%M").to_string()))
+                .collect()
+        };
+        assert_eq!(seen("2025-01-10 07:00"), [(1, "09:00".to_string()), (2, "08:00".to_string())]);
+        assert_eq!(seen("2025-01-10 08:30"), [(1, "09:00".to_string())]);
+        assert!(seen("2025-01-10 10:00").is_empty());
+        assert_eq!(history.latest_cycle().unwrap().map(|found| found.len()), Some(1));
+    }
+
+    #[test]
+    fn trend_queries_read_back_what_cycles_saw() {
+        let mut history = History::open(":memory:").unwrap();
+        let dates = ["2025-02-03", "2025-02-04", "2025-02-05"];
+        cycle(
+            &mut history,
+            "2025-01-10 08:00",
+            &dates,
+            &[
+                found(1, "2025-02-04", "2025-01-10 08:00"),
+                found(1, "2025-02-03", "2025-01-10 08:00"),
+                found(2, "2025-02-05", "2025-01-10 08:00"),
+            ],
+        );
+        cycle(&mut history, "2025-01-10 08:30", &dates, &[found(1, "2025-02-03", "2025-01-10 08:30")]);
+        cycle(&mut history, "2025-01-10 09:00", &dates, &[]);
+
+        let earliest = history.earliest_by_location_on(day("2025-01-10")).unwrap();
+        assert_eq!(earliest.get("1"), Some(&day("2025-02-03")));
+        assert_eq!(earliest.get("2"), Some(&day("2025-02-05")));
+        assert!(history.earliest_by_location_on(day("2025-01-11")).unwrap().is_empty());
+
+        // The latest cycle is left out for comparison.
+        let (cycles, open_days) = history.open_days_per_cycle(at("2025-01-10 00:00")).unwrap();
+        assert_eq!(cycles, 2);
+        assert_eq!(open_days.get("1"), Some(&vec![2, 1]));
+        assert_eq!(open_days.get("2"), Some(&vec![1]));
+
+        // Slots at 1 lasted 30 and 60 minutes; 2 had just the one.
+        let lifetimes = history.median_lifetimes(at("2025-01-10 00:00"), 2).unwrap();
+        assert_eq!(lifetimes.len(), 1);
+        assert!((lifetimes["1"] - 45.0).abs() < 0.01, "{lifetimes:?}");
+        assert_eq!(history.location_names().unwrap().get("2").map(String::as_str), Some("Center 2"));
+    }
+}
@@ -0,0 +1,147 @@
+//! US federal holidays, when enrollment centers are closed.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+enum Rule {
+    /// A fixed date, observed on the Friday before if it falls on a Saturday
+    /// or the Monday after if on a Sunday.
+    Fixed { month: u32, day: u32 },
+    /// The `n`th `weekday` of the month.
+    Nth { month: u32, weekday: Weekday, n: u8 },
+    /// The month's last `weekday`.
+    Last { month: u32, weekday: Weekday },
+}
+
+/// Holidays under 5 U.S.C. 6103, with the first year each was observed.
+const HOLIDAYS: &[(&str, i32, Rule)] = &[
+    ("New Year's Day", 1870, Rule::Fixed { month: 1, day: 1 }),
+    ("Martin Luther King Jr. Day", 1986, Rule::Nth { month: 1, weekday: Weekday::Mon, n: 3 }),
+    ("Washington's Birthday", 1971, Rule::Nth { month: 2, weekday: Weekday::Mon, n: 3 }),
+    ("Memorial Day", 1971, Rule::Last { month: 5, weekday: Weekday::Mon }),
+    ("Juneteenth", 2021, Rule::Fixed { month: 6, day: 19 }),
+    ("Independence Day", 1870, Rule::Fixed { month: 7, day: 4 }),
+    ("Labor Day", 1894, Rule::Nth { month: 9, weekday: Weekday::Mon, n: 1 }),
+    ("Columbus Day", 1971, Rule::Nth { month: 10, weekday: Weekday::Mon, n: 2 }),
+    ("Veterans Day", 1978, Rule::Fixed { month: 11, day: 11 }),
+    ("Thanksgiving Day", 1942, Rule::Nth { month: 11, weekday: Weekday::Thu, n: 4 }),
+    ("Christmas Day", 1870, Rule::Fixed { month: 12, day: 25 }),
+];
+
+/// The federal holiday on `date`, counting both the holiday itself and the
+/// weekday it's observed on.
+pub fn federal_holiday(date: NaiveDate) -> Option<&'static str> {
+    // New Year's Day on a Saturday is observed on December 31.
+    [date.year(), date.year() + 1].into_iter().find_map(|year| {
+        HOLIDAYS
+            .iter()
+            .filter(|(_, since, _)| year >= *since)
+            .find(|(_, _, rule)| rule.dates(year).contains(&Some(date)))
+            .map(|(name, _, _)| *name)
+    })
+}
+
+impl Rule {
+    /// The holiday and, if different, its observed date in `year`.
+    fn dates(&self, year: i32) -> [Option<NaiveDate>; 2] {
+        match *self {
+            Rule::Fixed { month, day } => {
+                let date = NaiveDate::from_ymd_opt(year, month, day);
+                let observed = date.and_then(|d| match d.weekday() {
+                    Weekday::Sat => d.checked_sub_signed(Duration::days(1)),
+                    Weekday::Sun => d.checked_add_signed(Duration::days(1)),
+                    _ => None,
+                });
+                [date, observed]
+            }
+            Rule::Nth { month, weekday, n } => [NaiveDate::from_weekday_of_month_opt(year, month, weekday, n), None],
+            Rule::Last { month, weekday } => {
+                // The fifth, if there is one, else the fourth.
+                let last = NaiveDate::from_weekday_of_month_opt(year, month, weekday, 5)
+                    .or_else(|| NaiveDate::from_weekday_of_month_opt(year, month, weekday, 4));
+                [last, None]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::{
+        clock::{self, SimulatedClock},
+        JeffConfig,
+    };
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn a_weekend_holiday_is_also_observed_on_the_nearest_weekday() {
+        // Saturday, observed the Friday before.
+        assert_eq!(federal_holiday(day("2026-07-04")), Some("Independence Day"));
+        assert_eq!(federal_holiday(day("2026-07-03")), Some("Independence Day"));
+        // Sunday, observed the Monday after.
+        assert_eq!(federal_holiday(day("2022-12-25")), Some("Christmas Day"));
+        assert_eq!(federal_holiday(day("2022-12-26")), Some("Christmas Day"));
+        // Saturday, January 1, observed in the year before.
+        assert_eq!(federal_holiday(day("2021-12-31")), Some("New Year's Day"));
+        assert_eq!(federal_holiday(day("2022-01-03")), None);
+        assert_eq!(federal_holiday(day("2026-07-06")), None);
+    }
+
+    #[test]
+    fn holidays_follow_their_rules_and_first_years() {
+        assert_eq!(federal_holiday(day("2021-06-18")), Some("Juneteenth"));
+        assert_eq!(federal_holiday(day("2020-06-19")), None);
+        // A May with five Mondays.
+        assert_eq!(federal_holiday(day("2021-05-31")), Some("Memorial Day"));
+        assert_eq!(federal_holiday(day("2021-05-24")), None);
+        assert_eq!(federal_holiday(day("2025-11-27")), Some("Thanksgiving Day"));
+    }
+
+    fn config() -> JeffConfig {
+        serde_json::from_value(serde_json::json!({
+            "enable_slack": false,
+            "slack_token": "",
+            "slack_channel_id": "",
+            "fetch_interval_minutes": 5,
+            "search_states": ["NY"],
+            "api_rate_limit_seconds": 1.0,
+            "max_concurrent_fetches": 1,
+            "max_retries": 1,
+            "window_mode": "rolling",
+            "window_days": 3,
+            "only_weekdays": ["Mon", "Tue", "Wed", "Thu", "Fri"],
+            "skip_federal_holidays": true,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dates_roll_over_at_utc_midnight() {
+        let config = config();
+        let dates = || config.scan_dates(clock::today()).iter().map(|d| d.to_string()).collect::<Vec<_>>();
+
+        // Veterans Day, a Tuesday, drops out on either side of midnight.
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2025, 11, 9, 23, 59, 59).unwrap()));
+        assert_eq!(dates(), ["2025-11-10"]);
+        tokio::time::advance(std::time::Duration::from_secs(1)).await;
+        assert_eq!(dates(), ["2025-11-10", "2025-11-12"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_dst_change_does_not_move_the_dates() {
+        let config = config();
+        let dates = || config.scan_dates(clock::today());
+
+        // US clocks fell back at 06:00 UTC; dates are UTC, so nothing moves.
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2025, 11, 2, 5, 59, 0).unwrap()));
+        let before = dates();
+        tokio::time::advance(std::time::Duration::from_secs(120)).await;
+        assert_eq!(dates(), before);
+        assert_eq!(before, [day("2025-11-03"), day("2025-11-04")]);
+    }
+}
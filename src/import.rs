@@ -0,0 +1,348 @@
+//! Backfills the history store from earlier CSV/JSON exports, one cycle per file.
+
+use std::{collections::HashSet, path::Path};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{
+    clock,
+    providers::GLOBAL_ENTRY_ID,
+    AppError, FetchedLocation, Location,
+};
+#[cfg(feature = "sqlite")]
+use {
+    crate::history::{CycleRecord, History},
+    tracing::info,
+};
+
+/// Rows parsed from one export file.
+pub struct ImportedFile {
+    #[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+    pub observed_at: DateTime<Utc>,
+    pub locations: Vec<FetchedLocation>,
+}
+
+/// Parses an export written by any `exports` format/layout except HTML.
+/// Exports don't record when they were taken, so the file's modification
+/// time stands in for the observation time. Malformed rows are skipped with
+/// a warning, and a slot listed twice is kept once.
+pub fn read(path: &Path) -> Result<ImportedFile, AppError> {
+    let observed_at: DateTime<Utc> = std::fs::metadata(path)?.modified()?.into();
+    let mut locations = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => read_csv(path)?,
+        Some("json") => read_json(path)?,
+        _ => {
+            return Err(AppError::General(format!(
+                "{}: expected a .csv or .json export",
+                path.display()
+            )))
+        }
+    };
+    let mut seen = HashSet::new();
+    locations.retain(|item| seen.insert(item.slot_key()));
+    Ok(ImportedFile { observed_at, locations })
+}
+
+/// Records each file as one cycle, oldest first, returning the number of rows imported.
+#[cfg(feature = "sqlite")]
+pub fn import(history: &mut History, mut files: Vec<ImportedFile>) -> Result<usize, AppError> {
+    files.sort_by_key(|f| f.observed_at);
+    let mut total = 0;
+    for file in files {
+        // Exports only list dates with availability; those dates were scanned
+        // successfully, so slots missing from them had disappeared.
+        let mut scanned_ok: Vec<(&'static str, NaiveDate)> =
+            file.locations.iter().map(|item| (item.loc.provider, item.date)).collect();
+        scanned_ok.sort();
+        scanned_ok.dedup();
+
+        let cycle = CycleRecord {
+            started_at: file.observed_at,
+            finished_at: file.observed_at,
+            scanned_ok,
+            dates_failed: 0,
+        };
+        history.record_cycle(&cycle, &file.locations)?;
+        info!("Imported {} rows observed at {}", file.locations.len(), file.observed_at);
+        total += file.locations.len();
+    }
+    Ok(total)
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|e| AppError::General(format!("Bad date {s:?}: {e}")))
+}
+
+/// A location known only from the summary columns of a grouped export.
+fn summary_location(id: usize, name: &str, state: &str, city: &str) -> Location {
+    Location {
+        id,
+        name: name.to_string(),
+        state: state.to_string(),
+        city: city.to_string(),
+        address: String::new(),
+        address_additional: None,
+        postal_code: String::new(),
+        phone_number: None,
+        short_name: None,
+        tz_data: None,
+        operational: None,
+        services: Vec::new(),
+        extras: Default::default(),
+        provider: GLOBAL_ENTRY_ID,
+    }
+}
+
+fn from_raw(date: NaiveDate, raw: Value) -> Result<FetchedLocation, AppError> {
+    let mut loc: Location = serde_json::from_value(raw.clone())?;
+    loc.provider = GLOBAL_ENTRY_ID;
+    Ok(FetchedLocation {
+        date,
+        loc,
+        raw_json: serde_json::to_string(&raw)?,
+        fetched_at: clock::now(),
+        undated: false,
+    })
+}
+
+fn read_csv(path: &Path) -> Result<Vec<FetchedLocation>, AppError> {
+    let mut rdr = csv::Reader::from_path(path)?;
+    let headers = rdr.headers()?.clone();
+    let col = |name: &str| headers.iter().position(|h| h == name);
+
+    let mut out = Vec::new();
+    if let (Some(date_col), Some(raw_col)) = (col("Date"), col("RawJSON")) {
+        // Flat layout: the RawJSON column has the full API record.
+        for record in rdr.records() {
+            let item = record.map_err(AppError::from).and_then(|record| {
+                let date = parse_date(&record[date_col])?;
+                from_raw(date, serde_json::from_str(&record[raw_col])?)
+            });
+            match item {
+                Ok(item) => out.push(item),
+                Err(e) => warn!("{}: skipping row: {e}", path.display()),
+            }
+        }
+    } else if let (Some(id), Some(name), Some(state), Some(city), Some(dates)) =
+        (col("ID"), col("Name"), col("State"), col("City"), col("Dates"))
+    {
+        // Grouped layout: one row per location, dates joined by `;`.
+        for record in rdr.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!("{}: skipping row: {e}", path.display());
+                    continue;
+                }
+            };
+            let Ok(loc_id) = record[id].parse() else {
+                warn!("{}: skipping row with ID {:?}", path.display(), &record[id]);
+                continue;
+            };
+            let slot_dates = record[dates].split(';').filter(|d| !d.is_empty()).map(parse_date);
+            let slot_dates = match slot_dates.collect::<Result<Vec<_>, _>>() {
+                Ok(slot_dates) => slot_dates,
+                Err(e) => {
+                    warn!("{}: skipping row: {e}", path.display());
+                    continue;
+                }
+            };
+            let loc = summary_location(loc_id, &record[name], &record[state], &record[city]);
+            let raw = serde_json::json!({"id": loc_id, "name": loc.name, "state": loc.state, "city": loc.city});
+            for date in slot_dates {
+                out.push(FetchedLocation {
+                    date,
+                    loc: loc.clone(),
+                    raw_json: raw.to_string(),
+                    fetched_at: clock::now(),
+                    undated: false,
+                });
+            }
+        }
+    } else {
+        return Err(AppError::General(format!(
+            "{}: not a flat or grouped appointments export",
+            path.display()
+        )));
+    }
+    Ok(out)
+}
+
+fn read_json(path: &Path) -> Result<Vec<FetchedLocation>, AppError> {
+    let rows: Vec<Value> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut out = Vec::new();
+    for row in rows {
+        let result = if let (Some(date), Some(raw)) = (row.get("date"), row.get("location")) {
+            // Flat layout: the API record is under `location`.
+            date.as_str()
+                .ok_or_else(|| AppError::General("date is not a string".to_string()))
+                .and_then(parse_date)
+                .and_then(|date| from_raw(date, raw.clone()))
+                .map(|item| vec![item])
+        } else {
+            // Grouped layout.
+            grouped_json_row(&row)
+        };
+        match result {
+            Ok(items) => out.extend(items),
+            Err(e) => warn!("{}: skipping row: {e}", path.display()),
+        }
+    }
+    Ok(out)
+}
+
+fn grouped_json_row(row: &Value) -> Result<Vec<FetchedLocation>, AppError> {
+    let field = |name: &str| {
+        row.get(name)
+            .ok_or_else(|| AppError::General(format!("missing {name}")))
+    };
+    let id = field("id")?
+        .as_u64()
+        .ok_or_else(|| AppError::General("id is not a number".to_string()))? as usize;
+    let text = |name: &str| Ok::<_, AppError>(field(name)?.as_str().unwrap_or_default().to_string());
+    let loc = summary_location(id, &text("name")?, &text("state")?, &text("city")?);
+    let raw = serde_json::json!({"id": id, "name": loc.name, "state": loc.state, "city": loc.city});
+
+    let dates: Vec<String> = serde_json::from_value(field("dates")?.clone())?;
+    dates
+        .iter()
+        .map(|d| {
+            Ok(FetchedLocation {
+                date: parse_date(d)?,
+                loc: loc.clone(),
+                raw_json: raw.to_string(),
+                fetched_at: clock::now(),
+                undated: false,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Writes an export under a temp name with the given extension.
+    fn export(name: &str, ext: &str, contents: &str) -> PathBuf {
+        let path = crate::notify::tests::temp_path(&format!("{name}.{ext}"));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn rows(path: &Path) -> Vec<(usize, String)> {
+        let file = read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        file.locations.iter().map(|item| (item.loc.id, item.date.to_string())).collect()
+    }
+
+    fn raw(id: usize) -> String {
+        serde_json::json!({
+            "id": id, "name": format!("Center {id}"), "state": "NY", "city": "Jamaica",
+            "address": "1 Main St", "addressAdditional": null, "postalCode": "11430", "phoneNumber": null,
+        })
+        .to_string()
+    }
+
+    fn csv_row(date: &str, raw: &str) -> String {
+        format!("{date},\"{}\"\n", raw.replace('"', "\"\""))
+    }
+
+    #[test]
+    fn malformed_flat_csv_rows_are_skipped() {
+        let contents = [
+            "Date,RawJSON\n".to_string(),
+            csv_row("2025-01-14", &raw(5140)),
+            csv_row("14/01/2025", &raw(5444)),
+            csv_row("2025-01-15", "{not json"),
+            csv_row("2025-01-16", r#"{"id": 5446}"#),
+            "2025-01-17\n".to_string(),
+            csv_row("2025-01-18", &raw(5446)),
+        ]
+        .concat();
+        let path = export("import-flat-csv", "csv", &contents);
+        assert_eq!(rows(&path), [(5140, "2025-01-14".into()), (5446, "2025-01-18".into())]);
+    }
+
+    #[test]
+    fn malformed_grouped_csv_rows_are_skipped() {
+        let contents = "ID,Name,State,City,Dates\n\
+            5140,JFK,NY,Jamaica,2025-01-14;2025-01-15\n\
+            x,Bad,NY,Nowhere,2025-01-14\n\
+            5444,Newark,NJ,Newark,2025-01-14;soon\n\
+            5446,Short,NY\n\
+            5447,Buffalo,NY,Buffalo,\n";
+        let path = export("import-grouped-csv", "csv", contents);
+        assert_eq!(rows(&path), [(5140, "2025-01-14".into()), (5140, "2025-01-15".into())]);
+    }
+
+    #[test]
+    fn malformed_json_rows_are_skipped() {
+        let contents = serde_json::json!([
+            {"date": "2025-01-14", "location": serde_json::from_str::<Value>(&raw(5140)).unwrap()},
+            {"date": 20250114, "location": serde_json::from_str::<Value>(&raw(5444)).unwrap()},
+            {"date": "2025-01-14", "location": {"id": 5446}},
+            {"id": 5447, "name": "Buffalo", "state": "NY", "city": "Buffalo", "dates": ["2025-01-20"]},
+            {"id": "5448", "name": "Albany", "state": "NY", "city": "Albany", "dates": ["2025-01-20"]},
+            {"id": 5449, "name": "Syracuse", "state": "NY", "city": "Syracuse", "dates": ["2025-01-20", "later"]},
+            {"id": 5450, "name": "Utica"},
+        ]);
+        let path = export("import-json", "json", &contents.to_string());
+        assert_eq!(rows(&path), [(5140, "2025-01-14".into()), (5447, "2025-01-20".into())]);
+    }
+
+    #[test]
+    fn a_slot_listed_twice_is_kept_once() {
+        let contents = [
+            "Date,RawJSON\n".to_string(),
+            csv_row("2025-01-14", &raw(5140)),
+            csv_row("2025-01-14", &raw(5140)),
+            csv_row("2025-01-15", &raw(5140)),
+        ]
+        .concat();
+        let path = export("import-duplicate-flat", "csv", &contents);
+        assert_eq!(rows(&path), [(5140, "2025-01-14".into()), (5140, "2025-01-15".into())]);
+
+        let contents = "ID,Name,State,City,Dates\n\
+            5140,JFK,NY,Jamaica,2025-01-14;2025-01-14\n\
+            5140,JFK,NY,Jamaica,2025-01-14\n";
+        let path = export("import-duplicate-grouped", "csv", contents);
+        assert_eq!(rows(&path), [(5140, "2025-01-14".into())]);
+    }
+
+    #[test]
+    fn unknown_files_are_rejected() {
+        let path = export("import-unknown-layout", "csv", "When,Where\n2025-01-14,JFK\n");
+        assert!(read(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let path = export("import-unknown-extension", "html", "<table></table>");
+        assert!(read(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn duplicate_rows_are_recorded_once() {
+        let contents = [
+            "Date,RawJSON\n".to_string(),
+            csv_row("2025-01-14", &raw(5140)),
+            csv_row("2025-01-14", &raw(5140)),
+            csv_row("2025-01-14", &raw(5444)),
+        ]
+        .concat();
+        let path = export("import-into-history", "csv", &contents);
+        let file = read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut history = History::open(":memory:").unwrap();
+        assert_eq!(import(&mut history, vec![file]).unwrap(), 2);
+        let count = |sql: &str| history.conn().query_row(sql, [], |r| r.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM cycles"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM observations"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM slots"), 2);
+    }
+}
@@ -0,0 +1,71 @@
+//! Keeps two finders off the same state file. A second copy, say a forgotten
+//! one in another terminal, would send every alert twice and double the load
+//! on the scheduler API. The lock is the operating system's, on
+//! `<state_path>.lock`, so it goes away with the process however that ends;
+//! the file itself only records who holds it.
+
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+};
+
+use tracing::warn;
+
+use crate::{clock, AppError};
+
+/// Held while the finder runs; dropping it releases the lock.
+pub struct InstanceLock {
+    _file: Option<File>,
+}
+
+impl InstanceLock {
+    /// Locks the file next to `state_path`, or fails naming the process that
+    /// holds it. With `force`, a held lock only logs a warning.
+    pub fn acquire(state_path: &str, force: bool) -> Result<Self, AppError> {
+        let path = format!("{state_path}.lock");
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {
+                file.set_len(0)?;
+                file.rewind()?;
+                writeln!(file, "pid {}, started {}", std::process::id(), clock::now().to_rfc3339())?;
+                Ok(InstanceLock { _file: Some(file) })
+            }
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                let holder = match holder.trim() {
+                    "" => "another process".to_string(),
+                    holder => format!("another process ({holder})"),
+                };
+                if force {
+                    warn!("{holder} is already running against {state_path}; carrying on because of --force");
+                    return Ok(InstanceLock { _file: None });
+                }
+                Err(AppError::AlreadyRunning(format!(
+                    "{holder} is already running against {state_path} (locked in {path}); \
+                     stop it, or pass --force to run anyway"
+                )))
+            }
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_instance_is_refused_unless_forced() {
+        let state_path = crate::notify::tests::state_path("instance");
+        let state_path = state_path.to_str().unwrap();
+        let first = InstanceLock::acquire(state_path, false).unwrap();
+        let err = InstanceLock::acquire(state_path, false).err().unwrap().to_string();
+        assert!(err.contains(&format!("pid {}", std::process::id())), "{err}");
+        assert!(InstanceLock::acquire(state_path, true).is_ok());
+        drop(first);
+        assert!(InstanceLock::acquire(state_path, false).is_ok());
+        let _ = std::fs::remove_file(format!("{state_path}.lock"));
+    }
+}
@@ -0,0 +1,2246 @@
+#[cfg(feature = "sqlite")]
+mod anomaly;
+mod application;
+mod badge;
+mod body_log;
+mod budget;
+mod build_info;
+mod calendar;
+pub mod cli;
+pub mod clock;
+mod concurrency;
+mod directory;
+mod dns;
+#[cfg(feature = "sqlite")]
+mod drought;
+mod events;
+mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod filters;
+mod format;
+mod geocode;
+#[cfg(feature = "heatmap")]
+mod heatmap;
+mod history;
+mod holidays;
+mod import;
+mod instance;
+mod metrics;
+#[cfg(feature = "mute-links")]
+mod mute;
+mod notify;
+mod pacing;
+#[cfg(feature = "plugins")]
+mod plugins;
+mod power;
+mod preflight;
+mod preview;
+mod providers;
+#[cfg(feature = "python")]
+mod python;
+mod query;
+mod redact;
+#[cfg(feature = "sqlite")]
+mod report;
+#[cfg(feature = "sqlite")]
+mod retention;
+mod schedule;
+mod schema;
+#[cfg(feature = "scripting")]
+mod script;
+mod session;
+mod slack;
+mod sniper;
+#[cfg(feature = "socket-mode")]
+mod socket_mode;
+mod state;
+mod status;
+#[cfg(feature = "tray")]
+mod tray;
+mod ttp_client;
+mod tz;
+
+#[cfg(test)]
+mod golden_tests;
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("enable the `native-tls` or `rustls` feature for HTTPS");
+
+pub use events::{watch, Availability, AvailabilityEvent};
+pub use ttp_client::{Location, Outage};
+
+use std::{
+    collections::{hash_map, BTreeMap, BTreeSet, HashMap, VecDeque},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use futures::stream::FuturesUnordered;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+use tokio::{
+    sync::{watch, Notify},
+    task::{JoinError, JoinSet},
+    time::{sleep, Instant, MissedTickBehavior},
+};
+use tracing::{debug, info, warn, error};
+
+use crate::{
+    application::{ApplicationStatusConfig, StatusWatcher},
+    badge::BadgeConfig,
+    body_log::{BodyLog, BodyLogConfig},
+    budget::{BudgetConfig, RequestBudget},
+    calendar::{CalendarConfig, Calendars, Conflicts},
+    clock::SkewCheck,
+    concurrency::{AdaptiveConcurrency, AdaptiveConcurrencyConfig},
+    directory::Directory,
+    dns::{Dns, DnsConfig},
+    export::{ExportFormat, ExportLayout},
+    format::{DisplayOptions, Verbosity},
+    geocode::{Home, HomeConfig},
+    history::{CycleRecord, History},
+    metrics::{Metrics, MetricsConfig},
+    pacing::Pacing,
+    power::{Governor, PowerConfig, WakeDetector},
+    notify::{
+        Alert, Attachment, BarkConfig, Delivery, Dispatcher, EscalationConfig, GitHubConfig, EventKind,
+        GotifyConfig, MatrixConfig, PolicyConfig, PushcutConfig, SignalConfig, WhatsAppConfig,
+    },
+    state::DigestLine,
+    providers::{Provider, ProviderKind, SharedProvider},
+    redact::Secret,
+    schedule::{alongside, next_finished, Schedule},
+    session::BrowserSession,
+    sniper::SniperConfig,
+    state::StateStore,
+    ttp_client::{
+        ApiEndpoint, ApiQuery, ExponentialBackoff, TimestampFilter, TtpClient, TtpError,
+        BOOKING_HORIZON_DAYS, GLOBAL_ENTRY,
+    },
+};
+#[cfg(feature = "sqlite")]
+use crate::{anomaly::AnomalyConfig, drought::DroughtNoteConfig, report::WeeklyReportConfig, retention::RetentionConfig};
+#[cfg(feature = "mute-links")]
+use crate::mute::MuteLinksConfig;
+#[cfg(feature = "email")]
+use crate::notify::EmailConfig;
+#[cfg(feature = "pubsub")]
+use crate::notify::PubSubConfig;
+#[cfg(feature = "sns")]
+use crate::notify::SnsConfig;
+#[cfg(feature = "scripting")]
+use crate::script::{Script, ScriptConfig};
+
+/// Days of disappeared slots used to estimate how fast a location's slots go.
+const URGENCY_WINDOW_DAYS: i64 = 30;
+/// Disappeared slots a location needs before it gets an urgency label.
+const URGENCY_MIN_SAMPLES: usize = 3;
+/// Cap on how many fetch intervals to wait while the API is down or throttling.
+const MAX_OUTAGE_BACKOFF: u32 = 8;
+/// How often a looping run checks whether a digest is due.
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often a looping run checks whether an application status is due.
+const STATUS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often a looping run checks whether the machine slept.
+const WAKE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("I/O: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON parse: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("HTTP: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("CSV: {0}")]
+    CsvError(#[from] csv::Error),
+    #[error("TTP API: {0}")]
+    TtpError(#[from] TtpError),
+    #[cfg(feature = "parquet")]
+    #[error("Parquet: {0}")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+    #[cfg(feature = "xlsx")]
+    #[error("XLSX: {0}")]
+    XlsxError(#[from] rust_xlsxwriter::XlsxError),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[cfg(feature = "socket-mode")]
+    #[error("WebSocket: {0}")]
+    WebSocketError(Box<tokio_tungstenite::tungstenite::Error>),
+    /// Settings that can't work, found validating the config or setting up from it.
+    #[error("Config: {0}")]
+    Config(String),
+    #[error("General: {0}")]
+    General(String),
+    /// Another finder holds the lock on the state file.
+    #[error("{0}")]
+    AlreadyRunning(String),
+}
+
+impl AppError {
+    /// How the process exits when a command fails with this error.
+    fn exit(&self) -> Exit {
+        match self {
+            AppError::Config(_) => Exit::Config,
+            AppError::HttpError(_) | AppError::TtpError(_) => Exit::Api,
+            _ => Exit::Failure,
+        }
+    }
+
+    /// This error as a config problem, for one found setting up from the
+    /// config after it loaded, such as a channel that can't be built.
+    fn into_config(self) -> AppError {
+        match self {
+            AppError::Config(_) => self,
+            e => AppError::Config(e.to_string()),
+        }
+    }
+}
+
+/// Process exit codes, documented in the README for monitoring wrappers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    /// Success; for a one-shot `run`, slots were found.
+    Ok = 0,
+    /// A one-shot `run` found no slots.
+    NoSlots = 1,
+    /// The config file or command line is invalid.
+    Config = 2,
+    /// The scheduler API couldn't be reached, or no date loaded.
+    Api = 3,
+    /// Slots were found but a notification channel failed.
+    Notify = 4,
+    /// Anything else, such as an unwritable history database.
+    Failure = 5,
+    /// `dev capture-schema` found the API's shape changed.
+    SchemaDrift = 6,
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+#[cfg(feature = "socket-mode")]
+impl From<tokio_tungstenite::tungstenite::Error> for AppError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        AppError::WebSocketError(Box::new(e))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JeffConfig {
+    enable_slack: bool,
+    slack_token: Secret,
+    /// One channel ID, or a list of them to post every alert to each.
+    #[serde(deserialize_with = "de_one_or_many")]
+    slack_channel_id: Vec<String>,
+    fetch_interval_minutes: u64,
+    /// Appointment sources to scan (default: Global Entry only).
+    #[serde(default = "default_providers")]
+    providers: Vec<ProviderKind>,
+    /// Scheduler API base URLs and proxies to fail over between (default: CBP directly).
+    #[serde(default)]
+    api_endpoints: Vec<ApiEndpoint>,
+    /// Truncation, sampling and on-disk capture of API response bodies.
+    #[serde(default)]
+    body_log: BodyLogConfig,
+    /// Scheduler API query parameters (`minimum`, `filterTimestampBy`, extras).
+    #[serde(default)]
+    api_query: ApiQuery,
+    /// Browser cookies and headers replayed on every scheduler API request.
+    #[serde(default)]
+    session_path: Option<String>,
+    /// DNS overrides and caching for all outgoing requests.
+    #[serde(default)]
+    dns: Option<DnsConfig>,
+    /// State codes; names and abbreviations are turned into codes on load.
+    #[serde(deserialize_with = "de_states")]
+    search_states: Vec<String>,
+    /// Locations dropped even though their state matches.
+    #[serde(default)]
+    exclude_location_ids: Vec<usize>,
+    /// Cities (case-insensitive) dropped even though their state matches.
+    #[serde(default)]
+    exclude_cities: Vec<String>,
+    /// API fields a location must have, e.g. `{"operational": true}`. A
+    /// list field such as `services` matches if it contains the value.
+    #[serde(default)]
+    match_fields: BTreeMap<String, Value>,
+    /// Dates to scan; required unless `window_mode` is `rolling`.
+    #[serde(default)]
+    date_range: Option<DateRange>,
+    #[serde(default)]
+    window_mode: WindowMode,
+    /// How many days ahead a rolling window covers, counting today.
+    #[serde(default)]
+    window_days: Option<u32>,
+    /// Only scan these days of the week, e.g. `["Sat", "Sun"]` (default: all).
+    #[serde(default)]
+    only_weekdays: Vec<Weekday>,
+    /// Don't scan US federal holidays, when enrollment centers are closed.
+    #[serde(default)]
+    skip_federal_holidays: bool,
+    /// `dates` asks about each date in turn; `snapshot` asks once per cycle
+    /// for the soonest slots nationwide.
+    #[serde(default)]
+    scan_mode: ScanMode,
+    /// With `scan_mode: snapshot`, also asks each wanted location for its
+    /// soonest slot, one request each, so alerts give the actual date.
+    #[serde(default)]
+    snapshot_lookups: bool,
+    /// An appointment already booked; once its date has passed the daemon stops.
+    #[serde(default)]
+    booked_date: Option<NaiveDate>,
+    api_rate_limit_seconds: f64,
+    /// Request order and spacing: `steady`, `humanlike` or `burst`.
+    #[serde(default)]
+    pacing: Pacing,
+    max_concurrent_fetches: usize,
+    /// Raise and lower the number of fetches in flight, starting from
+    /// `max_concurrent_fetches`, as the API copes or struggles.
+    #[serde(default)]
+    adaptive_concurrency: Option<AdaptiveConcurrencyConfig>,
+    max_retries: u8,
+    /// Most scheduler API requests to send per hour and/or day, whatever the
+    /// rest of the config asks for.
+    #[serde(default)]
+    request_budget: Option<BudgetConfig>,
+    /// Warn when the system clock and the scheduler API's disagree by more
+    /// than this; 0 turns the check off.
+    #[serde(default = "default_max_clock_skew_seconds")]
+    max_clock_skew_seconds: u64,
+    /// Trade speed for memory on small boards: one fetch at a time, one idle
+    /// connection per host and a smaller SQLite page cache.
+    #[serde(default)]
+    low_memory: bool,
+    /// Check channel credentials, output paths and the API before the first
+    /// cycle, and refuse to start if any check fails.
+    #[serde(default = "default_preflight")]
+    preflight: bool,
+    /// What to do when a cycle is still running at the next interval.
+    #[serde(default)]
+    cycle_overlap: CycleOverlap,
+    /// Whether a looping `run` starts its first cycle right away, or waits
+    /// for the first interval to pass. `--immediate` forces the former.
+    #[serde(default = "default_run_at_startup")]
+    run_at_startup: bool,
+    /// Poll less often, or not at all, on battery or a metered connection.
+    #[serde(default)]
+    power: Option<PowerConfig>,
+    /// Real-time Slack alerts (default) or a daily digest.
+    #[serde(default)]
+    slack_delivery: Delivery,
+    /// Skip a real-time alert identical to the last one a channel got within
+    /// this many minutes (default 0: always send).
+    #[serde(default)]
+    repeat_window_minutes: u64,
+    /// Severities for alerts, drought notes and API trouble, and which
+    /// channels send, queue or drop each severity.
+    #[serde(default)]
+    notification_policy: Option<PolicyConfig>,
+    /// Withhold an alert whose results are older than this many minutes,
+    /// say because the machine slept mid-cycle, and scan again instead.
+    #[serde(default)]
+    max_result_age_minutes: Option<u64>,
+    /// How old an alert left undelivered by a crash or failed send may be
+    /// and still be sent on the next start (default 30).
+    #[serde(default = "default_outbox_max_age_minutes")]
+    outbox_max_age_minutes: u64,
+    /// Also (or instead) send alerts by email.
+    #[cfg(feature = "email")]
+    #[serde(default)]
+    email: Option<EmailConfig>,
+    /// Kept only to refuse an `email` section this build can't send.
+    #[cfg(not(feature = "email"))]
+    #[serde(default)]
+    email: Option<Value>,
+    /// iOS push notifications through Bark.
+    #[serde(default)]
+    bark: Option<BarkConfig>,
+    /// iOS push notifications through Pushcut.
+    #[serde(default)]
+    pushcut: Option<PushcutConfig>,
+    /// Messages to a self-hosted Gotify server.
+    #[serde(default)]
+    gotify: Option<GotifyConfig>,
+    /// Messages to a Matrix room through a webhook bridge.
+    #[serde(default)]
+    matrix: Option<MatrixConfig>,
+    /// Signal messages through a signal-cli-rest-api instance.
+    #[serde(default)]
+    signal: Option<SignalConfig>,
+    /// WhatsApp template messages through the Business Cloud API.
+    #[serde(default)]
+    whatsapp: Option<WhatsAppConfig>,
+    /// Publishing to an Amazon SNS topic.
+    #[cfg(feature = "sns")]
+    #[serde(default)]
+    sns: Option<SnsConfig>,
+    /// Kept only to refuse an `sns` section this build can't sign for.
+    #[cfg(not(feature = "sns"))]
+    #[serde(default)]
+    sns: Option<Value>,
+    /// Publishing to a Google Cloud Pub/Sub topic.
+    #[cfg(feature = "pubsub")]
+    #[serde(default)]
+    pubsub: Option<PubSubConfig>,
+    /// Kept only to refuse a `pubsub` section this build can't sign for.
+    #[cfg(not(feature = "pubsub"))]
+    #[serde(default)]
+    pubsub: Option<Value>,
+    /// Per-cycle metrics for InfluxDB, VictoriaMetrics or Prometheus.
+    #[serde(default)]
+    metrics: Option<MetricsConfig>,
+    /// Status badge and summary JSON for embedding in websites.
+    #[serde(default)]
+    badge: Option<BadgeConfig>,
+    /// A GitHub issue or gist kept showing the latest alert.
+    #[serde(default)]
+    github: Option<GitHubConfig>,
+    /// Pages through PagerDuty/Opsgenie for critical slots.
+    #[serde(default)]
+    escalation: Option<EscalationConfig>,
+    /// A Rhai script with a custom `keep` filter and/or `notify` hook.
+    #[cfg(feature = "scripting")]
+    #[serde(default)]
+    script: Option<ScriptConfig>,
+    /// Kept only to refuse a `script` section this build can't run.
+    #[cfg(not(feature = "scripting"))]
+    #[serde(default)]
+    script: Option<Value>,
+    /// `script`, compiled by `load_config`.
+    #[cfg(feature = "scripting")]
+    #[serde(skip)]
+    loaded_script: Option<Arc<Script>>,
+    /// Requests counted against `request_budget`, set up by `load_config` and
+    /// shared by every client built from this config.
+    #[serde(skip)]
+    loaded_budget: Option<Arc<RequestBudget>>,
+    /// WebAssembly filter and notifier plugins.
+    #[cfg(feature = "plugins")]
+    #[serde(default)]
+    plugins: Option<plugins::PluginsConfig>,
+    /// Kept only to refuse a `plugins` section this build can't run.
+    #[cfg(not(feature = "plugins"))]
+    #[serde(default)]
+    plugins: Option<Value>,
+    /// `plugins`, compiled by `load_config`.
+    #[cfg(feature = "plugins")]
+    #[serde(skip)]
+    loaded_plugins: Vec<Arc<plugins::Plugin>>,
+    /// One location and date checked far more often than the general scan.
+    #[serde(default)]
+    sniper: Option<SniperConfig>,
+    /// More such watches, each on its own interval.
+    #[serde(default)]
+    watches: Vec<SniperConfig>,
+    /// Seconds for which one answer for a date serves the general scan and
+    /// every watch, instead of each sending its own request.
+    #[serde(default = "default_merge_window_seconds")]
+    merge_window_seconds: u64,
+    /// App-level token (`xapp-...`) enabling Socket Mode reaction handling.
+    #[serde(default)]
+    slack_app_token: Option<Secret>,
+    /// Signed links in alerts that mute a slot or location for a while.
+    #[cfg(feature = "mute-links")]
+    #[serde(default)]
+    mute_links: Option<MuteLinksConfig>,
+    /// Kept only to refuse a `mute_links` section this build can't sign.
+    #[cfg(not(feature = "mute-links"))]
+    #[serde(default)]
+    mute_links: Option<Value>,
+    #[serde(default = "default_state_path")]
+    state_path: String,
+    #[serde(default = "default_history_path")]
+    history_path: String,
+    /// Cached copy of the location directory, written by `locations update`.
+    #[serde(default = "default_directory_path")]
+    directory_path: String,
+    /// How long raw history is kept before it's rolled into daily summaries.
+    #[cfg(feature = "sqlite")]
+    #[serde(default)]
+    retention: Option<RetentionConfig>,
+    /// Kept only to refuse a `retention` section this build has no history for.
+    #[cfg(not(feature = "sqlite"))]
+    #[serde(default)]
+    retention: Option<Value>,
+    /// Availability heatmap images, per cycle or per day.
+    #[cfg(feature = "heatmap")]
+    #[serde(default)]
+    heatmap: Option<heatmap::HeatmapConfig>,
+    /// Kept only to refuse a `heatmap` section this build can't draw.
+    #[cfg(not(feature = "heatmap"))]
+    #[serde(default)]
+    heatmap: Option<Value>,
+    #[cfg(feature = "sqlite")]
+    #[serde(default)]
+    weekly_report: Option<WeeklyReportConfig>,
+    /// Kept only to refuse a `weekly_report` section this build has no history for.
+    #[cfg(not(feature = "sqlite"))]
+    #[serde(default)]
+    weekly_report: Option<Value>,
+    /// Flag locations with far more open days than usual.
+    #[cfg(feature = "sqlite")]
+    #[serde(default)]
+    anomalies: Option<AnomalyConfig>,
+    /// Kept only to refuse an `anomalies` section this build has no history for.
+    #[cfg(not(feature = "sqlite"))]
+    #[serde(default)]
+    anomalies: Option<Value>,
+    /// Weekly Slack note about states that have had no availability for a while.
+    #[cfg(feature = "sqlite")]
+    #[serde(default)]
+    drought_note: Option<DroughtNoteConfig>,
+    /// Kept only to refuse a `drought_note` section this build has no history for.
+    #[cfg(not(feature = "sqlite"))]
+    #[serde(default)]
+    drought_note: Option<Value>,
+    /// Watch applications' statuses through signed-in dashboard sessions and
+    /// announce changes: one account, or a list for a family or group.
+    #[serde(default, deserialize_with = "de_one_or_many")]
+    application_status: Vec<ApplicationStatusConfig>,
+    /// CalDAV calendars whose events are flagged against open dates, or one
+    /// per person in a group.
+    #[serde(default, deserialize_with = "de_one_or_many")]
+    calendar: Vec<CalendarConfig>,
+    /// Where you live, by address or coordinates, to limit results to
+    /// locations within `max_distance_miles` of it.
+    #[serde(default)]
+    home: Option<HomeConfig>,
+    /// Formats written when Slack is disabled.
+    #[serde(default = "default_exports")]
+    exports: Vec<ExportFormat>,
+    #[serde(default)]
+    export_layout: ExportLayout,
+    #[serde(flatten)]
+    display: DisplayOptions,
+}
+
+/// Refuses a config `section` that needs a cargo `feature` this build leaves
+/// out; unused in a build with every feature.
+#[allow(dead_code)]
+fn left_out(section: &str, feature: &str) -> AppError {
+    AppError::Config(format!(
+        "{section}: this build doesn't include the `{feature}` feature; rebuild with `--features {feature}`"
+    ))
+}
+
+impl JeffConfig {
+    fn max_result_age(&self) -> Option<chrono::Duration> {
+        self.max_result_age_minutes
+            .map(|minutes| chrono::Duration::minutes(i64::try_from(minutes).unwrap_or(i64::MAX)))
+    }
+
+    /// How old an undelivered alert may be and still be sent: the smaller of
+    /// `outbox_max_age_minutes` and `max_result_age_minutes`.
+    fn outbox_max_age(&self) -> chrono::Duration {
+        let max_age = chrono::Duration::minutes(i64::try_from(self.outbox_max_age_minutes).unwrap_or(i64::MAX));
+        self.max_result_age().map_or(max_age, |result_age| result_age.min(max_age))
+    }
+
+    /// Whether `loc` passes the state filter and isn't excluded.
+    fn wants(&self, loc: &Location) -> bool {
+        self.rejection(loc).is_none()
+    }
+
+    /// Why the filters drop `loc`, or `None` if it's wanted.
+    fn rejection(&self, loc: &Location) -> Option<String> {
+        if !self.search_states.contains(&loc.state) {
+            return Some(format!("state {} is not in search_states", loc.state));
+        }
+        if self.exclude_location_ids.contains(&loc.id) {
+            return Some(format!("ID {} is in exclude_location_ids", loc.id));
+        }
+        if self.exclude_cities.iter().any(|city| city.eq_ignore_ascii_case(&loc.city)) {
+            return Some(format!("city {} is in exclude_cities", loc.city));
+        }
+        for (name, want) in &self.match_fields {
+            match loc.field(name) {
+                Some(Value::Array(items)) if items.contains(want) => {}
+                Some(Value::Array(items)) => {
+                    return Some(format!("match_fields.{name}: {} doesn't contain {want}", Value::Array(items)));
+                }
+                Some(value) if value == *want => {}
+                Some(value) => return Some(format!("match_fields.{name}: {value}, not {want}")),
+                None => return Some(format!("match_fields.{name}: field missing")),
+            }
+        }
+        #[cfg(feature = "plugins")]
+        if let Some(reason) = self.loaded_plugins.iter().find_map(|plugin| plugin.rejection(loc)) {
+            return Some(reason);
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(reason) = self.loaded_script.as_ref().and_then(|script| script.rejection(loc)) {
+            return Some(reason);
+        }
+        None
+    }
+
+    /// Compiles `script` and `plugins`, if set, so their filters and hooks
+    /// are ready to run, and refuses sections this build leaves out.
+    fn load_extensions(&mut self) -> Result<(), AppError> {
+        let client = Dns::new(self.dns.as_ref()).low_memory(self.low_memory).builder().build()?;
+        self.loaded_budget = self.request_budget.clone().map(|budget| Arc::new(RequestBudget::new(budget)));
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &self.script {
+            self.loaded_script = Some(Arc::new(Script::load(script, client.clone())?));
+        }
+        #[cfg(not(feature = "scripting"))]
+        if self.script.is_some() {
+            return Err(left_out("script", "scripting"));
+        }
+        #[cfg(feature = "plugins")]
+        if let Some(config) = &self.plugins {
+            self.loaded_plugins = plugins::load(config, client)?;
+        }
+        #[cfg(not(feature = "plugins"))]
+        let _ = client;
+        #[cfg(not(feature = "plugins"))]
+        if self.plugins.is_some() {
+            return Err(left_out("plugins", "plugins"));
+        }
+        #[cfg(not(feature = "heatmap"))]
+        if self.heatmap.is_some() {
+            return Err(left_out("heatmap", "heatmap"));
+        }
+        #[cfg(not(feature = "xlsx"))]
+        if self.exports.contains(&ExportFormat::Xlsx) {
+            return Err(left_out("exports", "xlsx"));
+        }
+        #[cfg(not(feature = "sqlite"))]
+        {
+            let sections = [
+                ("retention", self.retention.is_some()),
+                ("weekly_report", self.weekly_report.is_some()),
+                ("drought_note", self.drought_note.is_some()),
+                ("anomalies", self.anomalies.is_some()),
+            ];
+            if let Some((section, _)) = sections.iter().find(|(_, set)| *set) {
+                return Err(left_out(section, "sqlite"));
+            }
+        }
+        #[cfg(not(feature = "email"))]
+        if self.email.is_some() {
+            return Err(left_out("email", "email"));
+        }
+        #[cfg(not(feature = "sns"))]
+        if self.sns.is_some() {
+            return Err(left_out("sns", "sns"));
+        }
+        #[cfg(not(feature = "pubsub"))]
+        if self.pubsub.is_some() {
+            return Err(left_out("pubsub", "pubsub"));
+        }
+        #[cfg(not(feature = "mute-links"))]
+        if self.mute_links.is_some() {
+            return Err(left_out("mute_links", "mute-links"));
+        }
+        #[cfg(not(feature = "archive"))]
+        if self.body_log.archive.is_some() {
+            return Err(left_out("body_log.archive", "archive"));
+        }
+        #[cfg(not(feature = "socket-mode"))]
+        if self.slack_app_token.is_some() {
+            return Err(left_out("slack_app_token", "socket-mode"));
+        }
+        Ok(())
+    }
+
+    /// The dates worth asking about on `today`: none in the past, none past
+    /// the booking horizon, where nothing is published yet, and none that
+    /// `only_weekdays` or `skip_federal_holidays` rule out.
+    fn scan_dates(&self, today: NaiveDate) -> Vec<NaiveDate> {
+        let (start, end) = match (self.window_mode, &self.date_range) {
+            (WindowMode::Rolling, _) => {
+                let days = self.window_days.unwrap_or(1).max(1);
+                (today, today + chrono::Duration::days(i64::from(days) - 1))
+            }
+            (WindowMode::Fixed, Some(range)) => (range.start, range.end),
+            (WindowMode::Fixed, None) => return Vec::new(),
+        };
+        let end = end.min(today + chrono::Duration::days(BOOKING_HORIZON_DAYS));
+        start
+            .max(today)
+            .iter_days()
+            .take_while(|d| *d <= end)
+            .filter(|d| self.only_weekdays.is_empty() || self.only_weekdays.contains(&d.weekday()))
+            .filter(|d| !self.skip_federal_holidays || holidays::federal_holiday(*d).is_none())
+            .collect()
+    }
+
+    /// Whether the booked appointment is behind us, so there's nothing left to find.
+    fn booking_passed(&self, today: NaiveDate) -> bool {
+        self.booked_date.is_some_and(|booked| booked < today)
+    }
+}
+
+fn default_providers() -> Vec<ProviderKind> {
+    vec![ProviderKind::GlobalEntry]
+}
+
+fn default_merge_window_seconds() -> u64 {
+    5
+}
+
+fn default_outbox_max_age_minutes() -> u64 {
+    30
+}
+
+fn default_preflight() -> bool {
+    true
+}
+
+fn default_run_at_startup() -> bool {
+    true
+}
+
+fn default_max_clock_skew_seconds() -> u64 {
+    120
+}
+
+fn default_state_path() -> String {
+    ".jeff_state.json".to_string()
+}
+
+fn default_history_path() -> String {
+    "history.sqlite".to_string()
+}
+
+fn default_directory_path() -> String {
+    "locations.json".to_string()
+}
+
+fn default_exports() -> Vec<ExportFormat> {
+    vec![ExportFormat::Csv]
+}
+
+/// What to do when `fetch_interval_minutes` passes before a cycle finishes.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CycleOverlap {
+    /// Drop that cycle and wait for the next interval.
+    #[default]
+    Skip,
+    /// Start it as soon as the running cycle finishes.
+    Queue,
+    /// Start it right away, alongside the running cycle.
+    Concurrent,
+}
+
+#[derive(Debug, Deserialize)]
+struct DateRange {
+    #[serde(deserialize_with = "de_date")]
+    start: NaiveDate,
+    #[serde(deserialize_with = "de_date")]
+    end: NaiveDate,
+}
+
+/// Which dates each cycle scans.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WindowMode {
+    /// The configured `date_range`.
+    #[default]
+    Fixed,
+    /// Today through `window_days` ahead, moving forward every day.
+    Rolling,
+}
+
+/// How each cycle asks for availability.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ScanMode {
+    /// One request per date, per provider.
+    #[default]
+    Dates,
+    /// One nationwide request for the locations with a slot in the window,
+    /// and with `snapshot_lookups` one per location for its soonest date.
+    Snapshot,
+}
+
+/// Turns state names and abbreviations into codes, leaving anything
+/// unrecognized for `validate` to reject.
+fn de_states<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    let mut states = Vec::new();
+    for state in Vec::<String>::deserialize(d)? {
+        let state = directory::state_code(&state).map_or(state, str::to_string);
+        if !states.contains(&state) {
+            states.push(state);
+        }
+    }
+    Ok(states)
+}
+
+/// Takes a setting such as `slack_channel_id` as either one value or a list.
+fn de_one_or_many<'de, D, T>(d: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        Many(Vec<T>),
+        One(T),
+    }
+
+    Ok(match OneOrMany::deserialize(d)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// Parses `YYYY-MM-DD` with a hint, so a bad date fails at load rather than mid-run.
+fn de_date<'de, D: serde::Deserializer<'de>>(d: D) -> Result<NaiveDate, D::Error> {
+    let value = String::deserialize(d)?;
+    NaiveDate::parse_from_str(&value, "%Y-%m-%d").map_err(|e| {
+        serde::de::Error::custom(format!("date_range: {value:?} is not a YYYY-MM-DD date ({e})"))
+    })
+}
+
+/// We capture both the date, our parsed `Location`, and the entire original JSON.
+#[derive(Debug, Clone)]
+struct FetchedLocation {
+    date: NaiveDate,
+    loc: Location,
+    raw_json: String,
+    /// When the API returned it, or for results rebuilt from the history,
+    /// when that cycle finished; results read from a file are as of now.
+    fetched_at: DateTime<Utc>,
+    /// Only known to be on or before `date`: `scan_mode: snapshot` without
+    /// `snapshot_lookups`. Alerts say "by" the date, and the history and the
+    /// event stream leave it out.
+    undated: bool,
+}
+
+impl FetchedLocation {
+    fn slot_key(&self) -> String {
+        slot_key(&self.loc.key(), self.date)
+    }
+}
+
+/// Every date a cycle found for one location. The API lists a location once
+/// per date, and sometimes twice on the same date; formatters, exporters and
+/// the event diff all work from this merged view instead.
+#[derive(Debug, Clone)]
+pub struct LocationAvailability {
+    pub loc: Location,
+    /// Sorted, without duplicates, never empty.
+    pub dates: Vec<NaiveDate>,
+    /// The location's slot is only known to be on or before its one date.
+    pub undated: bool,
+}
+
+impl LocationAvailability {
+    /// Merges results by provider and location ID, ordered by each
+    /// location's earliest date.
+    fn merge(fetched_locations: &[FetchedLocation]) -> Vec<LocationAvailability> {
+        let mut merged: Vec<LocationAvailability> = Vec::new();
+        for item in fetched_locations {
+            match merged.iter_mut().find(|m| m.loc.same_as(&item.loc)) {
+                Some(m) => {
+                    m.dates.push(item.date);
+                    m.undated &= item.undated;
+                }
+                None => merged.push(LocationAvailability {
+                    loc: item.loc.clone(),
+                    dates: vec![item.date],
+                    undated: item.undated,
+                }),
+            }
+        }
+        for m in &mut merged {
+            m.dates.sort();
+            m.dates.dedup();
+        }
+        merged.sort_by_key(LocationAvailability::earliest);
+        merged
+    }
+
+    pub fn earliest(&self) -> NaiveDate {
+        self.dates[0]
+    }
+
+    pub fn slot_keys(&self) -> impl Iterator<Item = String> + '_ {
+        let key = self.loc.key();
+        self.dates.iter().map(move |d| slot_key(&key, *d))
+    }
+}
+
+/// What one fetch cycle did, for logging and anything reporting on cycles.
+#[derive(Debug, Clone)]
+pub struct CycleReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub dates_scanned: usize,
+    pub dates_failed: usize,
+    /// Matching (date, location) results across all providers.
+    pub slots_found: usize,
+    /// Slots not open in the previous cycle; `None` if history couldn't be written.
+    pub new_slots: Option<usize>,
+    /// States with at least one slot found.
+    pub states: BTreeSet<String>,
+    /// Slots included in the alert handed to notifiers.
+    pub slots_alerted: usize,
+    /// Kinds of non-JSON error pages the API answered with.
+    pub outages: BTreeSet<Outage>,
+    /// Notification channels that failed to take this cycle's alert.
+    pub notify_failures: usize,
+    /// The results were older than `max_result_age_minutes` once the scan
+    /// finished, so no alert was sent.
+    pub stale: bool,
+}
+
+impl CycleReport {
+    pub fn dates_ok(&self) -> usize {
+        self.dates_scanned - self.dates_failed
+    }
+
+    pub fn duration(&self) -> chrono::Duration {
+        self.finished_at - self.started_at
+    }
+
+    /// One line of `key=value` pairs for scripts, e.g. `found=3 new=1
+    /// alerted=1 states=CA,NV errors=0 duration=12.4s`. `errors` counts
+    /// failed dates and failed channels; `new` is `-` if unknown.
+    pub fn summary(&self) -> String {
+        let states: Vec<&str> = self.states.iter().map(String::as_str).collect();
+        format!(
+            "found={} new={} alerted={} states={} errors={} duration={:.1}s",
+            self.slots_found,
+            self.new_slots.map_or("-".to_string(), |n| n.to_string()),
+            self.slots_alerted,
+            if states.is_empty() { "-".to_string() } else { states.join(",") },
+            self.dates_failed + self.notify_failures,
+            self.duration().num_milliseconds() as f64 / 1000.0,
+        )
+    }
+
+    /// The exit code for a one-shot run ending with this cycle. Without slots,
+    /// any failed date makes "none found" unreliable, so that's an API error.
+    fn exit(&self) -> Exit {
+        if self.slots_found == 0 {
+            if self.dates_failed > 0 {
+                Exit::Api
+            } else {
+                Exit::NoSlots
+            }
+        } else if self.notify_failures > 0 || self.stale {
+            Exit::Notify
+        } else {
+            Exit::Ok
+        }
+    }
+}
+
+impl std::fmt::Display for CycleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cycle finished in {:.1}s: {}/{} dates ok, {} slots found",
+            self.duration().num_milliseconds() as f64 / 1000.0,
+            self.dates_ok(),
+            self.dates_scanned,
+            self.slots_found
+        )?;
+        if let Some(new) = self.new_slots {
+            write!(f, " ({new} new)")?;
+        }
+        if self.stale {
+            write!(f, ", alert withheld as stale")?;
+        } else {
+            write!(f, ", {} alerted", self.slots_alerted)?;
+        }
+        for outage in &self.outages {
+            write!(f, "; {outage}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Identifies one appointment day at one location, e.g. `5140@2025-01-14`.
+fn slot_key(location_key: &str, date: NaiveDate) -> String {
+    format!("{location_key}@{date}")
+}
+
+/// Scheduler client using the configured `api_endpoints`, or the CBP default.
+fn api_client(config: &JeffConfig, dns: &Dns, http: Client) -> Result<TtpClient, AppError> {
+    let mut api = TtpClient::new(http)
+        .with_endpoints(&config.api_endpoints, || dns.builder())?
+        .with_query(config.api_query.clone())
+        .with_budget(config.loaded_budget.clone())
+        .with_body_log(Arc::new(BodyLog::new(config.body_log.clone())));
+    if let Some(path) = &config.session_path {
+        api = api.with_decorator(Arc::new(BrowserSession::load(path)?));
+    }
+    if config.max_clock_skew_seconds > 0 {
+        api = api.with_decorator(Arc::new(SkewCheck::new(config.max_clock_skew_seconds)));
+    }
+    Ok(api)
+}
+
+/// The configured `providers`, sharing one HTTP client, for scanning outside
+/// the run loop.
+fn build_providers(config: &JeffConfig) -> Result<Vec<Arc<dyn Provider>>, AppError> {
+    let dns = Dns::new(config.dns.as_ref()).low_memory(config.low_memory);
+    let client = dns.builder().build()?;
+    let api = retrying_api_client(config, &dns, client.clone())?;
+    config
+        .providers
+        .iter()
+        .map(|&kind| providers::build(kind, &api))
+        .collect()
+}
+
+/// [`api_client`] retrying failed requests up to `max_retries` times, for scans.
+fn retrying_api_client(config: &JeffConfig, dns: &Dns, http: Client) -> Result<TtpClient, AppError> {
+    Ok(api_client(config, dns, http)?.with_retry(ExponentialBackoff {
+        max_attempts: config.max_retries,
+        initial: Duration::from_secs(1),
+    }))
+}
+
+/// Checks config entries against the known enrollment centers. States with no
+/// Global Entry center are rejected when Global Entry is the only provider;
+/// other mismatches are warnings.
+fn check_against_directory(config: &JeffConfig, directory: &Directory) -> Result<(), AppError> {
+    if directory.is_empty() {
+        return Ok(());
+    }
+    let cbp_only = config.providers.iter().all(|&p| p == ProviderKind::GlobalEntry);
+    for state in &config.search_states {
+        if directory.offers_in_state(GLOBAL_ENTRY, state) {
+            continue;
+        }
+        let msg = format!(
+            "search_states: no {GLOBAL_ENTRY} enrollment center in {state}; centers exist in {}",
+            directory.states_offering(GLOBAL_ENTRY).join(", ")
+        );
+        if cbp_only {
+            return Err(AppError::Config(msg));
+        }
+        warn!("{msg}");
+    }
+    for id in &config.exclude_location_ids {
+        if directory.get(*id).is_none() {
+            warn!("exclude_location_ids: unknown location {id}");
+        }
+    }
+    // Keys for other providers look like `<provider>:12` and aren't in the CBP directory.
+    for id in config.display.location_aliases.keys().filter(|id| !id.contains(':')) {
+        if id.parse().ok().and_then(|id| directory.get(id)).is_none() {
+            warn!("location_aliases: unknown location {id}");
+        }
+    }
+    Ok(())
+}
+
+/// Lets a front end such as the tray icon steer the run loop.
+struct Controls {
+    /// Scheduled cycles are skipped while set.
+    paused: AtomicBool,
+    /// Set while `power` pauses polling; watches skip their checks too.
+    power_paused: AtomicBool,
+    /// Starts a cycle right away, paused or not.
+    run_now: Notify,
+    /// The most recent finished cycle.
+    latest: watch::Sender<Option<CycleReport>>,
+}
+
+impl Controls {
+    fn new() -> Self {
+        Controls {
+            paused: AtomicBool::new(false),
+            power_paused: AtomicBool::new(false),
+            run_now: Notify::new(),
+            latest: watch::channel(None).0,
+        }
+    }
+}
+
+/// Runs once or on the interval; a one-shot run's exit code reflects its cycle.
+async fn run(config: Arc<JeffConfig>, controls: Arc<Controls>) -> Result<Exit, AppError> {
+    info!("Loaded config: {:?}", config);
+    if !config.preflight {
+        check_against_directory(&config, &Directory::load(&config.directory_path)?)?;
+    }
+
+    let dns = Dns::new(config.dns.as_ref()).low_memory(config.low_memory);
+    let client = dns.builder().build()?;
+    let api = retrying_api_client(&config, &dns, client.clone())?;
+    // One shared instance per source, so the general scan and the watches
+    // merge requests for the same date.
+    let watches: Vec<&SniperConfig> = config.sniper.iter().chain(&config.watches).collect();
+    let mut shared: HashMap<ProviderKind, Arc<dyn Provider>> = HashMap::new();
+    for kind in config.providers.iter().copied().chain(watches.iter().map(|w| w.provider)) {
+        if let hash_map::Entry::Vacant(slot) = shared.entry(kind) {
+            let provider = providers::build(kind, &api)?;
+            let window = Duration::from_secs(config.merge_window_seconds);
+            slot.insert(Arc::new(SharedProvider::new(provider, window)));
+        }
+    }
+    let providers: Vec<Arc<dyn Provider>> = config.providers.iter().map(|kind| Arc::clone(&shared[kind])).collect();
+    let dispatcher =
+        Arc::new(Dispatcher::from_config(&client, &dns, &config).map_err(AppError::into_config)?);
+    let services = CycleServices {
+        concurrency: config
+            .adaptive_concurrency
+            .as_ref()
+            .map(|adaptive| Arc::new(AdaptiveConcurrency::new(adaptive, config.max_concurrent_fetches))),
+        metrics: Metrics::from_config(&client, &config),
+        calendars: Calendars::new(&client, &config.calendar),
+        home: config.home.as_ref().map(|home| Arc::new(Home::new(home, &client))),
+        past_dates_warned: AtomicBool::new(false),
+    };
+    if config.preflight {
+        let report = preflight::run(&config, &dispatcher, &providers).await;
+        report.log();
+        if let Some(exit) = report.exit() {
+            return Ok(exit);
+        }
+    }
+    let state = Arc::new(Mutex::new(StateStore::load(&config.state_path)?));
+    let history = History::open(&config.history_path)?;
+    if config.low_memory {
+        history.shrink_cache()?;
+    }
+    let history = Mutex::new(history);
+    dispatcher.resend_pending(config.outbox_max_age(), &state, &history).await;
+
+    #[cfg(feature = "socket-mode")]
+    if config.enable_slack {
+        if let Some(app_token) = config.slack_app_token.as_ref().map(|t| t.expose().to_string()) {
+            tokio::spawn(socket_mode::run_socket_mode(client.clone(), app_token, Arc::clone(&state)));
+        }
+    }
+    if let Some(addr) = config.badge.as_ref().and_then(|b| b.listen) {
+        tokio::spawn(badge::serve(addr));
+    }
+    if let Some(home) = &services.home {
+        // The last cycle's locations, so this run's cycles find them placed.
+        let seen = history.lock().unwrap().latest_cycle().ok().flatten().unwrap_or_default();
+        tokio::spawn(Arc::clone(home).warm(seen.into_iter().map(|item| item.loc).collect()));
+    }
+    #[cfg(feature = "mute-links")]
+    if let Some(mute) = &config.mute_links {
+        tokio::spawn(mute::serve(mute.listen, mute.secret.expose().to_string(), Arc::clone(&state)));
+    }
+
+    if let Some(budget) = &config.request_budget {
+        let minutes = config.fetch_interval_minutes;
+        let warning = match config.scan_mode {
+            ScanMode::Dates => budget.plan_warning(config.scan_dates(clock::today()).len(), providers.len(), minutes),
+            ScanMode::Snapshot => budget.snapshot_warning(snapshot_lookups(&config), minutes),
+        };
+        if let Some(text) = warning {
+            warn!("{text}");
+            dispatcher.notice("Appointment finder configuration", &text).await;
+        }
+    }
+
+    let mut exit = Exit::Ok;
+    if config.booking_passed(clock::today()) {
+        info!("Booked appointment has passed; nothing to do");
+    } else if config.fetch_interval_minutes == 0 {
+        let mut report = run_cycle(&dispatcher, &services, &providers, Arc::clone(&config), &state, &history).await;
+        if report.stale {
+            info!("{report}");
+            info!("Running a fresh cycle");
+            report = run_cycle(&dispatcher, &services, &providers, Arc::clone(&config), &state, &history).await;
+        }
+        info!("{report}");
+        exit = report.exit();
+        send_reports(&report, &client, &dispatcher, &config, &state, &history).await;
+        controls.latest.send_replace(Some(report));
+    } else {
+        let minutes = config.fetch_interval_minutes;
+        let period = Duration::from_secs(minutes * 60);
+        let mut schedule = Schedule::new(period);
+        // `interval` ticks right away; starting a period later waits for the
+        // first scheduled cycle instead.
+        let start = if config.run_at_startup {
+            Instant::now()
+        } else {
+            info!("Running the first cycle in {minutes} minutes");
+            Instant::now() + period
+        };
+        let mut ticker = tokio::time::interval_at(start, period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut outages = OutageTracker::default();
+        let mut running = FuturesUnordered::new();
+        // Cycles that ended while another's results were going out.
+        let mut finished = VecDeque::new();
+        // Tasks of their own, so they keep checking while a cycle finishes;
+        // dropping the set stops them.
+        let mut watching = JoinSet::new();
+        for watch in &watches {
+            watching.spawn(sniper::watch(
+                Arc::clone(&config),
+                (*watch).clone(),
+                Arc::clone(&shared[&watch.provider]),
+                Arc::clone(&dispatcher),
+                Arc::clone(&state),
+                Arc::clone(&controls),
+            ));
+        }
+        let mut governor = config.power.as_ref().map(|power| Governor::new(power, minutes));
+        // Digests go out on the hour they're due, however far apart cycles are.
+        let mut digest_ticker = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+        digest_ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let digests = dispatcher.has_digests();
+        let mut watchers = config
+            .application_status
+            .iter()
+            .map(|account| StatusWatcher::new(account, client.clone(), &dispatcher))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut status_ticker = tokio::time::interval(STATUS_CHECK_INTERVAL);
+        status_ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut wake = WakeDetector::new();
+        let mut wake_ticker = tokio::time::interval(WAKE_CHECK_INTERVAL);
+        wake_ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        // Whether the running cycle is already a rerun for stale results, so
+        // a scan too slow to ever be fresh enough doesn't rerun forever.
+        let mut rerun = false;
+        loop {
+            tokio::select! {
+                tick = ticker.tick() => {
+                    if config.booking_passed(clock::today()) {
+                        info!("Booked appointment has passed; stopping");
+                        while let Some(report) = next_finished(&mut finished, &mut running).await {
+                            let finishing = finish_cycle(&report, &client, &dispatcher, &config, &state, &history, &mut outages);
+                            alongside(finishing, &mut running, &mut finished).await;
+                            controls.latest.send_replace(Some(report));
+                        }
+                        break;
+                    }
+                    if let Some(governor) = &mut governor {
+                        let conditions = tokio::task::spawn_blocking(power::detect).await.unwrap_or_default();
+                        if let Some(text) = governor.update(conditions) {
+                            dispatcher.notice("Appointment finder status", &text).await;
+                        }
+                        controls.power_paused.store(governor.paused(), Ordering::Relaxed);
+                        if let Some(why) = governor.skip() {
+                            info!("{why}; skipping this cycle");
+                            continue;
+                        }
+                    }
+                    if schedule.backing_off(tick) {
+                        info!("Backing off; skipping this cycle");
+                        continue;
+                    }
+                    if controls.paused.load(Ordering::Relaxed) {
+                        info!("Paused; skipping this cycle");
+                        continue;
+                    }
+                    if !running.is_empty() {
+                        match config.cycle_overlap {
+                            CycleOverlap::Skip => {
+                                warn!("Previous cycle is still running after {minutes} minutes; skipping this one");
+                                continue;
+                            }
+                            CycleOverlap::Queue => {
+                                warn!("Previous cycle is still running after {minutes} minutes; starting the next when it finishes");
+                                while let Some(report) = next_finished(&mut finished, &mut running).await {
+                                    let finishing =
+                                        finish_cycle(&report, &client, &dispatcher, &config, &state, &history, &mut outages);
+                                    schedule.finished(alongside(finishing, &mut running, &mut finished).await);
+                                    controls.latest.send_replace(Some(report));
+                                }
+                                if schedule.backing_off(Instant::now()) {
+                                    info!("Backing off; not starting the queued cycle");
+                                    continue;
+                                }
+                            }
+                            CycleOverlap::Concurrent => {
+                                warn!("Previous cycle is still running after {minutes} minutes; starting another alongside it");
+                            }
+                        }
+                    }
+                    running.push(run_cycle(&dispatcher, &services, &providers, Arc::clone(&config), &state, &history));
+                }
+                () = controls.run_now.notified() => {
+                    if running.is_empty() {
+                        info!("Running a cycle now");
+                        running.push(run_cycle(&dispatcher, &services, &providers, Arc::clone(&config), &state, &history));
+                    } else {
+                        info!("A cycle is already running");
+                    }
+                }
+                Some(report) = next_finished(&mut finished, &mut running), if !running.is_empty() || !finished.is_empty() => {
+                    let finishing = finish_cycle(&report, &client, &dispatcher, &config, &state, &history, &mut outages);
+                    let backoff = alongside(finishing, &mut running, &mut finished).await;
+                    rerun = report.stale && !rerun;
+                    if rerun {
+                        info!("Running a fresh cycle");
+                        running.push(run_cycle(&dispatcher, &services, &providers, Arc::clone(&config), &state, &history));
+                    }
+                    controls.latest.send_replace(Some(report));
+                    schedule.finished(backoff);
+                }
+                _ = status_ticker.tick(), if !watchers.is_empty() => {
+                    for watcher in &mut watchers {
+                        watcher.check_if_due(&dispatcher, &state).await;
+                    }
+                }
+                _ = wake_ticker.tick() => {
+                    if let Some(gap) = wake.check() {
+                        // Timers stood still while asleep; start the schedule
+                        // over, so its first tick runs a cycle now.
+                        info!("Woke from sleep after {} minutes; running a cycle now", gap.num_minutes());
+                        ticker = tokio::time::interval(period);
+                        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    }
+                }
+                _ = digest_ticker.tick(), if digests => {
+                    if let Err(e) = dispatcher.flush_digests(&config.display, &state, &history).await {
+                        error!("Error sending digest: {e}");
+                    }
+                }
+                Some(done) = watching.join_next(), if !watching.is_empty() => {
+                    if let Err(e) = done {
+                        error!("A watch stopped: {e}");
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(exit)
+}
+
+pub fn load_config(path: impl AsRef<Path>) -> Result<JeffConfig, AppError> {
+    let merged = read_with_includes(path.as_ref(), &mut Vec::new())?;
+    let mut config: JeffConfig = serde_json::from_value(merged)?;
+    validate(&config)?;
+    config.load_extensions()?;
+    Ok(config)
+}
+
+/// Rejects settings that can never match anything, with a hint on how to fix them.
+fn validate(config: &JeffConfig) -> Result<(), AppError> {
+    for state in &config.search_states {
+        if !directory::is_state_code(state) {
+            let hint = match directory::suggest_state(state) {
+                Some((code, name)) => format!("did you mean \"{code}\" ({name})?"),
+                None => "use two uppercase letters such as \"CA\".".to_string(),
+            };
+            let codes: Vec<&str> = directory::US_STATES.iter().map(|(code, _)| *code).collect();
+            return Err(AppError::Config(format!(
+                "search_states: {state:?} is not a US state code; {hint} Supported: {}",
+                codes.join(", ")
+            )));
+        }
+    }
+
+    if config.max_result_age_minutes == Some(0) {
+        return Err(AppError::Config("max_result_age_minutes must be at least 1".to_string()));
+    }
+
+    if config.enable_slack {
+        if config.slack_channel_id.is_empty() {
+            return Err(AppError::Config("slack_channel_id: no channel given".to_string()));
+        }
+        for (i, id) in config.slack_channel_id.iter().enumerate() {
+            if config.slack_channel_id[..i].contains(id) {
+                return Err(AppError::Config(format!("slack_channel_id: {id} is listed twice")));
+            }
+        }
+    }
+
+    if config.max_concurrent_fetches == 0 {
+        return Err(AppError::Config(
+            "max_concurrent_fetches must be at least 1".to_string(),
+        ));
+    }
+    if config.max_retries > ttp_client::MAX_RETRIES {
+        return Err(AppError::Config(format!(
+            "max_retries must be at most {}, got {}",
+            ttp_client::MAX_RETRIES,
+            config.max_retries
+        )));
+    }
+    if config.max_clock_skew_seconds > clock::MAX_SKEW_SECONDS {
+        return Err(AppError::Config(format!(
+            "max_clock_skew_seconds must be at most {} (a day), got {}",
+            clock::MAX_SKEW_SECONDS,
+            config.max_clock_skew_seconds
+        )));
+    }
+    if config.scan_mode == ScanMode::Snapshot && config.providers.iter().any(|&p| p != ProviderKind::GlobalEntry) {
+        return Err(AppError::Config(
+            "scan_mode \"snapshot\" only works with the global_entry provider".to_string(),
+        ));
+    }
+    config.display.validate().map_err(AppError::Config)?;
+    let deliveries = [
+        ("bark", config.bark.as_ref().map(|c| c.delivery)),
+        ("pushcut", config.pushcut.as_ref().map(|c| c.delivery)),
+        ("gotify", config.gotify.as_ref().map(|c| c.delivery)),
+        ("matrix", config.matrix.as_ref().map(|c| c.delivery)),
+        ("signal", config.signal.as_ref().map(|c| c.delivery)),
+        ("whatsapp", config.whatsapp.as_ref().map(|c| c.delivery)),
+        ("github", config.github.as_ref().map(|c| c.delivery)),
+    ]
+    .into_iter();
+    #[cfg(feature = "email")]
+    let deliveries = deliveries.chain([("email", config.email.as_ref().map(|c| c.delivery))]);
+    #[cfg(feature = "sns")]
+    let deliveries = deliveries.chain([("sns", config.sns.as_ref().map(|c| c.delivery))]);
+    #[cfg(feature = "pubsub")]
+    let deliveries = deliveries.chain([("pubsub", config.pubsub.as_ref().map(|c| c.delivery))]);
+    #[cfg(feature = "scripting")]
+    let deliveries = deliveries.chain([("script", config.script.as_ref().map(|c| c.delivery))]);
+    #[cfg(feature = "plugins")]
+    let deliveries = deliveries.chain([("plugins", config.plugins.as_ref().map(|c| c.delivery))]);
+    if let Some((name, _)) = deliveries.into_iter().find(|(_, d)| matches!(d, Some(Delivery::Board { .. }))) {
+        return Err(AppError::Config(format!(
+            "{name}.delivery: \"board\" needs a channel that can edit messages; only Slack can"
+        )));
+    }
+    if let Some(adaptive) = &config.adaptive_concurrency {
+        adaptive
+            .validate()
+            .map_err(|e| AppError::Config(format!("adaptive_concurrency: {e}")))?;
+        if config.low_memory {
+            warn!("adaptive_concurrency: ignored with low_memory, which fetches one date at a time");
+        }
+    }
+    if let Some(budget) = &config.request_budget {
+        budget.validate().map_err(|e| AppError::Config(format!("request_budget: {e}")))?;
+    }
+
+    let today = clock::today();
+    let horizon = today + chrono::Duration::days(BOOKING_HORIZON_DAYS);
+    match (config.window_mode, &config.date_range) {
+        (WindowMode::Fixed, None) => {
+            return Err(AppError::Config(
+                "date_range is required unless window_mode is \"rolling\"".to_string(),
+            ));
+        }
+        (WindowMode::Fixed, &Some(DateRange { start, end })) => {
+            if end < start {
+                return Err(AppError::Config(format!(
+                    "date_range: end {end} is before start {start}"
+                )));
+            }
+            if start > horizon {
+                return Err(AppError::Config(format!(
+                    "date_range: slots are only published {BOOKING_HORIZON_DAYS} days ahead; \
+                     set date_range.start on or before {horizon}"
+                )));
+            }
+            if end > horizon {
+                warn!("date_range: slots are only published through {horizon}; later dates are skipped");
+            }
+        }
+        (WindowMode::Rolling, range) => {
+            match config.window_days {
+                None | Some(0) => {
+                    return Err(AppError::Config(
+                        "window_mode \"rolling\" needs window_days of at least 1".to_string(),
+                    ));
+                }
+                Some(days) if i64::from(days) > BOOKING_HORIZON_DAYS => {
+                    warn!("window_days: slots are only published {BOOKING_HORIZON_DAYS} days ahead; later dates are skipped");
+                }
+                Some(_) => {}
+            }
+            if range.is_some() {
+                warn!("date_range is ignored when window_mode is \"rolling\"");
+            }
+        }
+    }
+    if config.booking_passed(today) {
+        warn!("booked_date {} has passed; nothing will be scanned", config.booked_date.unwrap());
+    } else if (!config.only_weekdays.is_empty() || config.skip_federal_holidays) && config.scan_dates(today).is_empty() {
+        warn!("only_weekdays and skip_federal_holidays leave no dates to scan");
+    }
+
+    config
+        .api_query
+        .validate()
+        .map_err(|e| AppError::Config(format!("api_query: {e}")))?;
+    if !matches!(config.api_query.filter_timestamp_by, TimestampFilter::On) {
+        warn!("api_query: filter_timestamp_by other than \"on\" is experimental; results are recorded under the date asked for");
+    }
+    if let Some(badge) = &config.badge {
+        badge.validate().map_err(|e| AppError::Config(format!("badge: {e}")))?;
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(anomalies) = &config.anomalies {
+        anomalies.validate().map_err(|e| AppError::Config(format!("anomalies: {e}")))?;
+    }
+    #[cfg(feature = "mute-links")]
+    if let Some(mute) = &config.mute_links {
+        mute.validate().map_err(|e| AppError::Config(format!("mute_links: {e}")))?;
+    }
+    if let Some(whatsapp) = &config.whatsapp {
+        whatsapp.validate().map_err(|e| AppError::Config(format!("whatsapp: {e}")))?;
+    }
+    #[cfg(feature = "sns")]
+    if let Some(sns) = &config.sns {
+        sns.validate().map_err(|e| AppError::Config(format!("sns: {e}")))?;
+    }
+    #[cfg(feature = "pubsub")]
+    if let Some(pubsub) = &config.pubsub {
+        pubsub.validate().map_err(|e| AppError::Config(format!("pubsub: {e}")))?;
+    }
+    if let Some(github) = &config.github {
+        github.validate().map_err(|e| AppError::Config(format!("github: {e}")))?;
+    }
+    if let Some(escalation) = &config.escalation {
+        escalation
+            .validate()
+            .map_err(|e| AppError::Config(format!("escalation: {e}")))?;
+    }
+    let watches = config.sniper.iter().map(|w| ("sniper".to_string(), w));
+    for (key, watch) in watches.chain(config.watches.iter().enumerate().map(|(i, w)| (format!("watches[{i}]"), w))) {
+        watch.validate().map_err(|e| AppError::Config(format!("{key}: {e}")))?;
+        if watch.page && config.escalation.is_none() {
+            return Err(AppError::Config(format!("{key}: page needs an `escalation` section")));
+        }
+        // Otherwise the watch would keep getting answers older than its interval.
+        if config.merge_window_seconds >= watch.interval_seconds {
+            return Err(AppError::Config(format!(
+                "{key}: interval_seconds must be longer than merge_window_seconds ({})",
+                config.merge_window_seconds
+            )));
+        }
+        if config.fetch_interval_minutes == 0 {
+            warn!("{key} only runs when fetch_interval_minutes is above 0");
+        }
+    }
+    #[cfg(feature = "heatmap")]
+    if let Some(heatmap) = &config.heatmap {
+        heatmap.validate().map_err(|e| AppError::Config(format!("heatmap: {e}")))?;
+    }
+    if let Some(power) = &config.power {
+        power.validate().map_err(|e| AppError::Config(format!("power: {e}")))?;
+        if config.fetch_interval_minutes == 0 {
+            warn!("power only applies when fetch_interval_minutes is above 0");
+        }
+    }
+    if config.body_log.sample_every == 0 {
+        return Err(AppError::Config("body_log.sample_every must be at least 1".to_string()));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(report) = &config.weekly_report {
+        if report.hour > 23 {
+            return Err(AppError::Config(format!(
+                "weekly_report.hour must be 0-23, got {}",
+                report.hour
+            )));
+        }
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(note) = &config.drought_note {
+        if note.hour > 23 {
+            return Err(AppError::Config(format!(
+                "drought_note.hour must be 0-23, got {}",
+                note.hour
+            )));
+        }
+    }
+    for (i, account) in config.application_status.iter().enumerate() {
+        let what = account.name.as_deref().map_or("application_status".to_string(), |name| {
+            format!("application_status {name}")
+        });
+        account.validate().map_err(|e| AppError::Config(format!("{what}: {e}")))?;
+        if config.application_status.len() > 1 && account.name.is_none() {
+            return Err(AppError::Config(
+                "application_status: give each account a name when watching more than one".to_string(),
+            ));
+        }
+        if config.application_status[..i].iter().any(|a| a.name == account.name) {
+            return Err(AppError::Config(format!("{what}: the name is used twice")));
+        }
+        if let Some(state) = account.states.iter().find(|state| !config.search_states.contains(state)) {
+            return Err(AppError::Config(format!("{what}: states: {state} isn't in search_states")));
+        }
+    }
+    if !config.application_status.is_empty() && config.fetch_interval_minutes == 0 {
+        warn!("application_status only applies when fetch_interval_minutes is above 0");
+    }
+    for calendar in &config.calendar {
+        calendar.validate().map_err(|e| AppError::Config(format!("calendar: {e}")))?;
+    }
+    if let Some(home) = &config.home {
+        home.validate().map_err(|e| AppError::Config(format!("home: {e}")))?;
+    }
+    if let Some(policy) = &config.notification_policy {
+        policy.validate().map_err(|e| AppError::Config(format!("notification_policy: {e}")))?;
+    }
+    Ok(())
+}
+
+/// Reads a config file, first merging in each file named by its `include` list
+/// (relative to the including file), so later files override earlier ones.
+fn read_with_includes(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Value, AppError> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| AppError::General(format!("Config {}: {e}", path.display())))?;
+    if chain.contains(&canonical) {
+        return Err(AppError::General(format!("Config include cycle at {}", path.display())));
+    }
+    chain.push(canonical);
+
+    let mut config: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let includes = match config.as_object_mut().and_then(|o| o.remove("include")) {
+        None => Vec::new(),
+        Some(Value::Array(items)) => items,
+        Some(other) => vec![other],
+    };
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Object(Default::default());
+    for include in includes {
+        let Some(name) = include.as_str() else {
+            return Err(AppError::General(format!("include: expected a path, got {include}")));
+        };
+        debug!("Including config {name} from {}", path.display());
+        merge(&mut merged, read_with_includes(&dir.join(name), chain)?);
+    }
+    merge(&mut merged, config);
+
+    chain.pop();
+    Ok(merged)
+}
+
+/// Deep-merges `overlay` into `base`: objects merge key by key, anything else replaces.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Reacts to consecutive cycles answered with maintenance, rate-limit or
+/// firewall pages.
+#[derive(Default)]
+struct OutageTracker {
+    backoff: u32,
+    blocked: bool,
+    /// Whether `request_budget` refused requests since the previous cycle.
+    over_budget: bool,
+}
+
+/// Logs a finished cycle, sends anything due, and returns how many fetch
+/// intervals to wait before the next cycle.
+async fn finish_cycle(
+    report: &CycleReport,
+    client: &Client,
+    dispatcher: &Dispatcher,
+    config: &JeffConfig,
+    state: &Mutex<StateStore>,
+    history: &Mutex<History>,
+    outages: &mut OutageTracker,
+) -> u32 {
+    info!("{report}");
+    // On stdout whatever the log level, for grepping a fleet's journals.
+    println!("{}", report.summary());
+    send_reports(report, client, dispatcher, config, state, history).await;
+
+    // Pages in place of data mean the API needs a rest (or a human).
+    let blocked = report.outages.contains(&Outage::Blocked);
+    if blocked != outages.blocked {
+        let text = if blocked {
+            "Scheduler API requests are being blocked by its firewall; no slots can be found until this clears. \
+             If it persists, set `session_path` to replay a browser session."
+        } else {
+            "Scheduler API requests are getting through again."
+        };
+        dispatcher.notify(EventKind::ApiDown, "Appointment finder status", text, state).await;
+        outages.blocked = blocked;
+    }
+    let over_budget = config.loaded_budget.as_ref().is_some_and(|budget| budget.take_refused() > 0);
+    if over_budget != outages.over_budget {
+        let text = if over_budget {
+            "The request budget is spent, so some dates went unchecked. Your configuration asks for more requests than \
+             `request_budget` allows: check fewer dates or raise `fetch_interval_minutes`."
+        } else {
+            "Requests are within the request budget again."
+        };
+        dispatcher.notify(EventKind::ApiDown, "Appointment finder status", text, state).await;
+        outages.over_budget = over_budget;
+    }
+    outages.backoff = if report.outages.iter().any(|o| *o != Outage::Unrecognized) {
+        (outages.backoff.max(1) * 2).min(MAX_OUTAGE_BACKOFF)
+    } else {
+        1
+    };
+    if outages.backoff > 1 {
+        warn!(
+            "API unavailable; backing off to {} minutes between cycles",
+            config.fetch_interval_minutes * u64::from(outages.backoff)
+        );
+    }
+    outages.backoff
+}
+
+async fn send_reports(
+    report: &CycleReport,
+    client: &Client,
+    dispatcher: &Dispatcher,
+    config: &JeffConfig,
+    state: &Mutex<StateStore>,
+    history: &Mutex<History>,
+) {
+    #[cfg(feature = "sqlite")]
+    if let Err(e) = report::maybe_send_weekly(client, config, state, history).await {
+        error!("Error sending weekly report: {e}");
+    }
+    #[cfg(feature = "sqlite")]
+    if let Err(e) = drought::maybe_send_note(client, config, state, history).await {
+        error!("Error sending drought note: {e}");
+    }
+    #[cfg(not(feature = "sqlite"))]
+    let _ = client;
+    if let Err(e) = dispatcher.flush_digests(&config.display, state, history).await {
+        error!("Error sending digest: {e}");
+    }
+    #[cfg(feature = "sqlite")]
+    if let Err(e) = retention::maybe_prune(config, state, history) {
+        error!("Error pruning history: {e}");
+    }
+    #[cfg(feature = "heatmap")]
+    if let Err(e) = heatmap::maybe_render(report, dispatcher, config, state, history).await {
+        error!("Error drawing heatmap: {e}");
+    }
+    #[cfg(not(feature = "heatmap"))]
+    let _ = report;
+}
+
+/// Optional services a cycle consults or reports to.
+struct CycleServices<'a> {
+    /// The `adaptive_concurrency` limit, carried from cycle to cycle.
+    concurrency: Option<Arc<AdaptiveConcurrency>>,
+    metrics: Option<Metrics>,
+    calendars: Option<Calendars<'a>>,
+    home: Option<Arc<Home>>,
+    /// Set once this run has said that part of `date_range` is in the past.
+    past_dates_warned: AtomicBool,
+}
+
+async fn run_cycle(
+    dispatcher: &Dispatcher,
+    services: &CycleServices<'_>,
+    providers: &[Arc<dyn Provider>],
+    config: Arc<JeffConfig>,
+    state: &Mutex<StateStore>,
+    history: &Mutex<History>,
+) -> CycleReport {
+    info!("Starting cycle...");
+    let started_at = clock::now();
+    // Alerts a channel failed to take last cycle get another try.
+    dispatcher.resend_pending(config.outbox_max_age(), state, history).await;
+
+    let today = clock::today();
+    if let (WindowMode::Fixed, Some(range)) = (config.window_mode, &config.date_range) {
+        if range.start < today && !services.past_dates_warned.swap(true, Ordering::Relaxed) {
+            if range.end < today {
+                warn!("date_range: end {} has passed; nothing left to scan", range.end);
+            } else {
+                warn!("date_range: start {} has passed; scanning from today", range.start);
+            }
+        }
+    }
+    let dates = config.scan_dates(today);
+    let Scan {
+        locations: mut all_locations,
+        scanned_ok,
+        dates_failed,
+        outages,
+    } = scan(providers, &config, services.concurrency.as_ref(), &dates).await;
+    if let Some(home) = &services.home {
+        home.retain_nearby(&mut all_locations);
+    }
+
+    let cycle = CycleRecord {
+        started_at,
+        finished_at: clock::now(),
+        scanned_ok,
+        dates_failed,
+    };
+    #[cfg(feature = "sqlite")]
+    let new_slots = match history.lock().unwrap().record_cycle(&cycle, &all_locations) {
+        Ok(n) => Some(n),
+        Err(e) => {
+            error!("Error recording history: {e}");
+            None
+        }
+    };
+    #[cfg(not(feature = "sqlite"))]
+    let new_slots = None;
+    let mut report = CycleReport {
+        started_at,
+        finished_at: cycle.finished_at,
+        dates_scanned: cycle.scanned_ok.len() + dates_failed,
+        dates_failed,
+        slots_found: all_locations.len(),
+        new_slots,
+        states: all_locations.iter().map(|item| item.loc.state.clone()).collect(),
+        slots_alerted: 0,
+        outages,
+        notify_failures: 0,
+        stale: false,
+    };
+
+    badge::publish(&config, &all_locations, &report);
+    if let Some(metrics) = &services.metrics {
+        metrics.record(&config, &all_locations, &report).await;
+    }
+    // Picks up `suppress add`/`remove` from the command line.
+    if let Err(e) = state.lock().unwrap().reload_suppressions() {
+        warn!("Error reloading suppressions: {e}");
+    }
+    dispatcher.escalate(&config, &all_locations, &cycle.scanned_ok, state).await;
+
+    let oldest = all_locations.iter().map(|item| item.fetched_at).min();
+    if let (Some(max_age), Some(oldest)) = (config.max_result_age(), oldest) {
+        report.stale = clock::now() - oldest > max_age;
+    }
+    if report.stale {
+        warn!(
+            "Results are up to {} minutes old, over max_result_age_minutes; not alerting",
+            (clock::now() - oldest.unwrap_or(started_at)).num_minutes()
+        );
+    } else if !dispatcher.is_empty() {
+        let sources: Vec<&str> = providers.iter().map(|p| p.display_name()).collect();
+        let sources = sources.join(" / ");
+        let failed = (report.dates_failed, report.dates_scanned);
+        let conflicts = match &services.calendars {
+            Some(calendars) => {
+                let days: BTreeSet<(NaiveDate, Option<&str>)> =
+                    all_locations.iter().map(|item| (item.date, item.loc.tz_data.as_deref())).collect();
+                calendars.conflicts(&days.into_iter().collect::<Vec<_>>(), &config.display).await
+            }
+            None => Conflicts::default(),
+        };
+        // Applicants with their own channels get the slots that suit them.
+        let applicants: Vec<_> = config
+            .application_status
+            .iter()
+            .filter(|account| !account.notify.is_empty())
+            .map(|account| {
+                let found: Vec<_> =
+                    all_locations.iter().filter(|item| account.wants(&item.loc, item.date)).cloned().collect();
+                (account, found)
+            })
+            .collect();
+        let alert = compose_alert(&config, &sources, all_locations, failed, &conflicts, state, history);
+        let subject = format!("{sources} availability");
+        report.slots_alerted = alert.slots.len();
+        report.notify_failures = dispatcher.dispatch(&alert, &subject, state, history).await;
+        for (account, found) in applicants {
+            let mut alert = compose_alert(&config, &sources, found, failed, &conflicts, state, history);
+            if account.booked_date.is_some() {
+                alert.kind = EventKind::EarlierSlot;
+            }
+            let subject = match &account.name {
+                Some(name) => {
+                    alert.tag(name);
+                    format!("{subject} for {name}")
+                }
+                None => subject.clone(),
+            };
+            report.notify_failures += dispatcher.dispatch_to(&account.notify, &alert, &subject, state, history).await;
+        }
+    } else {
+        for &format in &config.exports {
+            if let Err(e) = export::export(format, &all_locations, history, &config) {
+                error!("Error writing {}: {e}", format.path());
+            } else {
+                info!("Exported data to {}", format.path());
+            }
+        }
+    }
+
+    report
+}
+
+/// The alert for `found`, decided once and then fanned out to every channel:
+/// suppressed slots and days the calendars block dropped, `summarize`
+/// applied, and trends, urgency, center notes and calendar conflicts added as
+/// configured. `failed` is dates failed of dates scanned.
+fn compose_alert(
+    config: &JeffConfig,
+    sources: &str,
+    found: Vec<FetchedLocation>,
+    failed: (usize, usize),
+    conflicts: &Conflicts,
+    state: &Mutex<StateStore>,
+    history: &Mutex<History>,
+) -> Alert {
+    // Judged on everything found, before suppressions and `summarize` thin it.
+    #[cfg(feature = "sqlite")]
+    let unusual = match &config.anomalies {
+        Some(anomalies) => anomaly::notes(anomalies, &found, &history.lock().unwrap()).unwrap_or_else(|e| {
+            error!("Error checking for unusual availability: {e}");
+            Default::default()
+        }),
+        None => Default::default(),
+    };
+    #[cfg(not(feature = "sqlite"))]
+    let unusual = format::CenterNotes::default();
+    let alerts: Vec<FetchedLocation> = {
+        let store = state.lock().unwrap();
+        found
+            .into_iter()
+            .filter(|item| !store.is_suppressed(&item.slot_key()) && !conflicts.is_blocked(item.date, item.loc.tz_data.as_deref()))
+            .collect()
+    };
+    let alerts = format::summarize(alerts, config.display.summarize);
+    let trends = if config.display.show_trends {
+        let yesterday = clock::today() - chrono::Duration::days(1);
+        match history.lock().unwrap().earliest_by_location_on(yesterday) {
+            Ok(prev) => format::trends(&alerts, &prev),
+            Err(e) => {
+                error!("Error loading trends: {e}");
+                Default::default()
+            }
+        }
+    } else {
+        Default::default()
+    };
+    let lifetimes = if config.display.show_urgency {
+        let since = clock::now() - chrono::Duration::days(URGENCY_WINDOW_DAYS);
+        history.lock().unwrap().median_lifetimes(since, URGENCY_MIN_SAMPLES).unwrap_or_else(|e| {
+            error!("Error loading slot lifetimes: {e}");
+            Default::default()
+        })
+    } else {
+        Default::default()
+    };
+    let mut notes = if config.display.show_center_info {
+        center_notes(config, &alerts)
+    } else {
+        Default::default()
+    };
+    for (key, warnings) in unusual {
+        notes.entry(key).or_default().splice(0..0, warnings);
+    }
+    for item in &alerts {
+        let flags = conflicts.flags(item.date, item.loc.tz_data.as_deref());
+        if !flags.is_empty() {
+            notes.insert(item.slot_key(), flags.iter().map(|flag| format!("📅 {flag}")).collect());
+        }
+    }
+    #[cfg(feature = "mute-links")]
+    if let Some(mute) = &config.mute_links {
+        // A grouped entry covers every date at its location.
+        let keys: BTreeSet<String> = if config.display.group_by_location {
+            alerts.iter().map(|item| item.loc.key()).collect()
+        } else {
+            alerts.iter().map(FetchedLocation::slot_key).collect()
+        };
+        for key in keys {
+            notes.entry(key.clone()).or_default().push(format!("Mute for {}h: {}", mute.hours, mute.link(&key)));
+        }
+    }
+    let render = |verbosity| {
+        let (mut text, slots) =
+            slack::build_slack_message(&alerts, sources, &config.display, verbosity, &trends, &lifetimes, &notes);
+        // Whatever did load still goes out, flagged as incomplete.
+        let (dates_failed, dates_scanned) = failed;
+        if dates_failed > 0 {
+            text.truncate(text.trim_end().len());
+            text.push_str(&format!(
+                "\n\n_{dates_failed} of {dates_scanned} date(s) failed to load; results may be incomplete._"
+            ));
+        }
+        (text, slots)
+    };
+    let (text, slots) = render(Verbosity::Normal);
+    let variants = config
+        .display
+        .verbosity
+        .in_use()
+        .into_iter()
+        .filter(|&verbosity| verbosity != Verbosity::Normal)
+        .map(|verbosity| (verbosity, render(verbosity)))
+        .collect();
+    let lines = alerts
+        .iter()
+        .map(|item| DigestLine {
+            slot: item.slot_key(),
+            line: format::digest_line(item, &config.display),
+        })
+        .collect();
+    let csv = config.signal.as_ref().filter(|s| s.attach_csv && !alerts.is_empty()).and_then(|_| {
+        match export::csv_bytes(&alerts, config.export_layout, &config.display) {
+            Ok(bytes) => Some(Attachment {
+                filename: ExportFormat::Csv.path().to_string(),
+                content_type: "text/csv".to_string(),
+                bytes,
+            }),
+            Err(e) => {
+                error!("Error exporting CSV attachment: {e}");
+                None
+            }
+        }
+    });
+    let kind = if config.booked_date.is_some() { EventKind::EarlierSlot } else { EventKind::NewSlot };
+    Alert {
+        text,
+        slots,
+        variants,
+        lines,
+        csv,
+        kind,
+    }
+}
+
+/// Directory notes for the Global Entry centers among `alerts`; the directory
+/// doesn't cover other providers.
+fn center_notes(config: &JeffConfig, alerts: &[FetchedLocation]) -> format::CenterNotes {
+    let directory = match Directory::load(&config.directory_path) {
+        Ok(directory) => directory,
+        Err(e) => {
+            error!("Error loading location directory: {e}");
+            return Default::default();
+        }
+    };
+    alerts
+        .iter()
+        .filter(|item| item.loc.provider == providers::GLOBAL_ENTRY_ID)
+        .filter_map(|item| Some((item.loc.key(), format::center_notes(directory.get(item.loc.id)?))))
+        .filter(|(_, notes)| !notes.is_empty())
+        .collect()
+}
+
+/// What asking every provider about every date turned up.
+#[derive(Default)]
+struct Scan {
+    /// Matching results, across providers.
+    locations: Vec<FetchedLocation>,
+    /// Dates that loaded, by provider.
+    scanned_ok: Vec<(&'static str, NaiveDate)>,
+    dates_failed: usize,
+    /// Kinds of non-JSON error pages the API answered with.
+    outages: BTreeSet<Outage>,
+}
+
+/// The most location lookups a `snapshot_lookups` cycle may make: one for
+/// each center in the directory that the filters could want.
+fn snapshot_lookups(config: &JeffConfig) -> usize {
+    if !config.snapshot_lookups {
+        return 0;
+    }
+    let directory = Directory::load(&config.directory_path).unwrap_or_default();
+    directory
+        .centers()
+        .iter()
+        .filter(|c| config.search_states.contains(&c.state) && !config.exclude_location_ids.contains(&c.id))
+        .count()
+}
+
+/// Fetches `dates` from every provider, at most `max_concurrent_fetches` (or
+/// the `adaptive_concurrency` limit) at a time, in the order and at the
+/// spacing `pacing` gives. One at a time (or with `low_memory`), fetches run
+/// on this task instead of being spawned.
+async fn scan(
+    providers: &[Arc<dyn Provider>],
+    config: &Arc<JeffConfig>,
+    adaptive: Option<&Arc<AdaptiveConcurrency>>,
+    dates: &[NaiveDate],
+) -> Scan {
+    if config.scan_mode == ScanMode::Snapshot {
+        return snapshot(providers, config, dates).await;
+    }
+    let mut scan = Scan::default();
+    let mut requests = Vec::new();
+    for provider in providers {
+        info!("Scanning {} date(s) for {}", dates.len(), provider.display_name());
+        requests.extend(dates.iter().map(|&date| (provider, date)));
+    }
+    config.pacing.order(&mut requests);
+    let mut pacer = config.pacing.pacer(Duration::from_secs_f64(config.api_rate_limit_seconds));
+
+    if config.low_memory || (config.max_concurrent_fetches == 1 && adaptive.is_none()) {
+        for (provider, date) in requests {
+            let fetched = fetch_for_date(provider.as_ref(), config, date).await;
+            // Nothing was sent, so there's nothing to space out.
+            let refused = matches!(fetched, Err(AppError::TtpError(TtpError::OverBudget(_))));
+            scan.add(provider.id(), date, fetched);
+            if !refused {
+                sleep(pacer.next_gap()).await;
+            }
+        }
+        return scan;
+    }
+
+    // A fetch is only spawned once one in flight has finished, and dropping
+    // the set (say, when the cycle is cancelled) aborts whatever is left.
+    let mut tasks = JoinSet::new();
+    let start = config.max_concurrent_fetches;
+    for (provider, date) in requests {
+        // An adaptive limit may have dropped below what's in flight.
+        while tasks.len() >= adaptive.map_or(start, |a| a.limit()) {
+            match tasks.join_next().await {
+                Some(joined) => scan.add_joined(joined),
+                None => break,
+            }
+        }
+        let provider = Arc::clone(provider);
+        let cfg = Arc::clone(config);
+        let adaptive = adaptive.cloned();
+        let gap = pacer.next_gap();
+
+        tasks.spawn(async move {
+            let started = Instant::now();
+            let fetched = fetch_for_date(provider.as_ref(), &cfg, date).await;
+            if let Some(adaptive) = &adaptive {
+                adaptive.record(started.elapsed(), &fetched);
+            }
+            // The task runs through the gap, so spacing holds per slot.
+            if !matches!(fetched, Err(AppError::TtpError(TtpError::OverBudget(_)))) {
+                sleep(gap).await;
+            }
+            (provider.id(), date, fetched)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        scan.add_joined(joined);
+    }
+    scan
+}
+
+/// Asks each provider once for its soonest slots nationwide, keeping those
+/// that fall on one of `dates`. A location's later slots aren't seen, so one
+/// whose soonest date is ruled out is skipped even if a later one would do.
+/// Without `snapshot_lookups` a location's date isn't known; it's listed as
+/// undated, by the last of `dates`.
+///
+/// A date counts as scanned only if no open slot on it can have gone unseen:
+/// every date when nothing is open, those up to the earliest soonest date
+/// otherwise, and none while some location's date is unknown.
+async fn snapshot(providers: &[Arc<dyn Provider>], config: &JeffConfig, dates: &[NaiveDate]) -> Scan {
+    let mut scan = Scan::default();
+    let Some((&last, before)) = dates.last().and_then(|d| Some((d, d.succ_opt()?))) else {
+        return scan;
+    };
+    for provider in providers {
+        info!("Checking the soonest {} slots before {before}", provider.display_name());
+        let fetched = provider.fetch_soonest(before, config).await.and_then(|found| {
+            // `None` sorts first, so an unknown date covers nothing.
+            let seen_through = found.iter().map(|(date, _)| *date).min();
+            let scanned = dates
+                .iter()
+                .filter(|&&date| seen_through.is_none_or(|soonest| soonest.is_some_and(|s| date <= s)))
+                .map(|&date| (provider.id(), date))
+                .collect::<Vec<_>>();
+            let fetched = found
+                .into_iter()
+                .filter_map(|(date, entry)| match date {
+                    Some(date) if dates.contains(&date) => Some((date, false, entry)),
+                    Some(_) => None,
+                    // Its lookup failed, and was logged.
+                    None if config.snapshot_lookups => None,
+                    None => Some((last, true, entry)),
+                })
+                .map(|(date, undated, entry)| {
+                    Ok(FetchedLocation {
+                        date,
+                        raw_json: serde_json::to_string(&entry.raw)?,
+                        loc: entry.value,
+                        fetched_at: clock::now(),
+                        undated,
+                    })
+                })
+                .collect::<Result<Vec<_>, AppError>>()?;
+            Ok((scanned, fetched))
+        });
+        match fetched {
+            Ok((scanned, fetched)) => {
+                scan.scanned_ok.extend(scanned);
+                scan.locations.extend(fetched);
+            }
+            Err(e) => scan.fail(dates.len(), e),
+        }
+    }
+    scan
+}
+
+/// What fetching one date from one provider turned up.
+type FetchResult = (&'static str, NaiveDate, Result<Vec<FetchedLocation>, AppError>);
+
+impl Scan {
+    /// Adds what fetching one date from `provider` turned up.
+    fn add(&mut self, provider: &'static str, date: NaiveDate, fetched: Result<Vec<FetchedLocation>, AppError>) {
+        match fetched {
+            Ok(fetched) => {
+                self.scanned_ok.push((provider, date));
+                self.locations.extend(fetched);
+            }
+            Err(e) => self.fail(1, e),
+        }
+    }
+
+    /// Adds the result of a spawned fetch; one that panicked counts as a failed date.
+    fn add_joined(&mut self, joined: Result<FetchResult, JoinError>) {
+        match joined {
+            Ok((provider, date, fetched)) => self.add(provider, date, fetched),
+            Err(e) => {
+                self.dates_failed += 1;
+                error!("Task panicked: {e}");
+            }
+        }
+    }
+
+    fn fail(&mut self, dates: usize, e: AppError) {
+        self.dates_failed += dates;
+        if let AppError::TtpError(TtpError::Unavailable { outage, .. }) = &e {
+            self.outages.insert(*outage);
+        }
+        warn!("Error: {e}");
+    }
+}
+
+/// Downloads the data for one date and returns all matched locations, each with raw JSON.
+async fn fetch_for_date(
+    provider: &dyn Provider,
+    config: &JeffConfig,
+    date: NaiveDate,
+) -> Result<Vec<FetchedLocation>, AppError> {
+    let entries = provider.fetch_date(date).await?;
+
+    let mut results = Vec::new();
+    for entry in entries {
+        // Filter by states and exclusions
+        if config.wants(&entry.value) {
+            results.push(FetchedLocation {
+                date,
+                raw_json: serde_json::to_string(&entry.raw)?,
+                loc: entry.value,
+                fetched_at: clock::now(),
+                undated: false,
+            });
+        }
+    }
+
+    Ok(results)
+}
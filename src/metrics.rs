@@ -0,0 +1,236 @@
+//! Per-cycle metrics pushed to a time-series database, for long-term
+//! dashboards: InfluxDB line protocol (InfluxDB 1.x/2.x, VictoriaMetrics) or
+//! Prometheus remote-write.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::{debug, error};
+
+use crate::{redact::Secret, AppError, CycleReport, FetchedLocation, JeffConfig, LocationAvailability};
+
+#[derive(Debug, Deserialize)]
+pub struct MetricsConfig {
+    pub format: MetricsFormat,
+    /// Write endpoint, e.g. `http://localhost:8086/api/v2/write?org=me&bucket=jeff`,
+    /// `http://localhost:8428/write` or `http://localhost:9090/api/v1/write`.
+    pub url: String,
+    /// Sent as `Authorization: Token ...` for InfluxDB, `Bearer ...` for remote-write.
+    #[serde(default)]
+    pub token: Option<Secret>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsFormat {
+    Influx,
+    PrometheusRemoteWrite,
+}
+
+enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+/// One measurement: a name, identifying tags, and its values.
+struct Series {
+    name: &'static str,
+    tags: Vec<(&'static str, String)>,
+    fields: Vec<(&'static str, Value)>,
+}
+
+pub struct Metrics {
+    client: Client,
+    format: MetricsFormat,
+    url: String,
+    token: Option<Secret>,
+}
+
+impl Metrics {
+    pub fn from_config(client: &Client, config: &JeffConfig) -> Option<Self> {
+        let metrics = config.metrics.as_ref()?;
+        Some(Metrics {
+            client: client.clone(),
+            format: metrics.format,
+            url: metrics.url.clone(),
+            token: metrics.token.clone(),
+        })
+    }
+
+    /// Writes one cycle's metrics; failures are logged, since a dashboard gap
+    /// shouldn't hold up alerts.
+    pub async fn record(&self, config: &JeffConfig, found: &[FetchedLocation], report: &CycleReport) {
+        let series = series_for(config, found, report);
+        if let Err(e) = self.write(&series, report.finished_at).await {
+            error!("Error writing metrics: {e}");
+        }
+    }
+
+    async fn write(&self, series: &[Series], at: DateTime<Utc>) -> Result<(), AppError> {
+        let request = match self.format {
+            MetricsFormat::Influx => {
+                let mut request = self.client.post(&self.url).body(line_protocol(series, at));
+                if let Some(token) = &self.token {
+                    request = request.header("Authorization", format!("Token {}", token.expose()));
+                }
+                request
+            }
+            MetricsFormat::PrometheusRemoteWrite => {
+                let body = snap::raw::Encoder::new()
+                    .compress_vec(&remote_write(series, at))
+                    .map_err(|e| AppError::General(format!("metrics: {e}")))?;
+                let mut request = self
+                    .client
+                    .post(&self.url)
+                    .header("Content-Type", "application/x-protobuf")
+                    .header("Content-Encoding", "snappy")
+                    .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+                    .body(body);
+                if let Some(token) = &self.token {
+                    request = request.bearer_auth(token.expose());
+                }
+                request
+            }
+        };
+        request.send().await?.error_for_status()?;
+        debug!("Wrote {} metric series to {}", series.len(), self.url);
+        Ok(())
+    }
+}
+
+/// Cycle totals, plus slot counts and the earliest slot per watched state and
+/// per location with slots.
+fn series_for(config: &JeffConfig, found: &[FetchedLocation], report: &CycleReport) -> Vec<Series> {
+    let today = report.finished_at.date_naive();
+    let mut series = vec![Series {
+        name: "appointment_cycle",
+        tags: Vec::new(),
+        fields: vec![
+            ("dates_scanned", Value::Int(report.dates_scanned as i64)),
+            ("dates_failed", Value::Int(report.dates_failed as i64)),
+            ("slots_found", Value::Int(report.slots_found as i64)),
+            ("slots_new", Value::Int(report.new_slots.unwrap_or(0) as i64)),
+            ("duration_seconds", Value::Float(report.duration().num_milliseconds() as f64 / 1000.0)),
+        ],
+    }];
+
+    let mut states: BTreeMap<&str, (usize, Option<NaiveDate>)> =
+        config.search_states.iter().map(|s| (s.as_str(), (0, None))).collect();
+    let locations = LocationAvailability::merge(found);
+    for location in &locations {
+        let state = states.entry(location.loc.state.as_str()).or_default();
+        state.0 += location.dates.len();
+        state.1 = Some(state.1.map_or(location.earliest(), |d| d.min(location.earliest())));
+    }
+
+    let earliest = |fields: &mut Vec<(&'static str, Value)>, date: NaiveDate| {
+        fields.push(("earliest_days", Value::Int((date - today).num_days())));
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        fields.push(("earliest_timestamp", Value::Int(midnight.timestamp())));
+    };
+    for (state, (slots, first)) in states {
+        let mut fields = vec![("slots", Value::Int(slots as i64))];
+        if let Some(date) = first {
+            earliest(&mut fields, date);
+        }
+        series.push(Series {
+            name: "appointment_state",
+            tags: vec![("state", state.to_string())],
+            fields,
+        });
+    }
+    for location in &locations {
+        let mut fields = vec![("slots", Value::Int(location.dates.len() as i64))];
+        earliest(&mut fields, location.earliest());
+        series.push(Series {
+            name: "appointment_location",
+            tags: vec![
+                ("location", config.display.name(&location.loc).to_string()),
+                ("location_id", location.loc.id.to_string()),
+                ("provider", location.loc.provider.to_string()),
+                ("state", location.loc.state.clone()),
+            ],
+            fields,
+        });
+    }
+    series
+}
+
+/// e.g. `appointment_state,state=CA slots=2i,earliest_days=3i 1736899200000000000`
+fn line_protocol(series: &[Series], at: DateTime<Utc>) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ");
+    let nanos = at.timestamp_nanos_opt().unwrap_or_default();
+    let mut out = String::new();
+    for s in series {
+        out.push_str(s.name);
+        for (key, value) in &s.tags {
+            out.push_str(&format!(",{key}={}", escape(value)));
+        }
+        let fields: Vec<String> = s
+            .fields
+            .iter()
+            .map(|(key, value)| match value {
+                Value::Int(v) => format!("{key}={v}i"),
+                Value::Float(v) => format!("{key}={v}"),
+            })
+            .collect();
+        out.push_str(&format!(" {} {nanos}\n", fields.join(",")));
+    }
+    out
+}
+
+/// A Prometheus `WriteRequest` protobuf with one time series per field,
+/// named `<series>_<field>`.
+fn remote_write(series: &[Series], at: DateTime<Utc>) -> Vec<u8> {
+    let millis = at.timestamp_millis();
+    let mut request = Vec::new();
+    for s in series {
+        for (field, value) in &s.fields {
+            let name = format!("{}_{field}", s.name);
+            // Labels must be sorted by name; `__name__` sorts first.
+            let mut labels = vec![("__name__", name.as_str())];
+            labels.extend(s.tags.iter().map(|(k, v)| (*k, v.as_str())));
+            labels.sort();
+
+            let mut ts = Vec::new();
+            for (key, value) in labels {
+                let mut label = Vec::new();
+                proto_bytes(&mut label, 1, key.as_bytes());
+                proto_bytes(&mut label, 2, value.as_bytes());
+                proto_bytes(&mut ts, 1, &label);
+            }
+            let mut sample = Vec::new();
+            let value = match value {
+                Value::Int(v) => *v as f64,
+                Value::Float(v) => *v,
+            };
+            proto_key(&mut sample, 1, 1);
+            sample.extend_from_slice(&value.to_le_bytes());
+            proto_key(&mut sample, 2, 0);
+            proto_varint(&mut sample, millis as u64);
+            proto_bytes(&mut ts, 2, &sample);
+            proto_bytes(&mut request, 1, &ts);
+        }
+    }
+    request
+}
+
+fn proto_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn proto_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    proto_varint(buf, (field << 3) | wire_type);
+}
+
+fn proto_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    proto_key(buf, field, 2);
+    proto_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
@@ -0,0 +1,187 @@
+//! Mute links in alerts: each listed slot, or location when alerts group by
+//! location, gets a signed link that silences it for a while. Following the
+//! link shows a confirmation page, and only its button mutes, so link
+//! previews in Slack and mail scanners can't mute anything by fetching it.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use hmac::{Hmac, Mac};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::{error, info, warn};
+
+use crate::{clock, redact::Secret, state::StateStore};
+
+/// Bytes of the HMAC kept in a link; plenty against guessing.
+const SIGNATURE_BYTES: usize = 16;
+
+#[derive(Debug, Deserialize)]
+pub struct MuteLinksConfig {
+    /// Address to serve the links on, e.g. `0.0.0.0:8081`.
+    pub listen: SocketAddr,
+    /// How the links reach that server from wherever alerts are read, e.g.
+    /// `https://finder.example.org`.
+    pub base_url: String,
+    /// Signs the links, so only those from alerts work.
+    pub secret: Secret,
+    /// How long a link mutes for.
+    #[serde(default = "default_hours")]
+    pub hours: u32,
+}
+
+fn default_hours() -> u32 {
+    24
+}
+
+impl MuteLinksConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.base_url.starts_with("https://") && !self.base_url.starts_with("http://") {
+            return Err(format!("base_url {:?} is not an http(s) URL", self.base_url));
+        }
+        if self.secret.expose().len() < 16 {
+            return Err("secret must be at least 16 characters".to_string());
+        }
+        if self.hours == 0 {
+            return Err("hours must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// The link that mutes `key`, a slot or location key, for `hours`.
+    pub fn link(&self, key: &str) -> String {
+        format!(
+            "{}/mute?key={key}&hours={}&sig={}",
+            self.base_url.trim_end_matches('/'),
+            self.hours,
+            sign(self.secret.expose(), key, self.hours)
+        )
+    }
+}
+
+fn mac(secret: &str, key: &str, hours: u32) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(format!("{key}|{hours}").as_bytes());
+    mac
+}
+
+fn sign(secret: &str, key: &str, hours: u32) -> String {
+    let bytes = mac(secret, key, hours).finalize().into_bytes();
+    bytes[..SIGNATURE_BYTES].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn verify(secret: &str, key: &str, hours: u32, sig: &str) -> bool {
+    if sig.len() != SIGNATURE_BYTES * 2 || !sig.is_ascii() {
+        return false;
+    }
+    let bytes: Option<Vec<u8>> =
+        (0..sig.len()).step_by(2).map(|i| u8::from_str_radix(&sig[i..i + 2], 16).ok()).collect();
+    bytes.is_some_and(|bytes| mac(secret, key, hours).verify_truncated_left(&bytes).is_ok())
+}
+
+/// A link's `key`, `hours` and `sig`, if it has them and they check out.
+fn checked(secret: &str, query: &str) -> Option<(String, u32)> {
+    let params: HashMap<&str, &str> = query.split('&').filter_map(|pair| pair.split_once('=')).collect();
+    let key = params.get("key")?.replace("%40", "@").replace("%3A", ":");
+    let hours = params.get("hours")?.parse().ok()?;
+    verify(secret, &key, hours, params.get("sig")?).then_some((key, hours))
+}
+
+/// Serves the links until the process exits, muting in `state`.
+pub async fn serve(addr: SocketAddr, secret: String, state: Arc<Mutex<StateStore>>) {
+    let secret = Arc::new(secret);
+    let make = make_service_fn(move |_| {
+        let (secret, state) = (Arc::clone(&secret), Arc::clone(&state));
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let (secret, state) = (Arc::clone(&secret), Arc::clone(&state));
+                async move { Ok::<_, Infallible>(handle(req, &secret, &state)) }
+            }))
+        }
+    });
+    let server = match Server::try_bind(&addr) {
+        Ok(builder) => builder.serve(make),
+        Err(e) => {
+            error!("Error serving mute links on {addr}: {e}");
+            return;
+        }
+    };
+    info!("Serving mute links on http://{addr}/mute");
+    if let Err(e) = server.await {
+        error!("Mute link server stopped: {e}");
+    }
+}
+
+fn handle(req: Request<Body>, secret: &str, state: &Mutex<StateStore>) -> Response<Body> {
+    if req.uri().path() != "/mute" {
+        return page(StatusCode::NOT_FOUND, "Not found.");
+    }
+    let query = req.uri().query().unwrap_or("");
+    let Some((key, hours)) = checked(secret, query) else {
+        return page(StatusCode::FORBIDDEN, "This mute link isn't valid.");
+    };
+    match *req.method() {
+        Method::GET => page(
+            StatusCode::OK,
+            &format!(
+                "Stop alerts for {} for {hours} hour(s)?\
+                 <form method=\"post\" action=\"/mute?{}\"><button>Mute</button></form>",
+                html_escape(&key),
+                html_escape(query)
+            ),
+        ),
+        Method::POST => {
+            let until = clock::now() + chrono::Duration::hours(i64::from(hours));
+            let mut store = state.lock().unwrap();
+            store.mute(std::slice::from_ref(&key), until);
+            if let Err(e) = store.save() {
+                warn!("Error saving state: {e}");
+                return page(StatusCode::INTERNAL_SERVER_ERROR, "Couldn't save the mute; see the finder's log.");
+            }
+            info!("Muted {key} for {hours} hour(s) from a mute link");
+            let until = until.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M");
+            page(StatusCode::OK, &format!("Muted {} until {until}.", html_escape(&key)))
+        }
+        _ => page(StatusCode::METHOD_NOT_ALLOWED, "Use the link from an alert."),
+    }
+}
+
+fn page(status: StatusCode, body: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Cache-Control", "no-store")
+        .body(Body::from(format!(
+            "<!doctype html><meta name=\"viewport\" content=\"width=device-width\"><title>Mute</title><p>{body}</p>"
+        )))
+        .unwrap_or_default()
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_only_work_as_signed() {
+        let secret = "0123456789abcdef";
+        let query = |key: &str, hours: u32, sig: &str| format!("key={key}&hours={hours}&sig={sig}");
+        let sig = sign(secret, "5140@2025-03-14", 24);
+        assert_eq!(checked(secret, &query("5140@2025-03-14", 24, &sig)), Some(("5140@2025-03-14".to_string(), 24)));
+        assert_eq!(checked(secret, &query("5140%402025-03-14", 24, &sig)), Some(("5140@2025-03-14".to_string(), 24)));
+        assert_eq!(checked(secret, &query("5140@2025-03-14", 240, &sig)), None);
+        assert_eq!(checked(secret, &query("5446@2025-03-14", 24, &sig)), None);
+        assert_eq!(checked("another secret!!", &query("5140@2025-03-14", 24, &sig)), None);
+    }
+}
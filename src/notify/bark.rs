@@ -0,0 +1,101 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{default_open_url, plain_text, shortcut_url, Delivery, Notifier, PUSH_MAX_CHARS};
+use crate::{redact::Secret, AppError};
+
+#[derive(Debug, Deserialize)]
+pub struct BarkConfig {
+    /// The key shown in the Bark app.
+    pub device_key: Secret,
+    /// A self-hosted bark-server, if not the public one.
+    #[serde(default = "default_server")]
+    pub server: String,
+    /// Shortcut run when the notification is tapped, given the booking page
+    /// URL as input; without one the page opens directly.
+    #[serde(default)]
+    pub shortcut: Option<String>,
+    /// Page opened (or handed to `shortcut`) on tap.
+    #[serde(default = "default_open_url")]
+    pub open_url: String,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_server() -> String {
+    "https://api.day.app".to_string()
+}
+
+/// Sends alerts as iOS push notifications through Bark.
+pub struct BarkNotifier {
+    client: Client,
+    endpoint: String,
+    device_key: Secret,
+    url: String,
+}
+
+impl BarkNotifier {
+    pub fn new(client: &Client, config: &BarkConfig) -> Self {
+        BarkNotifier {
+            client: client.clone(),
+            endpoint: format!("{}/push", config.server.trim_end_matches('/')),
+            device_key: config.device_key.clone(),
+            url: match &config.shortcut {
+                Some(name) => shortcut_url(name, &config.open_url),
+                None => config.open_url.clone(),
+            },
+        }
+    }
+}
+
+impl Notifier for BarkNotifier {
+    fn target(&self) -> &str {
+        "bark"
+    }
+
+    fn kind(&self) -> &'static str {
+        "bark"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        plain_text(text, PUSH_MAX_CHARS)
+    }
+
+    fn title(&self, subject: &str) -> Option<String> {
+        Some(subject.to_string())
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            let payload = serde_json::json!({
+                "device_key": self.device_key.expose(),
+                "title": self.title(subject),
+                "body": self.render(subject, text),
+                "url": self.url,
+                "group": "appointments",
+                "level": "timeSensitive",
+            });
+            let resp = self
+                .client
+                .post(&self.endpoint)
+                .json(&payload)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            #[derive(Deserialize)]
+            struct BarkResp {
+                code: u16,
+                message: String,
+            }
+
+            let br: BarkResp = resp.json().await?;
+            if br.code != 200 {
+                return Err(AppError::General(format!("bark: {}", br.message)));
+            }
+            Ok(None)
+        }
+        .boxed()
+    }
+}
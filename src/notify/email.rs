@@ -0,0 +1,158 @@
+use futures::{future::BoxFuture, FutureExt};
+use lettre::{
+    message::{header::ContentType, Mailbox, MessageBuilder},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Deserialize;
+
+use super::{plain_text, Delivery, Notifier};
+use crate::{redact::Secret, AppError};
+
+#[derive(Debug, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<Secret>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection (usually port 587).
+    #[default]
+    Starttls,
+    /// TLS from the start (usually port 465).
+    Wrapper,
+    /// No encryption, for a relay on the local network.
+    None,
+}
+
+/// Sends alerts as plain-text email over SMTP.
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    target: String,
+}
+
+impl EmailNotifier {
+    pub fn new(config: &EmailConfig) -> Result<Self, AppError> {
+        let parse = |addr: &str| {
+            addr.parse::<Mailbox>()
+                .map_err(|e| AppError::General(format!("email: invalid address {addr:?}: {e}")))
+        };
+        let host = &config.smtp_host;
+        let mut transport = match config.tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpTls::Wrapper => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+        }
+        .map_err(|e| AppError::General(format!("email: {e}")))?
+        .port(config.smtp_port);
+        if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+            transport = transport.credentials(Credentials::new(user.clone(), pass.expose().to_string()));
+        }
+        Ok(EmailNotifier {
+            transport: transport.build(),
+            from: parse(&config.from)?,
+            to: config.to.iter().map(|a| parse(a)).collect::<Result<_, _>>()?,
+            target: format!("email:{}", config.to.join(",")),
+        })
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "email"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        plain_text(text, usize::MAX)
+    }
+
+    fn title(&self, subject: &str) -> Option<String> {
+        Some(subject.to_string())
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<bool, AppError>> {
+        async move {
+            // Connects, logs in if configured, and sends NOOP.
+            self.transport
+                .test_connection()
+                .await
+                .map_err(|e| AppError::General(format!("email: {e}")))
+        }
+        .boxed()
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            let message = self
+                .builder(subject)
+                .header(ContentType::TEXT_PLAIN)
+                .body(self.render(subject, text))
+                .map_err(|e| AppError::General(format!("email: {e}")))?;
+            self.deliver(message).await?;
+            Ok(None)
+        }
+        .boxed()
+    }
+
+    #[cfg(feature = "heatmap")]
+    fn send_file<'a>(&'a self, file: &'a super::Attachment, caption: &'a str) -> BoxFuture<'a, Result<bool, AppError>> {
+        use lettre::message::{MultiPart, SinglePart};
+
+        async move {
+            let content_type =
+                ContentType::parse(&file.content_type).map_err(|e| AppError::General(format!("email: {e}")))?;
+            let message = self
+                .builder(&file.filename)
+                .multipart(
+                    MultiPart::mixed()
+                        .singlepart(SinglePart::plain(caption.to_string()))
+                        .singlepart(lettre::message::Attachment::new(file.filename.clone()).body(file.bytes.clone(), content_type)),
+                )
+                .map_err(|e| AppError::General(format!("email: {e}")))?;
+            self.deliver(message).await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+}
+
+impl EmailNotifier {
+    fn builder(&self, subject: &str) -> MessageBuilder {
+        let mut message = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        message
+    }
+
+    async fn deliver(&self, message: Message) -> Result<(), AppError> {
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| AppError::General(format!("email: {e}")))?;
+        Ok(())
+    }
+}
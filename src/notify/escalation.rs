@@ -0,0 +1,300 @@
+//! Pages someone through PagerDuty or Opsgenie when a critical slot opens,
+//! and resolves the incident once that slot is gone. Each slot maps to one
+//! dedup key (PagerDuty) or alias (Opsgenie), so repeats never double-page.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use chrono::NaiveDate;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{error, info};
+
+use super::BOOKING_URL;
+use crate::{
+    clock,
+    format::{self, DisplayOptions},
+    redact::Secret,
+    state::{Escalated, StateStore},
+    AppError, FetchedLocation, JeffConfig,
+};
+
+const SOURCE: &str = "global-entry-appointment-finder";
+
+#[derive(Debug, Deserialize)]
+pub struct EscalationConfig {
+    /// Only slots before this date are critical.
+    #[serde(default)]
+    pub before: Option<NaiveDate>,
+    /// Only slots at these locations are critical; empty means any.
+    #[serde(default)]
+    pub location_ids: Vec<usize>,
+    #[serde(default)]
+    pub pagerduty: Option<PagerDutyConfig>,
+    #[serde(default)]
+    pub opsgenie: Option<OpsgenieConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PagerDutyConfig {
+    /// Integration key of an Events API v2 integration.
+    pub routing_key: Secret,
+    /// `critical`, `error`, `warning` or `info`.
+    #[serde(default = "default_severity")]
+    pub severity: String,
+}
+
+fn default_severity() -> String {
+    "critical".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpsgenieConfig {
+    /// Key of an API integration.
+    pub api_key: Secret,
+    /// `P1` (highest) to `P5`.
+    #[serde(default = "default_priority")]
+    pub priority: String,
+    /// Accounts hosted in the EU use a different API host.
+    #[serde(default)]
+    pub eu: bool,
+}
+
+fn default_priority() -> String {
+    "P1".to_string()
+}
+
+impl EscalationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pagerduty.is_none() && self.opsgenie.is_none() {
+            return Err("set pagerduty and/or opsgenie".to_string());
+        }
+        if let Some(pd) = &self.pagerduty {
+            if !["critical", "error", "warning", "info"].contains(&pd.severity.as_str()) {
+                return Err(format!(
+                    "pagerduty.severity must be critical, error, warning or info, got {:?}",
+                    pd.severity
+                ));
+            }
+        }
+        if let Some(og) = &self.opsgenie {
+            if !["P1", "P2", "P3", "P4", "P5"].contains(&og.priority.as_str()) {
+                return Err(format!("opsgenie.priority must be P1-P5, got {:?}", og.priority));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_critical(&self, item: &FetchedLocation) -> bool {
+        self.before.is_none_or(|before| item.date < before)
+            && (self.location_ids.is_empty() || self.location_ids.contains(&item.loc.id))
+    }
+}
+
+pub struct Escalator {
+    client: Client,
+}
+
+impl Escalator {
+    pub fn new(client: &Client) -> Self {
+        Escalator { client: client.clone() }
+    }
+
+    /// Opens incidents for critical slots in `found` that don't have one, and
+    /// resolves those whose slot is gone: not found on a date that loaded
+    /// (`scanned_ok`), in the past, or acknowledged by reaction.
+    pub async fn run(
+        &self,
+        config: &JeffConfig,
+        found: &[FetchedLocation],
+        scanned_ok: &[(&'static str, NaiveDate)],
+        state: &Mutex<StateStore>,
+    ) {
+        let Some(esc) = &config.escalation else {
+            return;
+        };
+        let (open, critical) = {
+            let store = state.lock().unwrap();
+            let critical: BTreeMap<String, &FetchedLocation> = found
+                .iter()
+                .filter(|item| !item.undated && esc.is_critical(item) && !store.is_suppressed(&item.slot_key()))
+                .map(|item| (item.slot_key(), item))
+                .collect();
+            (store.escalated().clone(), critical)
+        };
+        let today = clock::today();
+
+        for (slot, item) in &critical {
+            if !open.contains_key(slot) {
+                self.open(esc, item, &config.display, state).await;
+            }
+        }
+
+        for (slot, incident) in &open {
+            // Still listed, and nobody has acknowledged it.
+            let acknowledged = state.lock().unwrap().is_suppressed(slot);
+            if !acknowledged && found.iter().any(|item| item.slot_key() == *slot) {
+                continue;
+            }
+            let loaded = scanned_ok
+                .iter()
+                .any(|(provider, date)| *provider == incident.provider && *date == incident.date);
+            if loaded || incident.date < today || acknowledged {
+                self.close(esc, slot, state).await;
+            }
+        }
+    }
+
+    /// Opens an incident for `item`'s slot and remembers it; errors are logged.
+    pub async fn open(
+        &self,
+        esc: &EscalationConfig,
+        item: &FetchedLocation,
+        display: &DisplayOptions,
+        state: &Mutex<StateStore>,
+    ) {
+        let slot = item.slot_key();
+        let summary = format!("Appointment open: {}", format::digest_line(item, display));
+        match self.trigger(esc, &slot, &summary, item).await {
+            Ok(()) => {
+                info!("Escalated {slot}");
+                let mut store = state.lock().unwrap();
+                store.add_escalated(
+                    &slot,
+                    Escalated {
+                        provider: item.loc.provider.to_string(),
+                        date: item.date,
+                    },
+                );
+                if let Err(e) = store.save() {
+                    error!("Error saving state: {e}");
+                }
+            }
+            Err(e) => error!("Error escalating {slot}: {e}"),
+        }
+    }
+
+    /// Resolves the incident for `slot` and forgets it; errors are logged.
+    pub async fn close(&self, esc: &EscalationConfig, slot: &str, state: &Mutex<StateStore>) {
+        match self.resolve(esc, slot).await {
+            Ok(()) => {
+                info!("Resolved escalation for {slot}");
+                let mut store = state.lock().unwrap();
+                store.remove_escalated(slot);
+                if let Err(e) = store.save() {
+                    error!("Error saving state: {e}");
+                }
+            }
+            Err(e) => error!("Error resolving escalation for {slot}: {e}"),
+        }
+    }
+
+    /// Opens an incident with every configured service. Both deduplicate on
+    /// the slot, so retrying after a partial failure is safe.
+    async fn trigger(
+        &self,
+        esc: &EscalationConfig,
+        slot: &str,
+        summary: &str,
+        item: &FetchedLocation,
+    ) -> Result<(), AppError> {
+        // Opsgenie only accepts string values here.
+        let details = json!({
+            "slot": slot,
+            "provider": item.loc.provider,
+            "date": item.date.to_string(),
+            "location_id": item.loc.id.to_string(),
+            "name": item.loc.name,
+            "city": item.loc.city,
+            "state": item.loc.state,
+            "address": item.loc.address,
+            "book": BOOKING_URL,
+        });
+        if let Some(pd) = &esc.pagerduty {
+            let event = json!({
+                "routing_key": pd.routing_key.expose(),
+                "event_action": "trigger",
+                "dedup_key": dedup_key(slot),
+                "payload": {
+                    "summary": summary,
+                    "source": SOURCE,
+                    "severity": pd.severity,
+                    "custom_details": details,
+                },
+                "links": [{ "href": BOOKING_URL, "text": "Book on TTP" }],
+            });
+            self.pagerduty(&event).await?;
+        }
+        if let Some(og) = &esc.opsgenie {
+            // Opsgenie cuts messages at 130 characters.
+            let message: String = summary.chars().take(130).collect();
+            let alert = json!({
+                "message": message,
+                "alias": dedup_key(slot),
+                "description": format!("{summary}\n\nBook at {BOOKING_URL}"),
+                "priority": og.priority,
+                "source": SOURCE,
+                "tags": ["appointment"],
+                "details": details,
+            });
+            self.client
+                .post(format!("{}/v2/alerts", opsgenie_host(og)))
+                .header("Authorization", format!("GenieKey {}", og.api_key.expose()))
+                .json(&alert)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+
+    async fn resolve(&self, esc: &EscalationConfig, slot: &str) -> Result<(), AppError> {
+        if let Some(pd) = &esc.pagerduty {
+            let event = json!({
+                "routing_key": pd.routing_key.expose(),
+                "event_action": "resolve",
+                "dedup_key": dedup_key(slot),
+            });
+            self.pagerduty(&event).await?;
+        }
+        if let Some(og) = &esc.opsgenie {
+            let url = format!("{}/v2/alerts/{}/close", opsgenie_host(og), dedup_key(slot));
+            let resp = self
+                .client
+                .post(url)
+                .query(&[("identifierType", "alias")])
+                .header("Authorization", format!("GenieKey {}", og.api_key.expose()))
+                .json(&json!({ "source": SOURCE, "note": "Slot is no longer available" }))
+                .send()
+                .await?;
+            // Already closed or deleted by hand.
+            if resp.status() != reqwest::StatusCode::NOT_FOUND {
+                resp.error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn pagerduty(&self, event: &serde_json::Value) -> Result<(), AppError> {
+        self.client
+            .post("https://events.pagerduty.com/v2/enqueue")
+            .json(event)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// e.g. `appointment-5140@2025-01-14`; the slot key is already URL-safe.
+fn dedup_key(slot: &str) -> String {
+    format!("appointment-{slot}")
+}
+
+fn opsgenie_host(config: &OpsgenieConfig) -> &'static str {
+    if config.eu {
+        "https://api.eu.opsgenie.com"
+    } else {
+        "https://api.opsgenie.com"
+    }
+}
@@ -0,0 +1,187 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use super::{Delivery, Notifier};
+use crate::{clock, redact::Secret, AppError};
+
+const API: &str = "https://api.github.com";
+
+#[derive(Debug, Deserialize)]
+pub struct GitHubConfig {
+    /// Token allowed to write issues in `repo`, or gists.
+    pub token: Secret,
+    /// `owner/name` of the repository holding the status issue.
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Issue to keep updated; otherwise the open issue labelled `label`,
+    /// created on the first alert if there is none.
+    #[serde(default)]
+    pub issue: Option<u64>,
+    #[serde(default = "default_label")]
+    pub label: String,
+    #[serde(default = "default_title")]
+    pub title: String,
+    /// Gist to keep updated instead of an issue.
+    #[serde(default)]
+    pub gist: Option<String>,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_label() -> String {
+    "appointment-status".to_string()
+}
+
+fn default_title() -> String {
+    "Appointment availability".to_string()
+}
+
+impl GitHubConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.repo, &self.gist) {
+            (Some(_), Some(_)) => Err("set either repo or gist, not both".to_string()),
+            (None, None) => Err("set repo (for an issue) or gist".to_string()),
+            (Some(repo), None) if repo.split('/').filter(|s| !s.is_empty()).count() != 2 => {
+                Err(format!("repo must look like \"owner/name\", got {repo:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps one GitHub issue or gist showing the latest alert, as a living
+/// status post for people who coordinate on GitHub.
+pub struct GitHubNotifier {
+    client: Client,
+    token: Secret,
+    repo: Option<String>,
+    label: String,
+    title: String,
+    gist: Option<String>,
+    /// The status issue, once known.
+    issue: Mutex<Option<u64>>,
+    target: String,
+}
+
+impl GitHubNotifier {
+    pub fn new(client: &Client, config: &GitHubConfig) -> Self {
+        let target = match (&config.gist, &config.repo) {
+            (Some(gist), _) => format!("gist:{gist}"),
+            (None, repo) => format!("github:{}", repo.as_deref().unwrap_or_default()),
+        };
+        GitHubNotifier {
+            client: client.clone(),
+            token: config.token.clone(),
+            repo: config.repo.clone(),
+            label: config.label.clone(),
+            title: config.title.clone(),
+            gist: config.gist.clone(),
+            issue: Mutex::new(config.issue),
+            target,
+        }
+    }
+
+    fn request(&self, builder: RequestBuilder) -> RequestBuilder {
+        builder
+            .bearer_auth(self.token.expose())
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("User-Agent", "global-entry-appointment-finder")
+    }
+
+    /// The configured issue, else the open one carrying our label, else a new one.
+    async fn issue(&self, repo: &str, body: &str) -> Result<(u64, bool), AppError> {
+        let mut issue = self.issue.lock().await;
+        if let Some(number) = *issue {
+            return Ok((number, false));
+        }
+
+        #[derive(Deserialize)]
+        struct Issue {
+            number: u64,
+        }
+
+        let open: Vec<Issue> = self
+            .request(self.client.get(format!("{API}/repos/{repo}/issues")))
+            .query(&[("labels", self.label.as_str()), ("state", "open"), ("per_page", "1")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(found) = open.first() {
+            *issue = Some(found.number);
+            return Ok((found.number, false));
+        }
+        let created: Issue = self
+            .request(self.client.post(format!("{API}/repos/{repo}/issues")))
+            .json(&json!({ "title": self.title, "body": body, "labels": [self.label] }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        info!("Opened status issue {repo}#{}", created.number);
+        *issue = Some(created.number);
+        Ok((created.number, true))
+    }
+}
+
+impl Notifier for GitHubNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "github"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        // Slack's *bold* is italic in GitHub Markdown.
+        format!(
+            "{}\n\n_Updated {}_\n",
+            text.trim_end().replace('*', "**"),
+            clock::local_now().format("%Y-%m-%d %H:%M %Z")
+        )
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<bool, AppError>> {
+        async move {
+            let url = match (&self.gist, &self.repo) {
+                (Some(gist), _) => format!("{API}/gists/{gist}"),
+                (None, repo) => format!("{API}/repos/{}", repo.as_deref().unwrap_or_default()),
+            };
+            self.request(self.client.get(url)).send().await?.error_for_status()?;
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            let body = self.render(subject, text);
+            if let Some(gist) = &self.gist {
+                self.request(self.client.patch(format!("{API}/gists/{gist}")))
+                    .json(&json!({ "files": { "availability.md": { "content": body } } }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            } else if let Some(repo) = &self.repo {
+                let (number, created) = self.issue(repo, &body).await?;
+                if !created {
+                    self.request(self.client.patch(format!("{API}/repos/{repo}/issues/{number}")))
+                        .json(&json!({ "body": body }))
+                        .send()
+                        .await?
+                        .error_for_status()?;
+                }
+            }
+            Ok(None)
+        }
+        .boxed()
+    }
+}
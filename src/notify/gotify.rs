@@ -0,0 +1,84 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{plain_text, Delivery, Notifier, TlsOptions};
+use crate::{redact::Secret, AppError};
+
+#[derive(Debug, Deserialize)]
+pub struct GotifyConfig {
+    /// Base URL of the Gotify server, e.g. `https://gotify.example.com`.
+    pub server: String,
+    /// Token of the Gotify application alerts are posted as.
+    pub token: Secret,
+    /// Gotify priority; clients typically alert loudly from 8.
+    #[serde(default = "default_priority")]
+    pub priority: u8,
+    #[serde(default)]
+    pub tls: TlsOptions,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_priority() -> u8 {
+    5
+}
+
+/// Posts alerts as messages to a Gotify server.
+pub struct GotifyNotifier {
+    client: Client,
+    endpoint: String,
+    token: Secret,
+    priority: u8,
+    target: String,
+}
+
+impl GotifyNotifier {
+    pub fn new(client: Client, config: &GotifyConfig) -> Self {
+        let server = config.server.trim_end_matches('/');
+        GotifyNotifier {
+            client,
+            endpoint: format!("{server}/message"),
+            token: config.token.clone(),
+            priority: config.priority,
+            target: format!("gotify:{server}"),
+        }
+    }
+}
+
+impl Notifier for GotifyNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "gotify"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        plain_text(text, usize::MAX)
+    }
+
+    fn title(&self, subject: &str) -> Option<String> {
+        Some(subject.to_string())
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            let payload = serde_json::json!({
+                "title": self.title(subject),
+                "message": self.render(subject, text),
+                "priority": self.priority,
+            });
+            self.client
+                .post(&self.endpoint)
+                .header("X-Gotify-Key", self.token.expose())
+                .json(&payload)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(None)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,83 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{plain_text, Delivery, Notifier, TlsOptions};
+use crate::{redact::Secret, AppError};
+
+#[derive(Debug, Deserialize)]
+pub struct MatrixConfig {
+    /// Webhook URL from a bridge such as matrix-hookshot or t2bot.io.
+    pub url: Secret,
+    /// Sent as a bearer token, for bridges that want one besides the URL.
+    #[serde(default)]
+    pub token: Option<Secret>,
+    /// Sender name shown in the room, where the bridge allows one.
+    #[serde(default = "default_display_name")]
+    pub display_name: String,
+    #[serde(default)]
+    pub tls: TlsOptions,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_display_name() -> String {
+    "Appointment finder".to_string()
+}
+
+/// Posts alerts into a Matrix room through a generic webhook bridge.
+pub struct MatrixNotifier {
+    client: Client,
+    url: Secret,
+    token: Option<Secret>,
+    display_name: String,
+    target: String,
+}
+
+impl MatrixNotifier {
+    pub fn new(client: Client, config: &MatrixConfig) -> Result<Self, AppError> {
+        let url = reqwest::Url::parse(config.url.expose())
+            .map_err(|e| AppError::General(format!("matrix: invalid url: {e}")))?;
+        Ok(MatrixNotifier {
+            client,
+            url: config.url.clone(),
+            token: config.token.clone(),
+            display_name: config.display_name.clone(),
+            // The URL usually embeds the webhook's secret; name only the host.
+            target: format!("matrix:{}", url.host_str().unwrap_or_default()),
+        })
+    }
+}
+
+impl Notifier for MatrixNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "matrix"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        plain_text(text, usize::MAX)
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            // `text`/`format`/`displayName` cover both hookshot and t2bot-style bridges.
+            let payload = serde_json::json!({
+                "text": self.render(subject, text),
+                "format": "plain",
+                "displayName": self.display_name,
+                "username": self.display_name,
+            });
+            let mut request = self.client.post(self.url.expose()).json(&payload);
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token.expose());
+            }
+            request.send().await?.error_for_status()?;
+            Ok(None)
+        }
+        .boxed()
+    }
+}
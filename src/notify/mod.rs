@@ -0,0 +1,1332 @@
+//! Notification channels. The dispatcher decides once per cycle which slot
+//! events to announce, then hands the same alert to every channel, each either
+//! in real time or collected into a daily digest.
+
+mod bark;
+#[cfg(feature = "email")]
+mod email;
+mod escalation;
+mod github;
+mod gotify;
+mod matrix;
+#[cfg(feature = "plugins")]
+mod plugin;
+mod policy;
+#[cfg(feature = "pubsub")]
+mod pubsub;
+mod pushcut;
+#[cfg(feature = "scripting")]
+mod script;
+mod signal;
+#[cfg(feature = "sns")]
+mod sns;
+mod whatsapp;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use futures::{
+    future::{join_all, BoxFuture},
+    FutureExt,
+};
+use reqwest::{Certificate, Client};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    clock,
+    dns::Dns,
+    format::{self, DisplayOptions, Verbosity, VerbosityRules},
+    history::History,
+    slack,
+    state::{Board, DigestLine, Pending, StateStore},
+    AppError, FetchedLocation, JeffConfig,
+};
+
+pub use bark::{BarkConfig, BarkNotifier};
+#[cfg(feature = "email")]
+pub use email::{EmailConfig, EmailNotifier};
+pub use escalation::{EscalationConfig, Escalator};
+pub use github::{GitHubConfig, GitHubNotifier};
+pub use gotify::{GotifyConfig, GotifyNotifier};
+pub use matrix::{MatrixConfig, MatrixNotifier};
+#[cfg(feature = "plugins")]
+pub use plugin::PluginNotifier;
+pub use policy::{Action, EventKind, PolicyConfig};
+#[cfg(feature = "pubsub")]
+pub use pubsub::{PubSubConfig, PubSubNotifier};
+pub use pushcut::{PushcutConfig, PushcutNotifier};
+#[cfg(feature = "scripting")]
+pub use script::ScriptNotifier;
+pub use signal::{SignalConfig, SignalNotifier};
+#[cfg(feature = "sns")]
+pub use sns::{SnsConfig, SnsNotifier};
+pub use whatsapp::{WhatsAppConfig, WhatsAppNotifier};
+
+/// Where push notifications send people to book by default.
+pub const BOOKING_URL: &str = "https://ttp.cbp.dhs.gov/";
+
+/// Push services show a few lines at most and cap payload size.
+const PUSH_MAX_CHARS: usize = 1000;
+
+pub trait Notifier: Send + Sync {
+    /// Where messages go, e.g. a Slack channel ID; recorded in history and state.
+    fn target(&self) -> &str;
+
+    /// The channel's name in the config, e.g. `slack` or `email`.
+    fn kind(&self) -> &'static str;
+
+    /// The message text as `send` delivers it, so `preview` shows the same.
+    fn render(&self, _subject: &str, text: &str) -> String {
+        text.to_string()
+    }
+
+    /// The title `send` puts above the message, for channels that have one.
+    fn title(&self, _subject: &str) -> Option<String> {
+        None
+    }
+
+    /// Delivers `text`, returning the message `ts` if it can receive reactions.
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>>;
+
+    /// Delivers `text` with `file` attached if this channel attaches files
+    /// to alerts, otherwise just `text`.
+    fn send_with<'a>(
+        &'a self,
+        subject: &'a str,
+        text: &'a str,
+        _file: Option<&'a Attachment>,
+    ) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        self.send(subject, text)
+    }
+
+    /// Confirms without sending anything that the channel's credentials
+    /// work, returning `false` if it has no way to.
+    fn check(&self) -> BoxFuture<'_, Result<bool, AppError>> {
+        async { Ok(false) }.boxed()
+    }
+
+    /// Replaces the text of message `id`, as returned by `send`, returning
+    /// `false` if this channel can't edit messages.
+    fn edit<'a>(&'a self, _id: &'a str, _text: &'a str) -> BoxFuture<'a, Result<bool, AppError>> {
+        async { Ok(false) }.boxed()
+    }
+
+    /// Delivers `file` with a caption, returning `false` if this channel
+    /// can't take files.
+    #[cfg(feature = "heatmap")]
+    fn send_file<'a>(&'a self, _file: &'a Attachment, _caption: &'a str) -> BoxFuture<'a, Result<bool, AppError>> {
+        async { Ok(false) }.boxed()
+    }
+}
+
+/// A file for the channels that can take one, such as a heatmap image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    /// Base64 when kept in the state file's outbox.
+    #[serde(with = "base64_bytes")]
+    pub bytes: Vec<u8>,
+}
+
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        STANDARD.decode(String::deserialize(d)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// When a channel receives alerts.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    /// As soon as a cycle finds something.
+    #[default]
+    Realtime,
+    /// Once a day at `hour` (local), listing every slot seen since the last
+    /// digest, or with `lookback_hours`, every slot the history saw in that
+    /// many hours before `hour`.
+    DailyDigest {
+        #[serde(default = "default_digest_hour")]
+        hour: u32,
+        #[serde(default)]
+        lookback_hours: Option<u32>,
+    },
+    /// One message edited every cycle to show what's available now, replaced
+    /// by a new one once it's `max_age_hours` old. Slack only.
+    Board {
+        #[serde(default = "default_board_max_age_hours")]
+        max_age_hours: u32,
+    },
+}
+
+fn default_digest_hour() -> u32 {
+    8
+}
+
+fn default_board_max_age_hours() -> u32 {
+    24
+}
+
+/// One cycle's alert, rendered once per `verbosity` preset in use and shared
+/// by all channels.
+pub struct Alert {
+    pub text: String,
+    pub slots: Vec<String>,
+    /// Text and slots for presets other than `normal` that a channel uses.
+    pub variants: BTreeMap<Verbosity, (String, Vec<String>)>,
+    pub lines: Vec<DigestLine>,
+    /// The alerted slots as a CSV export, when a channel attaches one.
+    pub csv: Option<Attachment>,
+    pub kind: EventKind,
+}
+
+impl Alert {
+    /// Heads every rendering with whom the alert is for.
+    pub fn tag(&mut self, name: &str) {
+        let tag = format!("*For {name}*\n");
+        self.text.insert_str(0, &tag);
+        for (text, _) in self.variants.values_mut() {
+            text.insert_str(0, &tag);
+        }
+    }
+
+    /// The text and slots rendered for `verbosity`, or the `normal` ones if
+    /// it wasn't rendered.
+    pub fn rendered(&self, verbosity: Verbosity) -> (&str, &[String]) {
+        match self.variants.get(&verbosity) {
+            Some((text, slots)) => (text, slots),
+            None => (&self.text, &self.slots),
+        }
+    }
+}
+
+/// Posts to one Slack channel. With several channels, their notifiers share
+/// `throttle`, so posts across all of them stay within Slack's rate limit.
+pub struct SlackNotifier {
+    client: Client,
+    token: String,
+    channel: String,
+    throttle: Arc<slack::Throttle>,
+}
+
+impl Notifier for SlackNotifier {
+    fn target(&self) -> &str {
+        &self.channel
+    }
+
+    fn kind(&self) -> &'static str {
+        "slack"
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            self.throttle.wait().await;
+            let text = self.render(subject, text);
+            Ok(Some(slack::post_to_slack(self.client.clone(), &self.token, &self.channel, &text).await?))
+        }
+        .boxed()
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<bool, AppError>> {
+        async move {
+            slack::auth_test(&self.client, &self.token).await?;
+            slack::verify_channel(&self.client, &self.token, &self.channel).await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    fn edit<'a>(&'a self, id: &'a str, text: &'a str) -> BoxFuture<'a, Result<bool, AppError>> {
+        async move {
+            self.throttle.wait().await;
+            slack::update_message(&self.client, &self.token, &self.channel, id, text).await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    #[cfg(feature = "heatmap")]
+    fn send_file<'a>(&'a self, file: &'a Attachment, caption: &'a str) -> BoxFuture<'a, Result<bool, AppError>> {
+        async move {
+            self.throttle.wait().await;
+            slack::upload_file(&self.client, &self.token, &self.channel, file, caption).await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+}
+
+/// Certificate checks for self-hosted servers.
+#[derive(Debug, Default, Deserialize)]
+pub struct TlsOptions {
+    /// PEM file with an extra trusted root, e.g. a home-lab CA.
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>,
+    /// Accept any certificate, even an expired or self-signed one.
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+impl TlsOptions {
+    /// `shared` when the defaults apply, otherwise a client with these options
+    /// (and the same DNS settings).
+    fn client(&self, shared: &Client, dns: &Dns, what: &str) -> Result<Client, AppError> {
+        if self.ca_cert.is_none() && !self.insecure_skip_verify {
+            return Ok(shared.clone());
+        }
+        let mut builder = dns.builder();
+        if let Some(path) = &self.ca_cert {
+            let pem = std::fs::read(path)
+                .map_err(|e| AppError::General(format!("{what}: tls.ca_cert {}: {e}", path.display())))?;
+            let cert = Certificate::from_pem(&pem)
+                .map_err(|e| AppError::General(format!("{what}: tls.ca_cert {}: {e}", path.display())))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        if self.insecure_skip_verify {
+            warn!("{what}: TLS certificate verification is disabled");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+struct Channel {
+    notifier: Box<dyn Notifier>,
+    delivery: Delivery,
+}
+
+impl Channel {
+    fn matches(&self, route: &str) -> bool {
+        self.notifier.kind() == route || self.notifier.target() == route
+    }
+}
+
+#[derive(Default)]
+pub struct Dispatcher {
+    channels: Vec<Channel>,
+    escalator: Option<Escalator>,
+    /// How long an alert identical to a channel's last one is held back.
+    repeat_window: Option<Duration>,
+    policy: Option<PolicyConfig>,
+    verbosity: VerbosityRules,
+    /// Routes that get an applicant's own alerts rather than the general ones.
+    claimed: Vec<String>,
+    /// Outbox alerts being sent right now, by target and when they were
+    /// queued, which [`Dispatcher::resend_pending`] leaves to their sender.
+    sending: Mutex<BTreeSet<(String, DateTime<Utc>)>>,
+}
+
+impl Dispatcher {
+    pub fn from_config(client: &Client, dns: &Dns, config: &JeffConfig) -> Result<Self, AppError> {
+        let mut dispatcher = Dispatcher {
+            repeat_window: (config.repeat_window_minutes > 0)
+                .then(|| Duration::minutes(i64::try_from(config.repeat_window_minutes).unwrap_or(i64::MAX))),
+            policy: config.notification_policy.clone(),
+            verbosity: config.display.verbosity.clone(),
+            claimed: config.application_status.iter().flat_map(|account| account.notify.clone()).collect(),
+            ..Dispatcher::default()
+        };
+        if config.enable_slack {
+            let throttle = Arc::new(slack::Throttle::default());
+            for channel in &config.slack_channel_id {
+                dispatcher.add(
+                    Box::new(SlackNotifier {
+                        client: client.clone(),
+                        token: config.slack_token.expose().to_string(),
+                        channel: channel.clone(),
+                        throttle: Arc::clone(&throttle),
+                    }),
+                    config.slack_delivery,
+                );
+            }
+        }
+        #[cfg(feature = "email")]
+        if let Some(email) = &config.email {
+            dispatcher.add(Box::new(EmailNotifier::new(email)?), email.delivery);
+        }
+        if let Some(bark) = &config.bark {
+            dispatcher.add(Box::new(BarkNotifier::new(client, bark)), bark.delivery);
+        }
+        if let Some(pushcut) = &config.pushcut {
+            dispatcher.add(Box::new(PushcutNotifier::new(client, pushcut)), pushcut.delivery);
+        }
+        if let Some(gotify) = &config.gotify {
+            let client = gotify.tls.client(client, dns, "gotify")?;
+            dispatcher.add(Box::new(GotifyNotifier::new(client, gotify)), gotify.delivery);
+        }
+        if let Some(matrix) = &config.matrix {
+            let client = matrix.tls.client(client, dns, "matrix")?;
+            dispatcher.add(Box::new(MatrixNotifier::new(client, matrix)?), matrix.delivery);
+        }
+        if let Some(signal) = &config.signal {
+            let client = signal.tls.client(client, dns, "signal")?;
+            dispatcher.add(Box::new(SignalNotifier::new(client, signal)), signal.delivery);
+        }
+        if let Some(whatsapp) = &config.whatsapp {
+            let client = whatsapp.tls.client(client, dns, "whatsapp")?;
+            for to in &whatsapp.recipients {
+                dispatcher.add(Box::new(WhatsAppNotifier::new(client.clone(), whatsapp, to)), whatsapp.delivery);
+            }
+        }
+        #[cfg(feature = "sns")]
+        if let Some(sns) = &config.sns {
+            let client = sns.tls.client(client, dns, "sns")?;
+            dispatcher.add(Box::new(SnsNotifier::new(client, sns)?), sns.delivery);
+        }
+        #[cfg(feature = "pubsub")]
+        if let Some(pubsub) = &config.pubsub {
+            let client = pubsub.tls.client(client, dns, "pubsub")?;
+            dispatcher.add(Box::new(PubSubNotifier::new(client, pubsub)), pubsub.delivery);
+        }
+        if let Some(github) = &config.github {
+            dispatcher.add(Box::new(GitHubNotifier::new(client, github)), github.delivery);
+        }
+        #[cfg(feature = "scripting")]
+        if let (Some(script), Some(loaded)) = (&config.script, &config.loaded_script) {
+            if loaded.has_notify() {
+                dispatcher.add(Box::new(ScriptNotifier::new(Arc::clone(loaded))), script.delivery);
+            }
+        }
+        #[cfg(feature = "plugins")]
+        if let Some(plugins) = &config.plugins {
+            for plugin in config.loaded_plugins.iter().filter(|p| p.has_notify()) {
+                dispatcher.add(Box::new(PluginNotifier::new(Arc::clone(plugin))), plugins.delivery);
+            }
+        }
+        if config.escalation.is_some() {
+            dispatcher.escalator = Some(Escalator::new(client));
+        }
+        if let Some(policy) = &dispatcher.policy {
+            if let Some(key) = policy.channels().find(|key| !dispatcher.routes_to(key)) {
+                return Err(AppError::Config(format!(
+                    "notification_policy: rules name {key:?}, which isn't a configured channel"
+                )));
+            }
+        }
+        if let Some(key) = dispatcher.verbosity.channels().find(|key| !dispatcher.routes_to(key)) {
+            return Err(AppError::Config(format!("verbosity: {key:?} isn't a configured channel")));
+        }
+        Ok(dispatcher)
+    }
+
+    pub fn add(&mut self, notifier: Box<dyn Notifier>, delivery: Delivery) {
+        self.channels.push(Channel { notifier, delivery });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    fn action(&self, event: EventKind, channel: &Channel) -> Action {
+        self.policy.as_ref().map_or(Action::Send, |policy| {
+            policy.action(event, channel.notifier.kind(), channel.notifier.target())
+        })
+    }
+
+    fn verbosity(&self, channel: &Channel) -> Verbosity {
+        self.verbosity.for_channel(channel.notifier.kind(), channel.notifier.target())
+    }
+
+    /// Sends `alert` to real-time channels and queues it for digest channels,
+    /// returning how many real-time channels failed. Channels an applicant
+    /// claims with `notify` are left to [`Dispatcher::dispatch_to`].
+    pub async fn dispatch(
+        &self,
+        alert: &Alert,
+        subject: &str,
+        state: &Mutex<StateStore>,
+        history: &Mutex<History>,
+    ) -> usize {
+        let channels = self.channels.iter().filter(|c| !self.claimed.iter().any(|route| c.matches(route)));
+        self.dispatch_on(channels, alert, subject, state, history).await
+    }
+
+    /// [`Dispatcher::dispatch`] to just the channels matching `routes`, such
+    /// as one applicant's.
+    pub async fn dispatch_to(
+        &self,
+        routes: &[String],
+        alert: &Alert,
+        subject: &str,
+        state: &Mutex<StateStore>,
+        history: &Mutex<History>,
+    ) -> usize {
+        let channels = self.channels.iter().filter(|c| routes.iter().any(|route| c.matches(route)));
+        self.dispatch_on(channels, alert, subject, state, history).await
+    }
+
+    async fn dispatch_on<'a>(
+        &'a self,
+        channels: impl Iterator<Item = &'a Channel>,
+        alert: &Alert,
+        subject: &str,
+        state: &Mutex<StateStore>,
+        history: &Mutex<History>,
+    ) -> usize {
+        let channels: Vec<&Channel> = channels.collect();
+        // Whether this alert is a repeat is decided once for all channels,
+        // however each renders it: if any got it within `repeat_window`,
+        // none get it again, save one whose last alert is still in the
+        // outbox, as it never got this one.
+        let hash = content_hash(&alert.text);
+        let repeat = self.repeat_window.and_then(|window| {
+            let store = state.lock().unwrap();
+            channels
+                .iter()
+                .filter(|c| matches!(c.delivery, Delivery::Realtime))
+                .filter_map(|c| store.last_sent(c.notifier.target(), &hash))
+                .filter(|at| clock::now() - *at < window)
+                .max()
+        });
+        if let Some(at) = repeat {
+            info!("Not sending alerts live: same alert as at {}", at.with_timezone(&Local).format("%H:%M"));
+        }
+        let mut failed = 0;
+        for channel in channels {
+            let target = channel.notifier.target();
+            let (text, slots) = alert.rendered(self.verbosity(channel));
+            match self.action(alert.kind, channel) {
+                Action::Send => {}
+                Action::Queue => {
+                    let mut store = state.lock().unwrap();
+                    store.queue_digest(target, alert.lines.clone());
+                    if let Err(e) = store.save() {
+                        error!("Error saving state: {e}");
+                    }
+                    continue;
+                }
+                Action::Suppress => {
+                    info!("Not notifying {target}: notification_policy suppresses this alert");
+                    continue;
+                }
+            }
+            match channel.delivery {
+                Delivery::Realtime => {
+                    if repeat.is_some() && !state.lock().unwrap().outbox().iter().any(|p| p.target == target) {
+                        continue;
+                    }
+                    // Kept until the channel takes it, in case we crash or it fails.
+                    let queued_at = clock::now();
+                    self.sending.lock().unwrap().insert((target.to_string(), queued_at));
+                    {
+                        let mut store = state.lock().unwrap();
+                        store.queue_outbox(Pending {
+                            target: target.to_string(),
+                            subject: subject.to_string(),
+                            text: text.to_string(),
+                            slots: slots.to_vec(),
+                            file: alert.csv.clone(),
+                            hash: hash.clone(),
+                            queued_at,
+                        });
+                        if let Err(e) = store.save() {
+                            error!("Error saving state: {e}");
+                        }
+                    }
+                    match channel.notifier.send_with(subject, text, alert.csv.as_ref()).await {
+                        Ok(ts) => {
+                            if let Err(e) = history.lock().unwrap().record_alert(target, slots) {
+                                error!("Error recording alert: {e}");
+                            }
+                            let mut store = state.lock().unwrap();
+                            store.mark_delivered(target, queued_at);
+                            if let Some(ts) = ts {
+                                store.record_alert(target, &ts, slots.to_vec());
+                            }
+                            if self.repeat_window.is_some() {
+                                store.record_sent(target, hash.clone(), clock::now());
+                            }
+                            if let Err(e) = store.save() {
+                                error!("Error saving state: {e}");
+                            }
+                        }
+                        Err(e) => {
+                            error!("Error notifying {target}: {e}");
+                            failed += 1;
+                        }
+                    }
+                    self.sending.lock().unwrap().remove(&(target.to_string(), queued_at));
+                }
+                // Read back from the history when it's due.
+                Delivery::DailyDigest {
+                    lookback_hours: Some(_),
+                    ..
+                } => {}
+                Delivery::DailyDigest { lookback_hours: None, .. } => {
+                    let mut store = state.lock().unwrap();
+                    store.queue_digest(target, alert.lines.clone());
+                    if let Err(e) = store.save() {
+                        error!("Error saving state: {e}");
+                    }
+                }
+                Delivery::Board { max_age_hours } => {
+                    let max_age = Duration::hours(i64::from(max_age_hours));
+                    let rendered = (text, slots);
+                    if let Err(e) = self.update_board(channel.notifier.as_ref(), max_age, rendered, subject, state, history).await {
+                        error!("Error updating board in {target}: {e}");
+                        failed += 1;
+                    }
+                }
+            }
+        }
+        failed
+    }
+
+    /// Sends the real-time alerts queued but never delivered, by a previous
+    /// run or an earlier cycle whose send failed, with a note of when they
+    /// were found. Those older than `max_age` are dropped instead, as their
+    /// slots have likely gone, and so are those for channels no longer
+    /// configured. One that fails again is kept for the next cycle; one still
+    /// being sent is left alone.
+    pub async fn resend_pending(&self, max_age: Duration, state: &Mutex<StateStore>, history: &Mutex<History>) {
+        let pending = state.lock().unwrap().outbox().to_vec();
+        for alert in pending {
+            let target = alert.target.as_str();
+            let key = (alert.target.clone(), alert.queued_at);
+            if !self.sending.lock().unwrap().insert(key.clone()) {
+                continue;
+            }
+            let found = alert.queued_at.with_timezone(&Local).format("%H:%M");
+            let channel = self
+                .channels
+                .iter()
+                .find(|c| c.notifier.target() == target && matches!(c.delivery, Delivery::Realtime));
+            match channel {
+                None => info!("Dropping undelivered alert for {target}: no longer a real-time channel"),
+                Some(_) if clock::now() - alert.queued_at > max_age => {
+                    warn!(
+                        "Dropping undelivered alert for {target} from {found}: \
+                         older than outbox_max_age_minutes or max_result_age_minutes"
+                    );
+                }
+                Some(channel) => {
+                    let text = format!("{}\n\n_Delayed: found at {found}._", alert.text.trim_end());
+                    match channel.notifier.send_with(&alert.subject, &text, alert.file.as_ref()).await {
+                        Ok(ts) => {
+                            info!("Sent undelivered alert from {found} to {target}");
+                            if let Err(e) = history.lock().unwrap().record_alert(target, &alert.slots) {
+                                error!("Error recording alert: {e}");
+                            }
+                            let mut store = state.lock().unwrap();
+                            if let Some(ts) = ts {
+                                store.record_alert(target, &ts, alert.slots.clone());
+                            }
+                            // As sent live, so `repeat_window` holds back the same alert next cycle.
+                            if self.repeat_window.is_some() {
+                                let hash = if alert.hash.is_empty() { content_hash(&alert.text) } else { alert.hash.clone() };
+                                store.record_sent(target, hash, clock::now());
+                            }
+                        }
+                        Err(e) => {
+                            error!("Error resending alert to {target}: {e}");
+                            self.sending.lock().unwrap().remove(&key);
+                            continue;
+                        }
+                    }
+                }
+            }
+            let mut store = state.lock().unwrap();
+            store.mark_delivered(target, alert.queued_at);
+            if let Err(e) = store.save() {
+                error!("Error saving state: {e}");
+            }
+            self.sending.lock().unwrap().remove(&key);
+        }
+    }
+
+    /// Edits `notifier`'s board to show an alert's `text` and `slots`, or
+    /// posts a new board if there is none yet, it's older than `max_age`, or
+    /// it can't be edited (say, it was deleted).
+    async fn update_board(
+        &self,
+        notifier: &dyn Notifier,
+        max_age: Duration,
+        (text, slots): (&str, &[String]),
+        subject: &str,
+        state: &Mutex<StateStore>,
+        history: &Mutex<History>,
+    ) -> Result<(), AppError> {
+        let target = notifier.target();
+        let hash = content_hash(text);
+        let board = state.lock().unwrap().board(target).cloned();
+        let board = board.filter(|b| clock::now() - b.posted_at < max_age);
+        if board.as_ref().is_some_and(|b| b.hash == hash) {
+            return Ok(());
+        }
+        let edited = match &board {
+            Some(board) => match notifier.edit(&board.ts, text).await {
+                Ok(edited) => edited.then(|| board.clone()),
+                Err(e) => {
+                    warn!("Couldn't edit board in {target} ({e}); posting a new one");
+                    None
+                }
+            },
+            None => None,
+        };
+        let board = match edited {
+            Some(board) => board,
+            None => {
+                let Some(ts) = notifier.send(subject, text).await? else {
+                    return Ok(());
+                };
+                info!("Posted a new availability board to {target}");
+                Board {
+                    ts,
+                    posted_at: clock::now(),
+                    hash: String::new(),
+                }
+            }
+        };
+        history.lock().unwrap().record_alert(target, slots)?;
+        let mut store = state.lock().unwrap();
+        store.record_alert(target, &board.ts, slots.to_vec());
+        store.set_board(
+            target,
+            Board { hash, ..board },
+        );
+        store.save()
+    }
+
+    /// What each channel, or each of `kinds` if any are given, would receive
+    /// for `alert`: `(kind, target, message)`. Digest channels show the
+    /// digest the alert would end up in.
+    pub fn preview(
+        &self,
+        alert: &Alert,
+        subject: &str,
+        kinds: &[String],
+        display: &DisplayOptions,
+    ) -> Vec<(&'static str, String, String)> {
+        self.channels
+            .iter()
+            .filter(|channel| kinds.is_empty() || kinds.iter().any(|k| k == channel.notifier.kind()))
+            .map(|channel| {
+                let notifier = &channel.notifier;
+                let show = |subject: &str, text: &str| {
+                    let text = notifier.render(subject, text);
+                    match notifier.title(subject) {
+                        Some(title) => format!("{title}\n{text}"),
+                        None => text,
+                    }
+                };
+                let message = match channel.delivery {
+                    Delivery::Realtime | Delivery::Board { .. } => {
+                        show(subject, alert.rendered(self.verbosity(channel)).0)
+                    }
+                    Delivery::DailyDigest { .. } => {
+                        show("Appointment digest", &render_digest(&alert.lines, None, display))
+                    }
+                };
+                (notifier.kind(), notifier.target().to_string(), message)
+            })
+            .collect()
+    }
+
+    /// Opens or resolves incidents for critical slots, if escalation is configured.
+    pub async fn escalate(
+        &self,
+        config: &JeffConfig,
+        found: &[FetchedLocation],
+        scanned_ok: &[(&'static str, NaiveDate)],
+        state: &Mutex<StateStore>,
+    ) {
+        if let Some(escalator) = &self.escalator {
+            escalator.run(config, found, scanned_ok, state).await;
+        }
+    }
+
+    /// Opens an incident for `item`'s slot whatever `escalation`'s filters say,
+    /// if escalation is configured.
+    pub async fn page(&self, config: &JeffConfig, item: &FetchedLocation, state: &Mutex<StateStore>) {
+        if let (Some(escalator), Some(esc)) = (&self.escalator, &config.escalation) {
+            escalator.open(esc, item, &config.display, state).await;
+        }
+    }
+
+    /// Resolves the incident for `slot`, if one is open.
+    pub async fn unpage(&self, config: &JeffConfig, slot: &str, state: &Mutex<StateStore>) {
+        let open = state.lock().unwrap().escalated().contains_key(slot);
+        if let (true, Some(escalator), Some(esc)) = (open, &self.escalator, &config.escalation) {
+            escalator.close(esc, slot, state).await;
+        }
+    }
+
+    /// Sends `file` to every channel that can take files, whatever its
+    /// delivery; failures are only logged.
+    #[cfg(feature = "heatmap")]
+    pub async fn send_file(&self, file: &Attachment, caption: &str) {
+        for channel in &self.channels {
+            let target = channel.notifier.target();
+            match channel.notifier.send_file(file, caption).await {
+                Ok(true) => info!("Sent {} to {target}", file.filename),
+                Ok(false) => tracing::debug!("{target} can't take files; not sending {}", file.filename),
+                Err(e) => error!("Error sending {} to {target}: {e}", file.filename),
+            }
+        }
+    }
+
+    /// Sends an operational message to every channel right away, whatever its
+    /// delivery; failures are only logged.
+    pub async fn notice(&self, subject: &str, text: &str) {
+        self.notice_to(&[], subject, text).await;
+    }
+
+    /// [`Dispatcher::notice`] to just the channels matching `routes`, each a
+    /// kind or a target, or to every channel if `routes` is empty.
+    pub async fn notice_to(&self, routes: &[String], subject: &str, text: &str) {
+        for channel in self.channels.iter().filter(|c| routes.is_empty() || routes.iter().any(|r| c.matches(r))) {
+            if let Err(e) = channel.notifier.send(subject, text).await {
+                error!("Error notifying {}: {e}", channel.notifier.target());
+            }
+        }
+    }
+
+    /// [`Dispatcher::notice`] for an `event` that `notification_policy` may
+    /// queue or suppress on some channels.
+    pub async fn notify(&self, event: EventKind, subject: &str, text: &str, state: &Mutex<StateStore>) {
+        for channel in &self.channels {
+            let target = channel.notifier.target();
+            match self.action(event, channel) {
+                Action::Send => {
+                    if let Err(e) = channel.notifier.send(subject, text).await {
+                        error!("Error notifying {target}: {e}");
+                    }
+                }
+                Action::Queue => {
+                    let line = DigestLine {
+                        slot: String::new(),
+                        line: format!("*{subject}*: {text}"),
+                    };
+                    let mut store = state.lock().unwrap();
+                    store.queue_digest(target, vec![line]);
+                    if let Err(e) = store.save() {
+                        error!("Error saving state: {e}");
+                    }
+                }
+                Action::Suppress => info!("Not notifying {target}: notification_policy suppresses {subject:?}"),
+            }
+        }
+    }
+
+    /// Whether any channel has `route` as its kind or target.
+    pub fn routes_to(&self, route: &str) -> bool {
+        self.channels.iter().any(|c| c.matches(route))
+    }
+
+    /// Each channel's kind, target and [`Notifier::check`] result.
+    pub async fn check(&self) -> Vec<(&'static str, &str, Result<bool, AppError>)> {
+        join_all(self.channels.iter().map(|channel| async {
+            let notifier = &channel.notifier;
+            (notifier.kind(), notifier.target(), notifier.check().await)
+        }))
+        .await
+    }
+
+    pub fn has_digests(&self) -> bool {
+        self.channels.iter().any(|c| matches!(c.delivery, Delivery::DailyDigest { .. }))
+            || self.policy.as_ref().is_some_and(PolicyConfig::queues)
+    }
+
+    /// Sends each digest channel's slots if its daily delivery is due, and
+    /// what `notification_policy` queued for other channels at its
+    /// `queue_hour`. A channel that fails doesn't hold up the rest; the
+    /// error names every one that did.
+    pub async fn flush_digests(
+        &self,
+        display: &DisplayOptions,
+        state: &Mutex<StateStore>,
+        history: &Mutex<History>,
+    ) -> Result<(), AppError> {
+        let mut failed = Vec::new();
+        for channel in &self.channels {
+            let (hour, lookback_hours) = match (channel.delivery, &self.policy) {
+                (Delivery::DailyDigest { hour, lookback_hours }, _) => (hour, lookback_hours),
+                (_, Some(policy)) if policy.queues() => (policy.queue_hour, None),
+                _ => continue,
+            };
+            if let Err(e) = self.flush_digest(channel, hour, lookback_hours, display, state, history).await {
+                failed.push(format!("{}: {e}", channel.notifier.target()));
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::General(failed.join("; ")))
+        }
+    }
+
+    async fn flush_digest(
+        &self,
+        channel: &Channel,
+        hour: u32,
+        lookback_hours: Option<u32>,
+        display: &DisplayOptions,
+        state: &Mutex<StateStore>,
+        history: &Mutex<History>,
+    ) -> Result<(), AppError> {
+        let target = channel.notifier.target();
+        let Some(due) = last_daily(clock::local_now(), hour) else {
+            return Ok(());
+        };
+        if state.lock().unwrap().last_digest(target).is_some_and(|last| last >= due) {
+            return Ok(());
+        }
+        let (lines, since) = match lookback_hours {
+            Some(hours) => {
+                let since = due - Duration::hours(i64::from(hours));
+                let found = history.lock().unwrap().seen_since(since)?;
+                let mut store = state.lock().unwrap();
+                let found = found.into_iter().filter(|item| !store.is_suppressed(&item.slot_key())).collect();
+                let mut lines: Vec<DigestLine> = format::summarize(found, display.summarize)
+                    .iter()
+                    .map(|item| DigestLine {
+                        slot: item.slot_key(),
+                        line: format::digest_line(item, display),
+                    })
+                    .collect();
+                // Slots come from the history; only queued notices are added.
+                lines.extend(store.take_digest(target).into_iter().filter(|l| l.slot.is_empty()));
+                (lines, Some(since))
+            }
+            None => {
+                let mut store = state.lock().unwrap();
+                (store.take_digest(target), store.last_digest(target))
+            }
+        };
+
+        if !lines.is_empty() {
+            let text = render_digest(&lines, since, display);
+            if let Err(e) = channel.notifier.send("Appointment digest", &text).await {
+                // Put the lines back so the next attempt includes them;
+                // a lookback digest reads its slots from the history again.
+                let lines = lines.into_iter().filter(|l| lookback_hours.is_none() || l.slot.is_empty()).collect();
+                let mut store = state.lock().unwrap();
+                store.queue_digest(target, lines);
+                store.save()?;
+                return Err(e);
+            }
+            // Queued notices have no slot.
+            let slots: Vec<String> = lines.iter().map(|l| l.slot.clone()).filter(|s| !s.is_empty()).collect();
+            history.lock().unwrap().record_alert(target, &slots)?;
+            info!("Sent digest of {} line(s) to {target}", lines.len());
+        }
+
+        let mut store = state.lock().unwrap();
+        store.set_last_digest(target, due);
+        store.save()
+    }
+}
+
+fn default_open_url() -> String {
+    BOOKING_URL.to_string()
+}
+
+/// `text` without Slack's *bold* markers, cut to at most `max` characters.
+fn plain_text(text: &str, max: usize) -> String {
+    let text = text.replace('*', "");
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// FNV-1a of `text` in hex: stable across builds, unlike `DefaultHasher`, so
+/// it can be kept in the state file.
+fn content_hash(text: &str) -> String {
+    let hash = text
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3));
+    format!("{hash:016x}")
+}
+
+/// `s` with everything but RFC 3986 unreserved characters percent-encoded.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// A `shortcuts://` URL that runs the named Shortcut with `input` as its text.
+fn shortcut_url(name: &str, input: &str) -> String {
+    format!(
+        "shortcuts://run-shortcut?name={}&input=text&text={}",
+        percent_encode(name),
+        percent_encode(input)
+    )
+}
+
+fn render_digest(lines: &[DigestLine], since: Option<DateTime<Utc>>, display: &DisplayOptions) -> String {
+    let slots = lines.iter().filter(|l| !l.slot.is_empty()).count();
+    let mut text = match since {
+        Some(since) => format!(
+            "*Daily digest*: {slots} slot(s) seen since {}\n\n",
+            display.date_time(&since.with_timezone(&Local))
+        ),
+        None => format!("*Daily digest*: {slots} slot(s) seen\n\n"),
+    };
+    for line in lines {
+        text.push_str(&format!("• {}\n", line.line));
+    }
+    text
+}
+
+/// The most recent `hour:00` local time at or before `now`.
+pub(crate) fn last_daily(now: DateTime<Local>, hour: u32) -> Option<DateTime<Utc>> {
+    let today = Local.from_local_datetime(&now.date_naive().and_hms_opt(hour, 0, 0)?).earliest()?;
+    let due = if today > now { today - Duration::days(1) } else { today };
+    Some(due.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    /// Records what it's sent, attachments as `file <name>`, and fails while
+    /// `down` is set.
+    struct Recorder {
+        target: &'static str,
+        sent: Arc<Mutex<Vec<String>>>,
+        down: Arc<AtomicBool>,
+    }
+
+    impl Notifier for Recorder {
+        fn target(&self) -> &str {
+            self.target
+        }
+
+        fn kind(&self) -> &'static str {
+            "recorder"
+        }
+
+        fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+            self.send_with(subject, text, None)
+        }
+
+        fn send_with<'a>(
+            &'a self,
+            _subject: &'a str,
+            text: &'a str,
+            file: Option<&'a Attachment>,
+        ) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+            async move {
+                if self.down.load(Ordering::Relaxed) {
+                    return Err(AppError::General(format!("{} is down", self.target)));
+                }
+                let mut sent = self.sent.lock().unwrap();
+                sent.push(text.to_string());
+                sent.extend(file.map(|file| format!("file {}", file.filename)));
+                Ok(None)
+            }
+            .boxed()
+        }
+    }
+
+    /// What a [`Recorder`] got, and the switch that takes it down.
+    pub(crate) type Handle = (Arc<Mutex<Vec<String>>>, Arc<AtomicBool>);
+
+    /// A dispatcher over one [`Recorder`] per target.
+    pub(crate) fn recorders(targets: &[&'static str]) -> (Dispatcher, Vec<Handle>) {
+        let mut dispatcher = Dispatcher::default();
+        let mut handles = Vec::new();
+        for &target in targets {
+            let (sent, down) = (Arc::default(), Arc::new(AtomicBool::new(false)));
+            let recorder = Recorder {
+                target,
+                sent: Arc::clone(&sent),
+                down: Arc::clone(&down),
+            };
+            dispatcher.add(Box::new(recorder), Delivery::Realtime);
+            handles.push((sent, down));
+        }
+        (dispatcher, handles)
+    }
+
+    /// A file in the temp directory, unique to this test process, for tests
+    /// that need a real path.
+    pub(crate) fn temp_path(file_name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("jeff-{}-{file_name}", std::process::id()))
+    }
+
+    pub(crate) fn state_path(name: &str) -> PathBuf {
+        temp_path(&format!("{name}.json"))
+    }
+
+    /// A local HTTP server answering each request with what `respond` gives
+    /// for its request line, e.g. `GET /token HTTP/1.1`, and keeping each
+    /// request's body.
+    pub(crate) async fn http_stub(
+        respond: impl Fn(&str) -> String + Send + 'static,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let bodies: Arc<Mutex<Vec<String>>> = Arc::default();
+        let kept = Arc::clone(&bodies);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let mut body_at = None;
+                let mut length = 0;
+                while body_at.is_none_or(|at| request.len() < at + length) {
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                    if body_at.is_none() {
+                        body_at = request.windows(4).position(|w| w == b"\r\n\r\n").map(|at| at + 4);
+                        let head = String::from_utf8_lossy(&request[..body_at.unwrap_or(0)]).to_lowercase();
+                        length = head
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length:"))
+                            .and_then(|n| n.trim().parse().ok())
+                            .unwrap_or(0);
+                    }
+                }
+                let body = &request[body_at.unwrap_or(request.len()).min(request.len())..];
+                kept.lock().unwrap().push(String::from_utf8_lossy(body).into_owned());
+                let line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string();
+                let response = respond(&line);
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\
+                     connection: close\r\n\r\n{response}",
+                    response.len()
+                );
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+        });
+        (url, bodies)
+    }
+
+    /// A state file of its own under the temp directory, and an in-memory history.
+    pub(crate) fn stores(name: &str) -> (Mutex<StateStore>, Mutex<History>) {
+        let path = state_path(name);
+        let _ = std::fs::remove_file(&path);
+        (Mutex::new(StateStore::load(path).unwrap()), Mutex::new(History::open(":memory:").unwrap()))
+    }
+
+    pub(crate) fn alert(text: &str) -> Alert {
+        Alert {
+            text: text.to_string(),
+            slots: vec!["5140@2025-03-14".to_string()],
+            variants: BTreeMap::new(),
+            lines: Vec::new(),
+            csv: None,
+            kind: EventKind::NewSlot,
+        }
+    }
+
+    #[tokio::test]
+    async fn applicants_channels_get_only_their_alerts() {
+        let (mut dispatcher, handles) = recorders(&["family", "alex"]);
+        dispatcher.claimed = vec!["alex".to_string()];
+        let (state, history) = stores("claimed");
+        dispatcher.dispatch(&alert("everyone"), "s", &state, &history).await;
+        let mut for_alex = alert("slots");
+        for_alex.tag("Alex");
+        dispatcher.dispatch_to(&["alex".to_string()], &for_alex, "s", &state, &history).await;
+        assert_eq!(*handles[0].0.lock().unwrap(), ["everyone"]);
+        assert_eq!(*handles[1].0.lock().unwrap(), ["*For Alex*\nslots"]);
+    }
+
+    #[tokio::test]
+    async fn only_the_recipient_that_failed_stays_queued() {
+        let (dispatcher, handles) = recorders(&["whatsapp:15551230001", "whatsapp:15551230002"]);
+        handles[1].1.store(true, Ordering::Relaxed);
+        let (state, history) = stores("recipients");
+        assert_eq!(dispatcher.dispatch(&alert("slots"), "s", &state, &history).await, 1);
+        assert_eq!(*handles[0].0.lock().unwrap(), ["slots"]);
+        let queued: Vec<_> = state.lock().unwrap().outbox().iter().map(|p| p.target.clone()).collect();
+        assert_eq!(queued, ["whatsapp:15551230002"]);
+    }
+
+    #[tokio::test]
+    async fn failed_alert_is_resent_with_its_file_on_restart() {
+        let (mut dispatcher, handles) = recorders(&["phone"]);
+        dispatcher.repeat_window = Some(Duration::minutes(30));
+        let (state, history) = stores("resend");
+        let mut found = alert("slots");
+        found.csv = Some(Attachment {
+            filename: "appointments.csv".to_string(),
+            content_type: "text/csv".to_string(),
+            bytes: b"id,date\n5140,2025-03-14\n".to_vec(),
+        });
+        handles[0].1.store(true, Ordering::Relaxed);
+        assert_eq!(dispatcher.dispatch(&found, "s", &state, &history).await, 1);
+        assert!(handles[0].0.lock().unwrap().is_empty());
+
+        // A restart reads the outbox back from the file.
+        let state = Mutex::new(StateStore::load(state_path("resend")).unwrap());
+        handles[0].1.store(false, Ordering::Relaxed);
+        dispatcher.resend_pending(Duration::hours(1), &state, &history).await;
+        let sent = handles[0].0.lock().unwrap().clone();
+        assert!(sent[0].starts_with("slots\n\n_Delayed: found at "), "{sent:?}");
+        assert_eq!(sent[1], "file appointments.csv");
+        let store = state.lock().unwrap();
+        assert!(store.outbox().is_empty());
+        assert!(store.last_sent("phone", &content_hash("slots")).is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failed_alert_goes_out_next_cycle_without_a_restart() {
+        use chrono::TimeZone;
+
+        use crate::clock::SimulatedClock;
+
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2026, 3, 13, 12, 0, 0).unwrap()));
+        let (dispatcher, handles) = recorders(&["phone", "email"]);
+        let (state, history) = stores("next-cycle");
+        let max_age = Duration::minutes(30);
+        handles[1].1.store(true, Ordering::Relaxed);
+        // Each cycle starts by retrying the outbox, as `run_cycle` does.
+        dispatcher.resend_pending(max_age, &state, &history).await;
+        assert_eq!(dispatcher.dispatch(&alert("slots"), "s", &state, &history).await, 1);
+        assert!(handles[1].0.lock().unwrap().is_empty());
+
+        tokio::time::advance(std::time::Duration::from_secs(5 * 60)).await;
+        handles[1].1.store(false, Ordering::Relaxed);
+        dispatcher.resend_pending(max_age, &state, &history).await;
+        let sent = handles[1].0.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].starts_with("slots\n\n_Delayed: found at "), "{sent:?}");
+        assert!(state.lock().unwrap().outbox().is_empty());
+        assert_eq!(handles[0].0.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_repeat_is_held_back_except_where_it_failed() {
+        let (mut dispatcher, handles) = recorders(&["phone", "email"]);
+        dispatcher.repeat_window = Some(Duration::minutes(30));
+        let (state, history) = stores("repeat");
+        handles[1].1.store(true, Ordering::Relaxed);
+        assert_eq!(dispatcher.dispatch(&alert("slots"), "s", &state, &history).await, 1);
+
+        // The next cycle finds the same slots: phone already has them, while
+        // email, which failed, gets them now.
+        handles[1].1.store(false, Ordering::Relaxed);
+        assert_eq!(dispatcher.dispatch(&alert("slots"), "s", &state, &history).await, 0);
+        assert_eq!(*handles[0].0.lock().unwrap(), ["slots"]);
+        assert_eq!(*handles[1].0.lock().unwrap(), ["slots"]);
+        assert!(state.lock().unwrap().outbox().is_empty());
+
+        assert_eq!(dispatcher.dispatch(&alert("slots"), "s", &state, &history).await, 0);
+        assert_eq!(handles[0].0.lock().unwrap().len(), 1);
+        assert_eq!(handles[1].0.lock().unwrap().len(), 1);
+
+        assert_eq!(dispatcher.dispatch(&alert("more slots"), "s", &state, &history).await, 0);
+        assert_eq!(handles[0].0.lock().unwrap().len(), 2);
+        assert_eq!(handles[1].0.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn a_rule_for_a_missing_channel_is_a_config_error() {
+        let config: JeffConfig = serde_json::from_value(serde_json::json!({
+            "enable_slack": false,
+            "slack_token": "",
+            "slack_channel_id": "",
+            "fetch_interval_minutes": 0,
+            "search_states": ["NY"],
+            "api_rate_limit_seconds": 1.0,
+            "max_concurrent_fetches": 1,
+            "max_retries": 1,
+            "verbosity": {"sns": "terse"},
+        }))
+        .unwrap();
+        let err = Dispatcher::from_config(&Client::new(), &Dns::new(None), &config).err().unwrap();
+        assert!(matches!(err, AppError::Config(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn a_failing_digest_doesnt_hold_up_the_others() {
+        let mut dispatcher = Dispatcher::default();
+        let mut handles = Vec::new();
+        for target in ["a@example.com", "b@example.com"] {
+            let (sent, down) = (Arc::default(), Arc::new(AtomicBool::new(false)));
+            let recorder = Recorder {
+                target,
+                sent: Arc::clone(&sent),
+                down: Arc::clone(&down),
+            };
+            dispatcher.add(Box::new(recorder), Delivery::DailyDigest { hour: 0, lookback_hours: None });
+            handles.push((sent, down));
+        }
+        let (state, history) = stores("digests");
+        for target in ["a@example.com", "b@example.com"] {
+            let line = DigestLine {
+                slot: "5140@2025-03-14".to_string(),
+                line: "JFK on Fri, Mar 14".to_string(),
+            };
+            state.lock().unwrap().queue_digest(target, vec![line]);
+        }
+        handles[0].1.store(true, Ordering::Relaxed);
+
+        let display = DisplayOptions::default();
+        let err = dispatcher.flush_digests(&display, &state, &history).await.unwrap_err();
+        assert!(err.to_string().ends_with("a@example.com: General: a@example.com is down"), "{err}");
+        assert_eq!(handles[1].0.lock().unwrap().len(), 1);
+        let mut store = state.lock().unwrap();
+        assert_eq!(store.take_digest("a@example.com").len(), 1);
+        assert!(store.last_digest("a@example.com").is_none());
+        assert!(store.last_digest("b@example.com").is_some());
+    }
+
+    #[tokio::test]
+    async fn channels_send_what_preview_shows() {
+        let (url, bodies) = http_stub(|_| r#"{"code": 200, "message": "success"}"#.to_string()).await;
+        fn config<T: serde::de::DeserializeOwned>(json: String) -> T {
+            serde_json::from_str(&json).unwrap()
+        }
+        let mut dispatcher = Dispatcher::default();
+        let client = Client::new();
+        dispatcher.add(
+            Box::new(GotifyNotifier::new(client.clone(), &config(format!(r#"{{"server": "{url}", "token": "t"}}"#)))),
+            Delivery::Realtime,
+        );
+        let matrix = MatrixNotifier::new(client.clone(), &config(format!(r#"{{"url": "{url}/hook"}}"#))).unwrap();
+        dispatcher.add(Box::new(matrix), Delivery::Realtime);
+        let bark = BarkNotifier::new(&client, &config(format!(r#"{{"device_key": "k", "server": "{url}"}}"#)));
+        dispatcher.add(Box::new(bark), Delivery::Realtime);
+        let signal = format!(r#"{{"server": "{url}", "number": "+15550100", "recipients": ["+15550101"]}}"#);
+        dispatcher.add(Box::new(SignalNotifier::new(client.clone(), &config(signal))), Delivery::Realtime);
+
+        let found = alert("*Global Entry* appointments\n• *5140* on Mar 14");
+        let subject = "Global Entry availability";
+        let previews = dispatcher.preview(&found, subject, &[], &DisplayOptions::default());
+        for channel in &dispatcher.channels {
+            channel.notifier.send(subject, &found.text).await.unwrap();
+        }
+        let bodies: Vec<serde_json::Value> =
+            bodies.lock().unwrap().iter().map(|body| serde_json::from_str(body).unwrap()).collect();
+        let field = |i: usize, name: &str| bodies[i][name].as_str().unwrap().to_string();
+        let sent = [
+            format!("{}\n{}", field(0, "title"), field(0, "message")),
+            field(1, "text"),
+            format!("{}\n{}", field(2, "title"), field(2, "body")),
+            field(3, "message"),
+        ];
+        assert_eq!(previews.len(), sent.len());
+        for ((kind, _, preview), sent) in previews.iter().zip(&sent) {
+            assert_eq!(preview, sent, "{kind}");
+            assert!(!preview.contains('*'), "{kind}: {preview}");
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use futures::{future::BoxFuture, FutureExt};
+
+use super::Notifier;
+use crate::{plugins::Plugin, AppError};
+
+/// Hands alerts to a WebAssembly plugin's `notify`.
+pub struct PluginNotifier {
+    plugin: Arc<Plugin>,
+    target: String,
+}
+
+impl PluginNotifier {
+    pub fn new(plugin: Arc<Plugin>) -> Self {
+        let target = format!("plugin:{}", plugin.name());
+        PluginNotifier { plugin, target }
+    }
+}
+
+impl Notifier for PluginNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "plugin"
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            let plugin = Arc::clone(&self.plugin);
+            let (subject, text) = (subject.to_string(), self.render(subject, text));
+            tokio::task::spawn_blocking(move || plugin.notify(&subject, &text))
+                .await
+                .map_err(|e| AppError::General(format!("{}: {e}", self.target)))??;
+            Ok(None)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,167 @@
+//! Severity levels for what the finder sends, and one table of per-channel
+//! rules on whether each severity goes out right away, waits for the
+//! channel's next digest or is dropped.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Info,
+    Warning,
+    High,
+}
+
+/// What a message is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// An availability alert.
+    NewSlot,
+    /// An availability alert while `booked_date` is set, so every slot in it
+    /// beats the appointment you have.
+    EarlierSlot,
+    /// The weekly note on states without slots.
+    DroughtReport,
+    /// The scheduler API blocking requests or the request budget running
+    /// out, and their recovery.
+    ApiDown,
+}
+
+impl EventKind {
+    fn default_severity(self) -> Severity {
+        match self {
+            EventKind::NewSlot => Severity::Info,
+            EventKind::EarlierSlot => Severity::High,
+            EventKind::DroughtReport => Severity::Low,
+            EventKind::ApiDown => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Send,
+    /// Hold for the channel's next digest.
+    Queue,
+    Suppress,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PolicyConfig {
+    /// Replaces the default severity of an event kind.
+    #[serde(default)]
+    pub severities: HashMap<EventKind, Severity>,
+    /// By channel kind or target, or `*` for the rest: what to do with each
+    /// severity. Severities a channel doesn't list are sent.
+    #[serde(default)]
+    pub rules: BTreeMap<String, HashMap<Severity, Action>>,
+    /// Hour (local) that queued messages go out on channels without a daily
+    /// digest of their own.
+    #[serde(default = "default_queue_hour")]
+    pub queue_hour: u32,
+}
+
+fn default_queue_hour() -> u32 {
+    8
+}
+
+impl PolicyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.queue_hour > 23 {
+            return Err(format!("queue_hour must be 0-23, got {}", self.queue_hour));
+        }
+        Ok(())
+    }
+
+    pub fn severity(&self, kind: EventKind) -> Severity {
+        self.severities.get(&kind).copied().unwrap_or_else(|| kind.default_severity())
+    }
+
+    /// The rule for a channel's `target`, else its `kind`, else `*`.
+    pub fn action(&self, event: EventKind, kind: &str, target: &str) -> Action {
+        let severity = self.severity(event);
+        [target, kind, "*"]
+            .iter()
+            .find_map(|key| self.rules.get(*key))
+            .and_then(|rule| rule.get(&severity))
+            .copied()
+            .unwrap_or(Action::Send)
+    }
+
+    /// Whether any rule queues, so queues need flushing.
+    pub fn queues(&self) -> bool {
+        self.rules.values().any(|rule| rule.values().any(|a| *a == Action::Queue))
+    }
+
+    /// Rule keys other than `*`, to check against the configured channels.
+    pub fn channels(&self) -> impl Iterator<Item = &str> {
+        self.rules.keys().map(String::as_str).filter(|key| *key != "*")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(json: serde_json::Value) -> PolicyConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn rules_apply_by_target_then_kind_then_default() {
+        let policy = policy(serde_json::json!({
+            "severities": { "drought_report": "info" },
+            "rules": {
+                "*": { "low": "suppress", "info": "queue" },
+                "slack": { "info": "send", "warning": "queue" },
+                "C0123": { "high": "suppress" },
+            },
+        }));
+        use Action::*;
+        use EventKind::*;
+        let cases = [
+            (NewSlot, "slack", "C0123", Send),
+            (EarlierSlot, "slack", "C0123", Suppress),
+            // C0123's rule has no warning, and the first rule found decides.
+            (ApiDown, "slack", "C0123", Send),
+            (ApiDown, "slack", "C0456", Queue),
+            (NewSlot, "email", "me@example.com", Queue),
+            (DroughtReport, "email", "me@example.com", Queue),
+            (EarlierSlot, "email", "me@example.com", Send),
+            (ApiDown, "ntfy", "alerts", Send),
+        ];
+        for (event, kind, target, action) in cases {
+            assert_eq!(policy.action(event, kind, target), action, "{event:?} to {kind} {target}");
+        }
+        assert!(policy.queues());
+        assert_eq!(policy.channels().collect::<Vec<_>>(), ["C0123", "slack"]);
+    }
+
+    #[test]
+    fn severities_default_by_event_and_can_be_replaced() {
+        let defaults = policy(serde_json::json!({}));
+        let cases = [
+            (EventKind::NewSlot, Severity::Info),
+            (EventKind::EarlierSlot, Severity::High),
+            (EventKind::DroughtReport, Severity::Low),
+            (EventKind::ApiDown, Severity::Warning),
+        ];
+        for (event, severity) in cases {
+            assert_eq!(defaults.severity(event), severity);
+            assert_eq!(defaults.action(event, "slack", "C0123"), Action::Send);
+        }
+        assert!(!defaults.queues());
+        assert_eq!(defaults.queue_hour, 8);
+
+        let replaced = policy(serde_json::json!({ "severities": { "new_slot": "high" }, "queue_hour": 24 }));
+        assert_eq!(replaced.severity(EventKind::NewSlot), Severity::High);
+        assert_eq!(replaced.severity(EventKind::ApiDown), Severity::Warning);
+        assert!(replaced.validate().is_err());
+        assert!(!policy(serde_json::json!({ "rules": { "*": { "low": "suppress" } } })).queues());
+    }
+}
@@ -0,0 +1,97 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{default_open_url, plain_text, Delivery, Notifier, PUSH_MAX_CHARS};
+use crate::{redact::Secret, AppError};
+
+#[derive(Debug, Deserialize)]
+pub struct PushcutConfig {
+    /// From Pushcut's Account settings.
+    pub api_key: Secret,
+    /// Name of a notification defined in the Pushcut app; its sound and
+    /// devices apply, while the title and text come from the alert.
+    pub notification: String,
+    /// Shortcut run when the notification is tapped, given the booking page
+    /// URL as input; without one the page opens directly.
+    #[serde(default)]
+    pub shortcut: Option<String>,
+    /// Page opened (or handed to `shortcut`) on tap.
+    #[serde(default = "default_open_url")]
+    pub open_url: String,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+/// Sends alerts as iOS push notifications through Pushcut, with actions to
+/// open the booking page or run a shortcut.
+pub struct PushcutNotifier {
+    client: Client,
+    endpoint: String,
+    api_key: Secret,
+    shortcut: Option<String>,
+    open_url: String,
+    target: String,
+}
+
+impl PushcutNotifier {
+    pub fn new(client: &Client, config: &PushcutConfig) -> Self {
+        let mut endpoint = reqwest::Url::parse("https://api.pushcut.io/v1/notifications/").unwrap();
+        endpoint.path_segments_mut().unwrap().pop_if_empty().push(&config.notification);
+        PushcutNotifier {
+            client: client.clone(),
+            endpoint: endpoint.to_string(),
+            api_key: config.api_key.clone(),
+            shortcut: config.shortcut.clone(),
+            open_url: config.open_url.clone(),
+            target: format!("pushcut:{}", config.notification),
+        }
+    }
+}
+
+impl Notifier for PushcutNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "pushcut"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        plain_text(text, PUSH_MAX_CHARS)
+    }
+
+    fn title(&self, subject: &str) -> Option<String> {
+        Some(subject.to_string())
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            let open = serde_json::json!({ "name": "Open booking page", "url": self.open_url });
+            let (default_action, actions) = match &self.shortcut {
+                Some(name) => {
+                    let run = serde_json::json!({ "name": name, "shortcut": name, "input": self.open_url });
+                    (run.clone(), vec![run, open])
+                }
+                None => (open.clone(), vec![open]),
+            };
+            let payload = serde_json::json!({
+                "title": self.title(subject),
+                "text": self.render(subject, text),
+                "isTimeSensitive": true,
+                "defaultAction": default_action,
+                "actions": actions,
+            });
+            self.client
+                .post(&self.endpoint)
+                .header("API-Key", self.api_key.expose())
+                .json(&payload)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(None)
+        }
+        .boxed()
+    }
+}
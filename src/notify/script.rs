@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use futures::{future::BoxFuture, FutureExt};
+
+use super::Notifier;
+use crate::{script::Script, AppError};
+
+/// Hands alerts to the user script's `notify(subject, text)`.
+pub struct ScriptNotifier {
+    script: Arc<Script>,
+    target: String,
+}
+
+impl ScriptNotifier {
+    pub fn new(script: Arc<Script>) -> Self {
+        let target = format!("script:{}", script.path().display());
+        ScriptNotifier { script, target }
+    }
+}
+
+impl Notifier for ScriptNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "script"
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            let script = Arc::clone(&self.script);
+            let (subject, text) = (subject.to_string(), self.render(subject, text));
+            tokio::task::spawn_blocking(move || script.notify(&subject, &text))
+                .await
+                .map_err(|e| AppError::General(format!("{}: {e}", self.target)))??;
+            Ok(None)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,126 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{plain_text, Attachment, Delivery, Notifier, TlsOptions};
+use crate::AppError;
+
+#[derive(Debug, Deserialize)]
+pub struct SignalConfig {
+    /// Base URL of a signal-cli-rest-api instance, e.g. `http://localhost:8080`.
+    pub server: String,
+    /// The number registered with signal-cli that messages are sent from.
+    pub number: String,
+    /// Phone numbers, or group IDs as listed by `/v1/groups` (`group.…`).
+    pub recipients: Vec<String>,
+    /// Attach the alerted slots as a CSV export to each real-time alert.
+    #[serde(default)]
+    pub attach_csv: bool,
+    #[serde(default)]
+    pub tls: TlsOptions,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+/// Sends alerts as Signal messages through signal-cli-rest-api.
+pub struct SignalNotifier {
+    client: Client,
+    endpoint: String,
+    accounts: String,
+    number: String,
+    recipients: Vec<String>,
+    target: String,
+}
+
+impl SignalNotifier {
+    pub fn new(client: Client, config: &SignalConfig) -> Self {
+        let server = config.server.trim_end_matches('/');
+        SignalNotifier {
+            client,
+            endpoint: format!("{server}/v2/send"),
+            accounts: format!("{server}/v1/accounts"),
+            number: config.number.clone(),
+            recipients: config.recipients.clone(),
+            target: format!("signal:{}", config.recipients.join(",")),
+        }
+    }
+
+    async fn post(&self, text: &str, file: Option<&Attachment>) -> Result<(), AppError> {
+        let attachments: Vec<String> = file
+            .map(|file| {
+                format!(
+                    "data:{};filename={};base64,{}",
+                    file.content_type,
+                    file.filename,
+                    STANDARD.encode(&file.bytes)
+                )
+            })
+            .into_iter()
+            .collect();
+        let payload = serde_json::json!({
+            "number": self.number,
+            "recipients": self.recipients,
+            "message": text,
+            "base64_attachments": attachments,
+        });
+        self.client.post(&self.endpoint).json(&payload).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Notifier for SignalNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "signal"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        plain_text(text, usize::MAX)
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<bool, AppError>> {
+        async move {
+            let accounts: Vec<String> =
+                self.client.get(&self.accounts).send().await?.error_for_status()?.json().await?;
+            if !accounts.contains(&self.number) {
+                return Err(AppError::General(format!("signal: {} isn't registered with the server", self.number)));
+            }
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            self.post(&self.render(subject, text), None).await?;
+            Ok(None)
+        }
+        .boxed()
+    }
+
+    fn send_with<'a>(
+        &'a self,
+        subject: &'a str,
+        text: &'a str,
+        file: Option<&'a Attachment>,
+    ) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            self.post(&self.render(subject, text), file).await?;
+            Ok(None)
+        }
+        .boxed()
+    }
+
+    #[cfg(feature = "heatmap")]
+    fn send_file<'a>(&'a self, file: &'a Attachment, caption: &'a str) -> BoxFuture<'a, Result<bool, AppError>> {
+        async move {
+            self.post(&plain_text(caption, usize::MAX), Some(file)).await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,537 @@
+use std::{collections::HashMap, env, path::PathBuf, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::{future::BoxFuture, FutureExt};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use super::{content_hash, percent_encode, plain_text, Delivery, Notifier, TlsOptions};
+use crate::{clock, redact, AppError};
+
+const FORM: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+/// SNS rejects subjects of 100 characters or more.
+const SUBJECT_MAX_CHARS: usize = 99;
+
+const INSTANCE_METADATA: &str = "http://169.254.169.254";
+
+/// How long the instance metadata service gets before it's taken to be absent.
+const METADATA_TIMEOUT: StdDuration = StdDuration::from_secs(2);
+
+/// Temporary credentials are renewed this long before they expire.
+const REFRESH_MARGIN_MINUTES: i64 = 5;
+
+#[derive(Debug, Deserialize)]
+pub struct SnsConfig {
+    /// ARN of the topic, e.g. `arn:aws:sns:us-east-1:123456789012:appointments`;
+    /// its region picks the endpoint.
+    pub topic_arn: String,
+    /// Profile to read from the shared credentials file; otherwise
+    /// `AWS_PROFILE`, or `default`.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Used instead of `https://sns.{region}.amazonaws.com`, e.g. for LocalStack.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub tls: TlsOptions,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+impl SnsConfig {
+    fn region(&self) -> Option<&str> {
+        match self.topic_arn.split(':').collect::<Vec<_>>()[..] {
+            ["arn", _, "sns", region, _, _] if !region.is_empty() => Some(region),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.region().is_none() {
+            return Err(format!(
+                "topic_arn {:?} doesn't look like arn:aws:sns:REGION:ACCOUNT:NAME",
+                self.topic_arn
+            ));
+        }
+        if let Some(endpoint) = &self.endpoint {
+            Url::parse(endpoint).map_err(|e| format!("endpoint {endpoint:?}: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    expiration: Option<DateTime<Utc>>,
+}
+
+impl Credentials {
+    fn fresh(&self) -> bool {
+        self.expiration
+            .is_none_or(|at| at - clock::now() > Duration::minutes(REFRESH_MARGIN_MINUTES))
+    }
+}
+
+/// Publishes alerts to an Amazon SNS topic, for fanning out to whatever is
+/// subscribed to it: queues, Lambda functions, SMS, email and so on.
+pub struct SnsNotifier {
+    client: Client,
+    url: Url,
+    host: String,
+    region: String,
+    topic_arn: String,
+    profile: Option<String>,
+    credentials: Mutex<Option<Credentials>>,
+}
+
+impl SnsNotifier {
+    pub fn new(client: Client, config: &SnsConfig) -> Result<Self, AppError> {
+        let region = config.region().unwrap_or_default().to_string();
+        let endpoint = config.endpoint.clone().unwrap_or_else(|| format!("https://sns.{region}.amazonaws.com/"));
+        let url = Url::parse(&endpoint).map_err(|e| AppError::General(format!("sns: invalid endpoint: {e}")))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(AppError::General(format!("sns: endpoint {endpoint:?} has no host"))),
+        };
+        Ok(SnsNotifier {
+            client,
+            url,
+            host,
+            region,
+            topic_arn: config.topic_arn.clone(),
+            profile: config.profile.clone(),
+            credentials: Mutex::new(None),
+        })
+    }
+
+    async fn credentials(&self) -> Result<Credentials, AppError> {
+        let mut cached = self.credentials.lock().await;
+        if let Some(credentials) = cached.as_ref().filter(|c| c.fresh()) {
+            return Ok(credentials.clone());
+        }
+        let found = discover(&self.client, self.profile.as_deref(), &|name| env::var(name).ok()).await?;
+        redact::register(&found.secret_access_key);
+        if let Some(token) = &found.token {
+            redact::register(token);
+        }
+        *cached = Some(found.clone());
+        Ok(found)
+    }
+
+    async fn publish(&self, subject: &str, text: &str) -> Result<(), AppError> {
+        let message = self.render(subject, text);
+        let mut params = vec![
+            ("Action", "Publish".to_string()),
+            ("TopicArn", self.topic_arn.clone()),
+            ("Message", message.clone()),
+        ];
+        if let Some(subject) = self.title(subject) {
+            params.push(("Subject", subject));
+        }
+        // FIFO topics need a group, and deduplicate on this ID for 5 minutes.
+        if self.topic_arn.ends_with(".fifo") {
+            params.push(("MessageGroupId", "appointments".to_string()));
+            params.push(("MessageDeduplicationId", content_hash(&message)));
+        }
+        self.call(params).await
+    }
+
+    /// Sends a signed SNS API request, with `Version` added to `params`.
+    async fn call(&self, mut params: Vec<(&str, String)>) -> Result<(), AppError> {
+        params.push(("Version", "2010-03-31".to_string()));
+        let body = params
+            .iter()
+            .map(|(key, value)| format!("{key}={}", percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let credentials = self.credentials().await?;
+        let signed = sign(&credentials, &self.region, &self.host, self.url.path(), &body, clock::now());
+        let mut request = self.client.post(self.url.clone()).body(body);
+        for (name, value) in signed {
+            request = request.header(name, value);
+        }
+        let resp = request.send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = resp.text().await.unwrap_or_default();
+        match (xml_tag(&text, "Code"), xml_tag(&text, "Message")) {
+            (Some(code), Some(message)) => Err(AppError::General(format!("sns: {code}: {message}"))),
+            _ => Err(AppError::General(format!("sns: HTTP {status}"))),
+        }
+    }
+}
+
+impl Notifier for SnsNotifier {
+    fn target(&self) -> &str {
+        &self.topic_arn
+    }
+
+    fn kind(&self) -> &'static str {
+        "sns"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        plain_text(text, usize::MAX)
+    }
+
+    fn title(&self, subject: &str) -> Option<String> {
+        Some(sns_subject(subject)).filter(|subject| !subject.is_empty())
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<bool, AppError>> {
+        async move {
+            // Needs `sns:GetTopicAttributes` as well as `sns:Publish`.
+            self.call(vec![("Action", "GetTopicAttributes".to_string()), ("TopicArn", self.topic_arn.clone())])
+                .await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            self.publish(subject, text).await?;
+            Ok(None)
+        }
+        .boxed()
+    }
+}
+
+/// SNS subjects are one line of printable ASCII.
+fn sns_subject(subject: &str) -> String {
+    let ascii: String = subject
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { ' ' })
+        .collect();
+    ascii.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(SUBJECT_MAX_CHARS).collect()
+}
+
+/// The text of the first `<tag>` in an AWS XML response.
+fn xml_tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    Some(&xml[start..end])
+}
+
+/// Looks up an environment variable; tests pass their own.
+type Env<'a> = &'a (dyn Fn(&str) -> Option<String> + Sync);
+
+/// Credentials found the way the AWS CLI finds them: environment variables,
+/// the shared credentials file, the ECS task role, then the EC2 instance role.
+async fn discover(client: &Client, profile: Option<&str>, env: Env<'_>) -> Result<Credentials, AppError> {
+    if let (Some(access_key_id), Some(secret_access_key)) = (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) {
+        return Ok(Credentials {
+            access_key_id,
+            secret_access_key,
+            token: env("AWS_SESSION_TOKEN"),
+            expiration: None,
+        });
+    }
+    if let Some(found) = from_shared_file(profile, env)? {
+        return Ok(found);
+    }
+    if let Some(found) = from_container(client, env).await? {
+        info!("sns: using the ECS task role");
+        return Ok(found);
+    }
+    let found = from_instance(client, env).await.map_err(|e| {
+        AppError::General(format!(
+            "sns: no AWS credentials in the environment, the shared credentials file or instance metadata ({e})"
+        ))
+    })?;
+    info!("sns: using the EC2 instance role");
+    Ok(found)
+}
+
+fn from_shared_file(profile: Option<&str>, env: Env<'_>) -> Result<Option<Credentials>, AppError> {
+    let path = match (env("AWS_SHARED_CREDENTIALS_FILE"), env("HOME")) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(home)) => PathBuf::from(home).join(".aws").join("credentials"),
+        (None, None) => return Ok(None),
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let wanted = profile
+        .map(str::to_string)
+        .or_else(|| env("AWS_PROFILE"))
+        .unwrap_or_else(|| "default".to_string());
+
+    let mut section = None;
+    let mut keys = HashMap::new();
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(name.trim());
+        } else if section == Some(wanted.as_str()) {
+            if let Some((key, value)) = line.split_once('=') {
+                keys.insert(key.trim(), value.trim().to_string());
+            }
+        }
+    }
+    match (keys.remove("aws_access_key_id"), keys.remove("aws_secret_access_key")) {
+        (Some(access_key_id), Some(secret_access_key)) => Ok(Some(Credentials {
+            access_key_id,
+            secret_access_key,
+            token: keys.remove("aws_session_token"),
+            expiration: None,
+        })),
+        _ if profile.is_some() => Err(AppError::General(format!(
+            "sns: profile {wanted:?} has no keys in {}",
+            path.display()
+        ))),
+        _ => Ok(None),
+    }
+}
+
+async fn from_container(client: &Client, env: Env<'_>) -> Result<Option<Credentials>, AppError> {
+    let url = match (
+        env("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI"),
+        env("AWS_CONTAINER_CREDENTIALS_FULL_URI"),
+    ) {
+        (Some(relative), _) => format!("http://169.254.170.2{relative}"),
+        (None, Some(full)) => full,
+        _ => return Ok(None),
+    };
+    let mut request = client.get(url);
+    if let Some(token) = env("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+        request = request.header("Authorization", token);
+    }
+    Ok(Some(request.send().await?.error_for_status()?.json().await?))
+}
+
+/// The instance role's credentials, through IMDSv2 at
+/// `AWS_EC2_METADATA_SERVICE_ENDPOINT` or the usual address.
+async fn from_instance(client: &Client, env: Env<'_>) -> Result<Credentials, AppError> {
+    let metadata = env("AWS_EC2_METADATA_SERVICE_ENDPOINT").unwrap_or_else(|| INSTANCE_METADATA.to_string());
+    let metadata = metadata.trim_end_matches('/');
+    let token = client
+        .put(format!("{metadata}/latest/api/token"))
+        .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+        .timeout(METADATA_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let get = |role: &str| {
+        client
+            .get(format!("{metadata}/latest/meta-data/iam/security-credentials/{role}"))
+            .header("X-aws-ec2-metadata-token", &token)
+            .timeout(METADATA_TIMEOUT)
+    };
+    let roles = get("").send().await?.error_for_status()?.text().await?;
+    let role = roles
+        .lines()
+        .next()
+        .ok_or_else(|| AppError::General("the instance has no IAM role".to_string()))?;
+    Ok(get(role).send().await?.error_for_status()?.json().await?)
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The Signature Version 4 key for one day (`YYYYMMDD`), region and service.
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{secret}").as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+/// Headers that sign a form POST of `body` to `path` on `host` with
+/// Signature Version 4. `Host` is signed but left for reqwest to send.
+fn sign(
+    credentials: &Credentials,
+    region: &str,
+    host: &str,
+    path: &str,
+    body: &str,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = &amz_date[..8];
+    let mut headers = vec![
+        ("content-type", FORM.to_string()),
+        ("host", host.to_string()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{name}:{value}\n")).collect();
+    let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+    let canonical_request = format!(
+        "POST\n{path}\n\n{canonical_headers}\n{signed_headers}\n{}",
+        hex(&Sha256::digest(body.as_bytes()))
+    );
+    let scope = format!("{date}/{region}/sns/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = signing_key(&credentials.secret_access_key, date, region, "sns");
+    let signature = hex(&hmac(&key, &string_to_sign));
+    headers.retain(|(name, _)| *name != "host");
+    headers.push((
+        "authorization",
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        ),
+    ));
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::notify::tests::{http_stub, temp_path};
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> + Sync {
+        let vars: HashMap<String, String> = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn credentials_file(name: &str) -> String {
+        let path = temp_path(name);
+        std::fs::write(
+            &path,
+            "[default]\naws_access_key_id = AKDEFAULT\naws_secret_access_key = default-secret\n\n\
+             [work]\naws_access_key_id=AKWORK\naws_secret_access_key=work-secret\naws_session_token=work-token\n",
+        )
+        .unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    const CREDENTIALS: &str = r#"{"AccessKeyId": "AKROLE", "SecretAccessKey": "role-secret", "Token": "role-token",
+        "Expiration": "2030-01-01T00:00:00Z"}"#;
+
+    #[test]
+    fn signs_requests_with_signature_version_4() {
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            token: Some("session".to_string()),
+            expiration: None,
+        };
+        let now = Utc.with_ymd_and_hms(2025, 3, 14, 12, 0, 0).unwrap();
+        let body = "Action=Publish&Message=hi&Version=2010-03-31";
+        let headers = sign(&credentials, "us-east-1", "sns.us-east-1.amazonaws.com", "/", body, now);
+        let get = |headers: &[(&str, String)], name: &str| {
+            headers.iter().find(|(n, _)| *n == name).map(|(_, value)| value.clone())
+        };
+        assert_eq!(get(&headers, "x-amz-date").as_deref(), Some("20250314T120000Z"));
+        assert_eq!(get(&headers, "x-amz-security-token").as_deref(), Some("session"));
+        assert_eq!(get(&headers, "host"), None);
+        let authorization = get(&headers, "authorization").unwrap();
+        // Checked against an independent SigV4 implementation.
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20250314/us-east-1/sns/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date;x-amz-security-token, \
+             Signature=f0ecded9a3d4be77ea8457948556900505306e8f518c05f523762aa19113d40b"
+        );
+        let other = sign(&credentials, "us-east-1", "sns.us-east-1.amazonaws.com", "/", "Action=Publish", now);
+        assert_ne!(get(&other, "authorization"), Some(authorization));
+    }
+
+    #[tokio::test]
+    async fn finds_credentials_in_the_environment_then_the_shared_file() {
+        let client = Client::new();
+        let file = credentials_file("aws-credentials");
+        let env = vars(&[
+            ("AWS_SHARED_CREDENTIALS_FILE", &file),
+            ("AWS_ACCESS_KEY_ID", "AKENV"),
+            ("AWS_SECRET_ACCESS_KEY", "env-secret"),
+        ]);
+        assert_eq!(discover(&client, None, &env).await.unwrap().access_key_id, "AKENV");
+
+        let env = vars(&[("AWS_SHARED_CREDENTIALS_FILE", &file)]);
+        assert_eq!(discover(&client, None, &env).await.unwrap().access_key_id, "AKDEFAULT");
+        let work = discover(&client, Some("work"), &env).await.unwrap();
+        assert_eq!((work.access_key_id.as_str(), work.token.as_deref()), ("AKWORK", Some("work-token")));
+        let env = vars(&[("AWS_SHARED_CREDENTIALS_FILE", &file), ("AWS_PROFILE", "work")]);
+        assert_eq!(discover(&client, None, &env).await.unwrap().access_key_id, "AKWORK");
+        let missing = discover(&client, Some("personal"), &env).await.err().unwrap().to_string();
+        assert!(missing.contains("\"personal\" has no keys"), "{missing}");
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_task_role_then_the_instance_role() {
+        let client = Client::new();
+        let (url, _) = http_stub(|_| CREDENTIALS.to_string()).await;
+        let env = vars(&[
+            ("AWS_SHARED_CREDENTIALS_FILE", "/nonexistent/credentials"),
+            ("AWS_CONTAINER_CREDENTIALS_FULL_URI", &format!("{url}/v2/credentials")),
+        ]);
+        let found = discover(&client, None, &env).await.unwrap();
+        assert_eq!((found.access_key_id.as_str(), found.token.as_deref()), ("AKROLE", Some("role-token")));
+        assert!(found.fresh());
+
+        let (url, bodies) = http_stub(|line| match line.split(' ').nth(1).unwrap_or_default() {
+            "/latest/api/token" => "imds-token".to_string(),
+            "/latest/meta-data/iam/security-credentials/" => "jeff-role\n".to_string(),
+            "/latest/meta-data/iam/security-credentials/jeff-role" => CREDENTIALS.to_string(),
+            _ => String::new(),
+        })
+        .await;
+        let env = vars(&[
+            ("AWS_SHARED_CREDENTIALS_FILE", "/nonexistent/credentials"),
+            ("AWS_EC2_METADATA_SERVICE_ENDPOINT", &url),
+        ]);
+        assert_eq!(discover(&client, None, &env).await.unwrap().access_key_id, "AKROLE");
+        assert_eq!(bodies.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn publishes_a_signed_form() {
+        let (url, bodies) = http_stub(|_| "<PublishResponse/>".to_string()).await;
+        let config: SnsConfig = serde_json::from_str(&format!(
+            r#"{{"topic_arn": "arn:aws:sns:us-east-1:123456789012:appointments", "endpoint": "{url}/"}}"#
+        ))
+        .unwrap();
+        let notifier = SnsNotifier::new(Client::new(), &config).unwrap();
+        *notifier.credentials.lock().await = Some(Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "secret".to_string(),
+            token: None,
+            expiration: None,
+        });
+        notifier.send("Global Entry availability", "*5140* on Mar 14").await.unwrap();
+        let body = bodies.lock().unwrap()[0].clone();
+        assert!(body.starts_with("Action=Publish&TopicArn=arn%3Aaws%3Asns%3Aus-east-1"), "{body}");
+        assert!(body.contains("&Message=5140%20on%20Mar%2014&Subject=Global%20Entry%20availability&"), "{body}");
+    }
+
+    #[test]
+    fn signing_key_matches_aws_example() {
+        // From "Examples of how to derive a signing key" in the AWS docs.
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex(&key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+    }
+}
@@ -0,0 +1,189 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{Client, Response};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{plain_text, Delivery, Notifier, TlsOptions};
+use crate::{redact::Secret, AppError};
+
+/// WhatsApp counts the whole template body against 1024 characters, so the
+/// alert gets what's left after the template's own text.
+const PARAM_MAX_CHARS: usize = 900;
+
+#[derive(Debug, Deserialize)]
+pub struct WhatsAppConfig {
+    /// System user access token with `whatsapp_business_messaging`.
+    pub token: Secret,
+    /// ID of the business phone number messages are sent from (not the
+    /// number itself).
+    pub phone_number_id: String,
+    /// Recipients' phone numbers with country code, e.g. `15551234567`.
+    pub recipients: Vec<String>,
+    /// Approved message template with one body variable, `{{1}}`.
+    pub template: String,
+    /// Language code the template was approved in.
+    #[serde(default = "default_language")]
+    pub language: String,
+    #[serde(default = "default_api")]
+    pub api: String,
+    #[serde(default)]
+    pub tls: TlsOptions,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_language() -> String {
+    "en_US".to_string()
+}
+
+fn default_api() -> String {
+    "https://graph.facebook.com/v21.0".to_string()
+}
+
+impl WhatsAppConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.recipients.is_empty() {
+            return Err("recipients is empty".to_string());
+        }
+        let is_number = |r: &str| r.trim_start_matches('+').chars().all(|c| c.is_ascii_digit());
+        if let Some(bad) = self.recipients.iter().find(|r| !is_number(r)) {
+            return Err(format!("recipient {bad:?} isn't a phone number with country code"));
+        }
+        if self.template.trim().is_empty() {
+            return Err(
+                "template is empty; WhatsApp only delivers business-initiated messages as approved templates".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Sends alerts through the WhatsApp Business Cloud API.
+///
+/// A business can only send free-form text within 24 hours of the recipient
+/// writing to it; anything else must be an approved template. Alerts arrive
+/// unprompted, so every one is sent as the configured template with the alert
+/// flattened into its `{{1}}` variable. Each recipient is a channel of its
+/// own, so one that fails is retried without repeating the alert to the rest.
+pub struct WhatsAppNotifier {
+    client: Client,
+    endpoint: String,
+    /// The sending number's own object in the Graph API.
+    number: String,
+    token: Secret,
+    /// Phone number with country code, without a leading `+`.
+    to: String,
+    template: String,
+    language: String,
+    target: String,
+}
+
+impl WhatsAppNotifier {
+    /// The channel to one of `config.recipients`.
+    pub fn new(client: Client, config: &WhatsAppConfig, recipient: &str) -> Self {
+        let api = config.api.trim_end_matches('/');
+        let to = recipient.trim_start_matches('+');
+        WhatsAppNotifier {
+            client,
+            endpoint: format!("{api}/{}/messages", config.phone_number_id),
+            number: format!("{api}/{}", config.phone_number_id),
+            token: config.token.clone(),
+            to: to.to_string(),
+            template: config.template.clone(),
+            language: config.language.clone(),
+            target: format!("whatsapp:{to}"),
+        }
+    }
+
+    async fn post(&self, param: &str) -> Result<(), AppError> {
+        let payload = json!({
+            "messaging_product": "whatsapp",
+            "to": self.to,
+            "type": "template",
+            "template": {
+                "name": self.template,
+                "language": { "code": self.language },
+                "components": [{
+                    "type": "body",
+                    "parameters": [{ "type": "text", "text": param }],
+                }],
+            },
+        });
+        let resp = self
+            .client
+            .post(&self.endpoint)
+            .bearer_auth(self.token.expose())
+            .json(&payload)
+            .send()
+            .await?;
+        Self::graph_result(resp).await
+    }
+
+    async fn graph_result(resp: Response) -> Result<(), AppError> {
+        if resp.status().is_success() {
+            return Ok(());
+        }
+
+        #[derive(Deserialize)]
+        struct GraphError {
+            message: String,
+            code: u32,
+        }
+
+        #[derive(Deserialize)]
+        struct GraphResp {
+            error: GraphError,
+        }
+
+        let status = resp.status();
+        match resp.json::<GraphResp>().await {
+            Ok(GraphResp { error }) => {
+                Err(AppError::General(format!("whatsapp: {} (code {})", error.message, error.code)))
+            }
+            Err(_) => Err(AppError::General(format!("whatsapp: HTTP {status}"))),
+        }
+    }
+}
+
+/// Template variables can't hold newlines, tabs or more than four spaces in
+/// a row, so the alert's lines are joined into one.
+fn template_param(text: &str) -> String {
+    let flat = plain_text(text, usize::MAX)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ");
+    plain_text(&flat, PARAM_MAX_CHARS)
+}
+
+impl Notifier for WhatsAppNotifier {
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn kind(&self) -> &'static str {
+        "whatsapp"
+    }
+
+    fn render(&self, _subject: &str, text: &str) -> String {
+        template_param(text)
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<bool, AppError>> {
+        async move {
+            let resp = self.client.get(&self.number).bearer_auth(self.token.expose()).send().await?;
+            Self::graph_result(resp).await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    fn send<'a>(&'a self, subject: &'a str, text: &'a str) -> BoxFuture<'a, Result<Option<String>, AppError>> {
+        async move {
+            self.post(&self.render(subject, text)).await?;
+            Ok(None)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,192 @@
+//! Orders and spaces a cycle's requests. `steady` keeps dates in order at a
+//! fixed spacing; the other profiles shuffle the order and vary the spacing,
+//! without ever averaging faster than `api_rate_limit_seconds`.
+
+use std::time::Duration;
+
+use fastrand::Rng;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pacing {
+    /// In date order, `api_rate_limit_seconds` apart.
+    #[default]
+    Steady,
+    /// Shuffled, each gap one to two times the limit, with a pause of three
+    /// to six times it now and then.
+    Humanlike,
+    /// Shuffled, in quick runs of two to four requests, each followed by a
+    /// rest that makes up the time.
+    Burst,
+}
+
+impl Pacing {
+    /// Puts a cycle's requests in the order to send them.
+    pub fn order<T>(self, requests: &mut [T]) {
+        if self != Pacing::Steady {
+            fastrand::shuffle(requests);
+        }
+    }
+
+    pub fn pacer(self, limit: Duration) -> Pacer {
+        Pacer {
+            pacing: self,
+            limit,
+            rng: Rng::new(),
+            run_left: 0,
+            owed: Duration::ZERO,
+        }
+    }
+}
+
+/// Hands out the wait after each request of one cycle, in order.
+pub struct Pacer {
+    pacing: Pacing,
+    limit: Duration,
+    rng: Rng,
+    /// Quick requests left in the current burst.
+    run_left: u32,
+    /// Time the current burst has gone under the limit.
+    owed: Duration,
+}
+
+impl Pacer {
+    pub fn next_gap(&mut self) -> Duration {
+        let limit = self.limit;
+        match self.pacing {
+            Pacing::Steady => limit,
+            Pacing::Humanlike if self.rng.u8(..10) == 0 => limit.mul_f64(3.0 + 3.0 * self.rng.f64()),
+            Pacing::Humanlike => limit.mul_f64(1.0 + self.rng.f64()),
+            Pacing::Burst => {
+                if self.run_left == 0 {
+                    self.run_left = self.rng.u32(2..=4);
+                }
+                self.run_left -= 1;
+                if self.run_left == 0 {
+                    return limit + std::mem::take(&mut self.owed);
+                }
+                let gap = limit.mul_f64(0.1 + 0.2 * self.rng.f64());
+                self.owed += limit - gap;
+                gap
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+    use futures::{future::BoxFuture, FutureExt};
+
+    use super::*;
+    use crate::{
+        clock::{self, SimulatedClock},
+        providers::Provider,
+        ttp_client::{Entry, Location},
+        AppError, JeffConfig,
+    };
+
+    const LIMIT: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn steady_keeps_the_order_and_the_limit() {
+        let mut requests = [1, 2, 3, 4];
+        Pacing::Steady.order(&mut requests);
+        assert_eq!(requests, [1, 2, 3, 4]);
+        let mut pacer = Pacing::Steady.pacer(LIMIT);
+        assert!((0..10).all(|_| pacer.next_gap() == LIMIT));
+    }
+
+    #[test]
+    fn humanlike_gaps_stay_between_one_and_six_limits() {
+        let mut pacer = Pacing::Humanlike.pacer(LIMIT);
+        for _ in 0..1000 {
+            let gap = pacer.next_gap();
+            assert!(gap >= LIMIT && gap <= LIMIT * 6, "{gap:?}");
+        }
+    }
+
+    #[test]
+    fn a_burst_makes_up_its_time_by_the_end_of_each_run() {
+        let mut pacer = Pacing::Burst.pacer(LIMIT);
+        let (mut total, mut sent) = (Duration::ZERO, 0);
+        for _ in 0..100 {
+            let mut run = 0;
+            loop {
+                let gap = pacer.next_gap();
+                total += gap;
+                sent += 1;
+                run += 1;
+                if gap >= LIMIT {
+                    break;
+                }
+                assert!(gap <= LIMIT.mul_f64(0.3), "{gap:?}");
+            }
+            assert!((2..=4).contains(&run), "{run}");
+            assert_eq!(total, LIMIT * sent);
+        }
+    }
+
+    #[test]
+    fn a_zero_limit_never_waits() {
+        for pacing in [Pacing::Steady, Pacing::Humanlike, Pacing::Burst] {
+            let mut pacer = pacing.pacer(Duration::ZERO);
+            assert!((0..20).all(|_| pacer.next_gap().is_zero()), "{pacing:?}");
+        }
+    }
+
+    /// Answers every date with nothing, noting when each was asked for.
+    #[derive(Default)]
+    struct Recording {
+        asked: Mutex<Vec<(NaiveDate, DateTime<Utc>)>>,
+    }
+
+    impl Provider for Recording {
+        fn id(&self) -> &'static str {
+            "global_entry"
+        }
+
+        fn fetch_date(&self, date: NaiveDate) -> BoxFuture<'_, Result<Vec<Entry<Location>>, AppError>> {
+            self.asked.lock().unwrap().push((date, clock::now()));
+            async { Ok(Vec::new()) }.boxed()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_cycle_across_midnight_keeps_its_dates_and_spacing() {
+        let config: Arc<JeffConfig> = Arc::new(
+            serde_json::from_value(serde_json::json!({
+                "enable_slack": false,
+                "slack_token": "",
+                "slack_channel_id": "",
+                "fetch_interval_minutes": 5,
+                "search_states": ["NY"],
+                "api_rate_limit_seconds": LIMIT.as_secs_f64(),
+                "max_concurrent_fetches": 1,
+                "max_retries": 1,
+                "window_mode": "rolling",
+                "window_days": 3,
+            }))
+            .unwrap(),
+        );
+        // The day US clocks sprang forward; UTC, which dates go by, has no such jump.
+        let start = Utc.with_ymd_and_hms(2025, 3, 9, 23, 59, 59).unwrap();
+        let _clock = clock::install(SimulatedClock::starting_at(start));
+        let recording = Arc::new(Recording::default());
+        let providers: Vec<Arc<dyn Provider>> = vec![recording.clone()];
+
+        let dates = config.scan_dates(clock::today());
+        crate::scan(&providers, &config, None, &dates).await;
+
+        let asked = recording.asked.lock().unwrap();
+        let asked: Vec<(String, i64)> =
+            asked.iter().map(|(date, at)| (date.to_string(), (*at - start).num_seconds())).collect();
+        assert_eq!(
+            asked,
+            [("2025-03-09".to_string(), 0), ("2025-03-10".to_string(), 2), ("2025-03-11".to_string(), 4)]
+        );
+    }
+}
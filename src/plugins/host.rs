@@ -0,0 +1,218 @@
+use std::{fmt, sync::Arc};
+
+use reqwest::{Client, Url};
+use serde_json::json;
+use tokio::runtime::Handle;
+use tracing::{info, warn};
+use wasmtime::{Caller, Config, Engine, Extern, Linker, Module, Store};
+
+use super::{PluginsConfig, ABI_VERSION};
+use crate::{ttp_client::Location, AppError};
+
+/// What a plugin may reach through `http_post`.
+struct Grants {
+    client: Client,
+    allowed_hosts: Vec<String>,
+}
+
+/// Per-call state; `grants` is `None` in `keep`, which runs while fetching
+/// and mustn't block.
+struct Ctx {
+    plugin: String,
+    grants: Option<Arc<Grants>>,
+}
+
+/// A compiled plugin. Every call gets a fresh instance, so plugins keep no
+/// state between calls.
+pub struct Plugin {
+    name: String,
+    module: Module,
+    linker: Arc<Linker<Ctx>>,
+    grants: Arc<Grants>,
+    fuel: u64,
+    has_keep: bool,
+    has_notify: bool,
+}
+
+/// Compiles and checks every `.wasm` file in `config.dir`.
+pub fn load(config: &PluginsConfig, client: Client) -> Result<Vec<Arc<Plugin>>, AppError> {
+    let engine = Engine::new(Config::new().consume_fuel(true))
+        .map_err(|e| AppError::General(format!("plugins: {e:#}")))?;
+    let linker = Arc::new(linker(&engine)?);
+    let grants = Arc::new(Grants {
+        client,
+        allowed_hosts: config.allowed_hosts.clone(),
+    });
+
+    let mut paths: Vec<_> = std::fs::read_dir(&config.dir)
+        .map_err(|e| AppError::General(format!("plugins: {}: {e}", config.dir.display())))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|p| p.extension().is_some_and(|ext| ext == "wasm"));
+    paths.sort();
+
+    let mut plugins = Vec::new();
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let error = |e: wasmtime::Error| AppError::General(format!("plugin {name}: {e:#}"));
+        let module = Module::from_file(&engine, &path).map_err(error)?;
+        let exports = |export: &str| module.exports().any(|e| e.name() == export);
+        let plugin = Plugin {
+            has_keep: exports("keep"),
+            has_notify: exports("notify"),
+            name: name.clone(),
+            module: module.clone(),
+            linker: Arc::clone(&linker),
+            grants: Arc::clone(&grants),
+            fuel: config.fuel,
+        };
+        if !plugin.has_keep && !plugin.has_notify {
+            return Err(AppError::General(format!("plugin {name}: exports neither keep nor notify")));
+        }
+        let version = plugin.instantiate(None)?.call("jeff_abi_version", None)?;
+        if version != ABI_VERSION {
+            return Err(AppError::General(format!(
+                "plugin {name}: built for ABI version {version}; this build supports {ABI_VERSION}"
+            )));
+        }
+        info!("Loaded plugin {name}{}{}", if plugin.has_keep { " (keep)" } else { "" }, if plugin.has_notify { " (notify)" } else { "" });
+        plugins.push(Arc::new(plugin));
+    }
+    Ok(plugins)
+}
+
+impl Plugin {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn has_notify(&self) -> bool {
+        self.has_notify
+    }
+
+    fn instantiate(&self, grants: Option<Arc<Grants>>) -> Result<Instance<'_>, AppError> {
+        let error = |e: wasmtime::Error| AppError::General(format!("plugin {}: {e:#}", self.name));
+        let mut store = Store::new(self.module.engine(), Ctx { plugin: self.name.clone(), grants });
+        store.set_fuel(self.fuel).map_err(error)?;
+        let instance = self.linker.instantiate(&mut store, &self.module).map_err(error)?;
+        Ok(Instance { name: &self.name, store, instance })
+    }
+
+    /// Why `keep` drops `loc`, or `None` if it keeps it (or isn't exported).
+    /// A failing plugin keeps the location, so alerts aren't lost.
+    pub fn rejection(&self, loc: &Location) -> Option<String> {
+        if !self.has_keep {
+            return None;
+        }
+        let input = loc.record().to_string();
+        match self.instantiate(None).and_then(|mut i| i.call("keep", Some(input.as_bytes()))) {
+            Ok(0) => Some(format!("plugin {}: keep returned 0", self.name)),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("keep failed for {}, keeping it: {e}", loc.id);
+                None
+            }
+        }
+    }
+
+    /// Calls `notify`. Blocks, so call it off the async workers.
+    pub fn notify(&self, subject: &str, text: &str) -> Result<(), AppError> {
+        let input = json!({ "subject": subject, "text": text }).to_string();
+        match self
+            .instantiate(Some(Arc::clone(&self.grants)))?
+            .call("notify", Some(input.as_bytes()))?
+        {
+            0 => Ok(()),
+            code => Err(AppError::General(format!("plugin {}: notify returned {code}", self.name))),
+        }
+    }
+}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Plugin").field(&self.name).finish()
+    }
+}
+
+struct Instance<'a> {
+    name: &'a str,
+    store: Store<Ctx>,
+    instance: wasmtime::Instance,
+}
+
+impl Instance<'_> {
+    /// Calls `export`, first copying `input` into the plugin's memory and
+    /// passing its address and length.
+    fn call(&mut self, export: &str, input: Option<&[u8]>) -> Result<i32, AppError> {
+        let name = self.name;
+        let error = |e: wasmtime::Error| AppError::General(format!("plugin {name}: {export}: {e:#}"));
+        let Some(input) = input else {
+            let func = self.instance.get_typed_func::<(), i32>(&mut self.store, export).map_err(error)?;
+            return func.call(&mut self.store, ()).map_err(error);
+        };
+        let len = i32::try_from(input.len()).map_err(|e| error(e.into()))?;
+        let alloc = self.instance.get_typed_func::<i32, i32>(&mut self.store, "alloc").map_err(error)?;
+        let ptr = alloc.call(&mut self.store, len).map_err(error)?;
+        let memory = self
+            .instance
+            .get_memory(&mut self.store, "memory")
+            .ok_or_else(|| error(wasmtime::Error::msg("no memory export")))?;
+        memory.write(&mut self.store, ptr as u32 as usize, input).map_err(|e| error(e.into()))?;
+        let func = self.instance.get_typed_func::<(i32, i32), i32>(&mut self.store, export).map_err(error)?;
+        func.call(&mut self.store, (ptr, len)).map_err(error)
+    }
+}
+
+/// The plugin's memory from `ptr` to `ptr + len` as text, if in bounds.
+fn read(caller: &mut Caller<'_, Ctx>, ptr: i32, len: i32) -> Option<String> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        return None;
+    };
+    let start = ptr as u32 as usize;
+    let bytes = memory.data(&caller).get(start..start.checked_add(len as u32 as usize)?)?;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn linker(engine: &Engine) -> Result<Linker<Ctx>, AppError> {
+    let error = |e: wasmtime::Error| AppError::General(format!("plugins: {e:#}"));
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap("jeff", "log", |mut caller: Caller<'_, Ctx>, ptr: i32, len: i32| {
+            if let Some(text) = read(&mut caller, ptr, len) {
+                info!("plugin {}: {text}", caller.data().plugin);
+            }
+        })
+        .map_err(error)?;
+    linker
+        .func_wrap(
+            "jeff",
+            "http_post",
+            |mut caller: Caller<'_, Ctx>, url_ptr: i32, url_len: i32, body_ptr: i32, body_len: i32| -> i32 {
+                let plugin = caller.data().plugin.clone();
+                let Some(grants) = caller.data().grants.clone() else {
+                    warn!("plugin {plugin}: http_post is only available in notify");
+                    return -1;
+                };
+                let (Some(url), Some(body)) = (read(&mut caller, url_ptr, url_len), read(&mut caller, body_ptr, body_len))
+                else {
+                    return -1;
+                };
+                let allowed = Url::parse(&url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(|h| grants.allowed_hosts.iter().any(|a| a.eq_ignore_ascii_case(h))));
+                if allowed != Some(true) {
+                    warn!("plugin {plugin}: http_post to {url} refused; its host isn't in plugins.allowed_hosts");
+                    return -1;
+                }
+                match Handle::current().block_on(grants.client.post(&url).body(body).send()) {
+                    Ok(response) => i32::from(response.status().as_u16()),
+                    Err(e) => {
+                        warn!("plugin {plugin}: http_post {url}: {e}");
+                        -1
+                    }
+                }
+            },
+        )
+        .map_err(error)?;
+    Ok(linker)
+}
@@ -0,0 +1,50 @@
+//! Third-party filters and notifiers as sandboxed WebAssembly modules, loaded
+//! from a directory. Built with the `plugins` feature.
+//!
+//! A plugin is a core WebAssembly module implementing ABI version 1. It exports
+//! `memory`, `jeff_abi_version() -> i32` returning 1, `alloc(len: i32) -> i32`
+//! returning where the host may write `len` bytes of input, and at least one of:
+//!
+//! - `keep(ptr: i32, len: i32) -> i32`: gets the location as JSON, returns 1
+//!   to keep it or 0 to drop it.
+//! - `notify(ptr: i32, len: i32) -> i32`: gets `{"subject", "text"}` as JSON,
+//!   returns 0 once delivered.
+//!
+//! It may import these from the `jeff` module, and nothing else:
+//!
+//! - `log(ptr: i32, len: i32)`: writes a line to the log.
+//! - `http_post(url_ptr: i32, url_len: i32, body_ptr: i32, body_len: i32) -> i32`:
+//!   POSTs the body to a URL on one of `allowed_hosts`, returning the status
+//!   code, or -1 if refused or failed. Always refused in `keep`.
+
+mod host;
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::notify::Delivery;
+
+pub use host::{load, Plugin};
+
+/// The ABI version plugins must report from `jeff_abi_version`.
+pub const ABI_VERSION: i32 = 1;
+
+#[derive(Debug, Deserialize)]
+pub struct PluginsConfig {
+    /// Directory of `.wasm` files, loaded in name order.
+    pub dir: PathBuf,
+    /// Hosts `http_post` may reach, e.g. `hooks.example.com`.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// Fuel, roughly instructions, a single call may burn before it's stopped.
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    /// When `notify` receives alerts.
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_fuel() -> u64 {
+    100_000_000
+}
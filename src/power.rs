@@ -0,0 +1,251 @@
+//! Slows down or pauses scheduled cycles while a laptop runs on battery or
+//! over a metered connection, and notices when it wakes from sleep.
+
+use std::{path::Path, process};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::clock;
+
+/// How far the wall clock may run ahead of the monotonic one between checks
+/// before it counts as a sleep, so clock adjustments and slow ticks don't.
+const WAKE_GAP: std::time::Duration = std::time::Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    /// Carry on as usual.
+    Ignore,
+    /// Run only every `stretch_factor`-th cycle.
+    Stretch,
+    /// Skip cycles, and watches, until the condition clears.
+    Pause,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PowerConfig {
+    #[serde(default = "default_on_battery")]
+    pub on_battery: PowerAction,
+    #[serde(default = "default_on_metered")]
+    pub on_metered: PowerAction,
+    /// How many times longer the interval gets when stretched.
+    #[serde(default = "default_stretch_factor")]
+    pub stretch_factor: u32,
+    /// Tell the notification channels when polling slows down, pauses or
+    /// returns to normal.
+    #[serde(default = "default_notify")]
+    pub notify: bool,
+}
+
+fn default_on_battery() -> PowerAction {
+    PowerAction::Stretch
+}
+
+fn default_on_metered() -> PowerAction {
+    PowerAction::Pause
+}
+
+fn default_stretch_factor() -> u32 {
+    4
+}
+
+fn default_notify() -> bool {
+    true
+}
+
+impl PowerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.stretch_factor < 2 {
+            return Err(format!("stretch_factor must be at least 2, got {}", self.stretch_factor));
+        }
+        Ok(())
+    }
+}
+
+/// What the platform reports; anything it can't tell is `false`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Conditions {
+    pub on_battery: bool,
+    pub metered: bool,
+}
+
+/// Reads battery and network state. Blocks briefly, as it may ask a system
+/// tool.
+pub fn detect() -> Conditions {
+    let conditions = Conditions {
+        on_battery: on_battery(),
+        metered: metered(),
+    };
+    debug!("Power: {conditions:?}");
+    conditions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Stretched(&'static str),
+    Paused(&'static str),
+}
+
+/// Decides, tick by tick, whether the scheduled cycle should run.
+pub struct Governor<'a> {
+    config: &'a PowerConfig,
+    minutes: u64,
+    mode: Mode,
+    /// Ticks skipped since the last cycle while stretched.
+    skipped: u32,
+}
+
+impl<'a> Governor<'a> {
+    pub fn new(config: &'a PowerConfig, minutes: u64) -> Self {
+        Governor {
+            config,
+            minutes,
+            mode: Mode::Normal,
+            skipped: 0,
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        matches!(self.mode, Mode::Paused(_))
+    }
+
+    /// Applies fresh `conditions`, returning a notice to send if the mode
+    /// changed and the config asks for one.
+    pub fn update(&mut self, conditions: Conditions) -> Option<String> {
+        let causes = [
+            (conditions.metered, self.config.on_metered, "on a metered connection"),
+            (conditions.on_battery, self.config.on_battery, "on battery"),
+        ];
+        let active = |action| causes.iter().find(|&&(on, a, _)| on && a == action).map(|&(_, _, why)| why);
+        let mode = match (active(PowerAction::Pause), active(PowerAction::Stretch)) {
+            (Some(why), _) => Mode::Paused(why),
+            (None, Some(why)) => Mode::Stretched(why),
+            (None, None) => Mode::Normal,
+        };
+        if mode == self.mode {
+            return None;
+        }
+        let was = std::mem::replace(&mut self.mode, mode);
+        self.skipped = 0;
+        let text = match mode {
+            Mode::Normal => {
+                let (Mode::Stretched(why) | Mode::Paused(why)) = was else {
+                    return None;
+                };
+                format!("No longer {why}; checking every {} minutes again.", self.minutes)
+            }
+            Mode::Stretched(why) => format!(
+                "Running {why}; checking every {} minutes instead of {} until that changes.",
+                self.minutes * u64::from(self.config.stretch_factor),
+                self.minutes
+            ),
+            Mode::Paused(why) => format!("Running {why}; paused until that changes."),
+        };
+        info!("{text}");
+        self.config.notify.then_some(text)
+    }
+
+    /// Why this tick's cycle should be skipped, if it should.
+    pub fn skip(&mut self) -> Option<String> {
+        match self.mode {
+            Mode::Normal => None,
+            Mode::Paused(why) => Some(format!("Paused {why}")),
+            Mode::Stretched(why) => {
+                self.skipped += 1;
+                if self.skipped >= self.config.stretch_factor {
+                    self.skipped = 0;
+                    return None;
+                }
+                Some(format!("Polling less often {why}"))
+            }
+        }
+    }
+}
+
+/// Notices a suspend by comparing clocks: the monotonic clock that timers
+/// run on stops while the machine sleeps, the wall clock doesn't.
+pub struct WakeDetector {
+    wall: DateTime<Utc>,
+    monotonic: Instant,
+}
+
+impl WakeDetector {
+    pub fn new() -> Self {
+        WakeDetector {
+            wall: clock::now(),
+            monotonic: Instant::now(),
+        }
+    }
+
+    /// How long the machine slept since the last check, if it did.
+    pub fn check(&mut self) -> Option<chrono::Duration> {
+        let (wall, monotonic) = (clock::now(), Instant::now());
+        let awake = chrono::Duration::from_std(monotonic - self.monotonic).unwrap_or_default();
+        let gap = (wall - self.wall) - awake;
+        self.wall = wall;
+        self.monotonic = monotonic;
+        (gap.to_std().is_ok_and(|gap| gap > WAKE_GAP)).then_some(gap)
+    }
+}
+
+/// Whether any battery is discharging.
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+    use std::fs;
+
+    let Ok(supplies) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    supplies.flatten().any(|supply| {
+        let read = |name: &str| fs::read_to_string(supply.path().join(name)).unwrap_or_default();
+        read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn on_battery() -> bool {
+    output("pmset", &["-g", "batt"]).is_some_and(|out| out.contains("'Battery Power'"))
+}
+
+#[cfg(windows)]
+fn on_battery() -> bool {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut [u32; 3]) -> i32;
+    }
+    // SYSTEM_POWER_STATUS: four flag bytes, ACLineStatus first (0 offline),
+    // then two battery times.
+    let mut status = [0u32; 3];
+    // SAFETY: `status` has SYSTEM_POWER_STATUS's size and alignment and
+    // outlives the call.
+    let ok = unsafe { GetSystemPowerStatus(&mut status) } != 0;
+    ok && status[0].to_le_bytes()[0] == 0
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn on_battery() -> bool {
+    false
+}
+
+/// Whether NetworkManager considers the connection metered, including its
+/// guesses from e.g. a phone hotspot. Other platforms aren't detected.
+fn metered() -> bool {
+    if !cfg!(target_os = "linux") || !Path::new("/run/NetworkManager").exists() {
+        return false;
+    }
+    // The `Metered` property is NMMetered: 1 yes, 3 guessed yes.
+    output(
+        "busctl",
+        &["--system", "get-property", "org.freedesktop.NetworkManager", "/org/freedesktop/NetworkManager", "org.freedesktop.NetworkManager", "Metered"],
+    )
+    .is_some_and(|out| matches!(out.trim(), "u 1" | "u 3"))
+}
+
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let out = process::Command::new(program).args(args).output().ok()?;
+    out.status.success().then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+}
@@ -0,0 +1,235 @@
+//! Checks `run` makes before its first cycle: the config against the location
+//! directory, each channel's credentials, the paths it writes to and the
+//! scheduler API. Every check runs and the results are reported together, so
+//! one start shows every problem rather than one per restart, or one hours
+//! into a run when the first alert fails to send.
+
+use std::{
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::future::join_all;
+use tracing::{error, info, warn};
+
+use crate::{
+    check_against_directory, clock,
+    directory::Directory,
+    dns::Dns,
+    notify::Dispatcher,
+    providers::{self, Provider},
+    retrying_api_client, AppError, Exit, JeffConfig,
+};
+
+enum Outcome {
+    Passed(String),
+    /// Nothing to check, or no way to check it without side effects.
+    Skipped(String),
+    Failed(String),
+}
+
+struct Check {
+    what: String,
+    outcome: Outcome,
+    /// Whether a failure is the API's rather than the setup's.
+    api: bool,
+}
+
+pub struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    fn push(&mut self, what: impl Into<String>, outcome: Outcome) {
+        self.checks.push(Check {
+            what: what.into(),
+            outcome,
+            api: false,
+        });
+    }
+
+    fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|c| matches!(c.outcome, Outcome::Failed(_)))
+    }
+
+    /// How to exit if a check failed: `Exit::Api` when only the API did.
+    pub fn exit(&self) -> Option<Exit> {
+        let mut failures = self.failures().peekable();
+        failures.peek()?;
+        Some(if failures.all(|c| c.api) { Exit::Api } else { Exit::Config })
+    }
+
+    pub fn log(&self) {
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Passed(detail) => info!("Pre-flight {}: {detail}", check.what),
+                Outcome::Skipped(detail) => info!("Pre-flight {}: skipped, {detail}", check.what),
+                Outcome::Failed(detail) => error!("Pre-flight {}: {detail}", check.what),
+            }
+        }
+        let failed: Vec<&str> = self.failures().map(|c| c.what.as_str()).collect();
+        if !failed.is_empty() {
+            error!("Pre-flight failed ({}); not starting", failed.join(", "));
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let (mark, detail) = match &check.outcome {
+                Outcome::Passed(detail) => ("ok", detail),
+                Outcome::Skipped(detail) => ("skip", detail),
+                Outcome::Failed(detail) => ("FAIL", detail),
+            };
+            writeln!(f, "{mark:<4}  {}: {detail}", check.what)?;
+        }
+        match self.failures().count() {
+            0 => writeln!(f, "All checks passed."),
+            n => writeln!(f, "{n} check(s) failed."),
+        }
+    }
+}
+
+/// `check`: runs the checks on their own and prints the report to `out`.
+pub async fn print(config: &JeffConfig, out: &mut impl Write) -> Result<Exit, AppError> {
+    let dns = Dns::new(config.dns.as_ref()).low_memory(config.low_memory);
+    let client = dns.builder().build()?;
+    let api = retrying_api_client(config, &dns, client.clone())?;
+    let providers = config
+        .providers
+        .iter()
+        .map(|&kind| providers::build(kind, &api))
+        .collect::<Result<Vec<_>, _>>()?;
+    let dispatcher = Dispatcher::from_config(&client, &dns, config).map_err(AppError::into_config)?;
+    let report = run(config, &dispatcher, &providers).await;
+    write!(out, "{report}")?;
+    Ok(report.exit().unwrap_or(Exit::Ok))
+}
+
+/// Runs every check; a check that fails doesn't stop the others.
+pub async fn run(config: &JeffConfig, dispatcher: &Dispatcher, providers: &[Arc<dyn Provider>]) -> Report {
+    let mut report = Report { checks: Vec::new() };
+
+    let directory = Directory::load(&config.directory_path);
+    report.push(
+        "locations",
+        match directory {
+            Ok(directory) if directory.is_empty() => {
+                Outcome::Skipped("no location directory in this build; run `locations update`".to_string())
+            }
+            Ok(directory) => match check_against_directory(config, &directory) {
+                Ok(()) => Outcome::Passed("config matches the location directory".to_string()),
+                Err(e) => Outcome::Failed(e.to_string()),
+            },
+            Err(e) => Outcome::Failed(e.to_string()),
+        },
+    );
+
+    for (kind, target, checked) in dispatcher.check().await {
+        let outcome = match checked {
+            Ok(true) => Outcome::Passed("credentials accepted".to_string()),
+            Ok(false) => Outcome::Skipped("can't be checked without sending".to_string()),
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+        let name = if target.starts_with(kind) { target.to_string() } else { format!("{kind} {target}") };
+        report.push(name, outcome);
+    }
+
+    for (name, path, dir) in output_paths(config, dispatcher) {
+        let outcome = match if dir { writable_dir(&path) } else { writable_file(&path) } {
+            Ok(()) => Outcome::Passed(format!("{} is writable", path.display())),
+            Err(e) => Outcome::Failed(format!("{}: {e}", path.display())),
+        };
+        report.push(name, outcome);
+    }
+
+    let date = config.scan_dates(clock::today()).first().copied();
+    let fetched = join_all(providers.iter().map(|provider| async move {
+        let outcome = match date {
+            None => Outcome::Skipped("no dates left to scan".to_string()),
+            Some(date) => match provider.fetch_date(date).await {
+                Ok(found) => Outcome::Passed(format!("answered for {date} ({} location(s) open)", found.len())),
+                Err(e) => Outcome::Failed(e.to_string()),
+            },
+        };
+        (provider.display_name(), outcome)
+    }))
+    .await;
+    for (name, outcome) in fetched {
+        report.checks.push(Check {
+            what: format!("{name} API"),
+            outcome,
+            api: true,
+        });
+    }
+    report
+}
+
+/// Every path `run` writes to, named by its config key, and whether it's a
+/// directory.
+fn output_paths(config: &JeffConfig, dispatcher: &Dispatcher) -> Vec<(&'static str, PathBuf, bool)> {
+    let mut paths = vec![
+        ("state_path", PathBuf::from(&config.state_path), false),
+        ("history_path", PathBuf::from(&config.history_path), false),
+    ];
+    // Exports are only written when no channel is configured.
+    if dispatcher.is_empty() && !config.exports.is_empty() {
+        paths.push(("exports", PathBuf::from("."), true));
+    }
+    if let Some(dir) = &config.body_log.capture_dir {
+        paths.push(("body_log.capture_dir", dir.clone(), true));
+    }
+    if let Some(archive) = &config.body_log.archive {
+        paths.push(("body_log.archive.dir", archive.dir.clone(), true));
+    }
+    if let Some(dir) = config.badge.as_ref().and_then(|b| b.output_dir.as_ref()) {
+        paths.push(("badge.output_dir", dir.clone(), true));
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(dir) = config.weekly_report.as_ref().and_then(|r| r.output_dir.as_ref()) {
+        paths.push(("weekly_report.output_dir", PathBuf::from(dir), true));
+    }
+    #[cfg(feature = "heatmap")]
+    if let Some(heatmap) = &config.heatmap {
+        paths.push(("heatmap.output_dir", PathBuf::from(&heatmap.output_dir), true));
+    }
+    paths
+}
+
+/// An existing file must open for appending (nothing is written); a new one
+/// needs a writable directory to be created in.
+fn writable_file(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return OpenOptions::new().append(true).open(path).map(drop).map_err(|e| e.to_string());
+    }
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => writable_dir(parent),
+        None => writable_dir(Path::new(".")),
+    }
+}
+
+/// A directory that exists must take a new file; one that doesn't must be
+/// creatable under its nearest existing ancestor.
+fn writable_dir(path: &Path) -> Result<(), String> {
+    let Some(existing) = path.ancestors().find(|p| p.as_os_str().is_empty() || p.exists()) else {
+        return Err("no existing parent directory".to_string());
+    };
+    let existing = if existing.as_os_str().is_empty() { Path::new(".") } else { existing };
+    if !existing.is_dir() {
+        return Err(format!("{} isn't a directory", existing.display()));
+    }
+    let probe = existing.join(format!(".preflight-{}", std::process::id()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .map_err(|e| format!("can't create files in {}: {e}", existing.display()))?;
+    if let Err(e) = std::fs::remove_file(&probe) {
+        warn!("Couldn't remove {}: {e}", probe.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! `preview`: renders what each configured channel would receive for a
+//! recorded or sample set of results, without sending anything.
+
+use std::{io::Write, path::Path, sync::Mutex};
+
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::{
+    build_providers, compose_alert,
+    calendar::Conflicts,
+    clock,
+    dns::Dns,
+    history::History,
+    import,
+    notify::Dispatcher,
+    providers::GLOBAL_ENTRY_ID,
+    state::StateStore,
+    ttp_client::{parse_entries, Location},
+    AppError, FetchedLocation, JeffConfig,
+};
+
+/// Prints each channel's message, or only those of the `kinds` given, for
+/// the results in `fixture` or else those of the latest recorded cycle.
+pub fn print(
+    config: &JeffConfig,
+    kinds: &[String],
+    fixture: Option<&Path>,
+    out: &mut impl Write,
+) -> Result<(), AppError> {
+    let found = match fixture {
+        Some(path) => load(config, path)?,
+        None => latest(config)?,
+    };
+    let found: Vec<FetchedLocation> = found.into_iter().filter(|item| config.wants(&item.loc)).collect();
+
+    let dns = Dns::new(config.dns.as_ref());
+    let dispatcher = Dispatcher::from_config(&Client::new(), &dns, config).map_err(AppError::into_config)?;
+    if dispatcher.is_empty() {
+        return Err(AppError::Config("no notification channel is configured".to_string()));
+    }
+    // A missing history file is left alone rather than created.
+    let history = if Path::new(&config.history_path).exists() {
+        History::open_read_only(&config.history_path)?
+    } else {
+        History::open(":memory:")?
+    };
+    let state = StateStore::load(&config.state_path)?;
+
+    let sources: Vec<&str> = build_providers(config)?.iter().map(|p| p.display_name()).collect();
+    let sources = sources.join(" / ");
+    // Calendars aren't read for a preview.
+    let conflicts = Conflicts::default();
+    let alert = compose_alert(config, &sources, found, (0, 0), &conflicts, &Mutex::new(state), &Mutex::new(history));
+    let messages = dispatcher.preview(&alert, &format!("{sources} availability"), kinds, &config.display);
+    if messages.is_empty() {
+        return Err(AppError::Config(format!("no configured channel is one of: {}", kinds.join(", "))));
+    }
+    for (kind, target, message) in messages {
+        writeln!(out, "=== {kind} ({target}) ===")?;
+        writeln!(out, "{}\n", message.trim_end())?;
+    }
+    Ok(())
+}
+
+/// An export, or a raw API response (such as a test fixture or a
+/// `body_log.capture_dir` file) read as availability on the first date a
+/// cycle would scan.
+fn load(config: &JeffConfig, path: &Path) -> Result<Vec<FetchedLocation>, AppError> {
+    let is = |ext: &str| path.extension().is_some_and(|e| e == ext);
+    if is("csv") {
+        return Ok(import::read(path)?.locations);
+    }
+    let text = std::fs::read_to_string(path)?;
+    let values: Vec<Value> = serde_json::from_str(&text)
+        .map_err(|e| AppError::General(format!("{}: not an API response or export: {e}", path.display())))?;
+    if values.first().is_some_and(|v| v.get("location").is_some() || v.get("dates").is_some()) {
+        return Ok(import::read(path)?.locations);
+    }
+    let today = clock::today();
+    let date = config.scan_dates(today).first().copied().unwrap_or(today);
+    parse_entries::<Location>(&text)?
+        .into_iter()
+        .map(|entry| {
+            let mut loc = entry.value;
+            loc.provider = GLOBAL_ENTRY_ID;
+            Ok(FetchedLocation {
+                date,
+                loc,
+                raw_json: serde_json::to_string(&entry.raw)?,
+                fetched_at: clock::now(),
+                undated: false,
+            })
+        })
+        .collect()
+}
+
+fn latest(config: &JeffConfig) -> Result<Vec<FetchedLocation>, AppError> {
+    let missing = || AppError::General("no cycle recorded yet; pass --fixture with sample results".to_string());
+    if !Path::new(&config.history_path).exists() {
+        return Err(missing());
+    }
+    History::open_read_only(&config.history_path)?.latest_cycle()?.ok_or_else(missing)
+}
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+use super::{Provider, Soonest};
+use crate::{
+    ttp_client::{Entry, Location, TtpApi, GLOBAL_ENTRY},
+    AppError, JeffConfig,
+};
+
+pub const ID: &str = "global_entry";
+
+/// Global Entry interview slots from the CBP scheduler.
+pub struct GlobalEntryProvider<A> {
+    api: A,
+}
+
+impl<A: TtpApi> GlobalEntryProvider<A> {
+    pub fn new(api: A) -> Self {
+        GlobalEntryProvider { api }
+    }
+}
+
+impl<A: TtpApi> Provider for GlobalEntryProvider<A> {
+    fn id(&self) -> &'static str {
+        ID
+    }
+
+    fn fetch_date(&self, date: NaiveDate) -> BoxFuture<'_, Result<Vec<Entry<Location>>, AppError>> {
+        async move {
+            let mut entries = self.api.slots_as_locations(date, GLOBAL_ENTRY).await?;
+            for entry in &mut entries {
+                entry.value.provider = ID;
+            }
+            Ok(entries)
+        }
+        .boxed()
+    }
+
+    /// One nationwide request, filtered here. It only says a location has a
+    /// slot before `before`, not when, so each comes without a date unless
+    /// `snapshot_lookups` asks each wanted location for its soonest slot, one
+    /// more request apiece. A location whose lookup fails also comes without
+    /// one, rather than failing the rest.
+    fn fetch_soonest<'a>(
+        &'a self,
+        before: NaiveDate,
+        config: &'a JeffConfig,
+    ) -> BoxFuture<'a, Result<Soonest, AppError>> {
+        async move {
+            let mut found = Vec::new();
+            for mut entry in self.api.soonest_as_locations(before, GLOBAL_ENTRY).await? {
+                entry.value.provider = ID;
+                if !config.wants(&entry.value) {
+                    continue;
+                }
+                if !config.snapshot_lookups {
+                    found.push((None, entry));
+                    continue;
+                }
+                sleep(Duration::from_secs_f64(config.api_rate_limit_seconds)).await;
+                match self.api.slots_for_location(entry.value.id, 1).await {
+                    Ok(slots) => match slots.iter().find(|s| s.active).map(|s| s.start_timestamp.date()) {
+                        Some(date) if date < before => found.push((Some(date), entry)),
+                        _ => debug!("{} no longer has a slot before {before}", entry.value.name),
+                    },
+                    Err(e) => {
+                        warn!("Skipping {}: couldn't look up its soonest slot: {e}", entry.value.name);
+                        found.push((None, entry));
+                    }
+                }
+            }
+            Ok(found)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use reqwest::{header::HeaderMap, Response};
+
+    use super::*;
+    use crate::ttp_client::{HttpClient, TtpClient};
+
+    /// Answers each path with its canned `(status, body)`, keeping the
+    /// paths asked for.
+    struct Stub {
+        answers: Vec<(&'static str, u16, &'static str)>,
+        asked: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl HttpClient for Stub {
+        fn get<'a>(
+            &'a self,
+            url: &'a str,
+            query: &'a [(&'a str, String)],
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, reqwest::Result<Response>> {
+            let location = query.iter().find(|(key, _)| *key == "locationId").map(|(_, id)| id.as_str());
+            let path = match location {
+                Some(id) => format!("slots?locationId={id}"),
+                None => url.rsplit("/schedulerapi/").next().unwrap_or(url).to_string(),
+            };
+            let (_, status, body) = self.answers.iter().find(|(p, ..)| *p == path).copied().unwrap_or(("", 404, ""));
+            self.asked.lock().unwrap().push(path);
+            let response = Response::from(http::Response::builder().status(status).body(body).unwrap());
+            async move { Ok(response) }.boxed()
+        }
+    }
+
+    const NATIONWIDE: &str = r#"[
+        {"id": 5140, "name": "JFK", "state": "NY", "city": "Jamaica", "address": "", "postalCode": "11430"},
+        {"id": 5446, "name": "SFO", "state": "CA", "city": "San Francisco", "address": "", "postalCode": "94128"},
+        {"id": 5444, "name": "Newark", "state": "NY", "city": "Newark", "address": "", "postalCode": "07114"}
+    ]"#;
+
+    fn config(lookups: bool) -> JeffConfig {
+        serde_json::from_value(serde_json::json!({
+            "enable_slack": false,
+            "slack_token": "",
+            "slack_channel_id": "",
+            "fetch_interval_minutes": 5,
+            "search_states": ["NY"],
+            "api_rate_limit_seconds": 1.0,
+            "max_concurrent_fetches": 1,
+            "max_retries": 1,
+            "scan_mode": "snapshot",
+            "snapshot_lookups": lookups,
+        }))
+        .unwrap()
+    }
+
+    fn provider(answers: Vec<(&'static str, u16, &'static str)>) -> (GlobalEntryProvider<TtpClient>, Arc<Mutex<Vec<String>>>) {
+        let asked = Arc::default();
+        let stub = Stub {
+            answers,
+            asked: Arc::clone(&asked),
+        };
+        (GlobalEntryProvider::new(TtpClient::new(stub)), asked)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn snapshot_is_one_request_filtered_here() {
+        let (provider, asked) = provider(vec![("slots/asLocations", 200, NATIONWIDE)]);
+        let before = NaiveDate::from_ymd_opt(2025, 4, 1).unwrap();
+        let found = provider.fetch_soonest(before, &config(false)).await.unwrap();
+        let found: Vec<_> = found.iter().map(|(date, entry)| (*date, entry.value.id)).collect();
+        assert_eq!(found, [(None, 5140), (None, 5444)]);
+        assert_eq!(*asked.lock().unwrap(), ["slots/asLocations"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failed_lookup_leaves_just_that_location_undated() {
+        let (provider, asked) = provider(vec![
+            ("slots/asLocations", 200, NATIONWIDE),
+            ("slots?locationId=5140", 500, "oops"),
+            (
+                "slots?locationId=5444",
+                200,
+                r#"[{"locationId": 5444, "startTimestamp": "2025-03-14T09:00", "endTimestamp": "2025-03-14T09:15", "active": true, "duration": 15}]"#,
+            ),
+        ]);
+        let before = NaiveDate::from_ymd_opt(2025, 4, 1).unwrap();
+        let found = provider.fetch_soonest(before, &config(true)).await.unwrap();
+        let found: Vec<_> = found.iter().map(|(date, entry)| (date.map(|d| d.to_string()), entry.value.id)).collect();
+        assert_eq!(found, [(None, 5140), (Some("2025-03-14".to_string()), 5444)]);
+        assert_eq!(asked.lock().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,94 @@
+//! Appointment sources. Each provider turns one date into matching locations;
+//! filtering, notification and persistence are shared.
+
+mod global_entry;
+mod shared;
+
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use futures::{future::BoxFuture, FutureExt};
+use serde::Deserialize;
+
+use crate::{
+    ttp_client::{Entry, Location, TtpClient},
+    AppError, JeffConfig,
+};
+
+pub use global_entry::{GlobalEntryProvider, ID as GLOBAL_ENTRY_ID};
+pub use shared::SharedProvider;
+
+/// Identifies a location across providers: `5140` for Global Entry, `<provider>:12` otherwise.
+pub fn location_key(provider: &str, location_id: usize) -> String {
+    if provider == global_entry::ID {
+        location_id.to_string()
+    } else {
+        format!("{provider}:{location_id}")
+    }
+}
+
+/// The built-in provider ID equal to `provider`, e.g. as read back from history.
+#[cfg(feature = "sqlite")]
+pub fn known_id(provider: &str) -> Option<&'static str> {
+    [global_entry::ID].into_iter().find(|id| *id == provider)
+}
+
+/// Human-readable name for a provider ID, used in messages.
+pub fn display_name(provider: &str) -> &'static str {
+    match provider {
+        global_entry::ID => "Global Entry",
+        _ => "Unknown",
+    }
+}
+
+impl Location {
+    pub fn key(&self) -> String {
+        location_key(self.provider, self.id)
+    }
+
+    pub fn same_as(&self, other: &Location) -> bool {
+        self.provider == other.provider && self.id == other.id
+    }
+}
+
+/// Each location with its soonest open date, or `None` when it's only known
+/// to have a slot before the date asked about.
+pub type Soonest = Vec<(Option<NaiveDate>, Entry<Location>)>;
+
+pub trait Provider: Send + Sync {
+    /// Stable identifier stored with every result, e.g. `global_entry`.
+    fn id(&self) -> &'static str;
+
+    /// Human-readable name used in messages.
+    fn display_name(&self) -> &'static str {
+        display_name(self.id())
+    }
+
+    /// Locations with availability on `date`, with `Location::provider` set.
+    fn fetch_date(&self, date: NaiveDate) -> BoxFuture<'_, Result<Vec<Entry<Location>>, AppError>>;
+
+    /// For `scan_mode: snapshot`: the locations `config` wants with an open
+    /// slot before `before`, each with its soonest date, found without asking
+    /// about each date in turn.
+    fn fetch_soonest<'a>(
+        &'a self,
+        _before: NaiveDate,
+        _config: &'a JeffConfig,
+    ) -> BoxFuture<'a, Result<Soonest, AppError>> {
+        let name = self.display_name();
+        async move { Err(AppError::General(format!("{name} has no nationwide soonest-slot view"))) }.boxed()
+    }
+}
+
+/// Providers that can be enabled in the config's `providers` list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    GlobalEntry,
+}
+
+pub fn build(kind: ProviderKind, api: &TtpClient) -> Result<Arc<dyn Provider>, AppError> {
+    Ok(match kind {
+        ProviderKind::GlobalEntry => Arc::new(GlobalEntryProvider::new(api.clone())),
+    })
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::time::Instant;
+use tracing::debug;
+
+use super::{Provider, Soonest};
+use crate::{
+    clock,
+    ttp_client::{Entry, Location},
+    AppError, JeffConfig,
+};
+
+/// The last answer for one date, guarded so concurrent askers wait for a
+/// single request instead of each sending their own.
+type DateSlot = Arc<tokio::sync::Mutex<Option<(Instant, Vec<Entry<Location>>)>>>;
+
+/// Lets the general scan and every watch share one provider: a date asked for
+/// again within `window` of the last answer, or while a request for it is in
+/// flight, gets that answer instead of a new request. Errors aren't shared.
+/// Answers are dropped once they're too old to reuse or their date has passed.
+pub struct SharedProvider {
+    inner: Arc<dyn Provider>,
+    window: Duration,
+    dates: Mutex<HashMap<NaiveDate, DateSlot>>,
+}
+
+impl SharedProvider {
+    pub fn new(inner: Arc<dyn Provider>, window: Duration) -> Self {
+        SharedProvider {
+            inner,
+            window,
+            dates: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drops dates before today, and answers no one is waiting on that are
+    /// too old to reuse or were errors.
+    fn evict(&self, dates: &mut HashMap<NaiveDate, DateSlot>) {
+        let today = clock::today();
+        dates.retain(|date, slot| {
+            *date >= today
+                && (Arc::strong_count(slot) > 1
+                    || slot.try_lock().map_or(true, |last| {
+                        last.as_ref().is_some_and(|(at, _)| at.elapsed() < self.window)
+                    }))
+        });
+    }
+}
+
+impl Provider for SharedProvider {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn fetch_date(&self, date: NaiveDate) -> BoxFuture<'_, Result<Vec<Entry<Location>>, AppError>> {
+        async move {
+            let slot = {
+                let mut dates = self.dates.lock().unwrap();
+                self.evict(&mut dates);
+                Arc::clone(dates.entry(date).or_default())
+            };
+            let mut last = slot.lock().await;
+            if let Some((at, entries)) = &*last {
+                if at.elapsed() < self.window {
+                    debug!("Reusing {} results for {date} from {:.0?} ago", self.id(), at.elapsed());
+                    return Ok(entries.clone());
+                }
+            }
+            let entries = self.inner.fetch_date(date).await?;
+            *last = Some((Instant::now(), entries.clone()));
+            Ok(entries)
+        }
+        .boxed()
+    }
+
+    fn fetch_soonest<'a>(
+        &'a self,
+        before: NaiveDate,
+        config: &'a JeffConfig,
+    ) -> BoxFuture<'a, Result<Soonest, AppError>> {
+        self.inner.fetch_soonest(before, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use chrono::{TimeZone, Utc};
+    use tokio::time::sleep;
+
+    use super::*;
+    use crate::clock::SimulatedClock;
+
+    /// Answers each date with nothing after a second, counting requests, and
+    /// fails while `down` is set.
+    #[derive(Default)]
+    struct Counting {
+        asked: AtomicUsize,
+        down: AtomicBool,
+    }
+
+    impl Provider for Counting {
+        fn id(&self) -> &'static str {
+            "global_entry"
+        }
+
+        fn fetch_date(&self, _date: NaiveDate) -> BoxFuture<'_, Result<Vec<Entry<Location>>, AppError>> {
+            async move {
+                self.asked.fetch_add(1, Ordering::Relaxed);
+                sleep(Duration::from_secs(1)).await;
+                if self.down.load(Ordering::Relaxed) {
+                    return Err(AppError::General("down".to_string()));
+                }
+                Ok(Vec::new())
+            }
+            .boxed()
+        }
+    }
+
+    fn shared() -> (Arc<Counting>, SharedProvider) {
+        let inner = Arc::new(Counting::default());
+        let provider: Arc<dyn Provider> = inner.clone();
+        (inner, SharedProvider::new(provider, Duration::from_secs(60)))
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn askers_within_the_window_share_one_request() {
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2025, 3, 10, 8, 0, 0).unwrap()));
+        let (inner, shared) = shared();
+        let date = day("2025-03-14");
+        let (a, b) = tokio::join!(shared.fetch_date(date), shared.fetch_date(date));
+        assert!(a.is_ok() && b.is_ok());
+        assert_eq!(inner.asked.load(Ordering::Relaxed), 1);
+        sleep(Duration::from_secs(30)).await;
+        shared.fetch_date(date).await.unwrap();
+        assert_eq!(inner.asked.load(Ordering::Relaxed), 1);
+        shared.fetch_date(day("2025-03-15")).await.unwrap();
+        assert_eq!(inner.asked.load(Ordering::Relaxed), 2);
+
+        sleep(Duration::from_secs(60)).await;
+        inner.down.store(true, Ordering::Relaxed);
+        assert!(shared.fetch_date(date).await.is_err());
+        assert!(shared.fetch_date(date).await.is_err());
+        assert_eq!(inner.asked.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn old_answers_and_past_dates_are_dropped() {
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2025, 3, 10, 8, 0, 0).unwrap()));
+        let (inner, shared) = shared();
+        for date in ["2025-03-10", "2025-03-11", "2025-03-12"] {
+            shared.fetch_date(day(date)).await.unwrap();
+        }
+        assert_eq!(shared.dates.lock().unwrap().len(), 3);
+
+        sleep(Duration::from_secs(24 * 3600)).await;
+        shared.fetch_date(day("2025-03-12")).await.unwrap();
+        assert_eq!(inner.asked.load(Ordering::Relaxed), 4);
+        let dates = shared.dates.lock().unwrap();
+        assert_eq!(dates.keys().collect::<Vec<_>>(), [&day("2025-03-12")]);
+    }
+}
@@ -0,0 +1,168 @@
+//! Python bindings, built by maturin into a `jeff` module (see
+//! `pyproject.toml`), so scripts can fetch availability and run the config's
+//! filters without shelling out to the binary and parsing its CSV.
+
+use std::{ffi::CString, sync::Arc};
+
+use chrono::NaiveDate;
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyRuntimeWarning},
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use serde_json::json;
+use tokio::runtime::Runtime;
+
+use crate::{
+    build_providers, directory::US_STATES, load_config, providers::GLOBAL_ENTRY_ID, scan, ttp_client::Location,
+    clock,
+    validate, AppError, JeffConfig, Scan,
+};
+
+create_exception!(jeff, JeffError, PyException, "A config, API or other error from jeff.");
+
+impl From<AppError> for PyErr {
+    fn from(e: AppError) -> PyErr {
+        JeffError::new_err(e.to_string())
+    }
+}
+
+/// A loaded config: its filters, dates and providers.
+#[pyclass(frozen, module = "jeff")]
+struct Config {
+    config: Arc<JeffConfig>,
+}
+
+#[pymethods]
+impl Config {
+    /// Loads and validates a config file, as the command line does.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Config> {
+        Ok(Config {
+            config: Arc::new(load_config(path)?),
+        })
+    }
+
+    /// Like `load`, from the config's JSON text; `include` isn't supported.
+    #[staticmethod]
+    fn from_json(text: &str) -> PyResult<Config> {
+        let mut config: JeffConfig = serde_json::from_str(text).map_err(AppError::from)?;
+        validate(&config)?;
+        config.load_extensions()?;
+        Ok(Config { config: Arc::new(config) })
+    }
+
+    /// Why the filters drop `location`, a `slots/asLocations` record as a
+    /// dict, or None if it's kept.
+    fn rejection(&self, location: &Bound<'_, PyAny>) -> PyResult<Option<String>> {
+        Ok(self.config.rejection(&to_location(location)?))
+    }
+
+    /// The records in `locations` the filters keep.
+    fn filter<'py>(&self, locations: Vec<Bound<'py, PyAny>>) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let mut kept = Vec::new();
+        for location in locations {
+            if self.rejection(&location)?.is_none() {
+                kept.push(location);
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Open slots on `dates`, or on the config's dates, with its providers
+    /// and filters; see the module's `fetch_availability`.
+    #[pyo3(signature = (dates=None))]
+    fn fetch_availability(&self, py: Python<'_>, dates: Option<Vec<NaiveDate>>) -> PyResult<Py<PyList>> {
+        let dates = dates.unwrap_or_else(|| self.config.scan_dates(clock::today()));
+        fetch(py, Arc::clone(&self.config), dates)
+    }
+}
+
+/// Open slots on `dates` in `states` (default: all), as dicts with `slot`,
+/// `date`, `provider`, `location_id`, `name`, `city`, `state` and the API's
+/// `location` record. `service` is a provider: `global_entry` (default)
+/// or `endpoint`, which needs its endpoint, so use `Config.fetch_availability`
+/// for that. Raises JeffError if no date loaded, and warns if some didn't.
+#[pyfunction]
+#[pyo3(signature = (dates, states=None, service="global_entry"))]
+fn fetch_availability(
+    py: Python<'_>,
+    dates: Vec<NaiveDate>,
+    states: Option<Vec<String>>,
+    service: &str,
+) -> PyResult<Py<PyList>> {
+    let (Some(start), Some(end)) = (dates.iter().min(), dates.iter().max()) else {
+        return Ok(PyList::empty(py).unbind());
+    };
+    let states = states.unwrap_or_else(|| US_STATES.iter().map(|(code, _)| code.to_string()).collect());
+    let config: JeffConfig = serde_json::from_value(json!({
+        "enable_slack": false,
+        "slack_token": "",
+        "slack_channel_id": "",
+        "fetch_interval_minutes": 0,
+        "providers": [service.to_lowercase().replace([' ', '-'], "_")],
+        "search_states": states,
+        "date_range": { "start": start, "end": end },
+        // The README's example settings.
+        "api_rate_limit_seconds": 1.0,
+        "max_concurrent_fetches": 5,
+        "max_retries": 3,
+    }))
+    .map_err(AppError::from)?;
+    validate(&config)?;
+    fetch(py, Arc::new(config), dates)
+}
+
+fn fetch(py: Python<'_>, config: Arc<JeffConfig>, mut dates: Vec<NaiveDate>) -> PyResult<Py<PyList>> {
+    dates.sort();
+    dates.dedup();
+    let found = py.allow_threads(|| -> Result<Scan, AppError> {
+        let runtime = Runtime::new()?;
+        runtime.block_on(async {
+            let providers = build_providers(&config)?;
+            Ok(scan(&providers, &config, &dates).await)
+        })
+    })?;
+
+    let attempted = found.scanned_ok.len() + found.dates_failed;
+    if found.dates_failed > 0 && found.dates_failed == attempted {
+        return Err(JeffError::new_err(format!("all {attempted} date(s) failed to load")));
+    }
+    if found.dates_failed > 0 {
+        let message = CString::new(format!("{} of {attempted} date(s) failed to load", found.dates_failed))?;
+        PyErr::warn(py, &py.get_type::<PyRuntimeWarning>(), &message, 1)?;
+    }
+
+    let loads = py.import("json")?.getattr("loads")?;
+    let slots = PyList::empty(py);
+    for item in &found.locations {
+        let slot = PyDict::new(py);
+        slot.set_item("slot", item.slot_key())?;
+        slot.set_item("date", item.date)?;
+        slot.set_item("provider", item.loc.provider)?;
+        slot.set_item("location_id", item.loc.id)?;
+        slot.set_item("name", config.display.name(&item.loc))?;
+        slot.set_item("city", &item.loc.city)?;
+        slot.set_item("state", &item.loc.state)?;
+        slot.set_item("location", loads.call1((&item.raw_json,))?)?;
+        slots.append(slot)?;
+    }
+    Ok(slots.unbind())
+}
+
+/// A Python dict as the `Location` the filters see.
+fn to_location(record: &Bound<'_, PyAny>) -> PyResult<Location> {
+    let text: String = record.py().import("json")?.call_method1("dumps", (record,))?.extract()?;
+    let mut location: Location = serde_json::from_str(&text).map_err(AppError::from)?;
+    location.provider = GLOBAL_ENTRY_ID;
+    Ok(location)
+}
+
+#[pymodule]
+fn jeff(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("JeffError", m.py().get_type::<JeffError>())?;
+    m.add_class::<Config>()?;
+    m.add_function(wrap_pyfunction!(fetch_availability, m)?)?;
+    Ok(())
+}
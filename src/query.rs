@@ -0,0 +1,94 @@
+#[cfg(feature = "sqlite")]
+use std::io::Write;
+
+use clap::ValueEnum;
+#[cfg(feature = "sqlite")]
+use rusqlite::types::ValueRef;
+
+#[cfg(feature = "sqlite")]
+use crate::{history::History, AppError};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+}
+
+/// Runs `sql` against the history database and prints the result set.
+#[cfg(feature = "sqlite")]
+pub fn run(
+    history: &History,
+    sql: &str,
+    format: OutputFormat,
+    out: &mut impl Write,
+) -> Result<(), AppError> {
+    let mut stmt = history.conn().prepare(sql)?;
+    let header: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let width = header.len();
+
+    let mut rows = Vec::new();
+    let mut cursor = stmt.query([])?;
+    while let Some(row) = cursor.next()? {
+        let mut cells = Vec::with_capacity(width);
+        for i in 0..width {
+            cells.push(cell_to_string(row.get_ref(i)?));
+        }
+        rows.push(cells);
+    }
+
+    match format {
+        OutputFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(out);
+            wtr.write_record(&header)?;
+            for row in &rows {
+                wtr.write_record(row)?;
+            }
+            wtr.flush()?;
+        }
+        OutputFormat::Table => write_table(&header, &rows, out)?,
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn cell_to_string(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+        ValueRef::Blob(b) => format!("<{} bytes>", b.len()),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn write_table(header: &[String], rows: &[Vec<String>], out: &mut impl Write) -> std::io::Result<()> {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: &[String]| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{c:<w$}"))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+
+    writeln!(out, "{}", line(header))?;
+    writeln!(
+        out,
+        "{}",
+        widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-")
+    )?;
+    for row in rows {
+        writeln!(out, "{}", line(row))?;
+    }
+    writeln!(out, "({} row{})", rows.len(), if rows.len() == 1 { "" } else { "s" })
+}
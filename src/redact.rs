@@ -0,0 +1,182 @@
+//! Keeps tokens and passwords out of logs: config secrets print as
+//! `[redacted]`, and every log line is scrubbed of known secret values and
+//! anything shaped like a Slack token or bearer credential.
+
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    sync::RwLock,
+};
+
+use serde::{Deserialize, Deserializer};
+use tracing_subscriber::fmt::MakeWriter;
+
+const REDACTED: &str = "[redacted]";
+
+/// Prefixes of Slack bot, user, refresh and app-level tokens.
+const TOKEN_PREFIXES: &[&str] = &["xoxb-", "xoxp-", "xoxa-", "xoxe-", "xoxr-", "xoxs-", "xapp-", "Bearer "];
+
+/// Every secret loaded from config, scrubbed verbatim wherever it shows up.
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// A config value that must never be logged. Its `Debug` output is
+/// `[redacted]`, and loading one registers it with the log scrubber.
+#[derive(Clone, Default)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(d)?;
+        register(&value);
+        Ok(Secret(value))
+    }
+}
+
+/// Adds `secret` to the values scrubbed from every log line.
+pub fn register(secret: &str) {
+    // Very short values would scrub ordinary words.
+    if secret.len() < 4 {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// `text` with registered secrets and token-shaped strings replaced.
+pub fn scrub(text: &str) -> Cow<'_, str> {
+    let mut out = Cow::Borrowed(text);
+    for secret in SECRETS.read().unwrap().iter() {
+        if out.contains(secret.as_str()) {
+            out = Cow::Owned(out.replace(secret.as_str(), REDACTED));
+        }
+    }
+    for prefix in TOKEN_PREFIXES {
+        let mut from = 0;
+        while let Some(pos) = out[from..].find(prefix) {
+            let start = from + pos + prefix.len();
+            let len = out[start..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .unwrap_or(out.len() - start);
+            if len > 0 {
+                out = Cow::Owned(format!("{}{REDACTED}{}", &out[..start], &out[start + len..]));
+            }
+            from = start + if len > 0 { REDACTED.len() } else { 0 };
+        }
+    }
+    out
+}
+
+/// Wraps a [`MakeWriter`] so each formatted log line is scrubbed before it's written.
+pub struct Scrubbed<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Scrubbed<M> {
+    type Writer = ScrubWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ScrubWriter {
+            inner: self.0.make_writer(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// Buffers one event and writes it, scrubbed, when dropped.
+pub struct ScrubWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Write for ScrubWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.buf);
+        self.inner.write_all(scrub(&text).as_bytes())?;
+        self.buf.clear();
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ScrubWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(all(feature = "email", feature = "mute-links"))]
+    fn loaded_config_debug_shows_no_secret() {
+        use serde_json::json;
+
+        use crate::{load_config, notify::tests::state_path};
+
+        let config = json!({
+            "enable_slack": true,
+            "slack_token": "secret-slack-token",
+            "slack_app_token": "secret-slack-app-token",
+            "slack_channel_id": "C123",
+            "fetch_interval_minutes": 5,
+            "search_states": ["NY"],
+            "api_rate_limit_seconds": 1.0,
+            "max_concurrent_fetches": 1,
+            "max_retries": 1,
+            "window_mode": "rolling",
+            "window_days": 30,
+            "email": {
+                "smtp_host": "smtp.example.com",
+                "username": "me",
+                "password": "secret-smtp-password",
+                "from": "me@example.com",
+                "to": ["me@example.com"],
+            },
+            "bark": { "device_key": "secret-bark-key" },
+            "pushcut": { "api_key": "secret-pushcut-key", "notification": "Slots" },
+            "gotify": { "server": "https://gotify.example.com", "token": "secret-gotify-token" },
+            "matrix": { "url": "https://matrix.example.com/hook/secret-matrix-url", "token": "secret-matrix-token" },
+            "whatsapp": {
+                "token": "secret-whatsapp-token",
+                "phone_number_id": "1",
+                "recipients": ["+15555550100"],
+                "template": "slots",
+            },
+            "github": { "token": "secret-github-token", "repo": "me/slots" },
+            "escalation": {
+                "pagerduty": { "routing_key": "secret-pagerduty-key" },
+                "opsgenie": { "api_key": "secret-opsgenie-key" },
+            },
+            "metrics": { "format": "influx", "url": "https://influx.example.com", "token": "secret-metrics-token" },
+            "mute_links": { "listen": "127.0.0.1:0", "base_url": "https://mute.example.com", "secret": "secret-mute-link-signing-key" },
+            "calendar": [{ "url": "https://dav.example.com", "username": "me", "password": "secret-calendar-password" }],
+        });
+        let path = state_path("redact-config");
+        std::fs::write(&path, config.to_string()).unwrap();
+        let loaded = load_config(&path);
+        let _ = std::fs::remove_file(&path);
+
+        let debug = format!("{:?}", loaded.unwrap());
+        assert!(debug.contains("[redacted]"));
+        assert!(!debug.contains("secret-"), "{debug}");
+    }
+}
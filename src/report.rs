@@ -0,0 +1,554 @@
+use std::{path::Path, sync::Mutex};
+
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Utc, Weekday};
+use reqwest::Client;
+use rusqlite::params;
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    clock,
+    format::escape_html,
+    history::{History, TS_FORMAT},
+    slack,
+    state::StateStore,
+    AppError, JeffConfig,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct WeeklyReportConfig {
+    #[serde(default = "default_weekday")]
+    pub weekday: Weekday,
+    /// Local hour of day (0-23) at which the report becomes due.
+    #[serde(default = "default_hour")]
+    pub hour: u32,
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// Directory to write reports into; omitted means don't write to disk.
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub post_to_slack: bool,
+}
+
+pub fn default_weekday() -> Weekday {
+    Weekday::Mon
+}
+
+pub fn default_hour() -> u32 {
+    9
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LocationStats {
+    pub name: String,
+    pub state: String,
+    pub open_days: i64,
+    pub earliest: String,
+    pub cycles_seen: i64,
+}
+
+#[derive(Debug)]
+pub struct SlotLifetime {
+    pub name: String,
+    pub slot_date: String,
+    pub minutes: f64,
+}
+
+/// How long alerted slots stayed open after their first alert.
+#[derive(Debug)]
+pub struct Survival {
+    pub slots: usize,
+    pub median_seconds: f64,
+    /// A quarter of the slots were gone within this.
+    pub quartile_seconds: f64,
+}
+
+/// Everything that goes into one weekly report.
+#[derive(Debug)]
+pub struct WeeklySummary {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub cycles: i64,
+    pub dates_scanned: i64,
+    pub dates_failed: i64,
+    pub alerts_sent: i64,
+    pub slots_alerted: i64,
+    pub locations: Vec<LocationStats>,
+    pub appeared_by_hour: [i64; 24],
+    pub fastest_gone: Vec<SlotLifetime>,
+    /// `None` if no alerted slot disappeared this week.
+    pub survival: Option<Survival>,
+}
+
+impl History {
+    pub fn weekly_summary(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<WeeklySummary, AppError> {
+        let conn = self.conn();
+        let from = since.format(TS_FORMAT).to_string();
+        let to = until.format(TS_FORMAT).to_string();
+
+        let (cycles, dates_scanned, dates_failed) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(dates_scanned), 0), COALESCE(SUM(dates_failed), 0)
+             FROM cycles WHERE finished_at >= ?1 AND finished_at < ?2",
+            params![from, to],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )?;
+
+        let (alerts_sent, slots_alerted) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(slot_count), 0)
+             FROM alerts WHERE sent_at >= ?1 AND sent_at < ?2",
+            params![from, to],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+
+        let mut stmt = conn.prepare(
+            "SELECT name, state, COUNT(DISTINCT slot_date), MIN(slot_date), COUNT(DISTINCT cycle_id)
+             FROM observations WHERE observed_at >= ?1 AND observed_at < ?2
+             GROUP BY provider, location_id ORDER BY 3 DESC, 1 LIMIT 10",
+        )?;
+        let locations = stmt
+            .query_map(params![from, to], |r| {
+                Ok(LocationStats {
+                    name: r.get(0)?,
+                    state: r.get(1)?,
+                    open_days: r.get(2)?,
+                    earliest: r.get(3)?,
+                    cycles_seen: r.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut appeared_by_hour = [0; 24];
+        let mut stmt = conn.prepare(
+            "SELECT CAST(strftime('%H', first_seen) AS INTEGER), COUNT(*)
+             FROM slots WHERE first_seen >= ?1 AND first_seen < ?2 GROUP BY 1",
+        )?;
+        let hours = stmt.query_map(params![from, to], |r| Ok((r.get::<_, usize>(0)?, r.get(1)?)))?;
+        for row in hours {
+            let (hour, count) = row?;
+            appeared_by_hour[hour % 24] = count;
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT (SELECT name FROM observations o
+                     WHERE o.provider = s.provider AND o.location_id = s.location_id LIMIT 1),
+                    slot_date,
+                    (julianday(gone_at) - julianday(first_seen)) * 1440
+             FROM slots s WHERE gone_at >= ?1 AND gone_at < ?2
+             ORDER BY 3 ASC LIMIT 5",
+        )?;
+        let fastest_gone = stmt
+            .query_map(params![from, to], |r| {
+                Ok(SlotLifetime {
+                    name: r.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                    slot_date: r.get(1)?,
+                    minutes: r.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let survival = self.alert_survival(since, until)?;
+
+        Ok(WeeklySummary {
+            since,
+            until,
+            cycles,
+            dates_scanned,
+            dates_failed,
+            alerts_sent,
+            slots_alerted,
+            locations,
+            appeared_by_hour,
+            fastest_gone,
+            survival,
+        })
+    }
+
+    /// Survival of alerted slots that disappeared between `since` and `until`.
+    /// Disappearance is only noticed by the next scan, so times are rounded up
+    /// to it.
+    pub fn alert_survival(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Option<Survival>, AppError> {
+        let mut stmt = self.conn().prepare(
+            "SELECT (julianday(gone_at) - julianday(alerted_at)) * 86400 FROM alerted_slots
+             WHERE gone_at >= ?1 AND gone_at < ?2 ORDER BY 1",
+        )?;
+        let seconds = stmt
+            .query_map(
+                params![since.format(TS_FORMAT).to_string(), until.format(TS_FORMAT).to_string()],
+                |r| r.get::<_, f64>(0),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        if seconds.is_empty() {
+            return Ok(None);
+        }
+        let at = |q: f64| seconds[((seconds.len() - 1) as f64 * q).round() as usize];
+        Ok(Some(Survival {
+            slots: seconds.len(),
+            median_seconds: at(0.5),
+            quartile_seconds: at(0.25),
+        }))
+    }
+}
+
+/// Format-independent building blocks of a report.
+enum Block {
+    Heading(String),
+    Paragraph(String),
+    Bullets(Vec<String>),
+    Table {
+        header: Vec<&'static str>,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+fn blocks(summary: &WeeklySummary) -> Vec<Block> {
+    let mut out = vec![
+        Block::Heading("Global Entry Weekly Report".to_string()),
+        Block::Paragraph(format!(
+            "{} – {} UTC",
+            summary.since.format("%Y-%m-%d %H:%M"),
+            summary.until.format("%Y-%m-%d %H:%M")
+        )),
+        Block::Heading("API health".to_string()),
+    ];
+
+    let ok = summary.dates_scanned - summary.dates_failed;
+    let success = if summary.dates_scanned > 0 {
+        format!("{:.1}%", ok as f64 * 100.0 / summary.dates_scanned as f64)
+    } else {
+        "n/a".to_string()
+    };
+    out.push(Block::Bullets(vec![
+        format!("Cycles run: {}", summary.cycles),
+        format!(
+            "Date fetches: {ok} ok, {} failed ({success} success)",
+            summary.dates_failed
+        ),
+    ]));
+
+    out.push(Block::Heading("Alerts".to_string()));
+    let mut alerts = vec![format!("Alerts sent: {} ({} slots)", summary.alerts_sent, summary.slots_alerted)];
+    if let Some(s) = &summary.survival {
+        alerts.push(format!(
+            "Median survival after an alert: {} ({} slots gone this week; a quarter lasted {} or less)",
+            short_duration(s.median_seconds),
+            s.slots,
+            short_duration(s.quartile_seconds)
+        ));
+    }
+    out.push(Block::Bullets(alerts));
+
+    out.push(Block::Heading("Availability by location".to_string()));
+    if summary.locations.is_empty() {
+        out.push(Block::Paragraph("No availability observed this week.".to_string()));
+    } else {
+        out.push(Block::Table {
+            header: vec!["Location", "State", "Open days seen", "Earliest date", "Cycles with availability"],
+            rows: summary
+                .locations
+                .iter()
+                .map(|l| {
+                    vec![
+                        l.name.clone(),
+                        l.state.clone(),
+                        l.open_days.to_string(),
+                        l.earliest.clone(),
+                        l.cycles_seen.to_string(),
+                    ]
+                })
+                .collect(),
+        });
+    }
+
+    let hours: Vec<Vec<String>> = summary
+        .appeared_by_hour
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| **n > 0)
+        .map(|(h, n)| vec![format!("{h:02}:00"), n.to_string()])
+        .collect();
+    if !hours.is_empty() {
+        out.push(Block::Heading("When new slots appeared (UTC)".to_string()));
+        out.push(Block::Table {
+            header: vec!["Hour", "New slots"],
+            rows: hours,
+        });
+    }
+
+    if !summary.fastest_gone.is_empty() {
+        out.push(Block::Heading("Fastest-disappearing slots".to_string()));
+        out.push(Block::Table {
+            header: vec!["Location", "Date", "Lifetime"],
+            rows: summary
+                .fastest_gone
+                .iter()
+                .map(|s| vec![s.name.clone(), s.slot_date.clone(), format!("{:.0} min", s.minutes)])
+                .collect(),
+        });
+    }
+
+    out
+}
+
+pub fn render(summary: &WeeklySummary, format: ReportFormat) -> String {
+    let blocks = blocks(summary);
+    match format {
+        ReportFormat::Markdown => render_markdown(&blocks),
+        ReportFormat::Html => render_html(&blocks),
+    }
+}
+
+fn render_markdown(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        match block {
+            Block::Heading(h) if i == 0 => out.push_str(&format!("# {h}\n\n")),
+            Block::Heading(h) => out.push_str(&format!("## {h}\n\n")),
+            Block::Paragraph(p) => out.push_str(&format!("{p}\n\n")),
+            Block::Bullets(items) => {
+                for item in items {
+                    out.push_str(&format!("- {item}\n"));
+                }
+                out.push('\n');
+            }
+            Block::Table { header, rows } => {
+                out.push_str(&format!("| {} |\n", header.join(" | ")));
+                out.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+                for row in rows {
+                    out.push_str(&format!("| {} |\n", row.join(" | ")));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn render_html(blocks: &[Block]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Global Entry Weekly Report</title></head><body>\n",
+    );
+    for (i, block) in blocks.iter().enumerate() {
+        match block {
+            Block::Heading(h) if i == 0 => out.push_str(&format!("<h1>{}</h1>\n", escape_html(h))),
+            Block::Heading(h) => out.push_str(&format!("<h2>{}</h2>\n", escape_html(h))),
+            Block::Paragraph(p) => out.push_str(&format!("<p>{}</p>\n", escape_html(p))),
+            Block::Bullets(items) => {
+                out.push_str("<ul>\n");
+                for item in items {
+                    out.push_str(&format!("<li>{}</li>\n", escape_html(item)));
+                }
+                out.push_str("</ul>\n");
+            }
+            Block::Table { header, rows } => {
+                out.push_str("<table>\n<tr>");
+                for h in header {
+                    out.push_str(&format!("<th>{}</th>", escape_html(h)));
+                }
+                out.push_str("</tr>\n");
+                for row in rows {
+                    out.push_str("<tr>");
+                    for cell in row {
+                        out.push_str(&format!("<td>{}</td>", escape_html(cell)));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+        }
+    }
+    out.push_str("</body></html>\n");
+    out
+}
+
+/// `45s`, `3m12s` or `2h05m`.
+fn short_duration(seconds: f64) -> String {
+    let s = seconds.max(0.0).round() as u64;
+    match s {
+        s if s < 60 => format!("{s}s"),
+        s if s < 3600 => format!("{}m{:02}s", s / 60, s % 60),
+        s => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+/// The most recent scheduled report time at or before `now`.
+pub fn last_scheduled(now: DateTime<Local>, weekday: Weekday, hour: u32) -> Option<DateTime<Local>> {
+    let today = now.date_naive();
+    let days_back = (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    let date = today - Duration::days(days_back.into());
+    let scheduled = Local.from_local_datetime(&date.and_hms_opt(hour, 0, 0)?).earliest()?;
+    if scheduled > now {
+        Some(scheduled - Duration::days(7))
+    } else {
+        Some(scheduled)
+    }
+}
+
+/// Generates and delivers the weekly report if one is due.
+pub async fn maybe_send_weekly(
+    client: &Client,
+    config: &JeffConfig,
+    state: &Mutex<StateStore>,
+    history: &Mutex<History>,
+) -> Result<(), AppError> {
+    let Some(report_cfg) = &config.weekly_report else {
+        return Ok(());
+    };
+    let Some(due) = last_scheduled(clock::local_now(), report_cfg.weekday, report_cfg.hour) else {
+        return Ok(());
+    };
+    let due = due.with_timezone(&Utc);
+    if state.lock().unwrap().last_weekly_report().is_some_and(|last| last >= due) {
+        return Ok(());
+    }
+
+    info!("Generating weekly report...");
+    let summary = history.lock().unwrap().weekly_summary(due - Duration::days(7), due)?;
+    let text = render(&summary, report_cfg.format);
+
+    if let Some(dir) = &report_cfg.output_dir {
+        std::fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(format!(
+            "weekly-report-{}.{}",
+            due.format("%Y-%m-%d"),
+            report_cfg.format.extension()
+        ));
+        std::fs::write(&path, &text)?;
+        info!("Wrote weekly report to {}", path.display());
+    }
+
+    if report_cfg.post_to_slack {
+        if config.enable_slack {
+            let md = render(&summary, ReportFormat::Markdown);
+            let throttle = slack::Throttle::default();
+            for channel in &config.slack_channel_id {
+                throttle.wait().await;
+                match slack::post_to_slack(client.clone(), config.slack_token.expose(), channel, &md).await {
+                    Ok(_) => info!("Posted weekly report to Slack channel {channel}"),
+                    Err(e) => error!("Error posting weekly report to Slack channel {channel}: {e}"),
+                }
+            }
+        } else {
+            warn!("weekly_report.post_to_slack is set but enable_slack is false");
+        }
+    }
+
+    let mut store = state.lock().unwrap();
+    store.set_last_weekly_report(due);
+    store.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+    use crate::{clock::SimulatedClock, history::CycleRecord, providers::GLOBAL_ENTRY_ID, FetchedLocation, Location};
+
+    fn at(time: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap().and_utc()
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    /// Records a cycle finishing at `time` that loaded `scanned`, failed on
+    /// `failed` more dates and saw `slots` at JFK.
+    fn cycle(history: &mut History, time: &str, scanned: &[&str], failed: usize, slots: &[&str]) {
+        let raw = serde_json::json!({
+            "id": 5140, "name": "JFK", "state": "NY", "city": "Jamaica", "address": "Terminal 4", "postalCode": "11430",
+        });
+        let mut loc: Location = serde_json::from_value(raw.clone()).unwrap();
+        loc.provider = GLOBAL_ENTRY_ID;
+        let found: Vec<FetchedLocation> = slots
+            .iter()
+            .map(|slot| FetchedLocation {
+                date: day(slot),
+                loc: loc.clone(),
+                raw_json: raw.to_string(),
+                fetched_at: at(time),
+                undated: false,
+            })
+            .collect();
+        let record = CycleRecord {
+            started_at: at(time),
+            finished_at: at(time),
+            scanned_ok: scanned.iter().map(|date| (GLOBAL_ENTRY_ID, day(date))).collect(),
+            dates_failed: failed,
+        };
+        history.record_cycle(&record, &found).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_week_is_summarized_from_its_own_cycles_and_alerts() {
+        let mut history = History::open(":memory:").unwrap();
+        let dates = ["2025-02-03", "2025-02-04"];
+        // The week before: only its slot's first sighting carries over.
+        cycle(&mut history, "2025-01-05 08:00", &dates[..1], 0, &dates[..1]);
+        cycle(&mut history, "2025-01-06 08:00", &dates, 1, &dates);
+        {
+            let _clock = clock::install(SimulatedClock::starting_at(at("2025-01-06 08:01")));
+            history.record_alert("C0123", &["5140@2025-02-03".to_string(), "5140@2025-02-04".to_string()]).unwrap();
+        }
+        cycle(&mut history, "2025-01-06 08:11", &dates, 0, &dates[1..]);
+        cycle(&mut history, "2025-01-06 09:01", &dates, 0, &[]);
+
+        let summary = history.weekly_summary(at("2025-01-06 00:00"), at("2025-01-13 00:00")).unwrap();
+        assert_eq!((summary.cycles, summary.dates_scanned, summary.dates_failed), (3, 7, 1));
+        assert_eq!((summary.alerts_sent, summary.slots_alerted), (1, 2));
+        assert_eq!(summary.appeared_by_hour[8], 1);
+        assert_eq!(summary.appeared_by_hour.iter().sum::<i64>(), 1);
+        let gone: Vec<(&str, i64)> =
+            summary.fastest_gone.iter().map(|s| (s.slot_date.as_str(), s.minutes.round() as i64)).collect();
+        assert_eq!(gone, [("2025-02-04", 61), ("2025-02-03", 1451)]);
+
+        let survival = summary.survival.as_ref().unwrap();
+        assert_eq!(survival.slots, 2);
+        assert_eq!((survival.quartile_seconds.round(), survival.median_seconds.round()), (600.0, 3600.0));
+
+        let markdown = render(&summary, ReportFormat::Markdown);
+        for line in [
+            "- Cycles run: 3",
+            "- Date fetches: 6 ok, 1 failed (85.7% success)",
+            "- Alerts sent: 1 (2 slots)",
+            "- Median survival after an alert: 1h00m (2 slots gone this week; a quarter lasted 10m00s or less)",
+            "| JFK | NY | 2 | 2025-02-03 | 2 |",
+            "| 08:00 | 1 |",
+        ] {
+            assert!(markdown.lines().any(|l| l == line), "{line:?} missing from\n{markdown}");
+        }
+        assert!(render(&summary, ReportFormat::Html).contains("<td>JFK</td><td>NY</td>"));
+    }
+
+    #[test]
+    fn an_empty_week_says_so() {
+        let history = History::open(":memory:").unwrap();
+        let summary = history.weekly_summary(at("2025-01-06 00:00"), at("2025-01-13 00:00")).unwrap();
+        assert!(summary.survival.is_none());
+        let markdown = render(&summary, ReportFormat::Markdown);
+        assert!(markdown.contains("- Date fetches: 0 ok, 0 failed (n/a success)"), "{markdown}");
+        assert!(markdown.contains("No availability observed this week."));
+        assert!(!markdown.contains("Fastest-disappearing"));
+    }
+}
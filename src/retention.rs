@@ -0,0 +1,387 @@
+//! Keeps the history database from growing without bound: observations older
+//! than `raw_days` are rolled up into `daily_summaries` and deleted, along with
+//! their cycles.
+
+use std::{path::Path, sync::Mutex};
+
+use chrono::{Duration, NaiveDate};
+use rusqlite::params;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{clock, body_log::ArchiveConfig, history::History, state::StateStore, AppError, JeffConfig};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionConfig {
+    /// Days of raw observations and cycles to keep.
+    #[serde(default = "default_raw_days")]
+    pub raw_days: u32,
+    /// Days of daily summaries to keep (default: all).
+    #[serde(default)]
+    pub summary_days: Option<u32>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            raw_days: default_raw_days(),
+            summary_days: None,
+        }
+    }
+}
+
+fn default_raw_days() -> u32 {
+    90
+}
+
+/// What one prune removed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Pruned {
+    pub observations: usize,
+    /// Summary rows written or merged into.
+    pub summarized: usize,
+    pub cycles: usize,
+    pub alerts: usize,
+    /// Rows of `slots` and `alerted_slots`.
+    pub slots: usize,
+    pub summaries: usize,
+    /// Days of `body_log.archive` removed.
+    pub archive_days: usize,
+}
+
+impl Pruned {
+    fn is_empty(&self) -> bool {
+        self.observations == 0 && self.cycles == 0 && self.alerts == 0 && self.slots == 0 && self.summaries == 0
+    }
+}
+
+/// Tables kept for `raw_days`, and the time column compared with the cutoff,
+/// observations before the cycles they refer to.
+const RAW_TABLES: &[(&str, &str)] = &[
+    ("observations", "observed_at"),
+    ("cycles", "finished_at"),
+    ("alerts", "sent_at"),
+    ("alerted_slots", "alerted_at"),
+    ("slots", "last_seen"),
+];
+
+impl History {
+    /// Rolls observations made before `cutoff` (UTC) into one summary row per
+    /// day and location, then deletes them and every other raw row from
+    /// before `cutoff`, and summaries for days before `summary_cutoff`. With
+    /// `dry_run`, only counts, so a read-only database will do.
+    pub fn prune(
+        &mut self,
+        cutoff: NaiveDate,
+        summary_cutoff: Option<NaiveDate>,
+        dry_run: bool,
+    ) -> Result<Pruned, AppError> {
+        let cutoff = cutoff.to_string();
+        let summary_cutoff = summary_cutoff.map(|day| day.to_string());
+        let tx = self.conn_mut().transaction()?;
+        let deleted = |table: &str, column: &str, before: &str| -> Result<usize, AppError> {
+            Ok(if dry_run {
+                let sql = format!("SELECT COUNT(*) FROM {table} WHERE {column} < ?1");
+                tx.query_row(&sql, params![before], |r| r.get(0))?
+            } else {
+                tx.execute(&format!("DELETE FROM {table} WHERE {column} < ?1"), params![before])?
+            })
+        };
+
+        let summarized = if dry_run {
+            tx.query_row(
+                "SELECT COUNT(*) FROM (
+                     SELECT 1 FROM observations WHERE observed_at < ?1 GROUP BY date(observed_at), provider, location_id)",
+                params![cutoff],
+                |r| r.get(0),
+            )?
+        } else {
+            // The dates themselves are kept so a day summarized twice, e.g.
+            // after an import, counts each slot date once.
+            tx.execute(
+                "INSERT OR IGNORE INTO summary_slot_dates (day, provider, location_id, slot_date)
+                 SELECT DISTINCT date(observed_at), provider, location_id, slot_date
+                 FROM observations WHERE observed_at < ?1",
+                params![cutoff],
+            )?;
+            tx.execute(
+                "INSERT INTO daily_summaries
+                     (day, provider, location_id, name, state, city, observations, slot_dates, earliest_slot, first_seen, last_seen)
+                 SELECT date(observed_at), provider, location_id, MAX(name), MAX(state), MAX(city),
+                        COUNT(*), COUNT(DISTINCT slot_date), MIN(slot_date), MIN(observed_at), MAX(observed_at)
+                 FROM observations WHERE observed_at < ?1
+                 GROUP BY 1, 2, 3
+                 ON CONFLICT (day, provider, location_id) DO UPDATE SET
+                     observations = observations + excluded.observations,
+                     slot_dates = MAX(slot_dates, (
+                         SELECT COUNT(*) FROM summary_slot_dates d
+                         WHERE d.day = excluded.day AND d.provider = excluded.provider
+                           AND d.location_id = excluded.location_id)),
+                     earliest_slot = MIN(earliest_slot, excluded.earliest_slot),
+                     first_seen = MIN(first_seen, excluded.first_seen),
+                     last_seen = MAX(last_seen, excluded.last_seen)",
+                params![cutoff],
+            )?
+        };
+        let counts = RAW_TABLES
+            .iter()
+            .map(|(table, column)| deleted(table, column, &cutoff))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (observations, cycles, alerts, slots) = (counts[0], counts[1], counts[2], counts[3] + counts[4]);
+        let summaries = match &summary_cutoff {
+            Some(day) => {
+                deleted("summary_slot_dates", "day", day)?;
+                deleted("daily_summaries", "day", day)?
+            }
+            None => 0,
+        };
+        if !dry_run {
+            tx.commit()?;
+        }
+        Ok(Pruned {
+            observations,
+            summarized,
+            cycles,
+            alerts,
+            slots,
+            summaries,
+            archive_days: 0,
+        })
+    }
+
+    /// Rewrites the database file so deleted rows stop taking up space.
+    pub fn vacuum(&self) -> Result<(), AppError> {
+        Ok(self.conn().execute_batch("VACUUM")?)
+    }
+}
+
+/// Prunes as `retention` says, `raw_days` overriding its `raw_days`. The
+/// response archive, if any, is kept as long as raw observations.
+pub fn prune(
+    history: &mut History,
+    retention: &RetentionConfig,
+    archive: Option<&ArchiveConfig>,
+    raw_days: Option<u32>,
+    dry_run: bool,
+) -> Result<(NaiveDate, Pruned), AppError> {
+    let today = clock::today();
+    let cutoff = today - Duration::days(i64::from(raw_days.unwrap_or(retention.raw_days)));
+    let summary_cutoff = retention.summary_days.map(|days| today - Duration::days(i64::from(days)));
+    let mut pruned = history.prune(cutoff, summary_cutoff, dry_run)?;
+    if !dry_run && !pruned.is_empty() {
+        history.vacuum()?;
+    }
+    if let Some(archive) = archive {
+        pruned.archive_days = archive.prune(cutoff, dry_run)?;
+    }
+    Ok((cutoff, pruned))
+}
+
+/// Prunes once a day if `retention` is configured.
+pub fn maybe_prune(config: &JeffConfig, state: &Mutex<StateStore>, history: &Mutex<History>) -> Result<(), AppError> {
+    let Some(retention) = &config.retention else {
+        return Ok(());
+    };
+    let now = clock::now();
+    if state.lock().unwrap().last_prune().is_some_and(|last| now - last < Duration::days(1)) {
+        return Ok(());
+    }
+    let archive = config.body_log.archive.as_ref();
+    let (cutoff, pruned) = prune(&mut history.lock().unwrap(), retention, archive, None, false)?;
+    if !pruned.is_empty() {
+        info!(
+            "Pruned history before {cutoff}: {} observation(s) rolled into daily summaries, {} cycle(s), {} alert(s), \
+             {} slot record(s) and {} old summary row(s) deleted",
+            pruned.observations, pruned.cycles, pruned.alerts, pruned.slots, pruned.summaries
+        );
+    }
+    if pruned.archive_days > 0 {
+        info!("Removed {} day(s) of archived responses from before {cutoff}", pruned.archive_days);
+    }
+    let mut store = state.lock().unwrap();
+    store.set_last_prune(now);
+    store.save()
+}
+
+/// `prune`: prunes now and reports what went, and how big the database is.
+pub fn run(
+    config: &JeffConfig,
+    raw_days: Option<u32>,
+    dry_run: bool,
+    out: &mut impl std::io::Write,
+) -> Result<(), AppError> {
+    let path = Path::new(&config.history_path);
+    let size = || std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let before = size();
+    let retention = config.retention.clone().unwrap_or_default();
+    let archive = config.body_log.archive.as_ref();
+    // A dry run leaves the file as it is, schema included.
+    let mut history = if dry_run {
+        let history = History::open_read_only(path)?;
+        if !history.is_current()? {
+            return Err(AppError::General(format!(
+                "{} is from an older version; run `prune` without --dry-run, or `run`, to upgrade it first",
+                path.display()
+            )));
+        }
+        history
+    } else {
+        History::open(path)?
+    };
+    let (cutoff, pruned) = prune(&mut history, &retention, archive, raw_days, dry_run)?;
+
+    let verb = if dry_run { "Would roll up" } else { "Rolled up" };
+    writeln!(
+        out,
+        "{verb} {} observation(s) made before {cutoff} into {} daily summary row(s)",
+        pruned.observations, pruned.summarized
+    )?;
+    let verb = if dry_run { "would delete" } else { "deleted" };
+    writeln!(
+        out,
+        "  and {verb} {} cycle(s), {} alert(s), {} slot record(s) and {} old summary row(s)",
+        pruned.cycles, pruned.alerts, pruned.slots, pruned.summaries
+    )?;
+    if let Some(archive) = archive {
+        writeln!(out, "  and {verb} {} day(s) of archived responses in {}", pruned.archive_days, archive.dir.display())?;
+    }
+    let mb = |bytes: u64| bytes as f64 / 1_048_576.0;
+    if dry_run {
+        writeln!(out, "{}: {:.1} MB", path.display(), mb(before))?;
+    } else {
+        writeln!(out, "{}: {:.1} MB -> {:.1} MB", path.display(), mb(before), mb(size()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDateTime, Utc};
+
+    use super::*;
+    use crate::{clock::SimulatedClock, history::CycleRecord, providers::GLOBAL_ENTRY_ID, FetchedLocation, Location};
+
+    fn at(time: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap().and_utc()
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    /// Records a cycle finishing at `time` that saw `slots` at location 1.
+    fn cycle(history: &mut History, time: &str, slots: &[&str]) {
+        let mut loc: Location = serde_json::from_value(serde_json::json!({
+            "id": 1, "name": "JFK", "state": "NY", "city": "Jamaica", "address": "Terminal 4", "postalCode": "11430",
+        }))
+        .unwrap();
+        loc.provider = GLOBAL_ENTRY_ID;
+        let found: Vec<FetchedLocation> = slots
+            .iter()
+            .map(|slot| FetchedLocation {
+                date: day(slot),
+                loc: loc.clone(),
+                raw_json: String::new(),
+                fetched_at: at(time),
+                undated: false,
+            })
+            .collect();
+        let record = CycleRecord {
+            started_at: at(time),
+            finished_at: at(time),
+            scanned_ok: Vec::new(),
+            dates_failed: 0,
+        };
+        history.record_cycle(&record, &found).unwrap();
+    }
+
+    fn summary(history: &History) -> (i64, i64, String) {
+        history
+            .conn()
+            .query_row("SELECT observations, slot_dates, earliest_slot FROM daily_summaries", [], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+            })
+            .unwrap()
+    }
+
+    fn rows(history: &History, table: &str) -> i64 {
+        history.conn().query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0)).unwrap()
+    }
+
+    #[test]
+    fn a_day_summarized_twice_counts_each_slot_date_once() {
+        let mut history = History::open(":memory:").unwrap();
+        cycle(&mut history, "2025-01-10 08:00", &["2025-02-03", "2025-02-04"]);
+        cycle(&mut history, "2025-01-10 09:00", &["2025-02-04"]);
+        let pruned = history.prune(day("2025-02-01"), None, false).unwrap();
+        assert_eq!((pruned.observations, pruned.summarized, pruned.cycles), (3, 1, 2));
+        assert_eq!(summary(&history), (3, 2, "2025-02-03".to_string()));
+
+        // Observations for that day again, as an import would add them.
+        cycle(&mut history, "2025-01-10 10:00", &["2025-02-04", "2025-02-05"]);
+        history.prune(day("2025-02-01"), None, false).unwrap();
+        assert_eq!(summary(&history), (5, 3, "2025-02-03".to_string()));
+
+        let pruned = history.prune(day("2025-02-01"), Some(day("2025-01-11")), false).unwrap();
+        assert_eq!(pruned.summaries, 1);
+        assert_eq!(rows(&history, "summary_slot_dates"), 0);
+    }
+
+    #[test]
+    fn alerts_and_slot_records_go_with_the_observations() {
+        let mut history = History::open(":memory:").unwrap();
+        {
+            let _clock = clock::install(SimulatedClock::starting_at(at("2025-01-10 08:01")));
+            cycle(&mut history, "2025-01-10 08:00", &["2025-02-03"]);
+            history.record_alert("C0123", &["1@2025-02-03".to_string()]).unwrap();
+        }
+        {
+            let _clock = clock::install(SimulatedClock::starting_at(at("2025-03-01 08:01")));
+            cycle(&mut history, "2025-03-01 08:00", &["2025-03-20"]);
+            history.record_alert("C0123", &["1@2025-03-20".to_string()]).unwrap();
+        }
+
+        let counted = history.prune(day("2025-02-01"), None, true).unwrap();
+        assert_eq!(rows(&history, "observations"), 2);
+        let pruned = history.prune(day("2025-02-01"), None, false).unwrap();
+        assert_eq!(counted, pruned);
+        assert_eq!((pruned.observations, pruned.cycles, pruned.alerts, pruned.slots), (1, 1, 1, 2));
+        for table in ["observations", "cycles", "alerts", "alerted_slots", "slots", "daily_summaries"] {
+            assert_eq!(rows(&history, table), 1, "{table}");
+        }
+    }
+
+    #[test]
+    fn a_dry_run_leaves_an_old_database_alone() {
+        let path = crate::notify::tests::temp_path("prune-dry-run.db");
+        let _ = std::fs::remove_file(&path);
+        {
+            let conn = rusqlite::Connection::open(&path).unwrap();
+            conn.execute_batch("CREATE TABLE notes (text TEXT)").unwrap();
+        }
+        let config: JeffConfig = serde_json::from_value(serde_json::json!({
+            "enable_slack": false,
+            "slack_token": "",
+            "slack_channel_id": "",
+            "fetch_interval_minutes": 0,
+            "search_states": ["NY"],
+            "api_rate_limit_seconds": 1.0,
+            "max_concurrent_fetches": 5,
+            "max_retries": 3,
+            "history_path": path,
+        }))
+        .unwrap();
+
+        let err = run(&config, None, true, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("older version"), "{err}");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let version: usize = conn.query_row("PRAGMA user_version", [], |r| r.get(0)).unwrap();
+        assert_eq!(version, 0);
+
+        let mut out = Vec::new();
+        run(&config, None, false, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("Rolled up 0 observation(s)"));
+        assert!(History::open_read_only(&path).unwrap().is_current().unwrap());
+        let _ = std::fs::remove_file(&path);
+    }
+}
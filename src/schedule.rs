@@ -0,0 +1,104 @@
+//! When cycles start, and keeping the ones already running moving while the
+//! run loop is busy with another's results.
+
+use std::{collections::VecDeque, future::Future, time::Duration};
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use tokio::time::Instant;
+
+/// Cycles start every `period`; after an outage, ticks before `resume_at`
+/// are skipped.
+pub struct Schedule {
+    period: Duration,
+    resume_at: Option<Instant>,
+}
+
+impl Schedule {
+    pub fn new(period: Duration) -> Self {
+        Schedule { period, resume_at: None }
+    }
+
+    /// Whether a cycle due at `at` waits out a backoff instead.
+    pub fn backing_off(&self, at: Instant) -> bool {
+        self.resume_at.is_some_and(|resume_at| at < resume_at)
+    }
+
+    /// Records a finished cycle's backoff, in periods: the next `backoff - 1`
+    /// ticks from now are skipped.
+    pub fn finished(&mut self, backoff: u32) {
+        self.resume_at = (backoff > 1).then(|| Instant::now() + self.period * (backoff - 1));
+    }
+}
+
+/// The next finished cycle: one that finished while something else was
+/// awaited, or else the next of `running`.
+pub async fn next_finished<F: Future>(
+    finished: &mut VecDeque<F::Output>,
+    running: &mut FuturesUnordered<F>,
+) -> Option<F::Output> {
+    match finished.pop_front() {
+        Some(report) => Some(report),
+        None => running.next().await,
+    }
+}
+
+/// Awaits `work` while still polling `running`, keeping what finishes
+/// meanwhile in `finished`, so a concurrent cycle doesn't stall while another
+/// one's alerts and reports go out.
+pub async fn alongside<W: Future, F: Future>(
+    work: W,
+    running: &mut FuturesUnordered<F>,
+    finished: &mut VecDeque<F::Output>,
+) -> W::Output {
+    tokio::pin!(work);
+    loop {
+        tokio::select! {
+            output = &mut work => return output,
+            Some(report) = running.next(), if !running.is_empty() => finished.push_back(report),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+    use tokio::time::sleep;
+
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_secs(300);
+
+    #[tokio::test(start_paused = true)]
+    async fn a_backoff_skips_the_next_ticks() {
+        let mut schedule = Schedule::new(PERIOD);
+        assert!(!schedule.backing_off(Instant::now()));
+        schedule.finished(3);
+        let start = Instant::now();
+        assert!(schedule.backing_off(start + PERIOD));
+        assert!(schedule.backing_off(start + PERIOD * 2 - Duration::from_secs(1)));
+        assert!(!schedule.backing_off(start + PERIOD * 2));
+
+        sleep(PERIOD).await;
+        schedule.finished(1);
+        assert!(!schedule.backing_off(Instant::now()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn running_cycles_finish_while_another_is_handled() {
+        let start = Instant::now();
+        let mut running = FuturesUnordered::new();
+        for (id, secs) in [(1, 5), (2, 30)] {
+            running.push(sleep(Duration::from_secs(secs)).map(move |()| (id, Instant::now())));
+        }
+        let mut finished = VecDeque::new();
+
+        let (first, _) = next_finished(&mut finished, &mut running).await.unwrap();
+        assert_eq!(first, 1);
+        // Handling the first takes a minute; the second still ends at 30s.
+        alongside(sleep(Duration::from_secs(60)), &mut running, &mut finished).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(65));
+        let (second, at) = next_finished(&mut finished, &mut running).await.unwrap();
+        assert_eq!((second, at - start), (2, Duration::from_secs(30)));
+        assert!(next_finished(&mut finished, &mut running).await.is_none());
+    }
+}
@@ -0,0 +1,328 @@
+//! `dev capture-schema`: fetches a live response from each scheduler API
+//! endpoint the finder relies on, infers its shape and compares it with the
+//! schema committed under `tests/schema`, so API drift shows up as a diff.
+//! With `--update` it rewrites those schemas and refreshes the `live_*`
+//! samples in `tests/fixtures/api`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::Path,
+};
+
+use chrono::Duration;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    api_client,
+    clock,
+    dns::Dns,
+    ttp_client::{TtpClient, GLOBAL_ENTRY},
+    AppError, JeffConfig,
+};
+
+/// Elements kept in a refreshed sample fixture.
+const SAMPLE_ELEMENTS: usize = 3;
+
+/// The inferred shape of a JSON value, merged over every value seen.
+#[derive(Debug, Default, PartialEq)]
+struct Shape {
+    types: BTreeSet<String>,
+    properties: BTreeMap<String, Shape>,
+    /// Properties present in every object seen.
+    required: BTreeSet<String>,
+    items: Option<Box<Shape>>,
+}
+
+impl Shape {
+    fn of(value: &Value) -> Shape {
+        let mut shape = Shape::default();
+        shape.add(value);
+        shape
+    }
+
+    fn add(&mut self, value: &Value) {
+        let kind = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_f64() => "number",
+            Value::Number(_) => "integer",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        let first_object = kind == "object" && !self.types.contains("object");
+        self.types.insert(kind.to_string());
+        if self.types.contains("number") {
+            self.types.remove("integer");
+        }
+        match value {
+            Value::Array(elements) => {
+                let items = self.items.get_or_insert_with(Default::default);
+                for element in elements {
+                    items.add(element);
+                }
+            }
+            Value::Object(fields) => {
+                let keys: BTreeSet<String> = fields.keys().cloned().collect();
+                if first_object {
+                    self.required = keys;
+                } else {
+                    self.required.retain(|k| keys.contains(k));
+                }
+                for (key, field) in fields {
+                    self.properties.entry(key.clone()).or_default().add(field);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// As a JSON Schema (the subset this module writes and reads back).
+    fn to_json(&self) -> Value {
+        let mut out = Map::new();
+        let types: Vec<&String> = self.types.iter().collect();
+        match types.as_slice() {
+            [] => {}
+            [one] => {
+                out.insert("type".to_string(), json!(one));
+            }
+            many => {
+                out.insert("type".to_string(), json!(many));
+            }
+        }
+        if self.types.contains("object") {
+            let properties: Map<String, Value> =
+                self.properties.iter().map(|(k, s)| (k.clone(), s.to_json())).collect();
+            out.insert("properties".to_string(), Value::Object(properties));
+            out.insert("required".to_string(), json!(self.required));
+        }
+        if let Some(items) = &self.items {
+            out.insert("items".to_string(), items.to_json());
+        }
+        Value::Object(out)
+    }
+
+    fn from_json(schema: &Value) -> Shape {
+        let types = match schema.get("type") {
+            Some(Value::String(t)) => BTreeSet::from([t.clone()]),
+            Some(Value::Array(ts)) => ts.iter().filter_map(|t| t.as_str().map(str::to_string)).collect(),
+            _ => BTreeSet::new(),
+        };
+        let properties = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .map(|p| p.iter().map(|(k, s)| (k.clone(), Shape::from_json(s))).collect())
+            .unwrap_or_default();
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|r| r.iter().filter_map(|k| k.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+        Shape {
+            types,
+            properties,
+            required,
+            items: schema.get("items").map(|i| Box::new(Shape::from_json(i))),
+        }
+    }
+
+    fn type_name(&self) -> String {
+        let types: Vec<&str> = self.types.iter().map(String::as_str).collect();
+        if types.is_empty() {
+            "nothing".to_string()
+        } else {
+            types.join("|")
+        }
+    }
+
+    /// How `now` differs from `self`, one line per change, under `path`.
+    fn diff(&self, now: &Shape, path: &str, out: &mut Vec<String>) {
+        if self.types != now.types {
+            out.push(format!("{path}: was {}, now {}", self.type_name(), now.type_name()));
+        }
+        for (key, was) in &self.properties {
+            let field = format!("{path}.{key}");
+            match now.properties.get(key) {
+                None => out.push(format!("{field}: no longer sent")),
+                Some(shape) => {
+                    match (self.required.contains(key), now.required.contains(key)) {
+                        (true, false) => out.push(format!("{field}: now sometimes missing")),
+                        (false, true) => out.push(format!("{field}: now always sent")),
+                        _ => {}
+                    }
+                    was.diff(shape, &field, out);
+                }
+            }
+        }
+        for (key, shape) in &now.properties {
+            if !self.properties.contains_key(key) {
+                out.push(format!("{path}.{key}: new field ({})", shape.type_name()));
+            }
+        }
+        match (&self.items, &now.items) {
+            (Some(was), Some(items)) => was.diff(items, &format!("{path}[]"), out),
+            (None, Some(items)) => out.push(format!("{path}[]: now holds {}", items.type_name())),
+            _ => {}
+        }
+    }
+}
+
+/// Captures every endpoint, printing what changed. Returns whether anything
+/// differs from the committed schemas (always `false` with `update`).
+pub async fn capture(config: &JeffConfig, root: &Path, update: bool, out: &mut impl Write) -> Result<bool, AppError> {
+    let schema_dir = root.join("tests/schema");
+    let fixture_dir = root.join("tests/fixtures/api");
+    if !fixture_dir.is_dir() {
+        return Err(AppError::General(format!(
+            "{} not found; run from the repository root or pass --root",
+            fixture_dir.display()
+        )));
+    }
+    let dns = Dns::new(config.dns.as_ref());
+    let api = api_client(config, &dns, dns.builder().build()?)?;
+
+    let mut drifted = false;
+    for (name, body) in fetch(config, &api).await? {
+        let value: Value = serde_json::from_str(&body)
+            .map_err(|e| AppError::General(format!("{name}: response is not JSON: {e}")))?;
+        let shape = Shape::of(&value);
+        let path = schema_dir.join(format!("{name}.json"));
+        let changes = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let mut changes = Vec::new();
+                Shape::from_json(&serde_json::from_str(&text)?).diff(&shape, "$", &mut changes);
+                changes
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec!["no committed schema yet".to_string()],
+            Err(e) => return Err(e.into()),
+        };
+        if changes.is_empty() {
+            writeln!(out, "{name}: unchanged")?;
+        } else {
+            writeln!(out, "{name}: {} change(s)", changes.len())?;
+            for change in &changes {
+                writeln!(out, "  {change}")?;
+            }
+        }
+
+        if update {
+            std::fs::create_dir_all(&schema_dir)?;
+            std::fs::write(&path, format!("{:#}\n", shape.to_json()))?;
+            let sample = fixture_dir.join(format!("live_{name}.json"));
+            std::fs::write(&sample, format!("{:#}\n", sanitize(value)))?;
+            writeln!(out, "  wrote {} and {}", path.display(), sample.display())?;
+        } else {
+            drifted |= !changes.is_empty();
+        }
+    }
+    Ok(drifted)
+}
+
+/// The raw body of each endpoint, by schema name. Slots are asked for at the
+/// first location with availability, or the first known location.
+async fn fetch(config: &JeffConfig, api: &TtpClient) -> Result<Vec<(&'static str, String)>, AppError> {
+    let today = clock::today();
+    let date = config.scan_dates(today).first().copied().unwrap_or(today + Duration::days(1));
+    let as_locations = api.slots_as_locations_body(date, GLOBAL_ENTRY).await?;
+    let locations = api.locations_body(GLOBAL_ENTRY).await?;
+    let first_id = |body: &str| {
+        serde_json::from_str::<Value>(body)
+            .ok()?
+            .as_array()?
+            .iter()
+            .find_map(|l| l.get("id")?.as_u64())
+    };
+    let mut bodies = vec![("slots_as_locations", as_locations.clone()), ("locations", locations.clone())];
+    match first_id(&as_locations).or_else(|| first_id(&locations)) {
+        Some(id) => bodies.push(("slots", api.slots_body(id as usize, SAMPLE_ELEMENTS).await?)),
+        None => tracing::warn!("No location to ask for slots; skipping the slots endpoint"),
+    }
+    Ok(bodies)
+}
+
+/// The first few elements, with phone and fax numbers swapped for 555
+/// numbers in the same area code.
+fn sanitize(value: Value) -> Value {
+    fn scrub(value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    match field {
+                        Value::String(s) if key.ends_with("Number") && !s.is_empty() => {
+                            // All but the last seven characters, which may not be ASCII.
+                            let area = s.char_indices().rev().nth(6).map_or("", |(i, _)| &s[..i]);
+                            *s = format!("{area}5550100");
+                        }
+                        _ => scrub(field),
+                    }
+                }
+            }
+            Value::Array(elements) => elements.iter_mut().for_each(scrub),
+            _ => {}
+        }
+    }
+    let mut value = match value {
+        Value::Array(elements) => Value::Array(elements.into_iter().take(SAMPLE_ELEMENTS).collect()),
+        other => other,
+    };
+    scrub(&mut value);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(was: Value, now: Value) -> Vec<String> {
+        let mut out = Vec::new();
+        let was = Shape::from_json(&Shape::of(&was).to_json());
+        was.diff(&Shape::of(&now), "$", &mut out);
+        out
+    }
+
+    #[test]
+    fn infers_merged_shape() {
+        let shape = Shape::of(&json!([{"id": 1, "name": "a"}, {"id": 2.5}]));
+        assert_eq!(
+            shape.to_json(),
+            json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {"id": {"type": "number"}, "name": {"type": "string"}},
+                    "required": ["id"],
+                },
+            })
+        );
+        assert_eq!(Shape::from_json(&shape.to_json()), shape);
+    }
+
+    #[test]
+    fn reports_added_removed_and_changed_fields() {
+        assert!(diff(json!([{"id": 1}]), json!([{"id": 2}])).is_empty());
+        assert_eq!(diff(json!([{"id": 1}]), json!([{"id": 1, "fax": "x"}])), ["$[].fax: new field (string)"]);
+        assert_eq!(diff(json!([{"id": 1, "fax": "x"}]), json!([{"id": 1}])), ["$[].fax: no longer sent"]);
+        assert_eq!(diff(json!([{"id": 1}]), json!([{"id": "1"}])), ["$[].id: was integer, now string"]);
+        assert_eq!(
+            diff(json!([{"id": 1, "fax": "x"}]), json!([{"id": 1, "fax": "x"}, {"id": 2}])),
+            ["$[].fax: now sometimes missing"]
+        );
+    }
+
+    #[test]
+    fn every_endpoint_has_a_committed_schema() {
+        for name in ["slots_as_locations", "locations", "slots"] {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/schema/{name}.json"));
+            let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            let schema: Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(Shape::from_json(&schema).to_json(), schema, "{name}");
+        }
+    }
+
+    #[test]
+    fn sanitize_keeps_the_area_code() {
+        let value = sanitize(json!([{"phoneNumber": "7185551234"}, {"faxNumber": "+é5551234"}, {}, {}]));
+        assert_eq!(value, json!([{"phoneNumber": "7185550100"}, {"faxNumber": "+é5550100"}, {}]));
+    }
+}
@@ -0,0 +1,160 @@
+//! User scripts in Rhai, for filters and notifications too niche to build in.
+//! A script defines `keep(location)`, `notify(subject, text)`, or both.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    process,
+};
+
+use reqwest::Client;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::runtime::Handle;
+use tracing::{debug, info, warn};
+
+use crate::{notify::Delivery, ttp_client::Location, AppError};
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptConfig {
+    pub path: PathBuf,
+    /// When `notify` receives alerts.
+    #[serde(default)]
+    pub delivery: Delivery,
+    /// Operations a single call may run before it's stopped, so a runaway
+    /// loop can't stall a cycle.
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+}
+
+fn default_max_operations() -> u64 {
+    1_000_000
+}
+
+pub struct Script {
+    path: PathBuf,
+    ast: AST,
+    /// Runs `keep`, which is called while fetching and so can't block.
+    filter_engine: Engine,
+    /// Runs `notify`, which may also call `http_post` and `run`.
+    hook_engine: Engine,
+}
+
+impl Script {
+    /// Compiles the script, failing if it defines neither `keep` nor `notify`.
+    pub fn load(config: &ScriptConfig, client: Client) -> Result<Script, AppError> {
+        let hook_engine = hook_engine(config, client);
+        let ast = hook_engine
+            .compile_file(config.path.clone())
+            .map_err(|e| AppError::General(format!("script {}: {e}", config.path.display())))?;
+        let script = Script {
+            path: config.path.clone(),
+            ast,
+            filter_engine: engine(config),
+            hook_engine,
+        };
+        if !script.defines("keep", 1) && !script.defines("notify", 2) {
+            return Err(AppError::General(format!(
+                "script {}: defines neither keep(location) nor notify(subject, text)",
+                config.path.display()
+            )));
+        }
+        Ok(script)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn defines(&self, name: &str, params: usize) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name && f.params.len() == params)
+    }
+
+    pub fn has_notify(&self) -> bool {
+        self.defines("notify", 2)
+    }
+
+    /// Why `keep` drops `loc`, or `None` if it keeps it (or isn't defined).
+    /// `keep` returns `true` to keep, `false` to drop, or a string saying why
+    /// it drops. A script error keeps the location, so alerts aren't lost.
+    pub fn rejection(&self, loc: &Location) -> Option<String> {
+        if !self.defines("keep", 1) {
+            return None;
+        }
+        let location = match rhai::serde::to_dynamic(loc.record()) {
+            Ok(location) => location,
+            Err(e) => {
+                warn!("script {}: location {}: {e}", self.path.display(), loc.id);
+                return None;
+            }
+        };
+        let verdict = self
+            .filter_engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "keep", (location,));
+        match verdict {
+            Ok(v) if v.as_bool() == Ok(true) => None,
+            Ok(v) if v.as_bool() == Ok(false) => Some("script: keep returned false".to_string()),
+            Ok(v) if v.is_string() => Some(format!("script: {v}")),
+            Ok(v) => {
+                warn!("script {}: keep returned {}, not a bool or string; keeping {}", self.path.display(), v.type_name(), loc.id);
+                None
+            }
+            Err(e) => {
+                warn!("script {}: keep failed for {}, keeping it: {e}", self.path.display(), loc.id);
+                None
+            }
+        }
+    }
+
+    /// Calls `notify`. Blocks, so call it off the async workers.
+    pub fn notify(&self, subject: &str, text: &str) -> Result<(), AppError> {
+        self.hook_engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "notify", (subject.to_string(), text.to_string()))
+            .map(|_| ())
+            .map_err(|e| AppError::General(format!("script {}: notify: {e}", self.path.display())))
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Script").field(&self.path).finish()
+    }
+}
+
+/// An engine with `print` and `debug` going to the log.
+fn engine(config: &ScriptConfig) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(config.max_operations);
+    engine.on_print(|text| info!("script: {text}"));
+    engine.on_debug(|text, _, pos| debug!("script {pos}: {text}"));
+    engine
+}
+
+/// [`engine`] plus `http_post(url, body)`, which returns the status code and
+/// sends a map or array body as JSON, and `run(program, args)`, which returns
+/// the exit code.
+fn hook_engine(config: &ScriptConfig, client: Client) -> Engine {
+    let mut engine = engine(config);
+    engine.register_fn("http_post", move |url: &str, body: Dynamic| -> Result<i64, Box<EvalAltResult>> {
+        let request = if body.is_map() || body.is_array() {
+            let json: Value = rhai::serde::from_dynamic(&body)?;
+            client.post(url).json(&json)
+        } else {
+            client.post(url).body(body.to_string())
+        };
+        let response = Handle::current()
+            .block_on(request.send())
+            .map_err(|e| format!("http_post {url}: {e}"))?;
+        Ok(i64::from(response.status().as_u16()))
+    });
+    engine.register_fn("run", |program: &str, args: Array| -> Result<i64, Box<EvalAltResult>> {
+        let args: Vec<String> = args.into_iter().map(|a| a.to_string()).collect();
+        let status = process::Command::new(program)
+            .args(&args)
+            .status()
+            .map_err(|e| format!("run {program}: {e}"))?;
+        Ok(i64::from(status.code().unwrap_or(-1)))
+    });
+    engine
+}
@@ -0,0 +1,154 @@
+//! Replays cookies and headers captured from a browser session, so requests
+//! keep getting through while the API sits behind a bot challenge.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, COOKIE, SET_COOKIE},
+    Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{ttp_client::RequestDecorator, AppError};
+
+/// Contents of the session file; edit it to paste in fresh values from a browser.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SessionData {
+    /// Sent verbatim, e.g. `User-Agent` matching the browser the cookies came from.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cookies: BTreeMap<String, String>,
+}
+
+struct Loaded {
+    data: SessionData,
+    modified: Option<SystemTime>,
+}
+
+/// A [`RequestDecorator`] backed by a JSON session file. The file is re-read
+/// whenever it changes on disk, and cookies the server sets are written back.
+pub struct BrowserSession {
+    path: PathBuf,
+    loaded: Mutex<Loaded>,
+}
+
+impl BrowserSession {
+    /// Loads the session at `path`, starting empty if the file doesn't exist yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let path = path.as_ref().to_path_buf();
+        let loaded = if path.exists() {
+            Loaded {
+                data: serde_json::from_str(&std::fs::read_to_string(&path)?)?,
+                modified: modified(&path),
+            }
+        } else {
+            warn!("Session file {} not found; starting without a session", path.display());
+            Loaded {
+                data: SessionData::default(),
+                modified: None,
+            }
+        };
+        Ok(BrowserSession {
+            path,
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    /// Picks up edits made to the file since it was last read.
+    fn refresh(&self, loaded: &mut Loaded) {
+        let now = modified(&self.path);
+        if now.is_none() || now == loaded.modified {
+            return;
+        }
+        match std::fs::read_to_string(&self.path)
+            .map_err(AppError::from)
+            .and_then(|s| Ok(serde_json::from_str(&s)?))
+        {
+            Ok(data) => {
+                info!("Reloaded session from {}", self.path.display());
+                loaded.data = data;
+                loaded.modified = now;
+            }
+            Err(e) => warn!("Ignoring unreadable session file {}: {e}", self.path.display()),
+        }
+    }
+
+    fn save(&self, loaded: &mut Loaded) -> Result<(), AppError> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&loaded.data)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        loaded.modified = modified(&self.path);
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl RequestDecorator for BrowserSession {
+    fn decorate(&self, headers: &mut HeaderMap) {
+        let mut loaded = self.loaded.lock().unwrap();
+        self.refresh(&mut loaded);
+        for (name, value) in &loaded.data.headers {
+            match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                _ => warn!("Skipping session header {name:?}: not a valid HTTP header"),
+            }
+        }
+        if !loaded.data.cookies.is_empty() {
+            let cookies: Vec<String> = loaded.data.cookies.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            match HeaderValue::from_str(&cookies.join("; ")) {
+                Ok(value) => {
+                    headers.insert(COOKIE, value);
+                }
+                Err(_) => warn!("Skipping session cookies: not a valid Cookie header"),
+            }
+        }
+    }
+
+    fn observe(&self, response: &Response) {
+        let mut loaded = self.loaded.lock().unwrap();
+        let mut changed = false;
+        for header in response.headers().get_all(SET_COOKIE) {
+            let Some((name, value)) = header
+                .to_str()
+                .ok()
+                .and_then(|h| h.split(';').next())
+                .and_then(|pair| pair.split_once('='))
+            else {
+                continue;
+            };
+            let (name, value) = (name.trim(), value.trim());
+            if loaded.data.cookies.get(name).map(String::as_str) != Some(value) {
+                debug!("Session cookie {name} refreshed by server");
+                loaded.data.cookies.insert(name.to_string(), value.to_string());
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(e) = self.save(&mut loaded) {
+                warn!("Error saving session to {}: {e}", self.path.display());
+            }
+        }
+
+        let challenged = response.headers().contains_key("cf-mitigated")
+            || matches!(response.status(), StatusCode::FORBIDDEN | StatusCode::SERVICE_UNAVAILABLE)
+                && response.headers().get("server").is_some_and(|s| s == "cloudflare");
+        if challenged {
+            warn!(
+                "Request was challenged by bot protection; paste fresh cookies from a browser into {}",
+                self.path.display()
+            );
+        }
+    }
+}
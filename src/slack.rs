@@ -0,0 +1,420 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use crate::{
+    format::{self, CenterNotes, DisplayOptions, Field, Lifetimes, Trends, Verbosity},
+    providers,
+    AppError, FetchedLocation, Location, LocationAvailability,
+};
+
+/// How many locations are listed individually in a Slack alert.
+pub const MAX_LISTED: usize = 5;
+/// How many a `terse` alert lists.
+const TERSE_LISTED: usize = 2;
+
+/// Renders an alert, returning its text and the slot keys it lists.
+///
+/// `sources` names the scanned providers, e.g. `Global Entry`.
+pub fn build_slack_message(
+    fetched_locations: &[FetchedLocation],
+    sources: &str,
+    display: &DisplayOptions,
+    verbosity: Verbosity,
+    trends: &Trends,
+    lifetimes: &Lifetimes,
+    notes: &CenterNotes,
+) -> (String, Vec<String>) {
+    if fetched_locations.is_empty() {
+        return (format!("No {sources} appointments found."), Vec::new());
+    }
+
+    let mut msg = String::new();
+    let mut slots = Vec::new();
+    let listed = match verbosity {
+        // No heading either; the subject or sender says what it's about.
+        Verbosity::Terse => TERSE_LISTED,
+        Verbosity::Normal => MAX_LISTED,
+        Verbosity::Detailed => usize::MAX,
+    };
+    if verbosity != Verbosity::Terse {
+        msg.push_str(&format!("*{sources} Availability*\n\n"));
+    }
+    // Only label entries with their provider when there's more than one.
+    let mixed = fetched_locations
+        .iter()
+        .any(|item| item.loc.provider != fetched_locations[0].loc.provider);
+
+    let total = if display.group_by_location {
+        let groups = LocationAvailability::merge(fetched_locations);
+        for (i, group) in groups.iter().enumerate().take(listed) {
+            let trend = trends.get(&group.loc.key()).map(|(_, t)| display.trend(*t)).unwrap_or_default();
+            let dates = match verbosity {
+                _ if group.undated => format!("by {}", display.date(group.earliest())),
+                Verbosity::Detailed => group.dates.iter().map(|&d| display.date(d)).collect::<Vec<_>>().join(", "),
+                _ => format::date_list(&group.dates, display),
+            };
+            msg.push_str(&format!("{}. {}*{}*", i + 1, label(&group.loc, mixed), display.name(&group.loc)));
+            if verbosity == Verbosity::Terse {
+                msg.push_str(&format!(", {}, {}: {dates}{trend}\n", group.loc.city, group.loc.state));
+            } else {
+                let keys: Vec<String> = group.slot_keys().collect();
+                push_location(&mut msg, &group.loc, &keys, display, verbosity, lifetimes, notes);
+                msg.push_str(&format!("Dates: {dates}{trend}\n\n"));
+            }
+            slots.extend(group.slot_keys());
+        }
+        groups.len()
+    } else {
+        for (i, item) in fetched_locations.iter().enumerate().take(listed) {
+            let trend = match trends.get(&item.loc.key()) {
+                Some((earliest, t)) if *earliest == item.date => display.trend(*t),
+                _ => String::new(),
+            };
+            if verbosity == Verbosity::Terse {
+                msg.push_str(&format!(
+                    "{}. {}{trend} {}*{}*, {}, {}\n",
+                    i + 1,
+                    format::slot_date(item, display),
+                    label(&item.loc, mixed),
+                    display.name(&item.loc),
+                    item.loc.city,
+                    item.loc.state
+                ));
+            } else {
+                msg.push_str(&format!(
+                    "{}. (Date: {}{trend}) {}*{}*",
+                    i + 1,
+                    format::slot_date(item, display),
+                    label(&item.loc, mixed),
+                    display.name(&item.loc)
+                ));
+                push_location(&mut msg, &item.loc, &[item.slot_key()], display, verbosity, lifetimes, notes);
+                msg.push('\n');
+            }
+            slots.push(item.slot_key());
+        }
+        fetched_locations.len()
+    };
+
+    if total > listed {
+        msg.push_str(&format!("...and {} more.\n", total - listed));
+    }
+    (msg, slots)
+}
+
+fn label(loc: &Location, mixed: bool) -> String {
+    if mixed {
+        format!("[{}] ", providers::display_name(loc.provider))
+    } else {
+        String::new()
+    }
+}
+
+/// Appends the ID, city, whichever contact fields aren't hidden, any urgency
+/// label, any directory notes and any notes on the slots in `slot_keys`;
+/// `detailed` adds the center's code and time zone.
+fn push_location(
+    msg: &mut String,
+    loc: &Location,
+    slot_keys: &[String],
+    display: &DisplayOptions,
+    verbosity: Verbosity,
+    lifetimes: &Lifetimes,
+    notes: &CenterNotes,
+) {
+    if display.shows(Field::Id) {
+        msg.push_str(&format!(" (ID: {})", loc.id));
+    }
+    msg.push_str(&format!(" in {}, {}\n", loc.city, loc.state));
+    if verbosity == Verbosity::Detailed {
+        if let Some(code) = &loc.short_name {
+            msg.push_str(&format!("Code: {code}\n"));
+        }
+        if let Some(tz) = &loc.tz_data {
+            msg.push_str(&format!("Time zone: {tz}\n"));
+        }
+    }
+    if display.shows(Field::Address) {
+        let extra = loc.address_additional.as_deref().unwrap_or("");
+        msg.push_str(&format!("Address: {} {}\n", loc.address, extra));
+    }
+    if display.shows(Field::PostalCode) {
+        msg.push_str(&format!("Zip: {}\n", loc.postal_code));
+    }
+    if display.shows(Field::Phone) {
+        let phone = loc.phone_number.as_deref().unwrap_or("N/A");
+        msg.push_str(&format!("Phone: {phone}\n"));
+    }
+    if let Some(&minutes) = lifetimes.get(&loc.key()) {
+        msg.push_str(&format!("_{}_\n", format::urgency(minutes)));
+    }
+    let keys = std::iter::once(loc.key()).chain(slot_keys.iter().cloned());
+    for note in keys.flat_map(|key| notes.get(&key).into_iter().flatten()) {
+        msg.push_str(&format!("{note}\n"));
+    }
+}
+
+/// Slack allows about one message a second; posts faster than that are
+/// throttled, and a burst to several channels can fail with `rate_limited`.
+const POST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Spaces posts `POST_INTERVAL` apart, shared by every channel posted to.
+#[derive(Default)]
+pub struct Throttle {
+    next: tokio::sync::Mutex<Option<tokio::time::Instant>>,
+}
+
+impl Throttle {
+    /// Waits for the next free slot and claims it.
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        if let Some(at) = *next {
+            tokio::time::sleep_until(at).await;
+        }
+        *next = Some(tokio::time::Instant::now() + POST_INTERVAL);
+    }
+}
+
+/// Posts `text` to `channel` and returns the `ts` of the new message.
+pub async fn post_to_slack(
+    client: Client,
+    token: &str,
+    channel: &str,
+    text: &str,
+) -> Result<String, AppError> {
+    let url = "https://slack.com/api/chat.postMessage";
+    debug!("Slack POST: {url}, channel={channel}");
+
+    let payload = serde_json::json!({
+        "channel": channel,
+        "text": text
+    });
+
+    let resp = client
+        .post(url)
+        .bearer_auth(token)
+        .json(&payload)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    #[derive(Deserialize)]
+    struct SlackResp {
+        ok: bool,
+        error: Option<String>,
+        ts: Option<String>,
+    }
+
+    let sr: SlackResp = resp.json().await?;
+    if !sr.ok {
+        Err(AppError::General(sr.error.unwrap_or("Slack unknown error".to_string())))
+    } else {
+        Ok(sr.ts.unwrap_or_default())
+    }
+}
+
+/// Confirms that `token` is valid (`auth.test`).
+pub async fn auth_test(client: &Client, token: &str) -> Result<(), AppError> {
+    #[derive(Deserialize)]
+    struct SlackResp {
+        ok: bool,
+        error: Option<String>,
+    }
+
+    let sr: SlackResp = client
+        .post("https://slack.com/api/auth.test")
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if !sr.ok {
+        return Err(AppError::General(sr.error.unwrap_or("Slack unknown error".to_string())));
+    }
+    Ok(())
+}
+
+/// Confirms that the bot can post to `channel`, joining it if it's public and
+/// the bot isn't in it yet (needs `channels:join`). Posting to a channel the
+/// bot isn't in fails with `channel_not_found` or `not_in_channel`.
+pub async fn verify_channel(client: &Client, token: &str, channel: &str) -> Result<(), AppError> {
+    // User IDs open a direct message, which needs no membership.
+    if channel.starts_with(['U', 'W']) {
+        return Ok(());
+    }
+
+    #[derive(Deserialize)]
+    struct Channel {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        is_private: bool,
+        #[serde(default)]
+        is_im: bool,
+        #[serde(default)]
+        is_member: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct SlackResp {
+        ok: bool,
+        error: Option<String>,
+        channel: Option<Channel>,
+    }
+
+    let sr: SlackResp = client
+        .post("https://slack.com/api/conversations.info")
+        .bearer_auth(token)
+        .form(&[("channel", channel)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let found = match (sr.ok, sr.error.as_deref(), sr.channel) {
+        (true, _, Some(found)) => found,
+        (_, Some("missing_scope"), _) => {
+            warn!("Slack: can't check membership of {channel} without the channels:read scope");
+            return Ok(());
+        }
+        (_, Some("channel_not_found"), _) => {
+            return Err(AppError::General(format!(
+                "channel_not_found: {channel} doesn't exist, or is private and the bot isn't in it; /invite the bot"
+            )));
+        }
+        (_, error, _) => return Err(AppError::General(error.unwrap_or("Slack unknown error").to_string())),
+    };
+    if found.is_member || found.is_im {
+        return Ok(());
+    }
+    let name = found.name.unwrap_or_else(|| channel.to_string());
+    if found.is_private {
+        return Err(AppError::General(format!(
+            "the bot isn't in private channel #{name}; /invite it there"
+        )));
+    }
+
+    let sr: SlackResp = client
+        .post("https://slack.com/api/conversations.join")
+        .bearer_auth(token)
+        .form(&[("channel", channel)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    match (sr.ok, sr.error) {
+        (true, _) => {
+            info!("Joined Slack channel #{name}");
+            Ok(())
+        }
+        (false, Some(error)) if error == "missing_scope" => Err(AppError::General(format!(
+            "the bot isn't in #{name}; /invite it there or grant the channels:join scope"
+        ))),
+        (false, error) => Err(AppError::General(format!(
+            "the bot isn't in #{name} and couldn't join: {}",
+            error.unwrap_or("Slack unknown error".to_string())
+        ))),
+    }
+}
+
+/// Replaces the text of the message `ts` in `channel` (`chat.update`).
+pub async fn update_message(client: &Client, token: &str, channel: &str, ts: &str, text: &str) -> Result<(), AppError> {
+    let url = "https://slack.com/api/chat.update";
+    debug!("Slack POST: {url}, channel={channel}, ts={ts}");
+
+    let payload = serde_json::json!({
+        "channel": channel,
+        "ts": ts,
+        "text": text
+    });
+
+    #[derive(Deserialize)]
+    struct SlackResp {
+        ok: bool,
+        error: Option<String>,
+    }
+
+    let sr: SlackResp = client
+        .post(url)
+        .bearer_auth(token)
+        .json(&payload)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if !sr.ok {
+        return Err(AppError::General(sr.error.unwrap_or("Slack unknown error".to_string())));
+    }
+    Ok(())
+}
+
+/// Uploads `file` to `channel` with `caption`, through Slack's two-step
+/// external upload (needs the `files:write` scope).
+#[cfg(feature = "heatmap")]
+pub async fn upload_file(
+    client: &Client,
+    token: &str,
+    channel: &str,
+    file: &crate::notify::Attachment,
+    caption: &str,
+) -> Result<(), AppError> {
+    #[derive(Deserialize)]
+    struct UploadUrl {
+        ok: bool,
+        error: Option<String>,
+        upload_url: Option<String>,
+        file_id: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Completed {
+        ok: bool,
+        error: Option<String>,
+    }
+    let failed = |error: Option<String>| AppError::General(format!("Slack upload: {}", error.unwrap_or("unknown error".to_string())));
+
+    debug!("Slack upload: {} ({} bytes) to {channel}", file.filename, file.bytes.len());
+    let length = file.bytes.len().to_string();
+    let resp: UploadUrl = client
+        .post("https://slack.com/api/files.getUploadURLExternal")
+        .bearer_auth(token)
+        .form(&[("filename", file.filename.as_str()), ("length", length.as_str())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let (true, Some(upload_url), Some(file_id)) = (resp.ok, resp.upload_url, resp.file_id) else {
+        return Err(failed(resp.error));
+    };
+    client
+        .post(upload_url)
+        .header(reqwest::header::CONTENT_TYPE, &file.content_type)
+        .body(file.bytes.clone())
+        .send()
+        .await?
+        .error_for_status()?;
+    let payload = serde_json::json!({
+        "files": [{ "id": file_id, "title": file.filename }],
+        "channel_id": channel,
+        "initial_comment": caption,
+    });
+    let resp: Completed = client
+        .post("https://slack.com/api/files.completeUploadExternal")
+        .bearer_auth(token)
+        .json(&payload)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if !resp.ok {
+        return Err(failed(resp.error));
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Watches one location on one date (say, the only day you're in town) far
+//! more often than the general scan, for cancellations. Each watch polls on
+//! its own interval and request budget, and its alerts skip digests and can
+//! page through `escalation`.
+
+use std::{
+    collections::VecDeque,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use tokio::time::{sleep, Instant};
+use tracing::{debug, info, warn};
+
+use crate::{
+    clock,
+    format,
+    notify::{Dispatcher, BOOKING_URL},
+    providers::{self, Provider, ProviderKind},
+    state::StateStore,
+    ttp_client::TtpError,
+    AppError, Controls, FetchedLocation, JeffConfig, MAX_OUTAGE_BACKOFF,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SniperConfig {
+    /// Used in logs and messages; defaults to the slot, e.g. `5446@2025-03-14`.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub location_id: usize,
+    pub date: NaiveDate,
+    #[serde(default = "default_provider")]
+    pub provider: ProviderKind,
+    /// Seconds between checks.
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    /// Checks allowed in any hour, on top of the general scan's requests.
+    #[serde(default = "default_max_requests_per_hour")]
+    pub max_requests_per_hour: usize,
+    /// Also open a PagerDuty/Opsgenie incident through `escalation`.
+    #[serde(default)]
+    pub page: bool,
+}
+
+fn default_provider() -> ProviderKind {
+    ProviderKind::GlobalEntry
+}
+
+fn default_interval_seconds() -> u64 {
+    60
+}
+
+fn default_max_requests_per_hour() -> usize {
+    60
+}
+
+impl SniperConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_seconds < 10 {
+            return Err(format!("interval_seconds must be at least 10, got {}", self.interval_seconds));
+        }
+        if self.max_requests_per_hour == 0 {
+            return Err("max_requests_per_hour must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks the sniped slot until the date passes, alerting when it opens and
+/// noting when it's taken again.
+pub async fn watch(
+    config: Arc<JeffConfig>,
+    sniper: SniperConfig,
+    provider: Arc<dyn Provider>,
+    dispatcher: Arc<Dispatcher>,
+    state: Arc<Mutex<StateStore>>,
+    controls: Arc<Controls>,
+) {
+    let (config, sniper, provider, state) = (config.as_ref(), &sniper, provider.as_ref(), state.as_ref());
+    let location = providers::location_key(provider.id(), sniper.location_id);
+    let slot = crate::slot_key(&location, sniper.date);
+    let name = sniper.name.as_deref().unwrap_or(&slot);
+    let title = sniper.name.as_deref().unwrap_or("Watched slot");
+    info!("Watch {name}: checking location {location} on {} every {}s", sniper.date, sniper.interval_seconds);
+
+    let interval = Duration::from_secs(sniper.interval_seconds);
+    let mut sent: VecDeque<Instant> = VecDeque::new();
+    let mut backoff = 1;
+    // The open slot's description, while it's open.
+    let mut open: Option<String> = None;
+    loop {
+        sleep(interval * backoff).await;
+        if sniper.date < clock::today() {
+            info!("Watch {name}: {} has passed; stopping", sniper.date);
+            if open.is_some() {
+                dispatcher.unpage(config, &slot, state).await;
+            }
+            return;
+        }
+        if controls.paused.load(Ordering::Relaxed) || controls.power_paused.load(Ordering::Relaxed) {
+            continue;
+        }
+        let hour_ago = Instant::now() - Duration::from_secs(3600);
+        while sent.front().is_some_and(|at| *at < hour_ago) {
+            sent.pop_front();
+        }
+        if sent.len() >= sniper.max_requests_per_hour {
+            debug!("Watch {name}: used its {} checks this hour; waiting", sniper.max_requests_per_hour);
+            continue;
+        }
+        sent.push_back(Instant::now());
+
+        let found = match check(config, sniper, provider).await {
+            Ok(found) => {
+                backoff = 1;
+                found
+            }
+            Err(e) => {
+                if let AppError::TtpError(TtpError::Unavailable { .. }) = &e {
+                    backoff = (backoff * 2).min(MAX_OUTAGE_BACKOFF);
+                }
+                warn!("Watch {name}: check failed: {e}");
+                continue;
+            }
+        };
+        match (found, &open) {
+            (Some(item), None) => {
+                info!("Watch {name}: {slot} is open");
+                let line = format::digest_line(&item, &config.display);
+                let text = format!("*{title} open*: {line}\n\nBook now at {BOOKING_URL} before it's taken.");
+                dispatcher.notice("Watched appointment open", &text).await;
+                if sniper.page {
+                    dispatcher.page(config, &item, state).await;
+                }
+                open = Some(line);
+            }
+            (None, Some(line)) => {
+                info!("Watch {name}: {slot} is gone");
+                dispatcher
+                    .notice("Watched appointment gone", &format!("*{title} taken*: {line}"))
+                    .await;
+                dispatcher.unpage(config, &slot, state).await;
+                open = None;
+            }
+            _ => debug!("Watch {name}: {slot} {}", if open.is_some() { "still open" } else { "still taken" }),
+        }
+    }
+}
+
+/// The sniped location if it has availability on the date. The location
+/// filters don't apply; naming it is enough.
+async fn check(
+    config: &JeffConfig,
+    sniper: &SniperConfig,
+    provider: &dyn Provider,
+) -> Result<Option<FetchedLocation>, AppError> {
+    let entries = provider.fetch_date(sniper.date).await?;
+    let Some(entry) = entries.into_iter().find(|e| e.value.id == sniper.location_id) else {
+        return Ok(None);
+    };
+    debug!("Watch found {} for {}", config.display.name(&entry.value), sniper.date);
+    Ok(Some(FetchedLocation {
+        date: sniper.date,
+        raw_json: serde_json::to_string(&entry.raw)?,
+        loc: entry.value,
+        fetched_at: clock::now(),
+        undated: false,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use chrono::{TimeZone, Utc};
+    use futures::{future::BoxFuture, FutureExt};
+
+    use super::*;
+    use crate::{
+        clock::SimulatedClock,
+        notify::tests::recorders,
+        providers::GLOBAL_ENTRY_ID,
+        ttp_client::{Entry, Location},
+    };
+
+    /// Has the watched location open on the second check only.
+    #[derive(Default)]
+    struct OpensOnce {
+        asked: AtomicUsize,
+    }
+
+    impl Provider for OpensOnce {
+        fn id(&self) -> &'static str {
+            GLOBAL_ENTRY_ID
+        }
+
+        fn fetch_date(&self, _date: NaiveDate) -> BoxFuture<'_, Result<Vec<Entry<Location>>, AppError>> {
+            async move {
+                if self.asked.fetch_add(1, Ordering::Relaxed) != 1 {
+                    return Ok(Vec::new());
+                }
+                let raw = serde_json::json!({
+                    "id": 5140, "name": "JFK", "state": "NY", "city": "Jamaica", "address": "Terminal 4",
+                    "postalCode": "11430",
+                });
+                let mut value: Location = serde_json::from_value(raw.clone())?;
+                value.provider = GLOBAL_ENTRY_ID;
+                Ok(vec![Entry { value, raw }])
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn checks_within_its_budget_until_the_date_passes() {
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2025, 3, 13, 8, 0, 0).unwrap()));
+        let config: JeffConfig = serde_json::from_value(serde_json::json!({
+            "enable_slack": false,
+            "slack_token": "",
+            "slack_channel_id": "",
+            "fetch_interval_minutes": 5,
+            "search_states": ["NY"],
+            "api_rate_limit_seconds": 1.0,
+            "max_concurrent_fetches": 5,
+            "max_retries": 3,
+        }))
+        .unwrap();
+        let sniper: SniperConfig = serde_json::from_value(serde_json::json!({
+            "location_id": 5140, "date": "2025-03-14", "interval_seconds": 10, "max_requests_per_hour": 3,
+        }))
+        .unwrap();
+        let provider = Arc::new(OpensOnce::default());
+        let (dispatcher, handles) = recorders(&["C0123"]);
+        let state = Arc::new(Mutex::new(StateStore::load(crate::notify::tests::state_path("sniper")).unwrap()));
+        let task = tokio::spawn(watch(
+            Arc::new(config),
+            sniper,
+            provider.clone(),
+            Arc::new(dispatcher),
+            state,
+            Arc::new(Controls::new()),
+        ));
+
+        sleep(Duration::from_secs(600)).await;
+        assert_eq!(provider.asked.load(Ordering::Relaxed), 3);
+        let sent = handles[0].0.lock().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].starts_with("*Watched slot open*: "), "{}", sent[0]);
+        assert!(sent[1].starts_with("*Watched slot taken*: "), "{}", sent[1]);
+
+        // The hour's first check has aged out.
+        sleep(Duration::from_secs(3025)).await;
+        assert_eq!(provider.asked.load(Ordering::Relaxed), 4);
+
+        sleep(Duration::from_secs(2 * 86_400)).await;
+        assert!(task.is_finished());
+    }
+}
@@ -0,0 +1,153 @@
+//! Slack Socket Mode: reactions on alerts mark their slots as handled, or
+//! alert on them again. Needs an app-level token in `slack_app_token`.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::time::sleep;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, warn};
+
+use crate::{state::StateStore, AppError};
+
+/// Reaction that marks every slot in an alert as handled.
+const ACK_REACTION: &str = "white_check_mark";
+/// Reaction that re-enables alerts for the slots in an alert.
+const REENABLE_REACTION: &str = "repeat";
+
+/// Listens for reactions on our alerts over Socket Mode, reconnecting forever.
+pub async fn run_socket_mode(client: Client, app_token: String, state: Arc<Mutex<StateStore>>) {
+    loop {
+        if let Err(e) = socket_session(&client, &app_token, &state).await {
+            warn!("Slack Socket Mode error: {e}");
+        }
+        info!("Reconnecting to Slack Socket Mode in 5 seconds...");
+        sleep(Duration::from_secs(5)).await;
+    }
+}
+
+async fn open_connection(client: &Client, app_token: &str) -> Result<String, AppError> {
+    #[derive(Deserialize)]
+    struct OpenResp {
+        ok: bool,
+        error: Option<String>,
+        url: Option<String>,
+    }
+
+    let resp: OpenResp = client
+        .post("https://slack.com/api/apps.connections.open")
+        .bearer_auth(app_token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    match resp.url {
+        Some(url) if resp.ok => Ok(url),
+        _ => Err(AppError::General(
+            resp.error.unwrap_or("Slack apps.connections.open failed".to_string()),
+        )),
+    }
+}
+
+async fn socket_session(
+    client: &Client,
+    app_token: &str,
+    state: &Mutex<StateStore>,
+) -> Result<(), AppError> {
+    let url = open_connection(client, app_token).await?;
+    let (mut ws, _) = connect_async(url.as_str()).await?;
+    info!("Connected to Slack Socket Mode");
+
+    #[derive(Deserialize)]
+    struct Envelope {
+        #[serde(rename = "type")]
+        kind: String,
+        envelope_id: Option<String>,
+        #[serde(default)]
+        payload: Value,
+    }
+
+    while let Some(msg) = ws.next().await {
+        let text = match msg? {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+
+        let envelope: Envelope = serde_json::from_str(&text)?;
+        if let Some(id) = &envelope.envelope_id {
+            let ack = serde_json::json!({ "envelope_id": id });
+            ws.send(Message::Text(ack.to_string())).await?;
+        }
+
+        match envelope.kind.as_str() {
+            "events_api" => handle_event(&envelope.payload["event"], state),
+            "disconnect" => {
+                info!("Slack requested disconnect");
+                return Ok(());
+            }
+            other => debug!("Ignoring Socket Mode envelope: {other}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_event(event: &Value, state: &Mutex<StateStore>) {
+    #[derive(Deserialize)]
+    struct ReactionEvent {
+        #[serde(rename = "type")]
+        kind: String,
+        reaction: String,
+        item: ReactionItem,
+    }
+
+    #[derive(Deserialize)]
+    struct ReactionItem {
+        channel: Option<String>,
+        ts: Option<String>,
+    }
+
+    let Ok(ev) = serde_json::from_value::<ReactionEvent>(event.clone()) else {
+        return;
+    };
+    if ev.kind != "reaction_added" {
+        return;
+    }
+    let (Some(channel), Some(ts)) = (ev.item.channel, ev.item.ts) else {
+        return;
+    };
+
+    let mut store = state.lock().unwrap();
+    if let Err(e) = store.reload_suppressions() {
+        warn!("Error reloading suppressions: {e}");
+    }
+    let Some(slots) = store.alert_slots(&channel, &ts).map(<[String]>::to_vec) else {
+        debug!("Reaction on unknown message {channel}/{ts}");
+        return;
+    };
+
+    match ev.reaction.as_str() {
+        ACK_REACTION => {
+            let n = store.suppress(&slots);
+            info!("Suppressed {n} slot(s) from alert {ts}");
+        }
+        REENABLE_REACTION => {
+            let n = store.unsuppress(&slots);
+            info!("Re-enabled {n} slot(s) from alert {ts}");
+        }
+        _ => return,
+    }
+
+    if let Err(e) = store.save() {
+        warn!("Error saving state: {e}");
+    }
+}
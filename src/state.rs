@@ -0,0 +1,558 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{clock, notify::Attachment, AppError};
+
+/// How many posted alerts we remember for mapping reactions back to slots.
+const MAX_ALERT_MESSAGES: usize = 500;
+
+/// A Slack message we posted, along with the slot keys it listed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertMessage {
+    pub channel: String,
+    pub ts: String,
+    pub slots: Vec<String>,
+}
+
+/// Small JSON-backed store for state that must survive restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StateStore {
+    #[serde(skip)]
+    path: PathBuf,
+    /// The file's modification time when this store last read or wrote it.
+    #[serde(skip)]
+    modified: Option<SystemTime>,
+    /// Bumped on every save, so a rewrite is noticed even when it lands
+    /// within the same modification time as the one before.
+    #[serde(default)]
+    saves: u64,
+    /// Slot keys, e.g. `5140@2025-01-14`.
+    #[serde(default)]
+    suppressed: BTreeSet<String>,
+    /// Location keys, e.g. `5140`, silenced for every date.
+    #[serde(default)]
+    suppressed_locations: BTreeSet<String>,
+    /// Slot or location keys silenced until a time, by mute links and
+    /// `suppress add --hours`.
+    #[serde(default)]
+    muted: BTreeMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    alert_messages: Vec<AlertMessage>,
+    #[serde(default)]
+    last_weekly_report: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_drought_note: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_prune: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_heatmap: Option<DateTime<Utc>>,
+    /// Slot events waiting for each digest channel's next delivery, keyed by notifier target.
+    #[serde(default)]
+    digests: BTreeMap<String, Vec<DigestLine>>,
+    #[serde(default)]
+    last_digest: BTreeMap<String, DateTime<Utc>>,
+    /// Slots with an open PagerDuty/Opsgenie incident, by slot key.
+    #[serde(default)]
+    escalated: BTreeMap<String, Escalated>,
+    /// The last real-time alert each channel got, by notifier target.
+    #[serde(default)]
+    last_sent: BTreeMap<String, Sent>,
+    /// Each board channel's current message, by notifier target.
+    #[serde(default)]
+    boards: BTreeMap<String, Board>,
+    /// Real-time alerts not yet delivered, at most one per notifier target.
+    #[serde(default)]
+    outbox: Vec<Pending>,
+    /// The last status `application_status` read for each account, by name
+    /// (empty for an unnamed one).
+    #[serde(default, alias = "application_status", deserialize_with = "de_statuses")]
+    application_statuses: BTreeMap<String, String>,
+}
+
+/// Reads statuses by account, or the single status that state files kept
+/// before accounts had names, as the unnamed account's.
+fn de_statuses<'de, D: serde::Deserializer<'de>>(d: D) -> Result<BTreeMap<String, String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Statuses {
+        ByAccount(BTreeMap<String, String>),
+        One(Option<String>),
+    }
+    Ok(match Statuses::deserialize(d)? {
+        Statuses::ByAccount(statuses) => statuses,
+        Statuses::One(status) => status.map(|status| (String::new(), status)).into_iter().collect(),
+    })
+}
+
+/// One slot event queued for a digest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestLine {
+    pub slot: String,
+    pub line: String,
+}
+
+/// A slot with an open incident, remembered so the incident can be resolved
+/// once a scan of its date no longer finds it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Escalated {
+    pub provider: String,
+    pub date: NaiveDate,
+}
+
+/// A hash of an alert's text and when it went out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sent {
+    pub hash: String,
+    pub at: DateTime<Utc>,
+}
+
+/// A real-time alert kept from just before it's sent until the channel takes
+/// it, so one lost to a crash or a failed send can be sent on restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pending {
+    pub target: String,
+    pub subject: String,
+    pub text: String,
+    pub slots: Vec<String>,
+    /// What was attached, such as the alert's CSV.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<Attachment>,
+    /// Identifies the alert across channels for `repeat_window`; empty in
+    /// older state files, where the text stands in.
+    #[serde(default)]
+    pub hash: String,
+    pub queued_at: DateTime<Utc>,
+}
+
+/// The message a `board` channel keeps editing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    pub ts: String,
+    pub posted_at: DateTime<Utc>,
+    /// A hash of the text it shows.
+    pub hash: String,
+}
+
+impl StateStore {
+    /// Loads the store from `path`, starting empty if the file doesn't exist yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let path = path.as_ref();
+        let mut store = if path.exists() {
+            let contents = std::fs::read_to_string(path)?;
+            serde_json::from_str::<StateStore>(&contents)?
+        } else {
+            StateStore::default()
+        };
+        store.path = path.to_path_buf();
+        store.modified = modified(path);
+        Ok(store)
+    }
+
+    /// Loads the store at `path`, changes it with `edit` and saves it, with
+    /// every other save to the file waiting until it's done, so nothing
+    /// written meanwhile (by a running finder, say) is lost.
+    pub fn edit<T>(path: impl AsRef<Path>, edit: impl FnOnce(&mut StateStore) -> T) -> Result<T, AppError> {
+        let _lock = save_lock(path.as_ref())?;
+        let mut store = StateStore::load(path)?;
+        let result = edit(&mut store);
+        store.write()?;
+        Ok(result)
+    }
+
+    /// Writes the store, first taking any suppressions changed on disk by
+    /// another process (such as `suppress add`) since it was last read.
+    pub fn save(&mut self) -> Result<(), AppError> {
+        let _lock = save_lock(&self.path)?;
+        self.write()
+    }
+
+    fn write(&mut self) -> Result<(), AppError> {
+        if self.path.exists() {
+            let disk = StateStore::load(&self.path)?;
+            if disk.saves != self.saves {
+                self.take_suppressions(disk);
+            }
+        }
+        self.saves += 1;
+        let now = clock::now();
+        self.muted.retain(|_, until| *until > now);
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        self.modified = modified(&self.path);
+        debug!("Saved state to {}", self.path.display());
+        Ok(())
+    }
+
+    /// Replaces the suppression lists with the file's if another process has
+    /// written it since this store last read or wrote it. Mutes are merged,
+    /// keeping the later end, so one set here since isn't lost.
+    pub fn reload_suppressions(&mut self) -> Result<(), AppError> {
+        let on_disk = modified(&self.path);
+        if on_disk.is_none() || on_disk == self.modified {
+            return Ok(());
+        }
+        self.take_suppressions(StateStore::load(&self.path)?);
+        Ok(())
+    }
+
+    fn take_suppressions(&mut self, disk: StateStore) {
+        debug!("Reloaded suppressions from {}", self.path.display());
+        self.suppressed = disk.suppressed;
+        self.suppressed_locations = disk.suppressed_locations;
+        for (key, until) in disk.muted {
+            let entry = self.muted.entry(key).or_insert(until);
+            *entry = (*entry).max(until);
+        }
+        self.modified = disk.modified;
+        self.saves = disk.saves;
+    }
+
+    pub fn suppressed_count(&self) -> usize {
+        self.suppressed.len()
+    }
+
+    pub fn suppressed_slots(&self) -> impl Iterator<Item = &str> {
+        self.suppressed.iter().map(String::as_str)
+    }
+
+    pub fn suppressed_locations(&self) -> impl Iterator<Item = &str> {
+        self.suppressed_locations.iter().map(String::as_str)
+    }
+
+    /// Slot events waiting across all digest channels.
+    pub fn queued_digest_count(&self) -> usize {
+        self.digests.values().map(Vec::len).sum()
+    }
+
+    /// Whether `slot`, or its whole location, is suppressed or muted.
+    pub fn is_suppressed(&self, slot: &str) -> bool {
+        let location = slot.split_once('@').map(|(location, _)| location);
+        self.suppressed.contains(slot)
+            || location.is_some_and(|location| self.suppressed_locations.contains(location))
+            || self.is_muted(slot)
+            || location.is_some_and(|location| self.is_muted(location))
+    }
+
+    fn is_muted(&self, key: &str) -> bool {
+        self.muted.get(key).is_some_and(|until| *until > clock::now())
+    }
+
+    /// Slot and location keys muted for now, and when each mute ends.
+    pub fn muted(&self) -> impl Iterator<Item = (&str, DateTime<Utc>)> {
+        let now = clock::now();
+        self.muted.iter().filter(move |(_, until)| **until > now).map(|(key, until)| (key.as_str(), *until))
+    }
+
+    /// Remembers the slots the message `ts` lists, replacing what an earlier
+    /// version of it (such as a board before an edit) listed.
+    pub fn record_alert(&mut self, channel: &str, ts: &str, slots: Vec<String>) {
+        self.alert_messages.retain(|m| m.channel != channel || m.ts != ts);
+        self.alert_messages.push(AlertMessage {
+            channel: channel.to_string(),
+            ts: ts.to_string(),
+            slots,
+        });
+        if self.alert_messages.len() > MAX_ALERT_MESSAGES {
+            let excess = self.alert_messages.len() - MAX_ALERT_MESSAGES;
+            self.alert_messages.drain(..excess);
+        }
+    }
+
+    /// Returns the slots listed in the alert identified by `channel` and `ts`.
+    #[cfg(feature = "socket-mode")]
+    pub fn alert_slots(&self, channel: &str, ts: &str) -> Option<&[String]> {
+        self.alert_messages
+            .iter()
+            .find(|m| m.channel == channel && m.ts == ts)
+            .map(|m| m.slots.as_slice())
+    }
+
+    /// Adds `slots` to the suppression list, returning how many were newly added.
+    pub fn suppress(&mut self, slots: &[String]) -> usize {
+        slots
+            .iter()
+            .filter(|s| self.suppressed.insert(s.to_string()))
+            .count()
+    }
+
+    /// Removes `slots` from the suppression list, returning how many were removed.
+    pub fn unsuppress(&mut self, slots: &[String]) -> usize {
+        slots.iter().filter(|s| self.suppressed.remove(*s)).count()
+    }
+
+    /// Suppresses every date at `locations`, returning how many were newly added.
+    pub fn suppress_locations(&mut self, locations: &[String]) -> usize {
+        locations
+            .iter()
+            .filter(|l| self.suppressed_locations.insert(l.to_string()))
+            .count()
+    }
+
+    /// Lifts location-wide suppressions, returning how many were removed.
+    pub fn unsuppress_locations(&mut self, locations: &[String]) -> usize {
+        locations.iter().filter(|l| self.suppressed_locations.remove(*l)).count()
+    }
+
+    /// Silences slot or location `keys` until `until`, or longer if already
+    /// muted longer, returning how many weren't muted before.
+    pub fn mute(&mut self, keys: &[String], until: DateTime<Utc>) -> usize {
+        let mut added = 0;
+        for key in keys {
+            if !self.is_muted(key) {
+                added += 1;
+            }
+            let entry = self.muted.entry(key.clone()).or_insert(until);
+            *entry = (*entry).max(until);
+        }
+        added
+    }
+
+    /// Lifts mutes on `keys`, returning how many were still muted.
+    pub fn unmute(&mut self, keys: &[String]) -> usize {
+        let now = clock::now();
+        keys.iter().filter(|key| self.muted.remove(*key).is_some_and(|until| until > now)).count()
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn last_weekly_report(&self) -> Option<DateTime<Utc>> {
+        self.last_weekly_report
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn set_last_weekly_report(&mut self, at: DateTime<Utc>) {
+        self.last_weekly_report = Some(at);
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn last_drought_note(&self) -> Option<DateTime<Utc>> {
+        self.last_drought_note
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn set_last_drought_note(&mut self, at: DateTime<Utc>) {
+        self.last_drought_note = Some(at);
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn last_prune(&self) -> Option<DateTime<Utc>> {
+        self.last_prune
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn set_last_prune(&mut self, at: DateTime<Utc>) {
+        self.last_prune = Some(at);
+    }
+
+    #[cfg(feature = "heatmap")]
+    pub fn last_heatmap(&self) -> Option<DateTime<Utc>> {
+        self.last_heatmap
+    }
+
+    #[cfg(feature = "heatmap")]
+    pub fn set_last_heatmap(&mut self, at: DateTime<Utc>) {
+        self.last_heatmap = Some(at);
+    }
+
+    /// Queues `lines` for `target`'s digest, skipping slots already queued.
+    pub fn queue_digest(&mut self, target: &str, lines: Vec<DigestLine>) {
+        let queued = self.digests.entry(target.to_string()).or_default();
+        for line in lines {
+            // Notices have no slot, so they're told apart by their text.
+            if !queued.iter().any(|q| q.slot == line.slot && (!q.slot.is_empty() || q.line == line.line)) {
+                queued.push(line);
+            }
+        }
+    }
+
+    pub fn take_digest(&mut self, target: &str) -> Vec<DigestLine> {
+        self.digests.remove(target).unwrap_or_default()
+    }
+
+    pub fn last_digest(&self, target: &str) -> Option<DateTime<Utc>> {
+        self.last_digest.get(target).copied()
+    }
+
+    pub fn set_last_digest(&mut self, target: &str, at: DateTime<Utc>) {
+        self.last_digest.insert(target.to_string(), at);
+    }
+
+    pub fn escalated(&self) -> &BTreeMap<String, Escalated> {
+        &self.escalated
+    }
+
+    pub fn add_escalated(&mut self, slot: &str, incident: Escalated) {
+        self.escalated.insert(slot.to_string(), incident);
+    }
+
+    pub fn remove_escalated(&mut self, slot: &str) {
+        self.escalated.remove(slot);
+    }
+
+    /// When `target` last got an alert hashing to `hash`, if that was its last alert.
+    pub fn last_sent(&self, target: &str, hash: &str) -> Option<DateTime<Utc>> {
+        self.last_sent.get(target).filter(|sent| sent.hash == hash).map(|sent| sent.at)
+    }
+
+    pub fn record_sent(&mut self, target: &str, hash: String, at: DateTime<Utc>) {
+        self.last_sent.insert(target.to_string(), Sent { hash, at });
+    }
+
+    pub fn board(&self, target: &str) -> Option<&Board> {
+        self.boards.get(target)
+    }
+
+    pub fn set_board(&mut self, target: &str, board: Board) {
+        self.boards.insert(target.to_string(), board);
+    }
+
+    pub fn application_status(&self, account: &str) -> Option<&str> {
+        self.application_statuses.get(account).map(String::as_str)
+    }
+
+    pub fn set_application_status(&mut self, account: &str, status: String) {
+        self.application_statuses.insert(account.to_string(), status);
+    }
+
+    pub fn outbox(&self) -> &[Pending] {
+        &self.outbox
+    }
+
+    /// Queues `pending`, replacing an older alert still waiting for its
+    /// target: the newer one says what's available now.
+    pub fn queue_outbox(&mut self, pending: Pending) {
+        self.outbox.retain(|p| p.target != pending.target);
+        self.outbox.push(pending);
+    }
+
+    /// Drops the alert queued for `target` at `queued_at`, unless a newer
+    /// one has replaced it.
+    pub fn mark_delivered(&mut self, target: &str, queued_at: DateTime<Utc>) {
+        self.outbox.retain(|p| p.target != target || p.queued_at != queued_at);
+    }
+}
+
+/// Held while the state file is read for a change or written, on
+/// `<path>.save.lock` since the file itself is replaced on each save. The
+/// instance lock next to it is held for the whole run, so it can't serve.
+fn save_lock(path: &Path) -> Result<File, AppError> {
+    let mut lock = path.as_os_str().to_owned();
+    lock.push(".save.lock");
+    let file = OpenOptions::new().write(true).create(true).truncate(false).open(lock)?;
+    file.lock()?;
+    Ok(file)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_single_application_status_of_older_state_files() {
+        let store: StateStore = serde_json::from_str(r#"{"application_status": "Conditionally approved"}"#).unwrap();
+        assert_eq!(store.application_status(""), Some("Conditionally approved"));
+        let store: StateStore = serde_json::from_str(r#"{"application_status": null}"#).unwrap();
+        assert_eq!(store.application_status(""), None);
+        let store: StateStore = serde_json::from_str(r#"{"application_statuses": {"Sam": "Approved"}}"#).unwrap();
+        assert_eq!(store.application_status("Sam"), Some("Approved"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mutes_cover_slots_and_locations_until_they_end() {
+        use chrono::TimeZone;
+
+        use crate::clock::{self, SimulatedClock};
+
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2026, 3, 13, 12, 0, 0).unwrap()));
+        let mut store = StateStore::default();
+        let now = clock::now();
+        assert_eq!(store.mute(&["5140@2025-03-14".to_string()], now + chrono::Duration::hours(1)), 1);
+        assert_eq!(store.mute(&["5300".to_string()], now + chrono::Duration::hours(3)), 1);
+        assert!(store.is_suppressed("5140@2025-03-14"));
+        assert!(!store.is_suppressed("5140@2025-03-15"));
+        assert!(store.is_suppressed("5300@2025-04-01"));
+
+        tokio::time::advance(std::time::Duration::from_secs(2 * 3600)).await;
+        assert!(!store.is_suppressed("5140@2025-03-14"));
+        assert!(store.is_suppressed("5300@2025-04-01"));
+        assert_eq!(store.muted().count(), 1);
+        tokio::time::advance(std::time::Duration::from_secs(3600)).await;
+        assert!(!store.is_suppressed("5300@2025-04-01"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn saving_merges_mutes_written_by_another_process() {
+        use chrono::TimeZone;
+
+        use crate::clock::{self, SimulatedClock};
+
+        let _clock = clock::install(SimulatedClock::starting_at(Utc.with_ymd_and_hms(2026, 3, 13, 12, 0, 0).unwrap()));
+        let path = crate::notify::tests::state_path("mute-merge");
+        let _ = std::fs::remove_file(&path);
+        let now = clock::now();
+        let mut watcher = StateStore::load(&path).unwrap();
+        watcher.mute(&["5140".to_string()], now + chrono::Duration::hours(4));
+
+        let mut other = StateStore::load(&path).unwrap();
+        other.mute(&["5140".to_string(), "5300".to_string()], now + chrono::Duration::hours(1));
+        other.save().unwrap();
+        watcher.save().unwrap();
+
+        let saved = StateStore::load(&path).unwrap();
+        let muted: BTreeMap<_, _> = saved.muted().collect();
+        assert_eq!(muted.get("5140"), Some(&(now + chrono::Duration::hours(4))));
+        assert_eq!(muted.get("5300"), Some(&(now + chrono::Duration::hours(1))));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn edits_keep_what_other_writers_saved() {
+        let path = crate::notify::tests::state_path("state-edit");
+        let _ = std::fs::remove_file(&path);
+        let line = |slot: &str| DigestLine {
+            slot: slot.to_string(),
+            line: slot.to_string(),
+        };
+        // A running finder, holding the store it loaded at startup.
+        let mut running = StateStore::load(&path).unwrap();
+        running.queue_digest("email", vec![line("5140@2025-03-14")]);
+        running.save().unwrap();
+
+        std::thread::scope(|scope| {
+            for writer in 0..4 {
+                let path = &path;
+                scope.spawn(move || {
+                    for i in 0..10 {
+                        StateStore::edit(path, |store| store.suppress(&[format!("{writer}@2025-03-{:02}", i + 1)]))
+                            .unwrap();
+                    }
+                });
+            }
+            for day in 1..=10 {
+                running.queue_digest("email", vec![line(&format!("5300@2025-03-{day:02}"))]);
+                running.save().unwrap();
+            }
+        });
+        assert_eq!(StateStore::load(&path).unwrap().suppressed_count(), 40);
+        let added = StateStore::edit(&path, |store| store.suppress(&["5140@2025-03-14".to_string()])).unwrap();
+        assert_eq!(added, 1);
+
+        running.save().unwrap();
+        let mut saved = StateStore::load(&path).unwrap();
+        assert_eq!(saved.suppressed_count(), 41);
+        assert_eq!(saved.take_digest("email").len(), 11);
+        let _ = std::fs::remove_file(&path);
+    }
+}
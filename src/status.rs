@@ -0,0 +1,252 @@
+//! `status`: whether the finder is keeping up, what its last cycle did, and
+//! what the history and state stores hold, for people and monitoring wrappers.
+
+use std::io::Write;
+#[cfg(feature = "sqlite")]
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+#[cfg(feature = "sqlite")]
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+#[cfg(feature = "sqlite")]
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+
+use crate::{clock, history::TS_FORMAT, state::StateStore, AppError, JeffConfig};
+#[cfg(feature = "sqlite")]
+use crate::history::History;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Health {
+    /// The last cycle loaded every date and, when looping, is recent.
+    Ok,
+    /// The last cycle is recent but some dates failed to load.
+    Degraded,
+    /// No cycle has finished for more than two intervals.
+    Stale,
+    /// No cycle is recorded.
+    NeverRun,
+}
+
+#[derive(Debug, Serialize)]
+struct Status {
+    health: Health,
+    checked_at: DateTime<Utc>,
+    fetch_interval_minutes: u64,
+    last_cycle: Option<LastCycle>,
+    history: HistoryStats,
+    state: StateStats,
+}
+
+#[derive(Debug, Serialize)]
+struct LastCycle {
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    age_seconds: i64,
+    dates_scanned: i64,
+    dates_failed: i64,
+    slots_found: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct HistoryStats {
+    path: String,
+    size_bytes: u64,
+    cycles: i64,
+    first_cycle: Option<DateTime<Utc>>,
+    observations: i64,
+    open_slots: i64,
+    alerts: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct StateStats {
+    path: String,
+    suppressed_slots: usize,
+    suppressed_locations: usize,
+    queued_digest_lines: usize,
+    undelivered_alerts: usize,
+    open_escalations: usize,
+}
+
+/// Writes the status as text or JSON; missing stores read as empty.
+pub fn print(config: &JeffConfig, format: Format, out: &mut impl Write) -> Result<(), AppError> {
+    let status = collect(config)?;
+    match format {
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(&status)?)?,
+        Format::Text => write_text(&status, out)?,
+    }
+    Ok(())
+}
+
+fn collect(config: &JeffConfig) -> Result<Status, AppError> {
+    let now = clock::now();
+    let (history, last_cycle) = read_history(&config.history_path, now)?;
+
+    let store = StateStore::load(&config.state_path)?;
+    let state = StateStats {
+        path: config.state_path.clone(),
+        suppressed_slots: store.suppressed_count(),
+        suppressed_locations: store.suppressed_locations().count(),
+        queued_digest_lines: store.queued_digest_count(),
+        undelivered_alerts: store.outbox().len(),
+        open_escalations: store.escalated().len(),
+    };
+
+    Ok(Status {
+        health: health(last_cycle.as_ref(), config.fetch_interval_minutes),
+        checked_at: now,
+        fetch_interval_minutes: config.fetch_interval_minutes,
+        last_cycle,
+        history,
+        state,
+    })
+}
+
+/// What the history database at `path` holds, and its latest cycle; empty
+/// before the first cycle.
+#[cfg(feature = "sqlite")]
+fn read_history(path: &str, now: DateTime<Utc>) -> Result<(HistoryStats, Option<LastCycle>), AppError> {
+    let mut history = HistoryStats {
+        path: path.to_string(),
+        ..Default::default()
+    };
+    if !Path::new(path).exists() {
+        return Ok((history, None));
+    }
+    let store = History::open_read_only(path)?;
+    let conn = store.conn();
+    let count = |sql: &str| conn.query_row(sql, [], |r| r.get::<_, i64>(0));
+    history.size_bytes = std::fs::metadata(path)?.len();
+    history.cycles = count("SELECT COUNT(*) FROM cycles")?;
+    history.observations = count("SELECT COUNT(*) FROM observations")?;
+    history.open_slots = count("SELECT COUNT(*) FROM slots WHERE gone_at IS NULL")?;
+    history.alerts = count("SELECT COUNT(*) FROM alerts")?;
+    history.first_cycle = conn
+        .query_row("SELECT MIN(started_at) FROM cycles", [], |r| r.get::<_, Option<String>>(0))?
+        .map(|ts| parse_ts(&ts))
+        .transpose()?;
+    let last_cycle = conn
+        .query_row(
+            "SELECT started_at, finished_at, dates_scanned, dates_failed, slots_found
+             FROM cycles ORDER BY id DESC LIMIT 1",
+            [],
+            |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                ))
+            },
+        )
+        .optional()?
+        .map(|(started, finished, dates_scanned, dates_failed, slots_found)| {
+            let finished_at = parse_ts(&finished)?;
+            Ok::<_, AppError>(LastCycle {
+                started_at: parse_ts(&started)?,
+                finished_at,
+                age_seconds: (now - finished_at).num_seconds(),
+                dates_scanned,
+                dates_failed,
+                slots_found,
+            })
+        })
+        .transpose()?;
+    Ok((history, last_cycle))
+}
+
+/// Builds without the `sqlite` feature keep no history.
+#[cfg(not(feature = "sqlite"))]
+fn read_history(path: &str, _now: DateTime<Utc>) -> Result<(HistoryStats, Option<LastCycle>), AppError> {
+    let history = HistoryStats {
+        path: path.to_string(),
+        ..Default::default()
+    };
+    Ok((history, None))
+}
+
+/// Staleness only applies when looping; a one-shot setup (e.g. cron) can't
+/// be judged by age.
+fn health(last: Option<&LastCycle>, interval_minutes: u64) -> Health {
+    let Some(last) = last else {
+        return Health::NeverRun;
+    };
+    if interval_minutes > 0 && last.age_seconds > (interval_minutes * 60 * 2) as i64 {
+        Health::Stale
+    } else if last.dates_failed > 0 {
+        Health::Degraded
+    } else {
+        Health::Ok
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn parse_ts(ts: &str) -> Result<DateTime<Utc>, AppError> {
+    NaiveDateTime::parse_from_str(ts, TS_FORMAT)
+        .map(|t| t.and_utc())
+        .map_err(|e| AppError::General(format!("bad timestamp {ts:?} in history: {e}")))
+}
+
+fn write_text(status: &Status, out: &mut impl Write) -> Result<(), AppError> {
+    let health = serde_json::to_value(status.health)?;
+    match &status.last_cycle {
+        Some(last) => {
+            writeln!(
+                out,
+                "Health: {} (last cycle {} ago{})",
+                health.as_str().unwrap_or_default(),
+                ago(last.age_seconds),
+                match status.fetch_interval_minutes {
+                    0 => String::new(),
+                    m => format!(", runs every {m} min"),
+                }
+            )?;
+            writeln!(
+                out,
+                "Last cycle: finished {}, {}/{} dates ok, {} slots found",
+                last.finished_at.format(TS_FORMAT),
+                last.dates_scanned - last.dates_failed,
+                last.dates_scanned,
+                last.slots_found
+            )?;
+        }
+        None => writeln!(out, "Health: {}", health.as_str().unwrap_or_default())?,
+    }
+    let h = &status.history;
+    write!(out, "History: {} ({:.1} MB), {} cycles", h.path, h.size_bytes as f64 / 1e6, h.cycles)?;
+    if let Some(first) = h.first_cycle {
+        write!(out, " since {}", first.format("%Y-%m-%d"))?;
+    }
+    writeln!(out, ", {} observations, {} open slots, {} alerts", h.observations, h.open_slots, h.alerts)?;
+    let s = &status.state;
+    writeln!(
+        out,
+        "State: {}, {} suppressed slots, {} suppressed locations, {} queued digest lines, {} undelivered alerts, {} open escalations",
+        s.path,
+        s.suppressed_slots,
+        s.suppressed_locations,
+        s.queued_digest_lines,
+        s.undelivered_alerts,
+        s.open_escalations
+    )?;
+    Ok(())
+}
+
+fn ago(seconds: i64) -> String {
+    match seconds {
+        s if s < 120 => format!("{s}s"),
+        s if s < 7200 => format!("{}m", s / 60),
+        s if s < 172_800 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86_400),
+    }
+}
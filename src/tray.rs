@@ -0,0 +1,159 @@
+//! Desktop tray icon for `watch`: green while slots have turned up recently,
+//! gray otherwise, with a menu to pause, run a cycle now or open the latest export.
+
+use std::{
+    path::Path,
+    process,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local, Utc};
+use tao::{
+    event::{Event, StartCause},
+    event_loop::{ControlFlow, EventLoopBuilder},
+};
+use tracing::{error, info, warn};
+use tray_icon::{
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    Icon, TrayIcon, TrayIconBuilder,
+};
+
+use crate::{clock, export::ExportFormat, Controls, JeffConfig};
+
+/// How long the icon stays green after a cycle last found slots.
+const RECENT_MINUTES: i64 = 60;
+
+const GREEN: [u8; 3] = [0x2e, 0xa0, 0x43];
+const GRAY: [u8; 3] = [0x8b, 0x94, 0x9e];
+
+/// Runs the tray on this thread, which must be the main one on macOS, until
+/// Quit is chosen; the run loop keeps going on the runtime's worker threads.
+pub fn run(config: Arc<JeffConfig>, controls: Arc<Controls>) -> ! {
+    let event_loop = EventLoopBuilder::new().build();
+
+    let pause = CheckMenuItem::new("Pause", true, false, None);
+    let run_now = MenuItem::new("Run now", true, None);
+    let open = MenuItem::new("Open latest results", true, None);
+    let quit = MenuItem::new("Quit", true, None);
+    let menu = Menu::new();
+    if let Err(e) = menu.append_items(&[&pause, &run_now, &open, &PredefinedMenuItem::separator(), &quit]) {
+        error!("Error building tray menu: {e}");
+        process::exit(1);
+    }
+
+    let mut latest = controls.latest.subscribe();
+    let mut tray: Option<TrayIcon> = None;
+    let mut last_cycle: Option<(usize, DateTime<Utc>)> = None;
+    let mut last_found: Option<DateTime<Utc>> = None;
+    let mut shown: Option<(bool, String)> = None;
+
+    event_loop.run(move |event, _, control_flow| {
+        // Woken once a second to pick up finished cycles.
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_secs(1));
+
+        if let Event::NewEvents(StartCause::Init) = event {
+            // Created once the loop runs; some platforms drop icons made earlier.
+            match TrayIconBuilder::new()
+                .with_menu(Box::new(menu.clone()))
+                .with_icon(dot(GRAY))
+                .with_tooltip("Appointment finder")
+                .build()
+            {
+                Ok(icon) => tray = Some(icon),
+                Err(e) => {
+                    error!("Error creating tray icon: {e}");
+                    process::exit(1);
+                }
+            }
+        }
+
+        while let Ok(event) = MenuEvent::receiver().try_recv() {
+            if event.id == pause.id() {
+                let paused = pause.is_checked();
+                controls.paused.store(paused, Ordering::Relaxed);
+                info!("{} from the tray", if paused { "Paused" } else { "Resumed" });
+            } else if event.id == run_now.id() {
+                controls.run_now.notify_one();
+            } else if event.id == open.id() {
+                open_latest(&config);
+            } else if event.id == quit.id() {
+                tray.take();
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+        }
+
+        if latest.has_changed().unwrap_or(false) {
+            if let Some(report) = &*latest.borrow_and_update() {
+                last_cycle = Some((report.slots_found, report.finished_at));
+                if report.slots_found > 0 {
+                    last_found = Some(report.finished_at);
+                }
+            }
+        }
+        let Some(icon) = &tray else {
+            return;
+        };
+        let found = last_found.is_some_and(|at| clock::now() - at < chrono::Duration::minutes(RECENT_MINUTES));
+        let mut tooltip = match last_cycle {
+            None => "Appointment finder: waiting for the first cycle".to_string(),
+            Some((slots, at)) => format!(
+                "Appointment finder: {slots} slot(s) at {}",
+                at.with_timezone(&Local).format("%H:%M")
+            ),
+        };
+        if controls.paused.load(Ordering::Relaxed) {
+            tooltip.push_str(" (paused)");
+        }
+        let wanted = (found, tooltip);
+        if shown.as_ref() != Some(&wanted) {
+            if let Err(e) = icon.set_icon(Some(dot(if found { GREEN } else { GRAY }))) {
+                warn!("Error updating tray icon: {e}");
+            }
+            if let Err(e) = icon.set_tooltip(Some(&wanted.1)) {
+                warn!("Error updating tray tooltip: {e}");
+            }
+            shown = Some(wanted);
+        }
+    })
+}
+
+/// Opens the newest configured export, preferring HTML, in the default app.
+fn open_latest(config: &JeffConfig) {
+    let newest = config
+        .exports
+        .iter()
+        .map(|format| Path::new(format.path()))
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .max_by_key(|&(modified, path)| (modified, path == Path::new(ExportFormat::Html.path())));
+    let Some((_, path)) = newest else {
+        warn!("No export to open yet; exports are written each cycle when no notification channel is enabled");
+        return;
+    };
+    #[cfg(target_os = "macos")]
+    let opened = process::Command::new("open").arg(path).spawn();
+    #[cfg(target_os = "windows")]
+    let opened = process::Command::new("cmd").args(["/C", "start", ""]).arg(path).spawn();
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let opened = process::Command::new("xdg-open").arg(path).spawn();
+    if let Err(e) = opened {
+        error!("Error opening {}: {e}", path.display());
+    }
+}
+
+/// A filled circle in `color`, for the tray.
+fn dot([r, g, b]: [u8; 3]) -> Icon {
+    const SIZE: u32 = 32;
+    let center = (SIZE as f32 - 1.0) / 2.0;
+    let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let distance = (x as f32 - center).hypot(y as f32 - center);
+            // A one-pixel soft edge keeps the circle from looking jagged.
+            let alpha = ((center + 0.5 - distance).clamp(0.0, 1.0) * 255.0) as u8;
+            rgba.extend_from_slice(&[r, g, b, alpha]);
+        }
+    }
+    Icon::from_rgba(rgba, SIZE, SIZE).expect("icon buffer matches its size")
+}
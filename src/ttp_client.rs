@@ -0,0 +1,856 @@
+//! Typed client for the CBP Trusted Traveler Programs scheduler API.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Client, ClientBuilder, Proxy, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use crate::{body_log::BodyLog, budget::RequestBudget};
+
+pub const DEFAULT_BASE_URL: &str = "https://ttp.cbp.dhs.gov/schedulerapi";
+
+/// Service name for Global Entry interviews.
+pub const GLOBAL_ENTRY: &str = "Global Entry";
+
+#[derive(Debug, Error)]
+pub enum TtpError {
+    #[error("HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("JSON parse: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A non-JSON page (or a 429) in place of API data.
+    #[error("{outage} (HTTP {status})")]
+    Unavailable {
+        outage: Outage,
+        status: u16,
+        /// From the `Retry-After` header, when it gives a number of seconds.
+        retry_after: Option<Duration>,
+    },
+    /// Refused before sending, to stay within `request_budget`.
+    #[error("request budget of {0} spent; request not sent")]
+    OverBudget(String),
+}
+
+/// Why the API answered with a web page instead of data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outage {
+    /// Planned downtime or an overloaded gateway; retrying soon won't help.
+    Maintenance,
+    /// Too many requests; retry after a pause.
+    RateLimited,
+    /// A web application firewall or bot challenge; more requests make it worse.
+    Blocked,
+    /// Some other non-JSON body, e.g. a proxy error page.
+    Unrecognized,
+}
+
+impl std::fmt::Display for Outage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Outage::Maintenance => "API is down for maintenance",
+            Outage::RateLimited => "API rate limit hit",
+            Outage::Blocked => "request blocked by the API's firewall",
+            Outage::Unrecognized => "API returned a non-JSON page",
+        })
+    }
+}
+
+const BLOCK_MARKERS: &[&str] = &[
+    "access denied",
+    "request rejected",
+    "attention required",
+    "captcha",
+    "cloudflare",
+    "incapsula",
+    "akamai",
+    "security check",
+];
+const MAINTENANCE_MARKERS: &[&str] = &[
+    "maintenance",
+    "temporarily unavailable",
+    "service unavailable",
+    "scheduled downtime",
+];
+
+/// Classifies a response whose body isn't JSON; `None` means it should be
+/// handled as an ordinary API response.
+pub fn classify(status: StatusCode, body: &str) -> Option<Outage> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Some(Outage::RateLimited);
+    }
+    if matches!(body.trim_start().chars().next(), Some('[' | '{')) {
+        return None;
+    }
+    let lower = body.to_ascii_lowercase();
+    let mentions = |markers: &[&str]| markers.iter().any(|m| lower.contains(m));
+    Some(if lower.contains("too many requests") || lower.contains("rate limit") {
+        Outage::RateLimited
+    } else if status == StatusCode::FORBIDDEN || mentions(BLOCK_MARKERS) {
+        Outage::Blocked
+    } else if status.is_server_error() || mentions(MAINTENANCE_MARKERS) {
+        Outage::Maintenance
+    } else {
+        Outage::Unrecognized
+    })
+}
+
+/// Reads a response body into `body_log`, turning maintenance, rate-limit and
+/// firewall pages into [`TtpError::Unavailable`] and other error statuses
+/// into HTTP errors.
+pub async fn read_body(resp: Response, body_log: &BodyLog) -> Result<String, TtpError> {
+    let status = resp.status();
+    let retry_after = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs);
+    let status_err = resp.error_for_status_ref().err();
+    let url = resp.url().clone();
+    let body = resp.text().await?;
+    body_log.record(&url, status, &body);
+    if let Some(outage) = classify(status, &body) {
+        return Err(TtpError::Unavailable {
+            outage,
+            status: status.as_u16(),
+            retry_after,
+        });
+    }
+    match status_err {
+        Some(e) => Err(e.into()),
+        None => Ok(body),
+    }
+}
+
+/// A location with availability, as returned by `slots/asLocations`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Location {
+    pub id: usize,
+    pub name: String,
+    pub state: String,
+    pub city: String,
+    pub address: String,
+    #[serde(rename = "addressAdditional")]
+    pub address_additional: Option<String>,
+    #[serde(rename = "postalCode")]
+    pub postal_code: String,
+    #[serde(rename = "phoneNumber")]
+    pub phone_number: Option<String>,
+    /// Airport-style code such as `JFK`.
+    #[serde(rename = "shortName", default)]
+    pub short_name: Option<String>,
+    /// IANA time zone of the center, e.g. `America/New_York`.
+    #[serde(rename = "tzData", default)]
+    pub tz_data: Option<String>,
+    #[serde(default)]
+    pub operational: Option<bool>,
+    #[serde(default)]
+    pub services: Vec<Service>,
+    /// Every other field of the API record, by its API name.
+    #[serde(flatten)]
+    pub extras: serde_json::Map<String, Value>,
+    /// Set by the provider that fetched this location, e.g. `global_entry`.
+    #[serde(skip)]
+    pub provider: &'static str,
+}
+
+impl Location {
+    /// A field by its API name (`shortName`, `tzData`, or anything in
+    /// `extras`), for config-driven filters and exports.
+    pub fn field(&self, name: &str) -> Option<Value> {
+        let text = |s: &str| Value::String(s.to_string());
+        match name {
+            "id" => Some(Value::from(self.id)),
+            "name" => Some(text(&self.name)),
+            "state" => Some(text(&self.state)),
+            "city" => Some(text(&self.city)),
+            "address" => Some(text(&self.address)),
+            "addressAdditional" => self.address_additional.as_deref().map(text),
+            "postalCode" => Some(text(&self.postal_code)),
+            "phoneNumber" => self.phone_number.as_deref().map(text),
+            "shortName" => self.short_name.as_deref().map(text),
+            "tzData" => self.tz_data.as_deref().map(text),
+            "operational" => self.operational.map(Value::Bool),
+            "services" => Some(Value::from(self.service_names())),
+            _ => self.extras.get(name).cloned(),
+        }
+    }
+
+    /// Every field by its API name, plus `provider`, for user scripts and plugins.
+    pub fn record(&self) -> Value {
+        const FIELDS: &[&str] = &[
+            "id", "name", "state", "city", "address", "addressAdditional", "postalCode", "phoneNumber", "shortName",
+            "tzData", "operational", "services",
+        ];
+        let mut record = self.extras.clone();
+        for name in FIELDS {
+            record.insert(name.to_string(), self.field(name).unwrap_or(Value::Null));
+        }
+        record.insert("provider".to_string(), Value::from(self.provider));
+        Value::Object(record)
+    }
+
+    pub fn service_names(&self) -> Vec<&str> {
+        self.services.iter().map(|s| s.name.as_str()).collect()
+    }
+}
+
+/// One enrollment center from the location directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CenterInfo {
+    pub id: usize,
+    pub name: String,
+    pub state: String,
+    pub city: String,
+    #[serde(default)]
+    pub tz_data: Option<String>,
+    /// Programs interviewed here, e.g. Global Entry, NEXUS, SENTRI.
+    #[serde(default)]
+    pub services: Vec<Service>,
+    /// Opening hours as free text, e.g. `Tue-Thu 8:00 AM - 4:00 PM`.
+    #[serde(default)]
+    pub hours: Option<String>,
+    #[serde(default)]
+    pub tentatively_closed: Option<bool>,
+    /// Anything the center wants applicants to know, as free text.
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Service {
+    pub name: String,
+}
+
+/// How far ahead the scheduler publishes slots; later dates never have availability.
+pub const BOOKING_HORIZON_DAYS: i64 = 365;
+
+/// A parsed API element together with the JSON it was parsed from.
+#[derive(Debug, Clone)]
+pub struct Entry<T> {
+    pub value: T,
+    pub raw: Value,
+}
+
+/// A single bookable interview slot.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Slot {
+    #[serde(deserialize_with = "de_timestamp")]
+    pub start_timestamp: NaiveDateTime,
+    #[serde(deserialize_with = "de_timestamp")]
+    pub end_timestamp: NaiveDateTime,
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default)]
+    pub duration: u32,
+}
+
+/// The API sends minute-precision local timestamps such as `2025-01-14T08:15`.
+fn de_timestamp<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveDateTime, D::Error> {
+    let s = String::deserialize(d)?;
+    NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S"))
+        .map_err(serde::de::Error::custom)
+}
+
+/// Longest wait between attempts, however many have failed.
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Most attempts `max_retries` may ask for; past this, doubling delays only
+/// hold a cycle up.
+pub const MAX_RETRIES: u8 = 10;
+
+/// Decides whether and when a failed request is retried.
+pub trait RetryPolicy: Send + Sync {
+    /// Delay before retrying after `attempt` (1-based) failed with `err`, or `None` to give up.
+    fn next_delay(&self, attempt: u8, err: &TtpError) -> Option<Duration>;
+}
+
+/// Retries HTTP failures, rate limits and unrecognized pages with doubling
+/// delays (or the server's `Retry-After`, if longer), up to `max_attempts` in
+/// total. Maintenance and firewall pages aren't retried within a cycle.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    pub max_attempts: u8,
+    pub initial: Duration,
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn next_delay(&self, attempt: u8, err: &TtpError) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let delay = 2u32
+            .checked_pow(u32::from(attempt.max(1)) - 1)
+            .map_or(MAX_RETRY_DELAY, |factor| self.initial.saturating_mul(factor))
+            .min(MAX_RETRY_DELAY);
+        match err {
+            TtpError::Http(_)
+            | TtpError::Unavailable {
+                outage: Outage::Unrecognized,
+                ..
+            } => Some(delay),
+            TtpError::Unavailable {
+                outage: Outage::RateLimited,
+                retry_after,
+                ..
+            } => Some(retry_after.map_or(delay, |after| after.max(delay))),
+            _ => None,
+        }
+    }
+}
+
+/// Sends the client's requests: [`reqwest::Client`] in production, or an
+/// in-memory stub so retries, backoff and failover can be tested offline.
+pub trait HttpClient: Send + Sync {
+    /// GETs `url` with `query` added to it and `headers` set.
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        query: &'a [(&'a str, String)],
+        headers: HeaderMap,
+    ) -> BoxFuture<'a, reqwest::Result<Response>>;
+}
+
+impl HttpClient for Client {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        query: &'a [(&'a str, String)],
+        headers: HeaderMap,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        Client::get(self, url).query(query).headers(headers).send().boxed()
+    }
+}
+
+/// Adjusts outgoing requests and learns from responses, e.g. to replay a
+/// browser session's cookies and headers past bot protection.
+pub trait RequestDecorator: Send + Sync {
+    fn decorate(&self, headers: &mut HeaderMap);
+
+    /// Called with every response before its status is checked.
+    fn observe(&self, _response: &Response) {}
+}
+
+/// The scheduler API operations the finder relies on, so they can be mocked.
+pub trait TtpApi: Send + Sync {
+    /// Locations with at least one open slot on `date` for `service`.
+    fn slots_as_locations(
+        &self,
+        date: NaiveDate,
+        service: &str,
+    ) -> impl Future<Output = Result<Vec<Entry<Location>>, TtpError>> + Send;
+
+    /// Locations nationwide with at least one open slot before `before` for
+    /// `service`, soonest first.
+    fn soonest_as_locations(
+        &self,
+        before: NaiveDate,
+        service: &str,
+    ) -> impl Future<Output = Result<Vec<Entry<Location>>, TtpError>> + Send;
+
+    /// The soonest open slots at one location.
+    fn slots_for_location(
+        &self,
+        location_id: usize,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<Slot>, TtpError>> + Send;
+
+    /// Every operational enrollment center offering `service`.
+    fn locations(
+        &self,
+        service: &str,
+    ) -> impl Future<Output = Result<Vec<Entry<CenterInfo>>, TtpError>> + Send;
+}
+
+/// One way of reaching the scheduler API: a base URL, optionally through a proxy.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiEndpoint {
+    pub base_url: String,
+    /// Proxy URL such as `http://proxy.example:3128` or `socks5://...`.
+    #[serde(default)]
+    pub proxy: Option<String>,
+}
+
+/// Query parameters the API accepts but the finder doesn't need to vary,
+/// exposed for experimenting with API behavior.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiQuery {
+    /// Fewest open slots a location needs to be listed.
+    #[serde(default = "default_minimum")]
+    pub minimum: u32,
+    /// How `slots/asLocations` matches the requested date.
+    #[serde(default)]
+    pub filter_timestamp_by: TimestampFilter,
+    /// Additional parameters sent verbatim on slot requests.
+    #[serde(default)]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl Default for ApiQuery {
+    fn default() -> Self {
+        ApiQuery {
+            minimum: default_minimum(),
+            filter_timestamp_by: TimestampFilter::default(),
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_minimum() -> u32 {
+    1
+}
+
+/// Parameters the finder sets itself, which `extra` must not override.
+const RESERVED_PARAMS: &[&str] = &[
+    "minimum",
+    "filterTimestampBy",
+    "timestamp",
+    "serviceName",
+    "locationId",
+    "orderBy",
+    "limit",
+];
+
+impl ApiQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.minimum == 0 {
+            return Err("minimum must be at least 1".to_string());
+        }
+        for key in self.extra.keys() {
+            if let Some(reserved) = RESERVED_PARAMS.iter().find(|r| r.eq_ignore_ascii_case(key)) {
+                return Err(format!("extra: {reserved:?} is set by the finder and can't be overridden"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFilter {
+    /// Slots on the requested date (default).
+    #[default]
+    On,
+    /// Slots before it.
+    Before,
+    /// Slots after it.
+    After,
+}
+
+impl TimestampFilter {
+    fn as_str(self) -> &'static str {
+        match self {
+            TimestampFilter::On => "on",
+            TimestampFilter::Before => "before",
+            TimestampFilter::After => "after",
+        }
+    }
+}
+
+/// Penalty, in milliseconds of latency, for each consecutive failure.
+const FAILURE_PENALTY_MS: f64 = 10_000.0;
+/// Weight of the newest sample in the latency moving average.
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Default)]
+struct Health {
+    latency_ms: Option<f64>,
+    consecutive_failures: u32,
+}
+
+impl Health {
+    /// Lower is better; endpoints not yet tried score 0 so they get a chance.
+    fn score(&self) -> f64 {
+        self.latency_ms.unwrap_or(0.0) + FAILURE_PENALTY_MS * f64::from(self.consecutive_failures)
+    }
+}
+
+struct Endpoint {
+    base_url: String,
+    http: Arc<dyn HttpClient>,
+    health: Mutex<Health>,
+}
+
+impl Endpoint {
+    fn new(base_url: String, http: Arc<dyn HttpClient>) -> Self {
+        Endpoint {
+            base_url,
+            http,
+            health: Mutex::new(Health::default()),
+        }
+    }
+
+    fn record(&self, outcome: Result<Duration, ()>) {
+        let mut health = self.health.lock().unwrap();
+        match outcome {
+            Ok(elapsed) => {
+                let ms = elapsed.as_secs_f64() * 1000.0;
+                health.latency_ms = Some(match health.latency_ms {
+                    Some(avg) => avg + LATENCY_SMOOTHING * (ms - avg),
+                    None => ms,
+                });
+                if health.consecutive_failures > 0 {
+                    info!("API endpoint {} recovered", self.base_url);
+                }
+                health.consecutive_failures = 0;
+            }
+            Err(()) => health.consecutive_failures += 1,
+        }
+    }
+}
+
+/// Scheduler API client. With several endpoints configured, each request goes
+/// to the healthiest one (lowest smoothed latency, penalized for recent
+/// failures) and fails over to the others before counting as a failed attempt.
+#[derive(Clone)]
+pub struct TtpClient {
+    endpoints: Arc<Vec<Endpoint>>,
+    retry: Arc<dyn RetryPolicy>,
+    decorators: Vec<Arc<dyn RequestDecorator>>,
+    query: Arc<ApiQuery>,
+    budget: Option<Arc<RequestBudget>>,
+    body_log: Arc<BodyLog>,
+}
+
+impl TtpClient {
+    pub fn new(http: impl HttpClient + 'static) -> Self {
+        TtpClient {
+            endpoints: Arc::new(vec![Endpoint::new(DEFAULT_BASE_URL.to_string(), Arc::new(http))]),
+            retry: Arc::new(ExponentialBackoff {
+                max_attempts: 1,
+                initial: Duration::from_secs(1),
+            }),
+            decorators: Vec::new(),
+            query: Arc::new(ApiQuery::default()),
+            budget: None,
+            body_log: Arc::default(),
+        }
+    }
+
+    /// Replaces the default endpoint with `endpoints`, tried in order of health.
+    /// Each gets its own HTTP client, starting from `builder()`.
+    pub fn with_endpoints(
+        mut self,
+        endpoints: &[ApiEndpoint],
+        builder: impl Fn() -> ClientBuilder,
+    ) -> Result<Self, TtpError> {
+        let endpoints = endpoints
+            .iter()
+            .map(|e| {
+                let mut builder = builder();
+                if let Some(proxy) = &e.proxy {
+                    builder = builder.proxy(Proxy::all(proxy)?);
+                }
+                Ok(Endpoint::new(e.base_url.trim_end_matches('/').to_string(), Arc::new(builder.build()?)))
+            })
+            .collect::<Result<Vec<_>, TtpError>>()?;
+        if !endpoints.is_empty() {
+            self.endpoints = Arc::new(endpoints);
+        }
+        Ok(self)
+    }
+
+    /// Endpoints ordered best first; ties keep their configured order.
+    fn ranked_endpoints(&self) -> Vec<&Endpoint> {
+        let mut ranked: Vec<&Endpoint> = self.endpoints.iter().collect();
+        ranked.sort_by(|a, b| {
+            let a = a.health.lock().unwrap().score();
+            let b = b.health.lock().unwrap().score();
+            a.total_cmp(&b)
+        });
+        ranked
+    }
+
+    pub fn with_retry(mut self, retry: impl RetryPolicy + 'static) -> Self {
+        self.retry = Arc::new(retry);
+        self
+    }
+
+    pub fn with_query(mut self, query: ApiQuery) -> Self {
+        self.query = Arc::new(query);
+        self
+    }
+
+    /// Counts every request against `budget`, refusing those past it.
+    pub fn with_budget(mut self, budget: Option<Arc<RequestBudget>>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_body_log(mut self, body_log: Arc<BodyLog>) -> Self {
+        self.body_log = body_log;
+        self
+    }
+
+    /// Adds a decorator; decorators run in the order they were added.
+    pub fn with_decorator(mut self, decorator: Arc<dyn RequestDecorator>) -> Self {
+        self.decorators.push(decorator);
+        self
+    }
+
+    /// GETs `path` with `query`, failing over between endpoints and retrying
+    /// per the policy, and returns the body.
+    async fn get_text(&self, path: &str, query: &[(&str, String)]) -> Result<String, TtpError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut last_err = None;
+            for endpoint in self.ranked_endpoints() {
+                let url = format!("{}/{path}", endpoint.base_url);
+                if let Some(budget) = &self.budget {
+                    budget.take().map_err(TtpError::OverBudget)?;
+                }
+                debug!("HTTP GET: {url} {query:?} (attempt {attempt})");
+
+                let started = Instant::now();
+                let result = async {
+                    let mut headers = HeaderMap::new();
+                    for decorator in &self.decorators {
+                        decorator.decorate(&mut headers);
+                    }
+                    let resp = endpoint.http.get(&url, query, headers).await?;
+                    debug!("Status code: {}", resp.status());
+                    for decorator in &self.decorators {
+                        decorator.observe(&resp);
+                    }
+                    read_body(resp, &self.body_log).await
+                }
+                .await;
+
+                match result {
+                    Ok(body) => {
+                        endpoint.record(Ok(started.elapsed()));
+                        return Ok(body);
+                    }
+                    Err(e) => {
+                        endpoint.record(Err(()));
+                        if self.endpoints.len() > 1 {
+                            warn!("Request to {url} failed: {e}; trying next endpoint");
+                        }
+                        last_err = Some(e);
+                    }
+                }
+            }
+
+            let e = last_err.expect("at least one endpoint");
+            match self.retry.next_delay(attempt, &e) {
+                Some(delay) => {
+                    warn!("Request to {path} failed: {e}; retrying in {delay:?}...");
+                    sleep(delay).await;
+                }
+                None => return Err(e),
+            }
+        }
+    }
+}
+
+/// Parses a JSON array, skipping elements that don't match `T`.
+pub fn parse_entries<T: DeserializeOwned>(body: &str) -> Result<Vec<Entry<T>>, TtpError> {
+    let values: Vec<Value> = serde_json::from_str(body)?;
+    Ok(values
+        .into_iter()
+        .filter_map(|raw| match serde_json::from_value::<T>(raw.clone()) {
+            Ok(value) => Some(Entry { value, raw }),
+            Err(e) => {
+                warn!("Failed to parse API element: {e}");
+                None
+            }
+        })
+        .collect())
+}
+
+impl TtpApi for TtpClient {
+    async fn slots_as_locations(
+        &self,
+        date: NaiveDate,
+        service: &str,
+    ) -> Result<Vec<Entry<Location>>, TtpError> {
+        parse_entries(&self.slots_as_locations_body(date, service).await?)
+    }
+
+    async fn soonest_as_locations(&self, before: NaiveDate, service: &str) -> Result<Vec<Entry<Location>>, TtpError> {
+        let mut query = vec![
+            ("minimum", self.query.minimum.to_string()),
+            ("filterTimestampBy", TimestampFilter::Before.as_str().to_string()),
+            ("timestamp", before.to_string()),
+            ("serviceName", service.to_string()),
+            ("orderBy", "soonest".to_string()),
+        ];
+        query.extend(self.query.extra.iter().map(|(k, v)| (k.as_str(), v.clone())));
+        let body = self.get_text("slots/asLocations", &query).await?;
+        parse_entries(&body)
+    }
+
+    async fn slots_for_location(&self, location_id: usize, limit: usize) -> Result<Vec<Slot>, TtpError> {
+        Ok(serde_json::from_str(&self.slots_body(location_id, limit).await?)?)
+    }
+
+    async fn locations(&self, service: &str) -> Result<Vec<Entry<CenterInfo>>, TtpError> {
+        parse_entries(&self.locations_body(service).await?)
+    }
+}
+
+/// Unparsed responses behind the [`TtpApi`] calls, for `dev capture-schema`.
+impl TtpClient {
+    pub(crate) async fn slots_as_locations_body(&self, date: NaiveDate, service: &str) -> Result<String, TtpError> {
+        let mut query = vec![
+            ("minimum", self.query.minimum.to_string()),
+            ("filterTimestampBy", self.query.filter_timestamp_by.as_str().to_string()),
+            ("timestamp", date.to_string()),
+            ("serviceName", service.to_string()),
+        ];
+        query.extend(self.query.extra.iter().map(|(k, v)| (k.as_str(), v.clone())));
+        self.get_text("slots/asLocations", &query).await
+    }
+
+    pub(crate) async fn slots_body(&self, location_id: usize, limit: usize) -> Result<String, TtpError> {
+        let mut query = vec![
+            ("orderBy", "soonest".to_string()),
+            ("limit", limit.to_string()),
+            ("locationId", location_id.to_string()),
+            ("minimum", self.query.minimum.to_string()),
+        ];
+        query.extend(self.query.extra.iter().map(|(k, v)| (k.as_str(), v.clone())));
+        self.get_text("slots", &query).await
+    }
+
+    pub(crate) async fn locations_body(&self, service: &str) -> Result<String, TtpError> {
+        self.get_text(
+            "locations/",
+            &[
+                ("temporary", "false".to_string()),
+                ("inviteOnly", "false".to_string()),
+                ("operational", "true".to_string()),
+                ("serviceName", service.to_string()),
+            ],
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Answers each request with the next canned `(status, Retry-After, body)`
+    /// and counts the requests.
+    struct Stub {
+        responses: Mutex<VecDeque<(u16, Option<u64>, &'static str)>>,
+        sent: Mutex<u32>,
+    }
+
+    impl Stub {
+        fn new(responses: &[(u16, Option<u64>, &'static str)]) -> Arc<Stub> {
+            Arc::new(Stub {
+                responses: Mutex::new(responses.iter().copied().collect()),
+                sent: Mutex::new(0),
+            })
+        }
+
+        fn sent(&self) -> u32 {
+            *self.sent.lock().unwrap()
+        }
+    }
+
+    impl HttpClient for Stub {
+        fn get<'a>(
+            &'a self,
+            _url: &'a str,
+            _query: &'a [(&'a str, String)],
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, reqwest::Result<Response>> {
+            *self.sent.lock().unwrap() += 1;
+            let (status, retry_after, body) = self.responses.lock().unwrap().pop_front().expect("no response left");
+            let mut response = http::Response::builder().status(status);
+            if let Some(secs) = retry_after {
+                response = response.header(RETRY_AFTER, secs);
+            }
+            let response = Response::from(response.body(body).unwrap());
+            async move { Ok(response) }.boxed()
+        }
+    }
+
+    fn client(stubs: &[&Arc<Stub>], max_attempts: u8) -> TtpClient {
+        let endpoints = stubs
+            .iter()
+            .enumerate()
+            .map(|(i, &stub)| Endpoint::new(format!("http://endpoint{i}"), Arc::clone(stub) as Arc<dyn HttpClient>))
+            .collect();
+        TtpClient {
+            endpoints: Arc::new(endpoints),
+            ..TtpClient::new(Client::new())
+        }
+        .with_retry(ExponentialBackoff {
+            max_attempts,
+            initial: Duration::from_secs(1),
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_waits_for_retry_after() {
+        let stub = Stub::new(&[(429, Some(30), "Too Many Requests"), (200, None, "[]")]);
+        let started = tokio::time::Instant::now();
+        assert_eq!(client(&[&stub], 3).get_text("slots", &[]).await.unwrap(), "[]");
+        assert_eq!(stub.sent(), 2);
+        assert!(started.elapsed() >= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_cap() {
+        let err = TtpError::Unavailable {
+            outage: Outage::Unrecognized,
+            status: 200,
+            retry_after: None,
+        };
+        let backoff = |initial| ExponentialBackoff {
+            max_attempts: u8::MAX,
+            initial,
+        };
+        let delay = |initial, attempt| backoff(initial).next_delay(attempt, &err).unwrap();
+        let second = Duration::from_secs(1);
+        assert_eq!(delay(second, 0), second);
+        assert_eq!(delay(second, 1), second);
+        assert_eq!(delay(second, 4), Duration::from_secs(8));
+        assert_eq!(delay(second, 33), MAX_RETRY_DELAY);
+        assert_eq!(delay(second, 254), MAX_RETRY_DELAY);
+        assert_eq!(delay(Duration::MAX, 2), MAX_RETRY_DELAY);
+        assert_eq!(backoff(second).next_delay(u8::MAX, &err), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn maintenance_is_not_retried() {
+        let stub = Stub::new(&[(503, None, "<html>Scheduled maintenance</html>")]);
+        let err = client(&[&stub], 3).get_text("slots", &[]).await.unwrap_err();
+        assert!(matches!(err, TtpError::Unavailable { outage: Outage::Maintenance, .. }));
+        assert_eq!(stub.sent(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failing_endpoint_is_passed_over() {
+        let down = Stub::new(&[(502, None, "<html>Bad gateway</html>")]);
+        let up = Stub::new(&[(200, None, "[]"), (200, None, "[]")]);
+        let api = client(&[&down, &up], 1);
+        api.get_text("slots", &[]).await.unwrap();
+        api.get_text("slots", &[]).await.unwrap();
+        assert_eq!((down.sent(), up.sent()), (1, 2));
+    }
+}
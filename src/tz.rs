@@ -0,0 +1,163 @@
+//! IANA time zones read from the system's tz database, enough to put a
+//! calendar's `TZID` times and a center's `tz_data` on the same clock.
+//! Only the transitions in the file are used, which for the usual "fat" files
+//! run to 2037; later times keep the last offset.
+
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+#[derive(Debug, Clone)]
+pub struct Zone {
+    /// When each offset starts, in Unix seconds, oldest first.
+    transitions: Vec<(i64, i32)>,
+    /// The offset before the first transition.
+    initial: i32,
+}
+
+impl Zone {
+    /// The zone named e.g. `America/New_York`, or `None` when the name is
+    /// unknown here.
+    pub fn named(name: &str) -> Option<Zone> {
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+            return None;
+        }
+        let dir = std::env::var_os("TZDIR").unwrap_or_else(|| ZONEINFO.into());
+        Zone::parse(&std::fs::read(Path::new(&dir).join(name)).ok()?)
+    }
+
+    /// A zone from the contents of a TZif file, using its 64-bit section when
+    /// it has one.
+    pub fn parse(data: &[u8]) -> Option<Zone> {
+        let (version, counts, body) = header(data)?;
+        if version >= b'2' {
+            let (_, counts, body) = header(body.get(block_len(&counts, 4)..)?)?;
+            return Zone::read(&counts, body, 8);
+        }
+        Zone::read(&counts, body, 4)
+    }
+
+    fn read(counts: &Counts, body: &[u8], time_len: usize) -> Option<Zone> {
+        let mut at = 0;
+        let mut take = |len: usize| {
+            let bytes = body.get(at..at + len);
+            at += len;
+            bytes
+        };
+        let times = take(counts.times * time_len)?;
+        let indices = take(counts.times)?;
+        let types = take(counts.types * 6)?;
+        let offset = |index: usize| {
+            let info = types.get(index * 6..index * 6 + 4)?;
+            Some(i32::from_be_bytes(info.try_into().ok()?))
+        };
+        let transitions = times
+            .chunks(time_len)
+            .zip(indices)
+            .map(|(time, &index)| {
+                let time = match time_len {
+                    8 => i64::from_be_bytes(time.try_into().ok()?),
+                    _ => i32::from_be_bytes(time.try_into().ok()?) as i64,
+                };
+                Some((time, offset(index as usize)?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        // The first standard-time type, as the tz library does.
+        let initial = (0..counts.types).find(|&i| types[i * 6 + 4] == 0).unwrap_or(0);
+        Some(Zone {
+            transitions,
+            initial: offset(initial)?,
+        })
+    }
+
+    /// The offset from UTC at `at`.
+    pub fn offset_at(&self, at: DateTime<Utc>) -> FixedOffset {
+        let seconds = at.timestamp();
+        let next = self.transitions.partition_point(|&(start, _)| start <= seconds);
+        let offset = next.checked_sub(1).map_or(self.initial, |i| self.transitions[i].1);
+        FixedOffset::east_opt(offset).unwrap_or(FixedOffset::east_opt(0).expect("zero offset"))
+    }
+
+    /// The wall-clock time here at `at`.
+    pub fn local(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        at.with_timezone(&self.offset_at(at)).naive_local()
+    }
+
+    /// The instant a wall-clock time here names. Times skipped by a change
+    /// to summer time are moved forward past it; repeated ones take the
+    /// first.
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let guess = local.and_utc();
+        let before = self.offset_at(guess - chrono::Duration::days(1));
+        let after = self.offset_at(guess + chrono::Duration::days(1));
+        [before, after]
+            .into_iter()
+            .map(|offset| local.and_utc() - chrono::Duration::seconds(offset.local_minus_utc() as i64))
+            .find(|&at| self.local(at) == local)
+            .unwrap_or_else(|| guess - chrono::Duration::seconds(before.local_minus_utc() as i64))
+    }
+}
+
+struct Counts {
+    ut: usize,
+    std: usize,
+    leaps: usize,
+    times: usize,
+    types: usize,
+    chars: usize,
+}
+
+/// The version and counts of a TZif header, and what follows it.
+fn header(data: &[u8]) -> Option<(u8, Counts, &[u8])> {
+    if data.get(..4)? != b"TZif" {
+        return None;
+    }
+    let count = |i: usize| Some(u32::from_be_bytes(data.get(20 + i * 4..24 + i * 4)?.try_into().ok()?) as usize);
+    let counts = Counts {
+        ut: count(0)?,
+        std: count(1)?,
+        leaps: count(2)?,
+        times: count(3)?,
+        types: count(4)?,
+        chars: count(5)?,
+    };
+    Some((data[4], counts, data.get(44..)?))
+}
+
+/// The length of a data block whose times take `time_len` bytes.
+fn block_len(counts: &Counts, time_len: usize) -> usize {
+    counts.times * (time_len + 1)
+        + counts.types * 6
+        + counts.chars
+        + counts.leaps * (time_len + 4)
+        + counts.std
+        + counts.ut
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn converts_around_daylight_saving_changes() {
+        let Some(zone) = Zone::named("America/New_York") else {
+            eprintln!("No tz database here; skipping");
+            return;
+        };
+        let winter = Utc.with_ymd_and_hms(2025, 3, 8, 15, 0, 0).unwrap();
+        assert_eq!(zone.local(winter).to_string(), "2025-03-08 10:00:00");
+        let summer = Utc.with_ymd_and_hms(2025, 3, 10, 14, 0, 0).unwrap();
+        assert_eq!(zone.local(summer).to_string(), "2025-03-10 10:00:00");
+        let wall = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(zone.to_utc(wall("2025-03-10 10:00")), summer);
+        // 2:30 doesn't happen that night.
+        assert_eq!(zone.to_utc(wall("2025-03-09 02:30")), Utc.with_ymd_and_hms(2025, 3, 9, 7, 30, 0).unwrap());
+        assert_eq!(zone.to_utc(wall("2025-11-02 01:30")), Utc.with_ymd_and_hms(2025, 11, 2, 5, 30, 0).unwrap());
+        assert!(Zone::named("../etc/passwd").is_none());
+        assert!(Zone::parse(b"not a zone").is_none());
+    }
+}